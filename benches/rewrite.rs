@@ -0,0 +1,93 @@
+use criterion::{criterion_group, criterion_main, BatchSize, Criterion};
+
+use raxio::{
+    ast::Expr,
+    lexer::Lexer,
+    parser::{Parser, Stmt},
+    runtime::{ast_traverse_match, Env},
+    search::prove,
+    strategy::StrategyRunner
+};
+
+fn parse(input_string: &str) -> Vec<Stmt> {
+    let mut lexer = Lexer::new();
+    lexer.lex(input_string);
+
+    let mut parser = Parser::new();
+    parser.parse(&mut lexer).unwrap();
+    parser.stmts
+}
+
+// The peano numeral s(s(...s(zero)...)) of n.
+fn numeral(n: usize) -> Expr {
+    let mut expr = Expr::variable("zero");
+    for _ in 0..n {
+        expr = Expr::functor("s", vec![expr]);
+    }
+    expr
+}
+
+fn peano_env() -> Env {
+    let mut env = Env::new();
+    env.interpret(parse("
+        def plus_0 as add(x, zero) => x
+        def plus_s as add(x, s(y)) => add(s(x), y)
+    ")).unwrap();
+    env
+}
+
+// Each iteration gets a new environment, as the rewrites that its match cache memoizes would otherwise be reused
+fn bench_traverse(c: &mut Criterion) {
+    let expr = numeral(500);
+    let left = Expr::functor("s", vec![Expr::variable("x")]);
+    let right = Expr::functor("t", vec![Expr::variable("x")]);
+
+    c.bench_function("traverse depth 250 of numeral 500", |b| {
+        b.iter_batched(Env::new, |env| ast_traverse_match(&expr, &left, &right, 250, &env).unwrap(), BatchSize::SmallInput)
+    });
+}
+
+// The same traversal as above, in an environment that already memoized it
+fn bench_traverse_cached(c: &mut Criterion) {
+    let env = Env::new();
+    let expr = numeral(500);
+    let left = Expr::functor("s", vec![Expr::variable("x")]);
+    let right = Expr::functor("t", vec![Expr::variable("x")]);
+    ast_traverse_match(&expr, &left, &right, 250, &env).unwrap();
+
+    c.bench_function("cached traverse depth 250 of numeral 500", |b| {
+        b.iter(|| ast_traverse_match(&expr, &left, &right, 250, &env).unwrap())
+    });
+}
+
+fn bench_strategy(c: &mut Criterion) {
+    let Stmt::ApplyStmt { strategy, depth } = parse("apply (plus_s | plus_0)* at 0").remove(0) else {
+        unreachable!()
+    };
+    let expr = Expr::functor("add", vec![numeral(50), numeral(50)]);
+
+    c.bench_function("repeat strategy on add of numerals 50", |b| {
+        b.iter_batched(
+            peano_env,
+            |env| StrategyRunner::new(&env, depth).run(&strategy, expr.clone()).unwrap(),
+            BatchSize::SmallInput
+        )
+    });
+}
+
+fn bench_prove(c: &mut Criterion) {
+    let env = peano_env();
+    let rules: Vec<(String, Expr, Expr)> = ["plus_0", "plus_s"]
+        .iter()
+        .map(|iden| (iden.to_string(), env.rules[*iden].left.clone(), env.rules[*iden].right.clone()))
+        .collect();
+    let left = Expr::functor("add", vec![numeral(3), numeral(3)]);
+    let right = numeral(6);
+
+    c.bench_function("prove add of numerals 3", |b| {
+        b.iter_batched(peano_env, |env| prove(&left, &right, &rules, &env).unwrap().unwrap(), BatchSize::SmallInput)
+    });
+}
+
+criterion_group!(benches, bench_traverse, bench_traverse_cached, bench_strategy, bench_prove);
+criterion_main!(benches);
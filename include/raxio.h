@@ -0,0 +1,46 @@
+/* C interface of the raxio interpreter, which is built with `cargo build --release --features ffi`.
+ * Strings are UTF-8 and NUL-terminated. Every string that is returned is owned by the caller,
+ * who frees it with raxio_string_free. */
+
+#ifndef RAXIO_H
+#define RAXIO_H
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+#define RAXIO_OK 0
+#define RAXIO_SYNTAX_ERROR 1
+#define RAXIO_RUNTIME_ERROR 2
+/* A null pointer, or a source that is not UTF-8 */
+#define RAXIO_INVALID_ARGUMENT 3
+
+typedef struct RaxioEnv RaxioEnv;
+
+/* Create an environment, which is freed with raxio_env_free. */
+RaxioEnv *raxio_env_new(void);
+void raxio_env_free(RaxioEnv *env);
+
+/* Interpret the statements of the source in the environment, which keeps its rules and pattern matching
+ * context across calls. Returns RAXIO_OK, or the kind of error, whose message is given by raxio_last_error. */
+int raxio_eval(RaxioEnv *env, const char *source);
+
+/* The current expression in infix notation, or NULL outside of a pattern matching context. */
+char *raxio_current_expr_string(const RaxioEnv *env);
+
+/* 1 in a pattern matching context, and 0 otherwise. */
+int raxio_is_matching(const RaxioEnv *env);
+
+/* The message of the error of the last evaluation, or NULL if it succeeded. */
+char *raxio_last_error(const RaxioEnv *env);
+
+/* The warnings of the last evaluation, one "CODE: message" per line. */
+char *raxio_warnings(const RaxioEnv *env);
+
+void raxio_string_free(char *s);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif
@@ -0,0 +1,650 @@
+use std::{
+    cmp::Ordering,
+    collections::{HashMap, HashSet},
+    fmt::{Debug, Display},
+    hash::{DefaultHasher, Hash, Hasher},
+    sync::{Arc, Mutex, OnceLock, Weak}
+};
+
+use crate::{lexer::Number, operator::Operators};
+
+// Expressions are immutable and hash-consed, i.e., structurally equal expressions share
+// a single interned node. Therefore, unchanged sub-expressions are shared between all
+// expressions in the history, and cloning, comparing and hashing takes constant time.
+#[derive(Clone)]
+pub struct Expr(Arc<ExprNode>);
+
+#[derive(Debug, PartialEq, Eq, Hash)]
+pub enum ExprNode {
+    Functor { iden: String, args: Vec<Expr> },
+    Variable { iden: String },
+    // Numbers are constants, which only match the same number
+    Number(Number),
+    // Strings are constants like numbers, e.g., a label such as "base case"
+    Str(String),
+    // Segments are variables in an argument list that match any number of arguments, e.g., xs..
+    Segment { iden: String },
+}
+
+// Weak references to all interned nodes, grouped by the hash of their content.
+// Nodes that are no longer used are dropped, and their references are purged
+// whenever the number of references doubles.
+struct Interner {
+    nodes: HashMap<u64, Vec<Weak<ExprNode>>>,
+    num_nodes: usize,
+    purge_at: usize,
+}
+
+impl Interner {
+    fn get() -> &'static Mutex<Interner> {
+        static INTERNER: OnceLock<Mutex<Interner>> = OnceLock::new();
+        INTERNER.get_or_init(|| Mutex::new(Interner {
+            nodes: HashMap::new(),
+            num_nodes: 0,
+            purge_at: 1024
+        }))
+    }
+
+    fn intern(&mut self, node: ExprNode) -> Arc<ExprNode> {
+        let mut hasher = DefaultHasher::new();
+        node.hash(&mut hasher);
+        let hash = hasher.finish();
+
+        let existing = self.nodes
+            .get(&hash)
+            .and_then(|bucket| bucket.iter().filter_map(Weak::upgrade).find(|other| **other == node));
+        if let Some(existing) = existing {
+            return existing;
+        }
+
+        if self.num_nodes >= self.purge_at {
+            self.purge();
+        }
+        let node = Arc::new(node);
+        self.nodes.entry(hash).or_default().push(Arc::downgrade(&node));
+        self.num_nodes += 1;
+        node
+    }
+
+    fn purge(&mut self) {
+        self.nodes.retain(|_, bucket| {
+            bucket.retain(|node| node.strong_count() > 0);
+            !bucket.is_empty()
+        });
+        self.num_nodes = self.nodes.values().map(|bucket| bucket.len()).sum();
+        self.purge_at = (2 * self.num_nodes).max(1024);
+    }
+}
+
+impl Expr {
+    pub fn functor(iden: impl Into<String>, args: Vec<Expr>) -> Self {
+        Self::new(ExprNode::Functor { iden: iden.into(), args })
+    }
+
+    pub fn variable(iden: impl Into<String>) -> Self {
+        Self::new(ExprNode::Variable { iden: iden.into() })
+    }
+
+    pub fn number(n: Number) -> Self {
+        Self::new(ExprNode::Number(n))
+    }
+
+    pub fn string(s: impl Into<String>) -> Self {
+        Self::new(ExprNode::Str(s.into()))
+    }
+
+    pub fn segment(iden: impl Into<String>) -> Self {
+        Self::new(ExprNode::Segment { iden: iden.into() })
+    }
+
+    pub fn is_segment(&self) -> bool {
+        matches!(self.node(), ExprNode::Segment { .. })
+    }
+
+    fn new(node: ExprNode) -> Self {
+        Expr(Interner::get().lock().unwrap().intern(node))
+    }
+
+    pub fn node(&self) -> &ExprNode {
+        &self.0
+    }
+
+    #[allow(clippy::inherent_to_string_shadow_display)]
+    pub fn to_string(&self) -> String {
+        self.render(Some(Operators::arithmetic()))
+    }
+
+    // Like to_string, but with infix notation for every operator of the symbol table
+    pub fn to_string_with(&self, operators: &Operators) -> String {
+        self.render(Some(operators))
+    }
+
+    // Print the expression with either functor prefix notation or infix notation for
+    // binary operators and parentheses for groups. Expressions can be nested
+    // arbitrarily deep by repeated rewriting, hence the explicit stack instead of recursion.
+    fn render(&self, operators: Option<&Operators>) -> String {
+        enum Frame<'a> {
+            Expr(&'a Expr),
+            Str(&'a str),
+        }
+
+        let mut res = String::new();
+        let mut stack = vec![Frame::Expr(self)];
+        while let Some(frame) = stack.pop() {
+            match frame {
+                Frame::Str(s) => res.push_str(s),
+                Frame::Expr(expr) => match expr.node() {
+                    ExprNode::Variable { iden } => res.push_str(iden),
+                    ExprNode::Number(n) => res.push_str(&n.to_string()),
+                    ExprNode::Str(s) => res.push_str(&quote(s)),
+                    ExprNode::Segment { iden } => {
+                        res.push_str(iden);
+                        res.push_str("..");
+                    },
+                    ExprNode::Functor { iden, args } => {
+                        if let Some(op) = operators.and_then(|operators| operators.infix(iden, args.len())) {
+                            stack.push(Frame::Expr(&args[1]));
+                            stack.push(Frame::Str(" "));
+                            stack.push(Frame::Str(&op.symbol));
+                            stack.push(Frame::Str(" "));
+                            stack.push(Frame::Expr(&args[0]));
+                            continue;
+                        }
+
+                        let close = if operators.is_some() && iden.as_str() == "group" {
+                            res.push('(');
+                            ")"
+                        } else if operators.is_some() && iden.as_str() == "list" {
+                            res.push('[');
+                            "]"
+                        } else {
+                            res.push_str(iden);
+                            res.push('(');
+                            ")"
+                        };
+                        stack.push(Frame::Str(close));
+                        for (i, arg) in args.iter().enumerate().rev() {
+                            stack.push(Frame::Expr(arg));
+                            if i > 0 {
+                                stack.push(Frame::Str(", "));
+                            }
+                        }
+                    }
+                }
+            }
+        }
+        res
+    }
+
+    // Print the expression such that parsing it gives the same expression, which to_string does not guarantee,
+    // e.g., mul(add(a, b), c) is printed as a + b * c. Binary arithmetic operators are printed infix wherever
+    // that parses to the same expression. An operand of the same precedence on the other side than the associativity
+    // of the operator, e.g., the right operand of sub(a, sub(b, c)), and an operand of lower precedence, e.g., 
+    // mul(add(a, b), c), keep functor notation, as parentheses would add a group.
+    pub fn to_source(&self) -> String {
+        self.to_source_with(Operators::arithmetic())
+    }
+
+    // Like to_source, for source that is parsed with the operators of the symbol table
+    pub fn to_source_with(&self, operators: &Operators) -> String {
+        enum Frame<'a> {
+            // An expression, with the minimum precedence for infix notation
+            Expr(&'a Expr, u8),
+            Str(&'a str),
+        }
+
+        let mut res = String::new();
+        let mut stack = vec![Frame::Expr(self, 0)];
+        while let Some(frame) = stack.pop() {
+            match frame {
+                Frame::Str(s) => res.push_str(s),
+                Frame::Expr(expr, min_precedence) => match expr.node() {
+                    ExprNode::Variable { iden } => res.push_str(iden),
+                    ExprNode::Number(n) => res.push_str(&n.to_string()),
+                    ExprNode::Str(s) => res.push_str(&quote(s)),
+                    ExprNode::Segment { iden } => {
+                        res.push_str(iden);
+                        res.push_str("..");
+                    },
+                    ExprNode::Functor { iden, args } => {
+                        match operators.infix(iden, args.len()) {
+                            Some(op) if op.precedence >= min_precedence => {
+                                let (left_precedence, right_precedence) = op.operand_precedences();
+                                stack.extend([
+                                    Frame::Expr(&args[1], right_precedence),
+                                    Frame::Str(" "),
+                                    Frame::Str(&op.symbol),
+                                    Frame::Str(" "),
+                                    Frame::Expr(&args[0], left_precedence)
+                                ]);
+                                continue;
+                            },
+                            _ => {}
+                        }
+                        // A group is a parenthesized expression and a list a bracketed one
+                        if iden == "list" {
+                            res.push('[');
+                            stack.push(Frame::Str("]"));
+                        } else {
+                            if iden != "group" || args.is_empty() {
+                                res.push_str(iden);
+                            }
+                            res.push('(');
+                            stack.push(Frame::Str(")"));
+                        }
+                        for (i, arg) in args.iter().enumerate().rev() {
+                            stack.push(Frame::Expr(arg, 0));
+                            if i > 0 {
+                                stack.push(Frame::Str(", "));
+                            }
+                        }
+                    }
+                }
+            }
+        }
+        res
+    }
+
+    // The number of variables, numbers and functors in the expression, where shared sub-expressions are counted per occurrence.
+    pub fn num_nodes(&self) -> usize {
+        let mut num_nodes = 0;
+        let mut stack = vec![self];
+        while let Some(expr) = stack.pop() {
+            num_nodes += 1;
+            if let ExprNode::Functor { args, .. } = expr.node() {
+                stack.extend(args.iter());
+            }
+        }
+        num_nodes
+    }
+
+    // The identifiers of the variables in the expression, from left to right, with repetitions
+    pub fn variables(&self) -> Vec<&str> {
+        let mut variables = vec![];
+        let mut stack = vec![self];
+        while let Some(expr) = stack.pop() {
+            match expr.node() {
+                ExprNode::Variable { iden } => variables.push(iden.as_str()),
+                ExprNode::Functor { args, .. } => stack.extend(args.iter().rev()),
+                _ => {}
+            }
+        }
+        variables
+    }
+
+    // The pairs of sub-expressions at which two expressions differ, from left to right. Functors with the
+    // same identifier and arity are compared argument-wise, such that only the rewritten regions remain.
+    pub fn diff<'a>(&'a self, other: &'a Expr) -> Vec<(&'a Expr, &'a Expr)> {
+        let mut res = vec![];
+        let mut stack = vec![(self, other)];
+        while let Some((old, new)) = stack.pop() {
+            if old == new {
+                continue;
+            }
+            match (old.node(), new.node()) {
+                (ExprNode::Functor { iden: old_iden, args: old_args }, 
+                 ExprNode::Functor { iden: new_iden, args: new_args }) 
+                    if old_iden == new_iden && old_args.len() == new_args.len() => {
+                    stack.extend(old_args.iter().zip(new_args.iter()).rev());
+                },
+                _ => res.push((old, new))
+            }
+        }
+        res
+    }
+
+    // The sub-expression at the argument indices, e.g., [1, 0] is the first argument of the second argument
+    pub fn at_path(&self, path: &[usize]) -> Option<&Expr> {
+        let mut expr = self;
+        for idx in path {
+            match expr.node() {
+                ExprNode::Functor { args, .. } => expr = args.get(*idx)?,
+                _ => return None
+            }
+        }
+        Some(expr)
+    }
+
+    // Replace the sub-expression at the argument indices, which must exist
+    pub fn replace_at(&self, path: &[usize], new: Expr) -> Expr {
+        let Some((idx, rest)) = path.split_first() else {
+            return new;
+        };
+        let ExprNode::Functor { iden, args } = self.node() else {
+            unreachable!("the path exists in the expression");
+        };
+        let mut args = args.clone();
+        args[*idx] = args[*idx].replace_at(rest, new);
+        Expr::functor(iden.as_str(), args)
+    }
+
+    // Replace every occurrence of the sub-expression, where variables are compared literally instead of matched.
+    // Occurrences inside the replacement are not replaced again.
+    pub fn replace(&self, old: &Expr, new: &Expr) -> Expr {
+        enum Frame<'a> {
+            Enter(&'a Expr),
+            Exit(&'a str, usize),
+        }
+
+        let mut stack = vec![Frame::Enter(self)];
+        let mut results = vec![];
+        while let Some(frame) = stack.pop() {
+            match frame {
+                Frame::Enter(expr) if expr == old => results.push(new.clone()),
+                Frame::Enter(expr) => match expr.node() {
+                    ExprNode::Functor { iden, args } => {
+                        stack.push(Frame::Exit(iden, args.len()));
+                        stack.extend(args.iter().rev().map(Frame::Enter));
+                    },
+                    _ => results.push(expr.clone())
+                },
+                Frame::Exit(iden, num_args) => {
+                    let args = results.split_off(results.len() - num_args);
+                    results.push(Expr::functor(iden, args));
+                }
+            }
+        }
+        results.pop().unwrap()
+    }
+
+    // Splice the arguments of nested functors with the identifier into their parent, 
+    // e.g., flattening add turns add(add(a, b), c) into add(a, b, c).
+    pub fn flatten(&self, iden: &str) -> Expr {
+        self.rebuild_functors(|functor_iden, args| {
+            if functor_iden != iden {
+                return args;
+            }
+            args.into_iter()
+                .flat_map(|arg| match arg.node() {
+                    ExprNode::Functor { iden: arg_iden, args } if arg_iden == iden => args.clone(),
+                    _ => vec![arg]
+                })
+                .collect()
+        })
+    }
+
+    // Nest functors with the identifier and more than two arguments from the left, 
+    // e.g., unflattening add turns add(a, b, c) into add(add(a, b), c).
+    pub fn unflatten(&self, iden: &str) -> Expr {
+        self.rebuild_functors(|functor_iden, args| {
+            if functor_iden != iden || args.len() <= 2 {
+                return args;
+            }
+            let mut args = args.into_iter();
+            let (first, second) = (args.next().unwrap(), args.next().unwrap());
+            let mut rest: Vec<Expr> = args.collect();
+            let last = rest.pop().unwrap();
+            let nested = rest
+                .into_iter()
+                .fold(Expr::functor(iden, vec![first, second]), |nested, arg| Expr::functor(iden, vec![nested, arg]));
+            vec![nested, last]
+        })
+    }
+
+    // Sort the arguments of the commutative functors by the term ordering, such that 
+    // expressions that only differ in the order of those arguments become identical.
+    pub fn canonicalize(&self, commutative: &HashSet<String>) -> Expr {
+        if commutative.is_empty() {
+            return self.clone();
+        }
+        self.rebuild_functors(|iden, mut args| {
+            if commutative.contains(iden) {
+                args.sort_by(Expr::cmp_terms);
+            }
+            args
+        })
+    }
+
+    // A stable total order of expressions, where numbers come before strings, strings before variables, variables 
+    // before segments, and segments before functors. Numbers are ordered by value, strings by content, variables and 
+    // segments by identifier, and functors by identifier, then by arity, and then by their arguments from left to right.
+    pub fn cmp_terms(&self, other: &Expr) -> Ordering {
+        fn rank(node: &ExprNode) -> u8 {
+            match node {
+                ExprNode::Number(_) => 0,
+                ExprNode::Str(_) => 1,
+                ExprNode::Variable { .. } => 2,
+                ExprNode::Segment { .. } => 3,
+                ExprNode::Functor { .. } => 4,
+            }
+        }
+
+        let mut stack = vec![(self, other)];
+        while let Some((left, right)) = stack.pop() {
+            if left == right {
+                continue;
+            }
+            let ord = match (left.node(), right.node()) {
+                (ExprNode::Number(x), ExprNode::Number(y)) => x.total_cmp(y),
+                (ExprNode::Str(x), ExprNode::Str(y)) |
+                (ExprNode::Variable { iden: x }, ExprNode::Variable { iden: y }) |
+                (ExprNode::Segment { iden: x }, ExprNode::Segment { iden: y }) => x.cmp(y),
+                (ExprNode::Functor { iden: x, args: x_args }, ExprNode::Functor { iden: y, args: y_args }) => {
+                    let ord = x.cmp(y).then(x_args.len().cmp(&y_args.len()));
+                    if ord == Ordering::Equal {
+                        stack.extend(x_args.iter().zip(y_args.iter()).rev());
+                    }
+                    ord
+                },
+                (x, y) => rank(x).cmp(&rank(y))
+            };
+            if ord != Ordering::Equal {
+                return ord;
+            }
+        }
+        Ordering::Equal
+    }
+
+    // Rebuild the expression bottom-up, where the rebuilt arguments of each functor 
+    // are replaced by the given function of the identifier and those arguments.
+    fn rebuild_functors(&self, f: impl Fn(&str, Vec<Expr>) -> Vec<Expr>) -> Expr {
+        enum Frame<'a> {
+            Enter(&'a Expr),
+            Exit(&'a str, usize),
+        }
+
+        let mut stack = vec![Frame::Enter(self)];
+        let mut results = vec![];
+        while let Some(frame) = stack.pop() {
+            match frame {
+                Frame::Enter(expr) => match expr.node() {
+                    ExprNode::Functor { iden, args } => {
+                        stack.push(Frame::Exit(iden, args.len()));
+                        stack.extend(args.iter().rev().map(Frame::Enter));
+                    },
+                    _ => results.push(expr.clone())
+                },
+                Frame::Exit(iden, num_args) => {
+                    let args = results.split_off(results.len() - num_args);
+                    results.push(Expr::functor(iden, f(iden, args)));
+                }
+            }
+        }
+        results.pop().unwrap()
+    }
+
+}
+
+// Interned expressions are equal if and only if they share the same node.
+impl PartialEq for Expr {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.0, &other.0)
+    }
+}
+
+impl Eq for Expr {}
+
+impl Hash for Expr {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        Arc::as_ptr(&self.0).hash(state);
+    }
+}
+
+// A string constant in double quotes, as it is written in the source, with escape sequences for the 
+// characters that the lexer unescapes
+pub fn quote(s: &str) -> String {
+    let mut res = String::from('"');
+    for c in s.chars() {
+        match c {
+            '"' => res.push_str("\\\""),
+            '\\' => res.push_str("\\\\"),
+            '\n' => res.push_str("\\n"),
+            '\t' => res.push_str("\\t"),
+            c => res.push(c)
+        }
+    }
+    res.push('"');
+    res
+}
+
+impl Debug for Expr {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:?}", self.node())
+    }
+}
+
+impl Display for Expr {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.render(None))
+    }
+}
+
+impl Drop for ExprNode {
+    fn drop(&mut self) {
+        // Move the arguments that are no longer shared into a flat list, so that
+        // deeply nested expressions are not dropped recursively.
+        if let ExprNode::Functor { args, .. } = self {
+            let mut stack = std::mem::take(args);
+            while let Some(expr) = stack.pop() {
+                if let Some(ExprNode::Functor { args, .. }) = &mut Arc::into_inner(expr.0) {
+                    stack.append(args);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hash_consing() {
+        let left = Expr::functor("f", vec![Expr::variable("x"), Expr::functor("g", vec![])]);
+        let right = Expr::functor("f", vec![Expr::variable("x"), Expr::functor("g", vec![])]);
+
+        assert_eq!(left, right);
+        assert!(Arc::ptr_eq(&left.0, &right.0));
+        assert_ne!(left, Expr::functor("f", vec![Expr::variable("y"), Expr::functor("g", vec![])]));
+    }
+
+    #[test]
+    fn print_expr() {
+        let expr = Expr::functor("mul", vec![
+            Expr::variable("c"),
+            Expr::functor("group", vec![Expr::functor("add", vec![Expr::variable("a"), Expr::variable("b")])])
+        ]);
+
+        assert_eq!(expr.to_string(), "c * (a + b)");
+        assert_eq!(format!("{}", expr), "mul(c, group(add(a, b)))");
+    }
+
+    #[test]
+    fn flatten_expr() {
+        // (a + b) + (c + f(d + e))
+        let expr = Expr::functor("add", vec![
+            Expr::functor("add", vec![Expr::variable("a"), Expr::variable("b")]),
+            Expr::functor("add", vec![
+                Expr::variable("c"),
+                Expr::functor("f", vec![Expr::functor("add", vec![Expr::variable("d"), Expr::variable("e")])])
+            ])
+        ]);
+
+        let flat = expr.flatten("add");
+        assert_eq!(format!("{}", flat), "add(a, b, c, f(add(d, e)))");
+        assert_eq!(flat.unflatten("add").to_string(), "a + b + c + f(d + e)");
+        assert_eq!(format!("{}", flat.unflatten("add")), "add(add(add(a, b), c), f(add(d, e)))");
+        assert_eq!(expr.flatten("mul"), expr);
+    }
+
+    #[test]
+    fn replace_expr() {
+        // f(x, g(x), h(y)), where x is replaced literally, also inside its replacement k(x)
+        let x = Expr::variable("x");
+        let expr = Expr::functor("f", vec![
+            x.clone(),
+            Expr::functor("g", vec![x.clone()]),
+            Expr::functor("h", vec![Expr::variable("y")])
+        ]);
+
+        let replaced = expr.replace(&x, &Expr::functor("k", vec![x.clone()]));
+        assert_eq!(format!("{}", replaced), "f(k(x), g(k(x)), h(y))");
+        assert_eq!(format!("{}", expr.replace(&Expr::functor("g", vec![x]), &Expr::variable("z"))), "f(x, z, h(y))");
+        assert_eq!(expr.replace(&Expr::variable("z"), &Expr::variable("w")), expr);
+    }
+
+    #[test]
+    fn canonicalize_expr() {
+        // mul(add(b, 2), add(a, f(x), 1)) and mul(add(f(x), a, 1), add(2, b))
+        let left = Expr::functor("mul", vec![
+            Expr::functor("add", vec![Expr::variable("b"), Expr::number(Number::Int(2))]),
+            Expr::functor("add", vec![Expr::variable("a"), Expr::functor("f", vec![Expr::variable("x")]), Expr::number(Number::Int(1))]),
+        ]);
+        let right = Expr::functor("mul", vec![
+            Expr::functor("add", vec![Expr::functor("f", vec![Expr::variable("x")]), Expr::variable("a"), Expr::number(Number::Int(1))]),
+            Expr::functor("add", vec![Expr::number(Number::Int(2)), Expr::variable("b")]),
+        ]);
+        let commutative = HashSet::from(["add".to_string(), "mul".to_string()]);
+
+        assert_eq!(left.canonicalize(&commutative), right.canonicalize(&commutative));
+        assert_eq!(format!("{}", left.canonicalize(&commutative)), "mul(add(2, b), add(1, a, f(x)))");
+        assert_eq!(left.canonicalize(&HashSet::new()), left);
+    }
+
+    #[test]
+    fn expr_to_source() {
+        let (a, b, c) = (Expr::variable("a"), Expr::variable("b"), Expr::variable("c"));
+        let sum = Expr::functor("add", vec![a.clone(), b.clone()]);
+        let product = Expr::functor("mul", vec![sum.clone(), c.clone()]);
+        assert_eq!(product.to_string(), "a + b * c");
+        assert_eq!(product.to_source(), "add(a, b) * c");
+
+        let difference = Expr::functor("sub", vec![c.clone(), Expr::functor("sub", vec![a, b])]);
+        assert_eq!(difference.to_source(), "c - sub(a, b)");
+        let group = Expr::functor("mul", vec![c, Expr::functor("group", vec![sum])]);
+        assert_eq!(group.to_source(), "c * (a + b)");
+
+        let list = Expr::functor("list", vec![Expr::variable("x"), Expr::functor("list", vec![])]);
+        assert_eq!(list.to_string(), "[x, []]");
+        assert_eq!(list.to_source(), "[x, []]");
+
+        let label = Expr::functor("case", vec![Expr::string("base case"), Expr::number(Number::Int(0))]);
+        assert_eq!(label.to_source(), "case(\"base case\", 0)");
+        assert_eq!(Expr::string("say \"hi\"\\n").to_source(), r#""say \"hi\"\\n""#);
+    }
+
+    #[test]
+    fn diff_expr() {
+        // f(g(a), b, h(c, d)) and f(g(k), b, h(c, d, e))
+        let old = Expr::functor("f", vec![
+            Expr::functor("g", vec![Expr::variable("a")]),
+            Expr::variable("b"),
+            Expr::functor("h", vec![Expr::variable("c"), Expr::variable("d")])
+        ]);
+        let new = Expr::functor("f", vec![
+            Expr::functor("g", vec![Expr::variable("k")]),
+            Expr::variable("b"),
+            Expr::functor("h", vec![Expr::variable("c"), Expr::variable("d"), Expr::variable("e")])
+        ]);
+
+        let res: Vec<(String, String)> = old.diff(&new)
+            .into_iter()
+            .map(|(old, new)| (old.to_string(), new.to_string()))
+            .collect();
+        assert_eq!(res, vec![
+            ("a".to_string(), "k".to_string()),
+            ("h(c, d)".to_string(), "h(c, d, e)".to_string())
+        ]);
+        assert!(old.diff(&old).is_empty());
+    }
+}
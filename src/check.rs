@@ -0,0 +1,354 @@
+use std::{collections::{HashMap, HashSet}, path::PathBuf};
+
+use crate::{error::Warning, operator::Operators, parser::{Binding, Stmt, Strategy}, library::{read_library, resolve_library_path}, lint::lint_rule, runtime::{arity_mismatches, Rule, DEFAULT_CONTEXT}};
+
+// Validates statements without interpreting them, by tracking what the interpretation would define: the
+// rules, declared functors, checkpoints and named contexts, and whether a pattern matching context has been started.
+// State is kept across calls, such that rules defined in one file are known in the next.
+#[derive(Debug, Default)]
+pub struct Checker {
+    // Number of meta-parameters of the defined rules, by identifier
+    rules: HashMap<String, usize>,
+    // The rules defined by def statements, by identifier
+    definitions: HashMap<String, Rule>,
+    arities: HashMap<String, usize>,
+    operators: Operators,
+    checkpoints: HashSet<String>,
+    // Tags of the defined rules without meta-parameters
+    tags: HashSet<String>,
+    allowed_warnings: HashSet<String>,
+    // Whether a goal is set, which 'prove EXPR == goal' needs
+    has_goal: bool,
+    is_matching: bool,
+    context: String,
+    // Whether each context that is not current is pattern matching, and its checkpoints, by name
+    contexts: HashMap<String, (bool, HashSet<String>)>,
+    // Directory that the paths of imports are relative to, which is the directory of the checked file, like for the interpretation
+    pub base_dir: Option<PathBuf>,
+}
+
+impl Checker {
+    pub fn new() -> Self {
+        Self { context: DEFAULT_CONTEXT.to_string(), ..Self::default() }
+    }
+
+    fn switch_context(&mut self, name: &str, (is_matching, checkpoints): (bool, HashSet<String>)) {
+        let prev_context = (
+            std::mem::replace(&mut self.is_matching, is_matching), 
+            std::mem::replace(&mut self.checkpoints, checkpoints)
+        );
+        let prev_name = std::mem::replace(&mut self.context, name.to_string());
+        self.contexts.insert(prev_name, prev_context);
+    }
+
+    // Whether the context is pattern matching, or a warning if it does not exist
+    fn context_is_matching(&self, name: &str) -> Result<bool, Warning> {
+        if name == self.context {
+            return Ok(self.is_matching);
+        }
+        match self.contexts.get(name) {
+            Some((is_matching, _)) => Ok(*is_matching),
+            None => Err(Warning::ContextDoesNotExist(name.to_string())),
+        }
+    }
+
+    pub fn allow_warning(&mut self, code: String) {
+        self.allowed_warnings.insert(code);
+    }
+
+    // The warnings that interpreting the statements would produce regardless of the expressions,
+    // together with the index of the statement that produces them.
+    pub fn check(&mut self, stmts: &[Stmt]) -> Vec<(usize, Warning)> {
+        let mut warnings = vec![];
+        for (idx, stmt) in stmts.iter().enumerate() {
+            let mut stmt_warnings = vec![];
+            self.check_stmt(stmt, &mut stmt_warnings);
+            warnings.extend(stmt_warnings
+                .into_iter()
+                .filter(|warn| !self.allowed_warnings.contains(warn.code()))
+                .map(|warn| (idx, warn)));
+        }
+        warnings
+    }
+
+    fn check_stmt(&mut self, stmt: &Stmt, warnings: &mut Vec<Warning>) {
+        warnings.extend(arity_mismatches(stmt, &self.arities));
+        match (stmt, self.is_matching) {
+            (Stmt::ExprStmt(_), true) => warnings.push(Warning::ExprHasNoEffect),
+            (Stmt::ApplyStmt { .. }, false) => warnings.push(Warning::ApplyRuleNoEffect),
+            (Stmt::RuleStmt { .. }, false) => warnings.push(Warning::InLineRuleNoEffect),
+            (Stmt::EndStmt { .. }, false) => warnings.push(Warning::EndStmtHasNoEffect),
+            (Stmt::ShowStmt(_), false) => warnings.push(Warning::ShowStmtHasNoEffect),
+            (Stmt::PreviewStmt { .. }, false) => warnings.push(Warning::PreviewStmtHasNoEffect),
+            (Stmt::CheckpointStmt(_) | Stmt::GotoStmt(_), false) => warnings.push(Warning::CheckpointStmtHasNoEffect),
+            (Stmt::RollbackStmt(_) | Stmt::RedoStmt, false) => warnings.push(Warning::RollbackStmtHasNoEffect),
+            (Stmt::CheckStmt(_), false) => warnings.push(Warning::CheckStmtHasNoEffect),
+            (Stmt::FlattenStmt(_) | Stmt::UnflattenStmt(_), false) => warnings.push(Warning::FlattenStmtHasNoEffect),
+            (Stmt::CanonicalizeStmt, false) => warnings.push(Warning::CanonicalizeStmtHasNoEffect),
+            (Stmt::ReplaceStmt { .. }, false) => warnings.push(Warning::ReplaceStmtHasNoEffect),
+            (Stmt::FindStmt(_), false) => warnings.push(Warning::FindStmtHasNoEffect),
+            (Stmt::FocusStmt(_) | Stmt::UnfocusStmt, false) => warnings.push(Warning::FocusStmtHasNoEffect),
+            (Stmt::ExprStmt(_), false) => self.is_matching = true,
+            (Stmt::ApplyStmt { strategy, .. } | Stmt::PreviewStmt { strategy, .. }, true) => {
+                self.check_strategy(strategy, warnings);
+            },
+            (Stmt::CheckpointStmt(name), true) => { self.checkpoints.insert(name.clone()); },
+            (Stmt::GotoStmt(name), true) if !self.checkpoints.contains(name) => {
+                warnings.push(Warning::CheckpointDoesNotExist(name.clone()));
+            },
+            (Stmt::EndStmt { binding, .. }, true) => {
+                if let Some(Binding::Rule(iden)) = binding {
+                    self.rules.insert(iden.clone(), 0);
+                }
+                self.checkpoints.clear();
+                self.is_matching = false;
+            },
+            (Stmt::DefineStmt { iden, params, left, right, tags, auto }, _) => {
+                let rule = Rule { params: params.clone(), left: left.clone(), right: right.clone(), tags: tags.clone(), auto: *auto };
+                warnings.extend(lint_rule(iden, &rule, self.definitions.iter()));
+                self.definitions.insert(iden.clone(), rule);
+                self.rules.insert(iden.clone(), params.len());
+                if params.is_empty() {
+                    self.tags.extend(tags.iter().cloned());
+                }
+            },
+            (Stmt::FunctorStmt(arities), _) => {
+                for (iden, arity) in arities.iter() {
+                    warnings.extend(self.operators.check_arity(iden, *arity));
+                }
+                self.arities.extend(arities.iter().cloned());
+            },
+            (Stmt::InfixStmt(op) | Stmt::OperatorStmt(op), _) => match self.operators.define(op.clone()) {
+                Ok(()) => warnings.extend(self.arities.get(&op.iden).and_then(|arity| self.operators.check_arity(&op.iden, *arity))),
+                Err(warning) => warnings.push(warning),
+            },
+            (Stmt::GoalStmt(goal), _) => self.has_goal = goal.is_some(),
+            (Stmt::ProveStmt { right: None, .. }, _) if !self.has_goal => warnings.push(Warning::GoalNotSet),
+            (Stmt::ProveStmt { rules: idens, .. } |
+             Stmt::CheckTerminationStmt(idens) |
+             Stmt::OverlapsStmt(idens), _) => {
+                for iden in idens.iter().filter(|iden| !self.rules.contains_key(*iden)) {
+                    warnings.push(Warning::RuleDoesNotExist(iden.clone()));
+                }
+            },
+            (Stmt::AllowStmt(codes), _) => {
+                for code in codes {
+                    if Warning::CODES.contains(&code.as_str()) {
+                        self.allowed_warnings.insert(code.clone());
+                    } else {
+                        warnings.push(Warning::UnknownWarningCode(code.clone()));
+                    }
+                }
+            },
+            (Stmt::SilentStmt(stmts), _) => {
+                for stmt in stmts {
+                    self.check_stmt(stmt, warnings);
+                }
+            },
+            // A library that cannot be read is a runtime error, so only the rules of a readable one are known
+            (Stmt::ImportStmt(path), _) => {
+                let stmts = read_library(&resolve_library_path(path, self.base_dir.as_deref())).unwrap_or_default();
+                for stmt in stmts.iter() {
+                    self.check_stmt(stmt, warnings);
+                }
+            },
+            (Stmt::ContextStmt(name), _) => {
+                if *name == self.context || self.contexts.contains_key(name) {
+                    warnings.push(Warning::ContextAlreadyExists(name.clone()));
+                } else {
+                    self.switch_context(name, (false, HashSet::new()));
+                }
+            },
+            (Stmt::SwitchStmt(name), _) if *name != self.context => match self.contexts.remove(name) {
+                Some(context) => self.switch_context(name, context),
+                None => warnings.push(Warning::ContextDoesNotExist(name.clone())),
+            },
+            (Stmt::EqualStmt { left, right }, _) => {
+                let is_matching = self.context_is_matching(left).and_then(|left_is_matching| {
+                    Ok((left_is_matching, self.context_is_matching(right)?))
+                });
+                match is_matching {
+                    Ok((false, _)) => warnings.push(Warning::ContextHasNoExpr(left.clone())),
+                    Ok((_, false)) => warnings.push(Warning::ContextHasNoExpr(right.clone())),
+                    Ok(_) => {},
+                    Err(warn) => warnings.push(warn),
+                }
+            },
+            _ => {}
+        }
+    }
+
+    // Every rule of the strategy must be defined, with an argument for each of its meta-parameters.
+    fn check_strategy(&self, strategy: &Strategy, warnings: &mut Vec<Warning>) {
+        let mut stack = vec![strategy];
+        while let Some(strategy) = stack.pop() {
+            match strategy {
+                Strategy::Rule { iden, args } => match self.rules.get(iden) {
+                    None => warnings.push(Warning::RuleDoesNotExist(iden.clone())),
+                    Some(&expected) if expected != args.len() => warnings.push(Warning::RuleArityMismatch {
+                        iden: iden.clone(),
+                        expected,
+                        got: args.len()
+                    }),
+                    Some(_) => {}
+                },
+                Strategy::Tagged(tag) if !self.tags.contains(tag) => warnings.push(Warning::TagHasNoRules(tag.clone())),
+                Strategy::Tagged(_) => {},
+                Strategy::Try(strategy) | Strategy::Repeat(strategy) => stack.push(strategy),
+                Strategy::Sequence(strategies) | Strategy::Choice(strategies) => stack.extend(strategies.iter().rev()),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{ast::Expr, lexer::Lexer, library::export_library, parser::Parser};
+
+    #[test]
+    fn check_stmts() {
+        let input_string = "
+            functor neg/1
+            def swap as f(x, y) => f(y, x)
+            def scale(k) as g(x) => mul(k, x)
+            f(a, b)
+            apply swap; (scale | missing)* at 0
+            goto start
+            end
+            end
+            neg(a, b)
+        ";
+        let mut lexer = Lexer::new();
+        lexer.lex(input_string);
+
+        let mut parser = Parser::new();
+        let _ = parser.parse(&mut lexer);
+
+        let mut checker = Checker::new();
+        let warnings = checker.check(&parser.stmts);
+        let codes: Vec<(usize, &str)> = warnings.iter().map(|(idx, warn)| (*idx, warn.code())).collect();
+        assert_eq!(codes, vec![
+            (4, "W006"), (4, "W005"),
+            (5, "W015"),
+            (7, "W004"),
+            (8, "W018")
+        ]);
+
+        // Rules and the pattern matching context carry over to the next call, like to the next file
+        let mut lexer = Lexer::new();
+        lexer.lex("allow W005 apply swap at 0 apply other at 0");
+        let mut parser = Parser::new();
+        let _ = parser.parse(&mut lexer);
+        assert!(checker.check(&parser.stmts).is_empty());
+    }
+
+    #[test]
+    fn check_import_relative_to_file() {
+        let mut lexer = Lexer::new();
+        lexer.lex("import \"swap.rxlib\" f(a, b) apply swap at 0");
+        let mut parser = Parser::new();
+        let _ = parser.parse(&mut lexer);
+
+        let base_dir = std::env::temp_dir().join(format!("raxio_check_imports_{}", std::process::id()));
+        let _ = std::fs::create_dir_all(&base_dir);
+        let swap = Rule { params: vec![], left: Expr::functor("f", vec![]), right: Expr::functor("g", vec![]), tags: vec![], auto: false };
+        let library = export_library(&HashMap::from([("swap".to_string(), swap)]), &Operators::default(), &HashSet::new());
+        let _ = std::fs::write(base_dir.join("swap.rxlib"), library);
+
+        // The library is not found relative to the working directory
+        let warnings = Checker::new().check(&parser.stmts);
+        assert!(matches!(&warnings[..], [(2, Warning::RuleDoesNotExist(iden))] if iden == "swap"));
+        let mut checker = Checker::new();
+        checker.base_dir = Some(base_dir.clone());
+        let warnings = checker.check(&parser.stmts);
+        let _ = std::fs::remove_dir_all(&base_dir);
+        assert!(warnings.is_empty());
+    }
+
+    #[test]
+    fn check_infix_operators() {
+        let input_string = "
+            functor oplus/3
+            infix ⊕ as oplus precedence 4
+            infix ⊗ as oplus precedence 4
+            functor add/1
+        ";
+        let mut lexer = Lexer::new();
+        lexer.lex(input_string);
+
+        let mut parser = Parser::new();
+        let _ = parser.parse(&mut lexer);
+
+        let warnings = Checker::new().check(&parser.stmts);
+        let codes: Vec<(usize, &str)> = warnings.iter().map(|(idx, warn)| (*idx, warn.code())).collect();
+        assert_eq!(codes, vec![(1, "W034"), (2, "W033"), (3, "W034")]);
+    }
+
+    #[test]
+    fn check_goal() {
+        let input_string = "
+            prove f(a) == goal
+            goal f(_)
+            prove f(a) == goal
+            goal off
+            prove f(a) == goal
+        ";
+        let mut lexer = Lexer::new();
+        lexer.lex(input_string);
+
+        let mut parser = Parser::new();
+        let _ = parser.parse(&mut lexer);
+
+        let warnings = Checker::new().check(&parser.stmts);
+        assert_eq!(warnings, vec![(0, Warning::GoalNotSet), (4, Warning::GoalNotSet)]);
+    }
+
+    #[test]
+    fn check_unbound_variables() {
+        let input_string = "
+            def swap as f(x, y) => f(y, x)
+            def typo as f(x) => g(y, rest.., y)
+            def lambda as f(x) => g(fresh v, v)
+            def scale(c) as f(x) => mul(c, x)
+            def wildcard as f(_) => _
+        ";
+        let mut lexer = Lexer::new();
+        lexer.lex(input_string);
+
+        let mut parser = Parser::new();
+        let _ = parser.parse(&mut lexer);
+
+        let warnings = Checker::new().check(&parser.stmts);
+        assert_eq!(warnings, vec![
+            (1, Warning::UnboundVariables { rule: "typo".to_string(), vars: vec!["y".to_string(), "rest..".to_string()] }),
+            (4, Warning::UnboundVariables { rule: "wildcard".to_string(), vars: vec!["_".to_string()] }),
+        ]);
+    }
+
+    #[test]
+    fn check_contexts() {
+        let input_string = "
+            f(a)
+            checkpoint start
+            context rhs
+            goto start
+            equal? main, rhs
+            g(a)
+            switch main
+            goto start
+            equal? main, rhs
+            context main
+            switch lhs
+        ";
+        let mut lexer = Lexer::new();
+        lexer.lex(input_string);
+
+        let mut parser = Parser::new();
+        let _ = parser.parse(&mut lexer);
+
+        let warnings = Checker::new().check(&parser.stmts);
+        let codes: Vec<(usize, &str)> = warnings.iter().map(|(idx, warn)| (*idx, warn.code())).collect();
+        assert_eq!(codes, vec![(3, "W014"), (4, "W029"), (9, "W027"), (10, "W028")]);
+    }
+}
@@ -0,0 +1,513 @@
+use std::{fmt::Display, io::{self, IsTerminal}};
+
+use raxio::{error::Warning, parser::Syntax};
+
+use crate::Options;
+
+// A command line option, with the name of its value if it takes one, and the values that are completed by the shell
+pub struct OptionSpec {
+    pub name: &'static str,
+    pub short: Option<&'static str>,
+    pub value: Option<&'static str>,
+    pub choices: &'static [&'static str],
+    pub help: &'static str,
+}
+
+pub const OPTIONS: &[OptionSpec] = &[
+    OptionSpec { name: "--eval", short: Some("-e"), value: Some("PROGRAM"), choices: &[],
+        help: "Interpret the given program after the files, if any" },
+    OptionSpec { name: "--stats", short: None, value: None, choices: &[],
+        help: "Report the elapsed time, number of nodes and number of match attempts of each rule application" },
+    OptionSpec { name: "--jobs", short: None, value: Some("N"), choices: &[],
+        help: "Rewrite sibling sub-expressions of large expressions in parallel on N threads" },
+    OptionSpec { name: "--strict", short: None, value: None, choices: &[],
+        help: "Treat warnings as errors, which stop the interpretation" },
+    OptionSpec { name: "--allow", short: None, value: Some("CODE"), choices: &Warning::CODES,
+        help: "Suppress the warnings with the given code, e.g., W001" },
+    OptionSpec { name: "--quiet", short: None, value: None, choices: &[],
+        help: "Only print the start and result of each pattern matching context, without warnings" },
+    OptionSpec { name: "--diff", short: None, value: None, choices: &[],
+        help: "Only print the rewritten regions of each intermediate expression" },
+    OptionSpec { name: "--watch", short: None, value: None, choices: &[],
+        help: "Interpret the files again whenever one of them changes" },
+    OptionSpec { name: "--no-color", short: None, value: None, choices: &[],
+        help: "Print errors and warnings without colors" },
+    OptionSpec { name: "--syntax", short: None, value: Some("SYNTAX"), choices: &["functor", "sexpr", "auto"],
+        help: "Parse expressions as functor (default), sexpr or auto to detect the syntax" },
+    OptionSpec { name: "--numbers-as-variables", short: None, value: None, choices: &[],
+        help: "Parse numbers as variables that match any expression, like older versions of raxio" },
+    OptionSpec { name: "--no-config", short: None, value: None, choices: &[],
+        help: "Skip the configuration file ~/.config/raxio/config.toml and the REPL history" },
+    OptionSpec { name: "--check", short: None, value: None, choices: &[],
+        help: "Report the files that are not formatted, instead of rewriting them" },
+    OptionSpec { name: "--json", short: None, value: None, choices: &[],
+        help: "Print the tokens and lexical errors as a JSON object" },
+    OptionSpec { name: "--help", short: Some("-h"), value: None, choices: &[],
+        help: "Print the help of the command" },
+];
+
+// A subcommand, with the names of the options it accepts and the values of its arguments that are completed by the shell
+pub struct CommandSpec {
+    pub name: &'static str,
+    pub args: &'static str,
+    pub arg_choices: &'static [&'static str],
+    pub help: &'static str,
+    pub options: &'static [&'static str],
+}
+
+// Options that configure the environment, which are accepted by every command that interprets statements
+const ENV_OPTIONS: [&str; 11] = [
+    "--stats", "--jobs", "--strict", "--allow", "--quiet", "--diff", "--no-color",
+    "--syntax", "--numbers-as-variables", "--no-config", "--help"
+];
+
+// The options of the environment followed by the options of a command that interprets statements, 
+// where the number of options is checked when the constant is evaluated
+const fn with_env_options<const N: usize, const M: usize>(options: [&'static str; N]) -> [&'static str; M] {
+    assert!(M == ENV_OPTIONS.len() + N);
+    let mut all = [""; M];
+    let mut idx = 0;
+    while idx < M {
+        all[idx] = if idx < ENV_OPTIONS.len() { ENV_OPTIONS[idx] } else { options[idx - ENV_OPTIONS.len()] };
+        idx += 1;
+    }
+    all
+}
+
+const RUN_OPTIONS: [&str; 13] = with_env_options(["--eval", "--watch"]);
+
+pub const SHELLS: [&str; 3] = ["bash", "zsh", "fish"];
+
+pub const COMMANDS: &[CommandSpec] = &[
+    CommandSpec { name: "run", args: "[FILE_NAME]...", arg_choices: &[],
+        help: "Interpret the files in order with shared rules, where '-' reads the program from stdin",
+        options: &RUN_OPTIONS },
+    CommandSpec { name: "repl", args: "", arg_choices: &[],
+        help: "Enter the REPL environment, which is the default without arguments",
+        options: &ENV_OPTIONS },
+    CommandSpec { name: "check", args: "[FILE_NAME]...", arg_choices: &[],
+        help: "Report syntax errors and the warnings that are found without interpreting the files, e.g., in CI",
+        options: &["--strict", "--allow", "--no-color", "--syntax", "--numbers-as-variables", "--no-config", "--help"] },
+    CommandSpec { name: "fmt", args: "[FILE_NAME]...", arg_choices: &[],
+        help: "Rewrite the files in the canonical layout, where '-' formats stdin to stdout",
+        options: &["--check", "--no-color", "--syntax", "--numbers-as-variables", "--help"] },
+    CommandSpec { name: "tokens", args: "FILE_NAME", arg_choices: &[],
+        help: "Print the tokens of the file with their kinds and locations, e.g., for syntax highlighting",
+        options: &["--json", "--help"] },
+    CommandSpec { name: "replay", args: "DERIVATION [FILE_NAME]...", arg_choices: &[],
+        help: "Apply each step of a derivation exported as json again with the rules that the files define, e.g., after a refactor",
+        options: &["--quiet", "--no-color", "--syntax", "--numbers-as-variables", "--no-config", "--help"] },
+    CommandSpec { name: "lsp", args: "", arg_choices: &[],
+        help: "Start a language server on stdin and stdout for editors, with diagnostics, definitions, hover and completion",
+        options: &["--help"] },
+    CommandSpec { name: "completions", args: "SHELL", arg_choices: &SHELLS,
+        help: "Print the completion script for the shell, which is bash, zsh or fish",
+        options: &["--help"] },
+    CommandSpec { name: "help", args: "[COMMAND]", arg_choices: &[],
+        help: "Print the help of the command, or an overview of all commands",
+        options: &[] },
+];
+
+impl CommandSpec {
+    fn accepts(&self, option: &OptionSpec) -> bool {
+        self.options.contains(&option.name)
+    }
+
+    pub fn find(name: &str) -> Option<&'static CommandSpec> {
+        COMMANDS.iter().find(|command| command.name == name)
+    }
+
+    fn print_help(&self) {
+        println!("{}\n", self.help);
+        println!("Usage: raxio {} [OPTIONS] {}", self.name, self.args);
+        let options: Vec<&OptionSpec> = OPTIONS.iter().filter(|option| self.accepts(option)).collect();
+        if !options.is_empty() {
+            println!("\nOptions:");
+        }
+        for option in options {
+            let mut flag = match option.short {
+                Some(short) => format!("{}, {}", short, option.name),
+                None => option.name.to_string(),
+            };
+            if let Some(value) = option.value {
+                flag.push_str(&format!(" [{}]", value));
+            }
+            println!("    {:<26}{}", flag, option.help);
+        }
+    }
+}
+
+pub fn print_overview() {
+    println!("Pattern matching and rewriting of expressions\n");
+    println!("Usage: raxio [COMMAND] [OPTIONS] [ARGS]\n");
+    println!("Commands:");
+    for command in COMMANDS {
+        println!("    {:<14}{}", command.name, command.help);
+    }
+    println!("\nWithout a command, raxio runs the given files, or enters the REPL if there are none, e.g.,");
+    println!("    $ raxio [FILE_NAME] [FILE_NAME] ...");
+    println!("    $ cat [FILE_NAME] | raxio -");
+    println!("    $ raxio -e [PROGRAM]");
+    println!("\nEnter 'raxio help [COMMAND]' to see the options of a command.");
+}
+
+// What to do after parsing the command line
+#[derive(Debug, PartialEq)]
+pub enum Command {
+    Run { file_names: Vec<String>, eval_source: Option<String> },
+    Repl,
+    Check { file_names: Vec<String> },
+    Fmt { file_names: Vec<String>, check: bool },
+    Tokens { file_name: String, json: bool },
+    Replay { derivation: String, file_names: Vec<String> },
+    Lsp,
+    Completions(String),
+    Help(Option<&'static str>),
+}
+
+#[derive(Debug, PartialEq)]
+pub enum CliError {
+    UnknownCommand(String),
+    UnknownOption { command: &'static str, option: String },
+    MissingValue { option: &'static str },
+    InvalidValue { option: &'static str, value: String },
+    UnexpectedArgument { command: &'static str, arg: String },
+    MissingArgument { command: &'static str, arg: &'static str },
+    CannotWatch,
+}
+
+impl Display for CliError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CliError::UnknownCommand(name) =>
+                write!(f, "unknown command '{}'", name),
+            CliError::UnknownOption { command, option } =>
+                write!(f, "unknown option '{}' for command '{}'", option, command),
+            CliError::MissingValue { option } =>
+                write!(f, "missing value of option '{}'", option),
+            CliError::InvalidValue { option, value } =>
+                write!(f, "invalid value '{}' of option '{}'", value, option),
+            CliError::UnexpectedArgument { command, arg } =>
+                write!(f, "unexpected argument '{}' for command '{}'", arg, command),
+            CliError::MissingArgument { command, arg } =>
+                write!(f, "missing argument {} for command '{}'", arg, command),
+            // The standard input cannot be read again after a change
+            CliError::CannotWatch =>
+                write!(f, "option '--watch' requires files, which cannot include the standard input '-'"),
+        }
+    }
+}
+
+impl CliError {
+    // Usage of the command that the error is about, if any
+    pub fn command(&self) -> Option<&'static CommandSpec> {
+        match self {
+            CliError::UnknownCommand(_) | CliError::MissingValue { .. } | CliError::InvalidValue { .. } => None,
+            CliError::CannotWatch => CommandSpec::find("run"),
+            CliError::UnknownOption { command, .. } |
+            CliError::UnexpectedArgument { command, .. } |
+            CliError::MissingArgument { command, .. } => CommandSpec::find(command),
+        }
+    }
+}
+
+pub fn default_options() -> Options {
+    Options {
+        stats: false,
+        strict: false,
+        quiet: false,
+        diff: false,
+        jobs: 1,
+        watch: false,
+        // Only color diagnostics when they are printed to a terminal
+        color: io::stdout().is_terminal(),
+        allowed_warnings: vec![],
+        syntax: Some(Syntax::Functor),
+        numbers_as_variables: false,
+        no_config: false,
+        prompt: None,
+        prelude: vec![],
+        history: None
+    }
+}
+
+// Parse the arguments after the program name. Without a command, the arguments are those of 'run',
+// or of 'repl' if there are no files and no program to evaluate, like before raxio had commands.
+pub fn parse_args(args: Vec<String>) -> Result<(Command, Options), CliError> {
+    let mut args = args.into_iter().peekable();
+    // A file that has the name of a command is interpreted with 'raxio run NAME'
+    let explicit = args.peek().and_then(|arg| CommandSpec::find(arg));
+    if explicit.is_some() {
+        args.next();
+    }
+    let spec = explicit.unwrap_or(&COMMANDS[0]);
+
+    let mut options = default_options();
+    let mut positionals = vec![];
+    let mut eval_source = None;
+    let mut check = false;
+    let mut json = false;
+    // Options may be given before or after the file names
+    while let Some(arg) = args.next() {
+        let option = OPTIONS.iter().find(|option| option.name == arg || option.short == Some(arg.as_str()));
+        let option = match option {
+            Some(option) if spec.accepts(option) => option,
+            _ if arg.starts_with('-') && arg != "-" => {
+                return Err(CliError::UnknownOption { command: spec.name, option: arg });
+            },
+            _ => {
+                positionals.push(arg);
+                continue;
+            }
+        };
+        let value = match option.value {
+            Some(_) => Some(args.next().ok_or(CliError::MissingValue { option: option.name })?),
+            None => None,
+        };
+        let invalid_value = || CliError::InvalidValue { option: option.name, value: value.clone().unwrap_or_default() };
+        match option.name {
+            "--help" => return Ok((Command::Help(explicit.map(|command| command.name)), options)),
+            "--eval" => eval_source = value,
+            "--check" => check = true,
+            "--json" => json = true,
+            "--stats" => options.stats = true,
+            "--strict" => options.strict = true,
+            "--quiet" => options.quiet = true,
+            "--diff" => options.diff = true,
+            "--watch" => options.watch = true,
+            "--no-color" => options.color = false,
+            "--numbers-as-variables" => options.numbers_as_variables = true,
+            "--no-config" => options.no_config = true,
+            "--jobs" => match value.as_deref().and_then(|n| n.parse().ok()) {
+                Some(jobs) if jobs > 0 => options.jobs = jobs,
+                _ => return Err(invalid_value())
+            },
+            "--allow" => match value {
+                Some(ref code) if Warning::CODES.contains(&code.as_str()) => options.allowed_warnings.push(code.clone()),
+                _ => return Err(invalid_value())
+            },
+            "--syntax" => match value.as_deref() {
+                Some("functor") => options.syntax = Some(Syntax::Functor),
+                Some("sexpr") => options.syntax = Some(Syntax::SExpr),
+                Some("auto") => options.syntax = None,
+                _ => return Err(invalid_value())
+            },
+            _ => unreachable!("option '{}' is not handled", option.name)
+        }
+    }
+
+    if options.watch && (positionals.is_empty() || positionals.iter().any(|file_name| file_name == "-")) {
+        return Err(CliError::CannotWatch);
+    }
+
+    let command = match (spec.name, explicit.is_some()) {
+        ("run", false) if positionals.is_empty() && eval_source.is_none() => Command::Repl,
+        ("run", true) if positionals.is_empty() && eval_source.is_none() => {
+            return Err(CliError::MissingArgument { command: spec.name, arg: spec.args });
+        },
+        ("run", _) => Command::Run { file_names: positionals, eval_source },
+        ("check" | "fmt", _) if positionals.is_empty() => {
+            return Err(CliError::MissingArgument { command: spec.name, arg: spec.args });
+        },
+        ("check", _) => Command::Check { file_names: positionals },
+        ("fmt", _) => Command::Fmt { file_names: positionals, check },
+        ("repl" | "lsp", _) => match positionals.pop() {
+            Some(arg) => return Err(CliError::UnexpectedArgument { command: spec.name, arg }),
+            None if spec.name == "lsp" => Command::Lsp,
+            None => Command::Repl,
+        },
+        ("replay", _) if positionals.is_empty() => {
+            return Err(CliError::MissingArgument { command: spec.name, arg: spec.args });
+        },
+        ("replay", _) => Command::Replay { derivation: positionals.remove(0), file_names: positionals },
+        ("tokens", _) => match positionals.len() {
+            0 => return Err(CliError::MissingArgument { command: spec.name, arg: spec.args }),
+            1 => Command::Tokens { file_name: positionals.remove(0), json },
+            _ => return Err(CliError::UnexpectedArgument { command: spec.name, arg: positionals.remove(1) }),
+        },
+        ("completions", _) => match positionals.len() {
+            0 => return Err(CliError::MissingArgument { command: spec.name, arg: spec.args }),
+            1 if SHELLS.contains(&positionals[0].as_str()) => Command::Completions(positionals.remove(0)),
+            _ => return Err(CliError::UnexpectedArgument { command: spec.name, arg: positionals.remove(0) }),
+        },
+        (_, _) => match positionals.first() {
+            None => Command::Help(None),
+            Some(name) => match CommandSpec::find(name) {
+                Some(command) => Command::Help(Some(command.name)),
+                None => return Err(CliError::UnknownCommand(name.clone())),
+            }
+        },
+    };
+    Ok((command, options))
+}
+
+pub fn print_help(command: Option<&str>) {
+    match command.and_then(CommandSpec::find) {
+        Some(command) => command.print_help(),
+        None => print_overview(),
+    }
+}
+
+// Completion script of the shell, which completes the commands, their options,
+// the values of options with a fixed set of values, and file names.
+pub fn completions(shell: &str) -> String {
+    match shell {
+        "bash" => bash_completions(),
+        "zsh" => zsh_completions(),
+        _ => fish_completions(),
+    }
+}
+
+fn option_names(command: &CommandSpec) -> Vec<&'static str> {
+    OPTIONS
+        .iter()
+        .filter(|option| command.accepts(option))
+        .flat_map(|option| option.short.into_iter().chain([option.name]))
+        .collect()
+}
+
+fn bash_completions() -> String {
+    let command_names: Vec<&str> = COMMANDS.iter().map(|command| command.name).collect();
+    let mut script = String::from("_raxio() {\n    local cur=\"${COMP_WORDS[COMP_CWORD]}\" prev=\"${COMP_WORDS[COMP_CWORD-1]}\"\n");
+    script.push_str("    case \"$prev\" in\n");
+    for option in OPTIONS.iter().filter(|option| !option.choices.is_empty()) {
+        script.push_str(&format!("        {}) COMPREPLY=($(compgen -W \"{}\" -- \"$cur\")); return ;;\n", option.name, option.choices.join(" ")));
+    }
+    script.push_str("    esac\n");
+    script.push_str(&format!("    if [ \"$COMP_CWORD\" -eq 1 ]; then\n        COMPREPLY=($(compgen -W \"{} {}\" -- \"$cur\") $(compgen -f -- \"$cur\"))\n        return\n    fi\n",
+        command_names.join(" "),
+        option_names(&COMMANDS[0]).join(" ")
+    ));
+    script.push_str("    case \"${COMP_WORDS[1]}\" in\n");
+    for command in COMMANDS {
+        let words = option_names(command).into_iter().chain(command.arg_choices.iter().copied());
+        let words: Vec<&str> = if command.name == "help" { command_names.clone() } else { words.collect() };
+        let files = if ["run", "check", "fmt", "tokens", "replay"].contains(&command.name) { " $(compgen -f -- \"$cur\")" } else { "" };
+        script.push_str(&format!("        {}) COMPREPLY=($(compgen -W \"{}\" -- \"$cur\"){}) ;;\n", command.name, words.join(" "), files));
+    }
+    script.push_str(&format!("        *) COMPREPLY=($(compgen -W \"{}\" -- \"$cur\") $(compgen -f -- \"$cur\")) ;;\n", option_names(&COMMANDS[0]).join(" ")));
+    script.push_str("    esac\n}\ncomplete -o filenames -F _raxio raxio\n");
+    script
+}
+
+// Escape a description for the brackets of an _arguments specification in single quotes
+fn zsh_escape(s: &str) -> String {
+    s.replace('\'', "'\\''").replace('[', "\\[").replace(']', "\\]").replace(':', "\\:")
+}
+
+fn zsh_arguments(command: &CommandSpec) -> Vec<String> {
+    let mut specs: Vec<String> = OPTIONS
+        .iter()
+        .filter(|option| command.accepts(option))
+        .map(|option| {
+            // The braces are expanded by the shell into a specification per name, so they are not quoted
+            let names = match option.short {
+                Some(short) => format!("{{{},{}}}'", short, option.name),
+                None => format!("'{}", option.name),
+            };
+            let value = match option.value {
+                Some(value) if option.choices.is_empty() => format!(":{}:", value),
+                Some(value) => format!(":{}:({})", value, option.choices.join(" ")),
+                None => String::new(),
+            };
+            format!("{}[{}]{}'", names, zsh_escape(option.help), value)
+        })
+        .collect();
+    match command.name {
+        "run" | "check" | "fmt" | "replay" => specs.push("'*:file:_files'".to_string()),
+        "tokens" => specs.push("':file:_files'".to_string()),
+        "help" => specs.push(format!("'1:command:({})'", COMMANDS.iter().map(|c| c.name).collect::<Vec<_>>().join(" "))),
+        _ if !command.arg_choices.is_empty() => specs.push(format!("'1:{}:({})'", command.args.to_lowercase(), command.arg_choices.join(" "))),
+        _ => {}
+    }
+    specs
+}
+
+fn zsh_completions() -> String {
+    let mut script = String::from("#compdef raxio\n\n_raxio() {\n    local -a commands\n    commands=(\n");
+    for command in COMMANDS {
+        script.push_str(&format!("        '{}:{}'\n", command.name, zsh_escape(command.help)));
+    }
+    script.push_str("    )\n    if (( CURRENT == 2 )); then\n        _describe 'command' commands\n        _files\n        return\n    fi\n");
+    script.push_str("    local name=$words[2]\n    if (( ! ${+commands[(r)$name:*]} )); then\n        name=run\n    else\n        shift words\n        (( CURRENT-- ))\n    fi\n");
+    script.push_str("    case $name in\n");
+    for command in COMMANDS {
+        script.push_str(&format!("        {})\n            _arguments \\\n", command.name));
+        for spec in zsh_arguments(command) {
+            script.push_str(&format!("                {} \\\n", spec));
+        }
+        script.push_str("            ;;\n");
+    }
+    script.push_str("    esac\n}\n\n_raxio \"$@\"\n");
+    script
+}
+
+fn fish_completions() -> String {
+    let command_names: Vec<&str> = COMMANDS.iter().map(|command| command.name).collect();
+    let mut script = String::from("complete -c raxio -e\n");
+    for command in COMMANDS {
+        script.push_str(&format!("complete -c raxio -n '__fish_use_subcommand' -a {} -d '{}'\n", command.name, command.help.replace('\'', "\\'")));
+    }
+    for command in COMMANDS {
+        let condition = format!("__fish_seen_subcommand_from {}", command.name);
+        for option in OPTIONS.iter().filter(|option| command.accepts(option)) {
+            let mut line = format!("complete -c raxio -n '{}' -l {}", condition, option.name.trim_start_matches("--"));
+            if let Some(short) = option.short {
+                line.push_str(&format!(" -s {}", short.trim_start_matches('-')));
+            }
+            if option.value.is_some() {
+                line.push_str(" -r");
+            }
+            if !option.choices.is_empty() {
+                line.push_str(&format!(" -f -a '{}'", option.choices.join(" ")));
+            }
+            line.push_str(&format!(" -d '{}'\n", option.help.replace('\'', "\\'")));
+            script.push_str(&line);
+        }
+        let choices = if command.name == "help" { command_names.clone() } else { command.arg_choices.to_vec() };
+        if !choices.is_empty() {
+            script.push_str(&format!("complete -c raxio -n '{}' -f -a '{}'\n", condition, choices.join(" ")));
+        }
+    }
+    script
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(s: &str) -> Vec<String> {
+        s.split_whitespace().map(str::to_string).collect()
+    }
+
+    #[test]
+    fn parse_commands() {
+        let (command, options) = parse_args(args("run a.rx --strict -e f(x) b.rx")).unwrap();
+        assert_eq!(command, Command::Run {
+            file_names: vec!["a.rx".to_string(), "b.rx".to_string()],
+            eval_source: Some("f(x)".to_string())
+        });
+        assert!(options.strict);
+
+        // Without a command, like before commands existed
+        assert_eq!(parse_args(args("a.rx")).unwrap().0, Command::Run { file_names: vec!["a.rx".to_string()], eval_source: None });
+        assert_eq!(parse_args(args("--quiet")).unwrap().0, Command::Repl);
+        assert_eq!(parse_args(args("repl --help")).unwrap().0, Command::Help(Some("repl")));
+        assert_eq!(parse_args(args("completions fish")).unwrap().0, Command::Completions("fish".to_string()));
+        assert_eq!(parse_args(args("check a.rx")).unwrap().0, Command::Check { file_names: vec!["a.rx".to_string()] });
+        assert_eq!(parse_args(args("fmt --check a.rx")).unwrap().0, Command::Fmt { file_names: vec!["a.rx".to_string()], check: true });
+        assert_eq!(parse_args(args("tokens a.rx --json")).unwrap().0, Command::Tokens { file_name: "a.rx".to_string(), json: true });
+        assert_eq!(parse_args(args("replay d.json a.rx")).unwrap().0, Command::Replay {
+            derivation: "d.json".to_string(),
+            file_names: vec!["a.rx".to_string()]
+        });
+
+        assert_eq!(parse_args(args("repl --watch")).err(), Some(CliError::UnknownOption { command: "repl", option: "--watch".to_string() }));
+        // Every option of the environment is accepted by run too
+        let run = CommandSpec::find("run").unwrap();
+        assert!(OPTIONS.iter().filter(|option| ENV_OPTIONS.contains(&option.name)).all(|option| run.accepts(option)));
+        assert_eq!(parse_args(args("run")).err(), Some(CliError::MissingArgument { command: "run", arg: "[FILE_NAME]..." }));
+        assert_eq!(parse_args(args("help rnu")).err(), Some(CliError::UnknownCommand("rnu".to_string())));
+        assert_eq!(parse_args(args("--jobs 0")).err(), Some(CliError::InvalidValue { option: "--jobs", value: "0".to_string() }));
+    }
+}
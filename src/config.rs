@@ -0,0 +1,302 @@
+use std::{env, error::Error, fs, io, path::{Path, PathBuf}};
+
+use crate::error::ConfigError;
+
+// Name of the configuration file in the configuration directory
+pub const CONFIG_FILE_NAME: &str = "config.toml";
+
+// Name of the file in the data directory with the previous inputs of the REPL
+pub const HISTORY_FILE_NAME: &str = "history";
+
+// Settings of the user configuration file, which apply to both the REPL and the interpretation of files.
+#[derive(Debug, PartialEq)]
+pub struct UserConfig {
+    // Prompt of the REPL, instead of '> '
+    pub prompt: Option<String>,
+
+    // Files that are interpreted before all other files and the REPL, e.g., with frequently used rules
+    pub prelude: Vec<PathBuf>,
+
+    // If false, errors and warnings are never colored
+    pub color: bool,
+
+    // Maximum number of REPL inputs that are kept in the history file, which is not written if 0
+    pub history_size: usize,
+}
+
+impl Default for UserConfig {
+    fn default() -> Self {
+        Self {
+            prompt: None,
+            prelude: vec![],
+            color: true,
+            history_size: 500
+        }
+    }
+}
+
+// Value of a setting, which is a subset of the values of TOML
+#[derive(Debug, PartialEq)]
+enum Value {
+    Str(String),
+    Int(usize),
+    Bool(bool),
+    Array(Vec<String>),
+}
+
+impl UserConfig {
+    // The directory is $XDG_CONFIG_HOME/raxio, or ~/.config/raxio if XDG_CONFIG_HOME is not set.
+    pub fn dir() -> Option<PathBuf> {
+        env::var_os("XDG_CONFIG_HOME")
+            .filter(|dir| !dir.is_empty())
+            .map(PathBuf::from)
+            .or_else(|| home_dir().map(|home| home.join(".config")))
+            .map(|dir| dir.join("raxio"))
+    }
+
+    // Read the configuration file in the directory, where a missing file is the default configuration.
+    pub fn load(dir: &Path) -> Result<Self, Box<dyn Error>> {
+        match fs::read_to_string(dir.join(CONFIG_FILE_NAME)) {
+            Ok(source) => Ok(Self::parse(&source, dir)?),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(e) => Err(Box::new(e)),
+        }
+    }
+
+    // Parse the configuration, which has one 'key = value' pair per line and '#' comments, e.g.,
+    //     prompt = "λ> "
+    //     prelude = ["~/rules/calculus.rx", "peano.rx"]
+    //     color = false
+    //     history_size = 100
+    // Relative prelude paths are relative to the configuration directory.
+    pub fn parse(source: &str, dir: &Path) -> Result<Self, ConfigError> {
+        let mut config = Self::default();
+        for (idx, line) in source.lines().enumerate() {
+            let line_num = idx + 1;
+            let line = strip_comment(line).trim();
+            if line.is_empty() {
+                continue;
+            }
+            let Some((key, value)) = line.split_once('=') else {
+                return Err(ConfigError::InvalidLine { line: line_num });
+            };
+            let key = key.trim();
+            let value = parse_value(value.trim());
+            let invalid_value = |expected: &'static str| ConfigError::InvalidValue {
+                line: line_num,
+                key: key.to_string(),
+                expected
+            };
+            match (key, value) {
+                ("prompt", Some(Value::Str(prompt))) => config.prompt = Some(prompt),
+                ("prompt", _) => return Err(invalid_value("a string")),
+                ("prelude", Some(Value::Array(paths))) => {
+                    config.prelude = paths.iter().map(|path| resolve_path(path, dir)).collect();
+                },
+                ("prelude", _) => return Err(invalid_value("an array of strings")),
+                ("color", Some(Value::Bool(color))) => config.color = color,
+                ("color", _) => return Err(invalid_value("true or false")),
+                ("history_size", Some(Value::Int(size))) => config.history_size = size,
+                ("history_size", _) => return Err(invalid_value("a non-negative integer")),
+                _ => return Err(ConfigError::UnknownKey { line: line_num, key: key.to_string() }),
+            }
+        }
+        Ok(config)
+    }
+}
+
+// The most recent inputs of the REPL, one per line, which are kept across sessions in a file.
+#[derive(Debug)]
+pub struct History {
+    pub path: PathBuf,
+    pub entries: Vec<String>,
+    pub max_size: usize,
+}
+
+impl History {
+    // The directory is $XDG_DATA_HOME/raxio, or ~/.local/share/raxio if XDG_DATA_HOME is not set,
+    // such that the history is kept apart from the configuration.
+    pub fn dir() -> Option<PathBuf> {
+        env::var_os("XDG_DATA_HOME")
+            .filter(|dir| !dir.is_empty())
+            .map(PathBuf::from)
+            .or_else(|| home_dir().map(|home| home.join(".local").join("share")))
+            .map(|dir| dir.join("raxio"))
+    }
+
+    // Read the entries of the history file, where a missing or unreadable file is an empty history.
+    pub fn load(path: PathBuf, max_size: usize) -> Self {
+        let entries = fs::read_to_string(&path)
+            .map(|data| data.lines().map(str::to_string).collect())
+            .unwrap_or_default();
+        let mut history = Self { path, entries, max_size };
+        history.truncate();
+        history
+    }
+
+    // Add the input as it was entered, where only the lines of a multi-line input are joined, as an entry is a line of the file
+    // and whitespace does not matter in a statement. The file is written after every input, so that the history survives 
+    // an interrupted REPL.
+    pub fn push(&mut self, input: &str) -> io::Result<()> {
+        self.entries.push(input.lines().collect::<Vec<_>>().join(" "));
+        self.truncate();
+        let mut data = self.entries.join("\n");
+        data.push('\n');
+        if let Some(dir) = self.path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        fs::write(&self.path, data)
+    }
+
+    // The entry that an input recalls, which is '!!' for the last entry, or '!N' for the Nth entry as listed by 'history'.
+    pub fn recall(&self, input: &str) -> Option<&str> {
+        let idx = match input.strip_prefix('!')? {
+            "!" => self.entries.len().checked_sub(1)?,
+            n => n.parse::<usize>().ok()?.checked_sub(1)?,
+        };
+        self.entries.get(idx).map(String::as_str)
+    }
+
+    fn truncate(&mut self) {
+        let excess = self.entries.len().saturating_sub(self.max_size);
+        self.entries.drain(..excess);
+    }
+}
+
+fn home_dir() -> Option<PathBuf> {
+    env::var_os("HOME").filter(|home| !home.is_empty()).map(PathBuf::from)
+}
+
+// A path that starts with '~/' is relative to the home directory, other relative paths to the given directory.
+fn resolve_path(path: &str, dir: &Path) -> PathBuf {
+    match (path.strip_prefix("~/"), home_dir()) {
+        (Some(rest), Some(home)) => home.join(rest),
+        _ => dir.join(path),
+    }
+}
+
+// Remove a comment from the line, unless the '#' is inside a string
+fn strip_comment(line: &str) -> &str {
+    let mut in_string = false;
+    let mut escaped = false;
+    for (i, c) in line.char_indices() {
+        match c {
+            '\\' if in_string => { escaped = !escaped; continue; },
+            '"' if !escaped => in_string = !in_string,
+            '#' if !in_string => return &line[..i],
+            _ => {}
+        }
+        escaped = false;
+    }
+    line
+}
+
+fn parse_value(value: &str) -> Option<Value> {
+    match value {
+        "true" => return Some(Value::Bool(true)),
+        "false" => return Some(Value::Bool(false)),
+        _ => {}
+    }
+    if let Some(items) = value.strip_prefix('[').and_then(|value| value.strip_suffix(']')) {
+        let mut strings = vec![];
+        let mut rest = items.trim();
+        while !rest.is_empty() {
+            let (string, after) = parse_string(rest)?;
+            strings.push(string);
+            rest = after.trim_start();
+            // A trailing comma is allowed, as in TOML
+            rest = match rest.strip_prefix(',') {
+                Some(after) => after.trim_start(),
+                None if rest.is_empty() => rest,
+                None => return None,
+            };
+        }
+        return Some(Value::Array(strings));
+    }
+    if value.starts_with('"') {
+        return match parse_string(value)? {
+            (string, "") => Some(Value::Str(string)),
+            _ => None,
+        };
+    }
+    // TOML allows underscores between digits, e.g., 1_000
+    value.replace('_', "").parse().ok().map(Value::Int)
+}
+
+// Parse a basic string at the start of the text, and return it together with the text after it.
+fn parse_string(text: &str) -> Option<(String, &str)> {
+    let mut chars = text.strip_prefix('"')?.char_indices();
+    let mut string = String::new();
+    while let Some((i, c)) = chars.next() {
+        match c {
+            '"' => return Some((string, &text[i + 2..])),
+            '\\' => match chars.next()?.1 {
+                'n' => string.push('\n'),
+                't' => string.push('\t'),
+                c @ ('"' | '\\') => string.push(c),
+                _ => return None,
+            },
+            c => string.push(c),
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_config() {
+        let source = [
+            "# raxio configuration",
+            "prompt = \"λ> \"   # with a trailing comment",
+            "prelude = [\"rules.rx\", \"/usr/share/raxio/peano.rx\",]",
+            "color = false",
+            "history_size = 1_000",
+        ].join("\n");
+        let dir = Path::new("/config/raxio");
+
+        assert_eq!(UserConfig::parse(&source, dir), Ok(UserConfig {
+            prompt: Some("λ> ".to_string()),
+            prelude: vec![PathBuf::from("/config/raxio/rules.rx"), PathBuf::from("/usr/share/raxio/peano.rx")],
+            color: false,
+            history_size: 1000,
+        }));
+        assert_eq!(UserConfig::parse("", dir), Ok(UserConfig::default()));
+        assert_eq!(UserConfig::parse("\ncolor = \"no\"", dir), Err(ConfigError::InvalidValue {
+            line: 2,
+            key: "color".to_string(),
+            expected: "true or false"
+        }));
+        assert_eq!(UserConfig::parse("theme = \"dark\"", dir), Err(ConfigError::UnknownKey { line: 1, key: "theme".to_string() }));
+        assert_eq!(UserConfig::parse("[repl]", dir), Err(ConfigError::InvalidLine { line: 1 }));
+    }
+
+    #[test]
+    fn recall_history() {
+        let history = History {
+            path: PathBuf::from("history"),
+            entries: vec!["def swap as f(x, y) => f(y, x)".to_string(), "f(a, b)".to_string()],
+            max_size: 10
+        };
+        assert_eq!(history.recall("!1"), Some("def swap as f(x, y) => f(y, x)"));
+        assert_eq!(history.recall("!!"), Some("f(a, b)"));
+        assert_eq!(history.recall("!0"), None);
+        assert_eq!(history.recall("!3"), None);
+        assert_eq!(history.recall("f(a, b)"), None);
+    }
+
+    #[test]
+    fn push_history() {
+        let path = std::env::temp_dir().join(format!("raxio_history_{}", std::process::id())).join("history");
+        let mut history = History::load(path.clone(), 10);
+        // Spaces in string constants are kept, and so are the indentation and spaces of each line
+        history.push("f(\"a  b\")").unwrap();
+        history.push("def swap as f(x,  y)\n    => f(y, x)").unwrap();
+        history.push("apply swap at 0").unwrap();
+        assert_eq!(history.entries, vec!["f(\"a  b\")", "def swap as f(x,  y)     => f(y, x)", "apply swap at 0"]);
+        assert_eq!(History::load(path.clone(), 10).entries, history.entries);
+        let _ = fs::remove_dir_all(path.parent().unwrap());
+    }
+}
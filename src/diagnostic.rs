@@ -0,0 +1,123 @@
+use std::fmt::Display;
+
+use crate::lexer::Span;
+
+const RED: &str = "\x1B[1;31m";
+const YELLOW: &str = "\x1B[1;33m";
+const BLUE: &str = "\x1B[1;34m";
+const RESET: &str = "\x1B[0m";
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+// Renders errors and warnings together with the offending line of the source,
+// where the location of the error is underlined by carets.
+pub struct Renderer<'a> {
+    // Name of the source, e.g., the file name
+    pub name: &'a str,
+    pub source: &'a str,
+    // If true, labels and underlines are colored with ANSI escape codes
+    pub color: bool,
+}
+
+impl<'a> Renderer<'a> {
+    pub fn new(name: &'a str, source: &'a str, color: bool) -> Self {
+        Self { name, source, color }
+    }
+
+    pub fn render(&self, message: &dyn Display, span: Option<Span>, severity: Severity) -> String {
+        let message = message.to_string();
+        let message = message.trim_end();
+        let mut res = String::new();
+
+        // Color the label in front of the first line of the message, e.g., 'Warning:'
+        match message.split_once(':') {
+            Some((label, rest)) => {
+                res.push_str(&self.paint(&format!("{}:", label), severity));
+                res.push_str(rest);
+            },
+            None => res.push_str(message)
+        }
+        res.push('\n');
+
+        let Some(span) = span else { return res; };
+        let start = span.start.min(self.source.len());
+        let line_start = self.source[..start].rfind('\n').map_or(0, |i| i + 1);
+        let line_end = self.source[start..].find('\n').map_or(self.source.len(), |i| start + i);
+        let line = self.source[line_start..line_end].trim_end_matches('\r');
+        let line_num = self.source[..line_start].matches('\n').count() + 1;
+        let column = self.source[line_start..start].chars().count();
+        let width = self.source[start..span.end.clamp(start, line_end)].chars().count().max(1);
+
+        let gutter = " ".repeat(line_num.to_string().len());
+        res.push_str(&format!("{}{} {}:{}:{}\n", gutter, self.gutter("-->"), self.name, line_num, column + 1));
+        res.push_str(&format!("{} {}\n", gutter, self.gutter("|")));
+        res.push_str(&format!("{} {} {}\n", self.gutter(&line_num.to_string()), self.gutter("|"), line));
+        res.push_str(&format!("{} {} {}{}\n", gutter, self.gutter("|"), " ".repeat(column), self.paint(&"^".repeat(width), severity)));
+        res
+    }
+
+    fn paint(&self, s: &str, severity: Severity) -> String {
+        if !self.color {
+            return s.to_string();
+        }
+        let color = match severity {
+            Severity::Error => RED,
+            Severity::Warning => YELLOW,
+        };
+        format!("{}{}{}", color, s, RESET)
+    }
+
+    fn gutter(&self, s: &str) -> String {
+        if self.color {
+            format!("{}{}{}", BLUE, s, RESET)
+        } else {
+            s.to_string()
+        }
+    }
+}
+
+// Line and column of the byte offset in the source, both starting at 1, where columns count characters
+pub fn line_column(source: &str, offset: usize) -> (usize, usize) {
+    let offset = offset.min(source.len());
+    let line_start = source[..offset].rfind('\n').map_or(0, |i| i + 1);
+    let line = source[..line_start].matches('\n').count() + 1;
+    (line, source[line_start..offset].chars().count() + 1)
+}
+
+// The message without its label, on a single line, e.g., 'Parsing error: expected ...' becomes 'expected ...'
+pub fn plain_message(message: &dyn Display) -> String {
+    let message = message.to_string();
+    let text = message.split_once(": ").map_or(message.as_str(), |(_, text)| text);
+    text.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::Warning;
+
+    #[test]
+    fn render_warning() {
+        let source = "f(x)\n    apply foo at 0\nend";
+        let renderer = Renderer::new("test.rx", source, false);
+        let res = renderer.render(
+            &Warning::RuleDoesNotExist("foo".to_string()),
+            Some(Span { start: 9, end: 23 }),
+            Severity::Warning
+        );
+
+        assert_eq!(res, [
+            "Warning[W005]: cannot find rule 'foo'. First define the rule before applying it like",
+            "               'def YOUR_RULE_NAME as LEFT_EXPR => RIGHT_EXPR'. Thus this statement is ignored.",
+            " --> test.rx:2:5",
+            "  |",
+            "2 |     apply foo at 0",
+            "  |     ^^^^^^^^^^^^^^",
+            ""
+        ].join("\n"));
+    }
+}
@@ -1,26 +1,43 @@
-use std::{fmt::Display, error::Error};
+use std::{fmt::Display, error::Error, time::Duration};
+
+use crate::{lexer::Span, runtime::WILDCARD};
 
 #[derive(Debug, PartialEq, Clone)]
 pub enum LexError {
     UnterminatedStringLiteral { pos: usize },
-    UnterminatedStringLiteralAtEnd,
-    ExpectCharAfter { pos: usize, expected: char, after: char, got: char },
-    UnknownChar { pos: usize, got: char}
+    UnknownChar { pos: usize, got: char},
+    InvalidNumber { start: usize, end: usize, lexeme: String, reason: &'static str },
+    // An argument index of a path, or the position of a captured variable, that does not fit in an index
+    InvalidIndex { start: usize, end: usize, lexeme: String },
 }
 
 impl Error for LexError {}
 
+impl LexError {
+    // Location of the offending character, or of the whole offending number or index, in the source
+    pub fn span(&self, source: &str) -> Span {
+        let pos = match self {
+            LexError::UnterminatedStringLiteral { pos } |
+            LexError::UnknownChar { pos, .. } => *pos,
+            LexError::InvalidNumber { start, end, .. } |
+            LexError::InvalidIndex { start, end, .. } => return Span { start: *start, end: *end },
+        };
+        let len = source[pos..].chars().next().map_or(0, char::len_utf8);
+        Span { start: pos, end: pos + len }
+    }
+}
+
 impl Display for LexError {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
             LexError::UnterminatedStringLiteral { pos } => 
-                writeln!(f, "Syntax error: unterminated string literal in path at position {}", pos),
-            LexError::UnterminatedStringLiteralAtEnd => 
-                writeln!(f, "Syntax error: unterminated string literal in path at the end of the line"),
-            LexError::ExpectCharAfter { pos, expected, after, got } => 
-                writeln!(f, "Syntax error: expected '{}' after '{}', but got '{}' at position {}", expected, after, got, pos),
+                writeln!(f, "Syntax error: unterminated string literal starting at position {}", pos),
             LexError::UnknownChar { pos, got } => 
-                writeln!(f, "Syntax error: Unknown character found '{}' at position {}", got, pos)
+                writeln!(f, "Syntax error: Unknown character found '{}' at position {}", got, pos),
+            LexError::InvalidNumber { start, lexeme, reason, .. } => 
+                writeln!(f, "Syntax error: invalid number '{}' at position {}: {}", lexeme, start, reason),
+            LexError::InvalidIndex { start, lexeme, .. } => 
+                writeln!(f, "Syntax error: invalid index '{}' at position {}: the index is out of range", lexeme, start)
         }
     }
 }
@@ -30,7 +47,13 @@ pub enum ParsingError {
     ExpectToken { expected: String, got: Option<String> },
     ExpectTokenAfter { expected: String, after: String, got: Option<String> },
     ExpectDepthValue,
-    UnexpectedToken { got: Option<String> }
+    EmptyDepthRange { start: usize, end: usize },
+    UnexpectedDepth { expr: String },
+    UnexpectedToken { got: Option<String> },
+    MultipleSegments { functor: String },
+    NonAssociativeOperator { symbol: String },
+    UnboundCapture { capture: String, left: String },
+    AutoRuleWithParams { iden: String },
 }
 
 impl Error for ParsingError {}
@@ -48,53 +71,489 @@ impl Display for ParsingError {
                     after, 
                     got.clone().unwrap_or("nothing".to_string())),
             ParsingError::ExpectDepthValue => 
-                writeln!(f, "Parsing error: expected a depth value after in-line rule"),
+                writeln!(f, "Parsing error: expected a depth value, i.e., a number, a range of numbers or 'any', after the at-keyword"),
+            ParsingError::EmptyDepthRange { start, end } => 
+                writeln!(f, "Parsing error: the depth range {}..{} is empty, as it ends before it starts", start, end),
+            ParsingError::UnexpectedDepth { expr } => {
+                writeln!(f, "Parsing error: the expression '{}' cannot be applied at a depth.", expr)?;
+                writeln!(f, "               Consider an in-line rule, 'LEFT_EXPR => RIGHT_EXPR at SOME_DEPTH', or")?;
+                writeln!(f, "               a rule statement, 'apply YOUR_RULE_NAME at SOME_DEPTH'.")
+            },
             ParsingError::UnexpectedToken { got } => 
                 writeln!(f, "Parsing error: unexpected token found, got {}",
                     got.clone().unwrap_or("nothing".to_string())),
+            ParsingError::MultipleSegments { functor } => {
+                writeln!(f, "Parsing error: the arguments of functor '{}' contain more than one segment.", functor)?;
+                writeln!(f, "               A segment matches any number of arguments, thus at most one segment")?;
+                writeln!(f, "               per argument list is allowed, e.g., '{}(first, rest..)'.", functor)
+            },
+            ParsingError::UnboundCapture { capture, left } => {
+                writeln!(f, "Parsing error: the capture '{}' does not occur in the left expression '{}',", capture, left)?;
+                writeln!(f, "               thus it is not bound to any sub-expression when the rule matches.")
+            },
+            ParsingError::NonAssociativeOperator { symbol } => {
+                writeln!(f, "Parsing error: the operator '{}' is non-associative, so it cannot be chained with", symbol)?;
+                writeln!(f, "               an operator of the same precedence. Consider functor notation for one of them.")
+            },
+            ParsingError::AutoRuleWithParams { iden } => {
+                writeln!(f, "Parsing error: the rule '{}' has meta-parameters, so it cannot be an auto rule,", iden)?;
+                writeln!(f, "               as it is applied after every rewrite without arguments for them.")
+            },
         }
     }
 }
 
+// Errors in the user configuration file, by line number
+#[derive(Debug, PartialEq)]
+pub enum ConfigError {
+    InvalidLine { line: usize },
+    UnknownKey { line: usize, key: String },
+    InvalidValue { line: usize, key: String, expected: &'static str },
+}
 
-#[derive(Debug)]
+impl Error for ConfigError {}
+
+impl Display for ConfigError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ConfigError::InvalidLine { line } => 
+                writeln!(f, "Config error: expected 'KEY = VALUE' at line {}", line),
+            ConfigError::UnknownKey { line, key } => {
+                writeln!(f, "Config error: unknown setting '{}' at line {}.", key, line)?;
+                writeln!(f, "              The settings are prompt, prelude, color and history_size.")
+            },
+            ConfigError::InvalidValue { line, key, expected } => 
+                writeln!(f, "Config error: expected {} as value of '{}' at line {}", expected, key, line),
+        }
+    }
+}
+
+// Errors in a derivation that is read back for a replay, where steps are numbered from the first rule application
+#[derive(Debug, PartialEq)]
+pub enum ReplayError {
+    InvalidJson,
+    UnsupportedVersion { version: String, supported: usize },
+    InvalidStep { step: usize, reason: String },
+}
+
+impl Error for ReplayError {}
+
+impl Display for ReplayError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ReplayError::InvalidJson => 
+                writeln!(f, "Replay error: expected a derivation exported with 'end \"PATH\" as json'"),
+            ReplayError::UnsupportedVersion { version, supported } => 
+                writeln!(f, "Replay error: unsupported version {} of the derivation, expected version {}", version, supported),
+            ReplayError::InvalidStep { step, reason } => 
+                writeln!(f, "Replay error: {} at step {}", reason, step),
+        }
+    }
+}
+
+// Errors in a rule library that is imported from a '.rxlib' file, where entries are numbered from 0
+#[derive(Debug, PartialEq)]
+pub enum LibraryError {
+    InvalidJson,
+    UnsupportedVersion { version: String, supported: usize },
+    InvalidOperator { index: usize },
+    InvalidRule { index: usize, reason: String },
+    UnknownStdLibrary { available: Vec<String> },
+}
+
+impl Error for LibraryError {}
+
+impl Display for LibraryError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            LibraryError::InvalidJson => 
+                write!(f, "Library error: expected a rule library exported with 'export rules \"PATH\"'"),
+            LibraryError::UnsupportedVersion { version, supported } => 
+                write!(f, "Library error: unsupported version {} of the rule library, expected version {}", version, supported),
+            LibraryError::InvalidOperator { index } => 
+                write!(f, "Library error: expected the symbol, functor, precedence and associativity of operator {}", index),
+            LibraryError::InvalidRule { index, reason } => 
+                write!(f, "Library error: {} of rule {}", reason, index),
+            LibraryError::UnknownStdLibrary { available } => 
+                write!(f, "Library error: unknown standard library, expected one of {}", available.join(", ")),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub enum RuntimeError {
+    MaxDepthExceeded { max_depth: usize },
+    MaxNodesExceeded { max_nodes: usize },
+    TimeoutExceeded { timeout: Duration },
+    Interrupted,
+    WarningInStrictMode { num_warnings: usize },
+    NoCurrentExpr,
+    CannotWriteToPath { path: String, reason: String },
+    ImportFailed { path: String, reason: String },
+    ExpectationFailed { expected: String, got: String },
+}
+
+impl Error for RuntimeError {}
+
+impl Display for RuntimeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RuntimeError::MaxDepthExceeded { max_depth } => 
+                writeln!(f, "Runtime error: the derived expression exceeds the maximum depth of {}, which can be changed with 'set max_depth NUMBER'", max_depth),
+            RuntimeError::MaxNodesExceeded { max_nodes } => 
+                writeln!(f, "Runtime error: the derived expression exceeds the maximum of {} nodes, which can be changed with 'set max_nodes NUMBER'", max_nodes),
+            RuntimeError::TimeoutExceeded { timeout } => 
+                writeln!(f, "Runtime error: the statement did not finish within the timeout of {:?}, which can be changed with 'set timeout DURATION'", timeout),
+            RuntimeError::Interrupted => 
+                writeln!(f, "Runtime error: the statement was interrupted, and the current expression is left unchanged"),
+            RuntimeError::WarningInStrictMode { num_warnings } => 
+                writeln!(f, "Runtime error: interpretation stopped after {} warning(s), which are errors in strict mode", num_warnings),
+            RuntimeError::NoCurrentExpr => 
+                writeln!(f, "Runtime error: there is no current expression in this pattern matching context"),
+            RuntimeError::CannotWriteToPath { path, reason } => 
+                writeln!(f, "Runtime error: cannot write to '{}': {}", path, reason),
+            RuntimeError::ImportFailed { path, reason } => 
+                writeln!(f, "Runtime error: cannot import the rules of '{}': {}", path, reason),
+            RuntimeError::ExpectationFailed { expected, got } => 
+                writeln!(f, "Runtime error: expected the current expression to be '{}', but got '{}'", expected, got),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
 pub enum Warning {
     ExprHasNoEffect,
     ApplyRuleNoEffect,
     InLineRuleNoEffect,
     EndStmtHasNoEffect,
-    RuleDoesNotExist(String)
+    RuleDoesNotExist(String),
+    RuleArityMismatch { iden: String, expected: usize, got: usize },
+    ProofNotFound { left: String, right: String, max_steps: usize },
+    RewriteCycleDetected(String),
+    UnknownWarningCode(String),
+    UnknownNotation { notation: String, available: Vec<String> },
+    ShowStmtHasNoEffect,
+    UnknownExportFormat { format: String, available: Vec<String> },
+    PreviewStmtHasNoEffect,
+    CheckpointStmtHasNoEffect,
+    CheckpointDoesNotExist(String),
+    CheckStmtHasNoEffect,
+    CheckFailed { expected: String, got: String },
+    FunctorArityMismatch { iden: String, expected: usize, got: usize },
+    FlattenStmtHasNoEffect,
+    CanonicalizeStmtHasNoEffect,
+    RuleMayNotTerminate { iden: String, reason: String },
+    NotUnifiable { left: String, right: String },
+    RuleDidNotMatch { rule: String, depth: String, reason: String },
+    RollbackStmtHasNoEffect,
+    StepDoesNotExist { step: usize, num_steps: usize },
+    NothingToRedo,
+    ContextAlreadyExists(String),
+    ContextDoesNotExist(String),
+    ContextHasNoExpr(String),
+    ContextsDiffer { left: String, right: String, left_expr: String, right_expr: String },
+    TagHasNoRules(String),
+    OperatorSymbolExists { symbol: String, iden: String },
+    OperatorFunctorExists { iden: String, symbol: String },
+    OperatorArityMismatch { iden: String, symbol: String, arity: usize },
+    ReplaceStmtHasNoEffect,
+    SubExprDoesNotOccur { sub_expr: String, expr: String },
+    FocusStmtHasNoEffect,
+    PathDoesNotExist { path: String, expr: String },
+    NotFocused,
+    FindStmtHasNoEffect,
+    PatternNotFound { pattern: String, expr: String },
+    UnboundVariables { rule: String, vars: Vec<String> },
+    RuleHasNoEffect(String),
+    LeftExprIsVariable { rule: String, var: String },
+    DuplicateRule { rule: String, other: String },
+    GoalNotSet,
 }
 
-impl Display for Warning {
+impl Warning {
+    // Codes of all warnings, which can be suppressed with 'allow CODE' or '--allow CODE'
+    pub const CODES: [&'static str; 46] = ["W001", "W002", "W003", "W004", "W005", "W006", "W007", "W008", "W009", "W010", "W011", "W012", "W013", "W014", "W015", "W016", "W017", "W018", "W019", "W020", "W021", "W022", "W023", "W024", "W025", "W026", "W027", "W028", "W029", "W030", "W031", "W032", "W033", "W034", "W035", "W036", "W037", "W038", "W039", "W040", "W041", "W042", "W043", "W044", "W045", "W046"];
+
+    pub fn code(&self) -> &'static str {
+        let idx = match self {
+            Warning::ExprHasNoEffect => 0,
+            Warning::ApplyRuleNoEffect => 1,
+            Warning::InLineRuleNoEffect => 2,
+            Warning::EndStmtHasNoEffect => 3,
+            Warning::RuleDoesNotExist(_) => 4,
+            Warning::RuleArityMismatch { .. } => 5,
+            Warning::ProofNotFound { .. } => 6,
+            Warning::RewriteCycleDetected(_) => 7,
+            Warning::UnknownWarningCode(_) => 8,
+            Warning::UnknownNotation { .. } => 9,
+            Warning::ShowStmtHasNoEffect => 10,
+            Warning::UnknownExportFormat { .. } => 11,
+            Warning::PreviewStmtHasNoEffect => 12,
+            Warning::CheckpointStmtHasNoEffect => 13,
+            Warning::CheckpointDoesNotExist(_) => 14,
+            Warning::CheckStmtHasNoEffect => 15,
+            Warning::CheckFailed { .. } => 16,
+            Warning::FunctorArityMismatch { .. } => 17,
+            Warning::FlattenStmtHasNoEffect => 18,
+            Warning::CanonicalizeStmtHasNoEffect => 19,
+            Warning::RuleMayNotTerminate { .. } => 20,
+            Warning::NotUnifiable { .. } => 21,
+            Warning::RuleDidNotMatch { .. } => 22,
+            Warning::RollbackStmtHasNoEffect => 23,
+            Warning::StepDoesNotExist { .. } => 24,
+            Warning::NothingToRedo => 25,
+            Warning::ContextAlreadyExists(_) => 26,
+            Warning::ContextDoesNotExist(_) => 27,
+            Warning::ContextHasNoExpr(_) => 28,
+            Warning::ContextsDiffer { .. } => 29,
+            Warning::TagHasNoRules(_) => 30,
+            Warning::OperatorSymbolExists { .. } => 31,
+            Warning::OperatorFunctorExists { .. } => 32,
+            Warning::OperatorArityMismatch { .. } => 33,
+            Warning::ReplaceStmtHasNoEffect => 34,
+            Warning::SubExprDoesNotOccur { .. } => 35,
+            Warning::FocusStmtHasNoEffect => 36,
+            Warning::PathDoesNotExist { .. } => 37,
+            Warning::NotFocused => 38,
+            Warning::FindStmtHasNoEffect => 39,
+            Warning::PatternNotFound { .. } => 40,
+            Warning::UnboundVariables { .. } => 41,
+            Warning::RuleHasNoEffect(_) => 42,
+            Warning::LeftExprIsVariable { .. } => 43,
+            Warning::DuplicateRule { .. } => 44,
+            Warning::GoalNotSet => 45,
+        };
+        Self::CODES[idx]
+    }
+}
+
+// Why the left expression of a rule does not match an expression
+#[derive(Debug, Clone, PartialEq)]
+pub enum MatchFailure {
+    IdentifierMismatch { expected: String, got: String },
+    ArityMismatch { iden: String, expected: usize, got: usize },
+    // A variable that occurs more than once in the left expression would be bound to different expressions
+    InconsistentBinding { var: String, first: String, second: String },
+    // A variable or number that is not equal to the expression, or a functor that is matched against a variable or number
+    ExprMismatch { expected: String, got: String },
+    // The current expression has no sub-expression at the depth
+    NoExprAtDepth { depth: usize },
+}
+
+impl Display for MatchFailure {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
-            Warning::ExprHasNoEffect => {
-                writeln!(f, "Warning: the provided expression has no effect inside a pattern matching context.")?;
-                writeln!(f, "         Consider applying a rule statement, 'apply YOUR_RULE_NAME at SOME_DEPTH'; or,")?;
-                writeln!(f, "         consider applying an in-line rule statement, 'LEFT_EXPR => RIGHT_EXPR at SOME_DEPTH'; or")?;
-                Ok(())
-            },
-            Warning::ApplyRuleNoEffect => {
-                writeln!(f, "Warning: cannot apply rule statement outside of pattern matching context.")?;
-                writeln!(f, "         Thus this statement is ignored.")?;
-                Ok(())
-            },
-            Warning::InLineRuleNoEffect => {
-                writeln!(f, "Warning: cannot apply in-line rule statement outside of pattern matching context.")?;
-                writeln!(f, "         Thus this statement is ignored.")?;
-                Ok(())
-            },
-            Warning::EndStmtHasNoEffect => {
-                writeln!(f, "Warning: End-statement takes no effect, as there is no pattern matching context to end. ")?;
-                writeln!(f, "         Thus this statement is ignored.")?;
-                Ok(())
-            },
-            Warning::RuleDoesNotExist(s) => {
-                writeln!(f, "Warning: cannot find rule '{}'. First define the rule before applying it like", s)?;
-                writeln!(f, "         'def YOUR_RULE_NAME as LEFT_EXPR => RIGHT_EXPR'. Thus this statement is ignored.")?;
-                Ok(())
+            MatchFailure::IdentifierMismatch { expected, got } => 
+                write!(f, "identifier mismatch, expected '{}', but got '{}'", expected, got),
+            MatchFailure::ArityMismatch { iden, expected, got } => 
+                write!(f, "arity mismatch, expected '{}' with {} argument(s), but got {}", iden, expected, got),
+            MatchFailure::InconsistentBinding { var, first, second } => 
+                write!(f, "inconsistent binding, '{}' is bound to both '{}' and '{}'", var, first, second),
+            MatchFailure::ExprMismatch { expected, got } => 
+                write!(f, "expression mismatch, expected '{}', but got '{}'", expected, got),
+            MatchFailure::NoExprAtDepth { depth } => 
+                write!(f, "the current expression has no sub-expression at depth {}", depth),
+        }
+    }
+}
+
+impl Display for Warning {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let lines = match self {
+            Warning::ExprHasNoEffect => vec![
+                "the provided expression has no effect inside a pattern matching context.".to_string(),
+                "Consider applying a rule statement, 'apply YOUR_RULE_NAME at SOME_DEPTH'; or,".to_string(),
+                "consider applying an in-line rule statement, 'LEFT_EXPR => RIGHT_EXPR at SOME_DEPTH'; or".to_string(),
+            ],
+            Warning::ApplyRuleNoEffect => vec![
+                "cannot apply rule statement outside of pattern matching context.".to_string(),
+                "Thus this statement is ignored.".to_string(),
+            ],
+            Warning::InLineRuleNoEffect => vec![
+                "cannot apply in-line rule statement outside of pattern matching context.".to_string(),
+                "Thus this statement is ignored.".to_string(),
+            ],
+            Warning::EndStmtHasNoEffect => vec![
+                "End-statement takes no effect, as there is no pattern matching context to end. ".to_string(),
+                "Thus this statement is ignored.".to_string(),
+            ],
+            Warning::RuleDoesNotExist(s) => vec![
+                format!("cannot find rule '{}'. First define the rule before applying it like", s),
+                "'def YOUR_RULE_NAME as LEFT_EXPR => RIGHT_EXPR'. Thus this statement is ignored.".to_string(),
+            ],
+            Warning::RuleArityMismatch { iden, expected, got } => vec![
+                format!("rule '{}' expects {} argument(s), but got {}.", iden, expected, got),
+                "Thus this statement is ignored.".to_string(),
+            ],
+            Warning::ProofNotFound { left, right, max_steps } => vec![
+                format!("could not derive '{}' from '{}' in at most {} steps.", right, left, max_steps),
+                "Consider providing other rules with 'prove LEFT_EXPR == RIGHT_EXPR using RULE, ...'.".to_string(),
+            ],
+            Warning::RewriteCycleDetected(s) => vec![
+                format!("the expression '{}' was already derived in this pattern matching context.", s),
+                "The applied rules form a cycle, which is not repeated any further.".to_string(),
+            ],
+            Warning::UnknownWarningCode(s) => vec![
+                format!("unknown warning code '{}', expected one of {}.", s, Self::CODES.join(", ")),
+                "Thus this code is ignored.".to_string(),
+            ],
+            Warning::UnknownNotation { notation, available } => vec![
+                format!("unknown notation '{}', expected one of {}.", notation, available.join(", ")),
+                "Thus this statement is ignored.".to_string(),
+            ],
+            Warning::ShowStmtHasNoEffect => vec![
+                "cannot show an expression outside of pattern matching context.".to_string(),
+                "Thus this statement is ignored.".to_string(),
+            ],
+            Warning::UnknownExportFormat { format, available } => vec![
+                format!("unknown export format '{}', expected one of {}.", format, available.join(", ")),
+                "Thus the derivation is not written.".to_string(),
+            ],
+            Warning::PreviewStmtHasNoEffect => vec![
+                "cannot preview a rule application outside of pattern matching context.".to_string(),
+                "Thus this statement is ignored.".to_string(),
+            ],
+            Warning::CheckpointStmtHasNoEffect => vec![
+                "cannot save or restore a checkpoint outside of pattern matching context.".to_string(),
+                "Thus this statement is ignored.".to_string(),
+            ],
+            Warning::CheckpointDoesNotExist(s) => vec![
+                format!("cannot find checkpoint '{}'. First save the derivation with 'checkpoint {}'.", s, s),
+                "Thus this statement is ignored.".to_string(),
+            ],
+            Warning::CheckStmtHasNoEffect => vec![
+                "cannot check the current expression outside of pattern matching context.".to_string(),
+                "Thus this statement is ignored.".to_string(),
+            ],
+            Warning::CheckFailed { expected, got } => vec![
+                format!("the current expression '{}' is not identical to '{}'.", got, expected),
+            ],
+            Warning::FunctorArityMismatch { iden, expected, got } => vec![
+                format!("functor '{}' is declared with {} argument(s), but is used with {}.", iden, expected, got),
+                format!("Consider checking the expression, or the declaration 'functor {}/{}'.", iden, expected),
+            ],
+            Warning::FlattenStmtHasNoEffect => vec![
+                "cannot flatten or unflatten an expression outside of pattern matching context.".to_string(),
+                "Thus this statement is ignored.".to_string(),
+            ],
+            Warning::CanonicalizeStmtHasNoEffect => vec![
+                "cannot canonicalize an expression outside of pattern matching context.".to_string(),
+                "Thus this statement is ignored.".to_string(),
+            ],
+            Warning::RuleMayNotTerminate { iden, reason } => vec![
+                format!("rule '{}' may not terminate when applied repeatedly, as {}.", iden, reason),
+                format!("Consider applying it a bounded number of times instead of 'apply {}* at SOME_DEPTH'.", iden),
+            ],
+            Warning::NotUnifiable { left, right } => vec![
+                format!("'{}' and '{}' cannot be unified, as they differ in a functor or number,", left, right),
+                "or a variable would have to contain itself.".to_string(),
+            ],
+            Warning::RuleDidNotMatch { rule, depth, reason } => vec![
+                format!("applying '{}' at depth {} left the expression unchanged: {}.", rule, depth, reason),
+                "Consider another depth, or 'set trace on' to see every match attempt.".to_string(),
+            ],
+            Warning::RollbackStmtHasNoEffect => vec![
+                "cannot roll back or redo a derivation outside of pattern matching context.".to_string(),
+                "Thus this statement is ignored.".to_string(),
+            ],
+            Warning::StepDoesNotExist { step, num_steps } => vec![
+                format!("cannot roll back to step {}, as the derivation has {} step(s).", step, num_steps),
+                "Thus this statement is ignored.".to_string(),
+            ],
+            Warning::NothingToRedo => vec![
+                "cannot redo a step, as no step was undone or rolled back after the last derived step.".to_string(),
+                "Thus this statement is ignored.".to_string(),
+            ],
+            Warning::ContextAlreadyExists(s) => vec![
+                format!("context '{}' already exists. Resume it with 'switch {}'.", s, s),
+                "Thus this statement is ignored.".to_string(),
+            ],
+            Warning::ContextDoesNotExist(s) => vec![
+                format!("cannot find context '{}'. First start it with 'context {}'.", s, s),
+                "Thus this statement is ignored.".to_string(),
+            ],
+            Warning::ContextHasNoExpr(s) => vec![
+                format!("context '{}' has no current expression, as it is not pattern matching.", s),
+                "Thus this statement is ignored.".to_string(),
+            ],
+            Warning::ContextsDiffer { left, right, left_expr, right_expr } => vec![
+                format!("the current expression '{}' of context '{}' is not identical to", left_expr, left),
+                format!("the current expression '{}' of context '{}'.", right_expr, right),
+            ],
+            Warning::TagHasNoRules(s) => vec![
+                format!("no rule without meta-parameters has tag '{}'.", s),
+                "Consider adding it to a rule with 'def YOUR_RULE_NAME as LEFT_EXPR => RIGHT_EXPR tags [TAG]'.".to_string(),
+            ],
+            Warning::OperatorSymbolExists { symbol, iden } => vec![
+                format!("operator '{}' is already the infix notation of functor '{}'.", symbol, iden),
+                "Thus this statement is ignored.".to_string(),
+            ],
+            Warning::OperatorFunctorExists { iden, symbol } => vec![
+                format!("functor '{}' is already written with operator '{}', so printing it would be ambiguous.", iden, symbol),
+                "Thus this statement is ignored.".to_string(),
+            ],
+            Warning::OperatorArityMismatch { iden, symbol, arity } => vec![
+                format!("functor '{}' is declared with {} argument(s), but it is written with the binary operator '{}'.", iden, arity, symbol),
+                "Consider another identifier for the functor.".to_string(),
+            ],
+            Warning::ReplaceStmtHasNoEffect => vec![
+                "cannot replace a sub-expression outside of pattern matching context.".to_string(),
+                "Thus this statement is ignored.".to_string(),
+            ],
+            Warning::SubExprDoesNotOccur { sub_expr, expr } => vec![
+                format!("the sub-expression '{}' does not occur in the current expression '{}'.", sub_expr, expr),
+                "Thus this statement is ignored.".to_string(),
+            ],
+            Warning::FocusStmtHasNoEffect => vec![
+                "cannot focus on or unfocus a sub-expression outside of pattern matching context.".to_string(),
+                "Thus this statement is ignored.".to_string(),
+            ],
+            Warning::PathDoesNotExist { path, expr } => vec![
+                format!("the current expression '{}' has no sub-expression at path {}.", expr, path),
+                "Consider the argument indices from 0, separated by dots, e.g., 'focus path 1.0' for the first argument of the second argument.".to_string(),
+            ],
+            Warning::NotFocused => vec![
+                "there is no focused sub-expression to unfocus.".to_string(),
+                "Thus this statement is ignored.".to_string(),
+            ],
+            Warning::FindStmtHasNoEffect => vec![
+                "cannot find a pattern outside of pattern matching context.".to_string(),
+                "Thus this statement is ignored.".to_string(),
+            ],
+            Warning::PatternNotFound { pattern, expr } => vec![
+                format!("no sub-expression of the current expression '{}' matches the pattern '{}'.", expr, pattern),
+            ],
+            Warning::UnboundVariables { rule, vars } => vec![
+                format!("the right expression of rule '{}' contains '{}', which the left expression does not bind.", rule, vars.join("', '")),
+                "Such variables are produced literally, consider binding them in the left expression, or 'fresh v' for a new variable v.".to_string(),
+            ],
+            Warning::RuleHasNoEffect(rule) => vec![
+                format!("the left and right expression of rule '{}' are the same, so applying it never changes an expression.", rule),
+            ],
+            Warning::LeftExprIsVariable { rule, var } if var == WILDCARD => vec![
+                format!("the left expression of rule '{}' is the wildcard, which matches every expression.", rule),
+            ],
+            Warning::LeftExprIsVariable { rule, var } => vec![
+                format!("the left expression of rule '{}' is the variable '{}', which only matches the variable itself.", rule, var),
+                "Consider the wildcard '_' to match every expression, or a functor to match expressions of its form.".to_string(),
+            ],
+            Warning::DuplicateRule { rule, other } => vec![
+                format!("rule '{}' is the same as rule '{}' up to the names of its variables.", rule, other),
+            ],
+            Warning::GoalNotSet => vec![
+                "there is no goal to prove, set one with 'goal TEMPLATE' first.".to_string(),
+            ],
+        };
+
+        // Continuation lines are aligned with the first line after the header
+        let header = format!("Warning[{}]: ", self.code());
+        for (i, line) in lines.iter().enumerate() {
+            if i == 0 {
+                writeln!(f, "{}{}", header, line)?;
+            } else {
+                writeln!(f, "{:indent$}{}", "", line, indent=header.len())?;
             }
         }
+        Ok(())
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,56 @@
+use std::sync::mpsc::Sender;
+
+use crate::{ast::Expr, error::Warning, runtime::AppliedRule};
+
+// What happens while a pattern matching context is interpreted, in order, such that a front-end
+// can render derivations itself instead of reading the printed output.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Event {
+    // An expression statement started a pattern matching context on the expression
+    MatchStarted(Expr),
+    // A rule, strategy or built-in transformation derived the expression
+    StepApplied { expr: Expr, rule: AppliedRule },
+    // A warning that is not allowed, after the statement that produced it
+    Warning(Warning),
+    // An end statement finished the pattern matching context with the expression
+    Result(Expr),
+}
+
+// Receives the events of an environment, which are passed to every observer in the order they subscribed.
+pub trait Observer: Send {
+    fn notify(&mut self, event: &Event);
+}
+
+impl<F: FnMut(&Event) + Send> Observer for F {
+    fn notify(&mut self, event: &Event) {
+        self(event)
+    }
+}
+
+// Which of the sub-expressions that a rule matches at the same depth it is applied to
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Choice {
+    // Index of the chosen candidate
+    One(usize),
+    All,
+}
+
+// Picks the sub-expression a rule is applied to, if it matches more than one at the depth, e.g., by asking the user.
+// Without a chooser, the rule is applied at every match.
+pub trait Chooser: Send {
+    // The candidates are the paths and sub-expressions of the matches, in order from left to right
+    fn choose(&mut self, candidates: &[(Vec<usize>, Expr)]) -> Choice;
+}
+
+impl<F: FnMut(&[(Vec<usize>, Expr)]) -> Choice + Send> Chooser for F {
+    fn choose(&mut self, candidates: &[(Vec<usize>, Expr)]) -> Choice {
+        self(candidates)
+    }
+}
+
+// Events can be sent to another thread, e.g., the UI thread of a GUI. A closed channel is ignored.
+impl Observer for Sender<Event> {
+    fn notify(&mut self, event: &Event) {
+        let _ = self.send(event.clone());
+    }
+}
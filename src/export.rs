@@ -0,0 +1,676 @@
+use std::time::Duration;
+
+use crate::{ast::{quote, Expr, ExprNode}, format::{Formatter, Lean}, json::Json, lexer::{path_to_string, Lexer}, parser::{Parser, Stmt}, runtime::{step_bindings, AppliedRule}};
+
+// Format of a derivation that is written to a file with 'end "PATH" as FORMAT'.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum ExportFormat {
+    Text,
+    Markdown,
+    Html,
+    // A script that derives the same expressions with in-line rules when it is interpreted
+    Source,
+    // A versioned document of the steps, which 'raxio replay' checks against the current rules
+    Json,
+    // A Lean proof that rewrites with a lemma of the same name for each step, after a preamble that declares them
+    Lean,
+}
+
+// Settings of a written derivation besides its format
+#[derive(Debug, Default, Clone)]
+pub struct ExportOptions {
+    // Time spent on the rule applications, which is only measured with --stats
+    pub elapsed: Option<Duration>,
+
+    // Text before the proof of the lean format, e.g., imports and the declarations of the rules as lemmas
+    pub preamble: String,
+}
+
+// Version of the JSON format of derivations, which is increased whenever the format changes incompatibly
+pub const JSON_VERSION: usize = 1;
+
+impl ExportFormat {
+    // Names of all formats, where plain text is the default
+    pub const NAMES: [&'static str; 6] = ["text", "markdown", "html", "raxio", "json", "lean"];
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "text" => Some(ExportFormat::Text),
+            "markdown" | "md" => Some(ExportFormat::Markdown),
+            "html" => Some(ExportFormat::Html),
+            "raxio" | "rx" => Some(ExportFormat::Source),
+            "json" => Some(ExportFormat::Json),
+            "lean" => Some(ExportFormat::Lean),
+            _ => None
+        }
+    }
+
+    // Render the derivation that starts at the first expression of the history, where each following expression 
+    // results from the rule at the same step, with a summary that includes the elapsed time if it was measured.
+    pub fn render(&self, history: &[Expr], rules: &[AppliedRule], options: &ExportOptions) -> String {
+        let Some((first_expr, steps)) = history.split_first() else {
+            return String::new();
+        };
+        let last_expr = history.last().unwrap_or(first_expr);
+        let summary = Summary::new(steps, rules, last_expr, options.elapsed);
+        match self {
+            ExportFormat::Text => render_text(first_expr, steps, rules, last_expr, &summary),
+            ExportFormat::Markdown => render_markdown(first_expr, steps, rules, last_expr, &summary),
+            ExportFormat::Html => render_html(first_expr, steps, rules, last_expr, &summary),
+            ExportFormat::Source => render_source(first_expr, steps, rules, last_expr),
+            ExportFormat::Json => render_json(first_expr, steps, rules, &summary),
+            ExportFormat::Lean => render_lean(first_expr, steps, rules, last_expr, &options.preamble),
+        }
+    }
+}
+
+// Measurements of a derivation, which are written before its result, such that a written derivation describes itself
+#[derive(Debug, PartialEq)]
+pub struct Summary {
+    pub num_steps: usize,
+
+    // Name of each applied rule with the number of steps it derived, in order of first application, 
+    // where all in-line rules are counted together
+    pub rule_counts: Vec<(String, usize)>,
+
+    // Number of variables, numbers and functors in the result
+    pub num_nodes: usize,
+
+    // Time spent on the rule applications, which is only measured with --stats
+    pub elapsed: Option<Duration>,
+}
+
+impl Summary {
+    pub fn new(steps: &[Expr], rules: &[AppliedRule], last_expr: &Expr, elapsed: Option<Duration>) -> Self {
+        let mut rule_counts: Vec<(String, usize)> = vec![];
+        for rule in rules.iter().take(steps.len()) {
+            let name = rule.name.as_deref().unwrap_or("in-line rule");
+            match rule_counts.iter_mut().find(|(other, _)| other == name) {
+                Some((_, count)) => *count += 1,
+                None => rule_counts.push((name.to_string(), 1)),
+            }
+        }
+        Summary { num_steps: steps.len(), rule_counts, num_nodes: last_expr.num_nodes(), elapsed }
+    }
+
+    // The lines of the summary, e.g., "Rules: swap (2), in-line rule (1)"
+    fn lines(&self) -> Vec<String> {
+        let rules = self.rule_counts
+            .iter()
+            .map(|(name, count)| format!("{} ({})", name, count))
+            .collect::<Vec<_>>();
+        let mut lines = vec![
+            format!("Steps: {}", self.num_steps),
+            format!("Rules: {}", if rules.is_empty() { "none".to_string() } else { rules.join(", ") }),
+            format!("Nodes in the result: {}", self.num_nodes),
+        ];
+        if let Some(elapsed) = self.elapsed {
+            lines.push(format!("Elapsed: {:?}", elapsed));
+        }
+        lines
+    }
+
+    fn to_json(&self) -> Json {
+        let rules = self.rule_counts
+            .iter()
+            .map(|(name, count)| Json::object([("name", name.as_str().into()), ("count", (*count).into())]))
+            .collect::<Vec<_>>();
+        Json::object([
+            ("steps", self.num_steps.into()),
+            ("rules", rules.into()),
+            ("nodes", self.num_nodes.into()),
+            ("elapsed_ms", self.elapsed.map_or(Json::Null, |elapsed| Json::Number(elapsed.as_secs_f64() * 1000.0))),
+        ])
+    }
+}
+
+fn render_text(first_expr: &Expr, steps: &[Expr], rules: &[AppliedRule], last_expr: &Expr, summary: &Summary) -> String {
+    let mut data = format!("Start pattern matching on {}\n", first_expr.to_source());
+    for (i, (expr, rule)) in steps.iter().zip(rules).enumerate() {
+        let applied = match &rule.name {
+            Some(name) => format!("rule {}", name),
+            None => "in-line rule".to_string(),
+        };
+        data.push_str(&format!("\n{}. Applying {}: {} => {} at depth {}, results in:\n    {}\n",
+            i+1,
+            applied,
+            rule.left.to_source(),
+            rule.right.to_source(),
+            rule.depth,
+            expr.to_source()
+        ));
+    }
+    data.push_str("\nSummary:\n");
+    for line in summary.lines() {
+        data.push_str(&format!("    {}\n", line));
+    }
+    data.push_str(&format!("\nResult: {}", last_expr.to_source()));
+    data
+}
+
+// A numbered list of steps, each with the applied rule inline and the resulting expression in a fenced code block.
+fn render_markdown(first_expr: &Expr, steps: &[Expr], rules: &[AppliedRule], last_expr: &Expr, summary: &Summary) -> String {
+    let mut data = format!("# Derivation\n\nStart pattern matching on:\n\n```\n{}\n```\n\n", first_expr.to_source());
+    for (i, (expr, rule)) in steps.iter().zip(rules).enumerate() {
+        let applied = match &rule.name {
+            Some(name) => format!("Apply `{}` (`{} => {}`)", name, rule.left.to_source(), rule.right.to_source()),
+            None => format!("Apply in-line rule `{} => {}`", rule.left.to_source(), rule.right.to_source()),
+        };
+        data.push_str(&format!("{}. {} at depth {}, resulting in:\n\n   ```\n   {}\n   ```\n\n",
+            i+1,
+            applied,
+            rule.depth,
+            expr.to_source()
+        ));
+    }
+    data.push_str("## Summary\n\n");
+    for line in summary.lines() {
+        data.push_str(&format!("- {}\n", line));
+    }
+    data.push_str(&format!("\n**Result:** `{}`\n", last_expr.to_source()));
+    data
+}
+
+// The first expression, followed by each step as an in-line rule, as the rules of the derivation may be defined 
+// elsewhere, and an expect statement that the result is derived again. A step that was applied to a sub-expression
+// is applied to the focused sub-expression, at the same depth, e.g., innermost.
+fn render_source(first_expr: &Expr, steps: &[Expr], rules: &[AppliedRule], last_expr: &Expr) -> String {
+    let mut data = format!("{}\n", first_expr.to_source());
+    for rule in rules.iter().take(steps.len()) {
+        let applied = format!("{} => {} {}", rule.left.to_source(), rule.right.to_source(), rule.at.to_source());
+        if rule.path.is_empty() {
+            data.push_str(&format!("    {}\n", applied));
+        } else {
+            data.push_str(&format!("    focus path {}\n        {}\n    unfocus\n", path_to_string(&rule.path), applied));
+        }
+    }
+    data.push_str(&format!("    expect {}\nend\n", last_expr.to_source()));
+    data
+}
+
+// The expression of every step in source notation, where the first step has a null rule and every following step
+// has the rule that derived it, like the steps of the WebAssembly interface. Each rule also has the substitution of 
+// its variables at every rewritten sub-expression, such that a step can be verified without matching it again.
+fn render_json(first_expr: &Expr, steps: &[Expr], rules: &[AppliedRule], summary: &Summary) -> String {
+    let mut json_steps = vec![Json::object([("expr", first_expr.to_source().into()), ("rule", Json::Null)])];
+    let prev_exprs = std::iter::once(first_expr).chain(steps);
+    for ((prev_expr, expr), rule) in prev_exprs.zip(steps).zip(rules) {
+        json_steps.push(Json::object([
+            ("expr", expr.to_source().into()),
+            ("rule", Json::object([
+                ("name", rule.name.clone().map_or(Json::Null, Json::from)),
+                ("left", rule.left.to_source().into()),
+                ("right", rule.right.to_source().into()),
+                ("depth", rule.depth.into()),
+                ("path", rule.path.iter().copied().map(Json::from).collect::<Vec<_>>().into()),
+                ("at", rule.at.to_string().into()),
+                ("substitutions", substitutions_to_json(prev_expr, rule, expr)),
+            ])),
+        ]));
+    }
+    format!("{}\n", Json::object([
+        ("version", JSON_VERSION.into()), 
+        ("steps", json_steps.into()), 
+        ("summary", summary.to_json())
+    ]))
+}
+
+// A proof stub that the first expression equals the result, where every step that applied a defined rule rewrites with
+// the lemma of the same name, which the preamble is expected to declare. Other steps, e.g., in-line rules, have no lemma, 
+// so they are comments and the proof ends with sorry.
+fn render_lean(first_expr: &Expr, steps: &[Expr], rules: &[AppliedRule], last_expr: &Expr, preamble: &str) -> String {
+    let mut data = String::new();
+    if !preamble.is_empty() {
+        data.push_str(preamble.trim_end());
+        data.push_str("\n\n");
+    }
+    data.push_str(&format!("example : {} = {} := by\n", Lean.format(first_expr), Lean.format(last_expr)));
+    let mut is_complete = true;
+    for rule in rules.iter().take(steps.len()) {
+        match rule.name.as_deref().filter(|name| !name.contains(' ')) {
+            Some(name) => data.push_str(&format!("  rw [{}]\n", name)),
+            None => {
+                let name = rule.name.as_deref().unwrap_or("in-line rule");
+                data.push_str(&format!("  -- {}: {} => {} at depth {}\n", name, Lean.format(&rule.left), Lean.format(&rule.right), rule.depth));
+                is_complete = false;
+            }
+        }
+    }
+    if !is_complete {
+        data.push_str("  sorry\n");
+    }
+    data
+}
+
+// The path of each rewritten sub-expression with the expression that each variable of the left expression is bound to, 
+// or the list of expressions for a segment, e.g., [{"path":[1],"bindings":{"x":"a + b","rest..":["c"]}}]
+fn substitutions_to_json(prev_expr: &Expr, rule: &AppliedRule, expr: &Expr) -> Json {
+    let substitutions = step_bindings(prev_expr, rule, expr)
+        .into_iter()
+        .map(|(path, bindings)| {
+            let bindings = bindings
+                .into_iter()
+                .map(|(var, exprs)| {
+                    let value = if var.is_segment() {
+                        exprs.iter().map(|expr| expr.to_source().into()).collect::<Vec<Json>>().into()
+                    } else {
+                        exprs.first().map_or(Json::Null, |expr| expr.to_source().into())
+                    };
+                    (var.to_source(), value)
+                })
+                .collect();
+            Json::object([
+                ("path", path.into_iter().map(Json::from).collect::<Vec<_>>().into()),
+                ("bindings", Json::Object(bindings)),
+            ])
+        })
+        .collect::<Vec<_>>();
+    substitutions.into()
+}
+
+// Parse an expression that was written in source notation with the arithmetic operators, such as the expressions of
+// the json format, which therefore parse without the operators that were defined when it was written.
+pub fn parse_expr(source: &str) -> Option<Expr> {
+    let mut lexer = Lexer::new();
+    lexer.lex(source);
+    let mut parser = Parser::new();
+    if parser.parse(&mut lexer).is_err() || !lexer.errors.is_empty() {
+        return None;
+    }
+    match parser.stmts.as_slice() {
+        [Stmt::ExprStmt(expr)] => Some(expr.clone()),
+        _ => None
+    }
+}
+
+const HTML_HEADER: &str = "<!DOCTYPE html>
+<html>
+<head>
+<meta charset=\"utf-8\">
+<title>Derivation</title>
+<style>
+body { font-family: sans-serif; margin: 2em; }
+.step { border-left: 3px solid #ccc; margin: 1em 0; padding-left: 1em; }
+.expr, .rule, .tree { font-family: monospace; }
+.tree ul { list-style: none; margin: 0; padding-left: 1.5em; border-left: 1px dotted #999; }
+.tree summary { cursor: pointer; }
+.var { color: #07a; }
+.num { color: #a50; }
+.str { color: #282; }
+</style>
+</head>
+<body>
+<h1>Derivation</h1>
+";
+
+// A standalone HTML page, where each step shows the resulting expression, the applied rule,
+// and the abstract syntax tree of the expression, whose functors can be collapsed.
+fn render_html(first_expr: &Expr, steps: &[Expr], rules: &[AppliedRule], last_expr: &Expr, summary: &Summary) -> String {
+    let mut data = HTML_HEADER.to_string();
+    data.push_str("<div class=\"step\">\n<h2>Start</h2>\n");
+    push_html_expr(&mut data, first_expr);
+    data.push_str("</div>\n");
+    for (i, (expr, rule)) in steps.iter().zip(rules).enumerate() {
+        let name = match &rule.name {
+            Some(name) => escape_html(name),
+            None => "in-line rule".to_string(),
+        };
+        data.push_str(&format!("<div class=\"step\">\n<h2>Step {}</h2>\n<p>Apply {} <code class=\"rule\">{} =&gt; {}</code> at depth {}</p>\n",
+            i+1,
+            name,
+            escape_html(&rule.left.to_source()),
+            escape_html(&rule.right.to_source()),
+            rule.depth
+        ));
+        push_html_expr(&mut data, expr);
+        data.push_str("</div>\n");
+    }
+    data.push_str("<h2>Summary</h2>\n<ul class=\"summary\">\n");
+    for line in summary.lines() {
+        data.push_str(&format!("<li>{}</li>\n", escape_html(&line)));
+    }
+    data.push_str("</ul>\n");
+    data.push_str(&format!("<p><strong>Result:</strong> <code class=\"expr\">{}</code></p>\n</body>\n</html>\n", escape_html(&last_expr.to_source())));
+    data
+}
+
+fn push_html_expr(data: &mut String, expr: &Expr) {
+    data.push_str(&format!("<p><code class=\"expr\">{}</code></p>\n<div class=\"tree\">\n", escape_html(&expr.to_source())));
+    push_html_tree(data, expr);
+    data.push_str("</div>\n");
+}
+
+// Output fragments of a tree that is printed with an explicit stack,
+// as expressions can be nested arbitrarily deep by repeated rewriting.
+enum Frame<'a> {
+    Expr(&'a Expr),
+    Str(&'static str),
+}
+
+// Nested lists, where every functor is a collapsible element with its arguments as children.
+fn push_html_tree(data: &mut String, expr: &Expr) {
+    let mut stack = vec![Frame::Str("</ul>\n"), Frame::Expr(expr)];
+    data.push_str("<ul>\n");
+    while let Some(frame) = stack.pop() {
+        match frame {
+            Frame::Str(s) => data.push_str(s),
+            Frame::Expr(expr) => match expr.node() {
+                ExprNode::Variable { iden } => data.push_str(&format!("<li><span class=\"var\">{}</span></li>\n", escape_html(iden))),
+                ExprNode::Segment { iden } => data.push_str(&format!("<li><span class=\"var\">{}..</span></li>\n", escape_html(iden))),
+                ExprNode::Number(n) => data.push_str(&format!("<li><span class=\"num\">{}</span></li>\n", n)),
+                ExprNode::Str(s) => data.push_str(&format!("<li><span class=\"str\">{}</span></li>\n", escape_html(&quote(s)))),
+                ExprNode::Functor { iden, args } => {
+                    data.push_str(&format!("<li><details open><summary>{}</summary>\n<ul>\n", escape_html(iden)));
+                    stack.push(Frame::Str("</ul>\n</details></li>\n"));
+                    stack.extend(args.iter().rev().map(Frame::Expr));
+                }
+            }
+        }
+    }
+}
+
+fn escape_html(s: &str) -> String {
+    s.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::runtime::Env;
+
+    #[test]
+    fn export_markdown() {
+        let history = vec![
+            Expr::functor("pair", vec![Expr::variable("a"), Expr::variable("b")]),
+            Expr::functor("pair", vec![Expr::variable("b"), Expr::variable("a")]),
+            Expr::functor("f", vec![Expr::variable("a")]),
+        ];
+        let rules = vec![
+            AppliedRule::new(
+                Some("swap".to_string()),
+                Expr::functor("pair", vec![Expr::variable("x"), Expr::variable("y")]),
+                Expr::functor("pair", vec![Expr::variable("y"), Expr::variable("x")]),
+                0
+            ),
+            AppliedRule::new(
+                None,
+                Expr::functor("pair", vec![Expr::variable("x"), Expr::variable("y")]),
+                Expr::functor("f", vec![Expr::variable("y")]),
+                0
+            ),
+        ];
+
+        assert_eq!(ExportFormat::from_name("md"), Some(ExportFormat::Markdown));
+        assert_eq!(ExportFormat::Markdown.render(&history, &rules, &ExportOptions::default()), [
+            "# Derivation",
+            "",
+            "Start pattern matching on:",
+            "",
+            "```",
+            "pair(a, b)",
+            "```",
+            "",
+            "1. Apply `swap` (`pair(x, y) => pair(y, x)`) at depth 0, resulting in:",
+            "",
+            "   ```",
+            "   pair(b, a)",
+            "   ```",
+            "",
+            "2. Apply in-line rule `pair(x, y) => f(y)` at depth 0, resulting in:",
+            "",
+            "   ```",
+            "   f(a)",
+            "   ```",
+            "",
+            "## Summary",
+            "",
+            "- Steps: 2",
+            "- Rules: swap (1), in-line rule (1)",
+            "- Nodes in the result: 2",
+            "",
+            "**Result:** `f(a)`",
+            ""
+        ].join("\n"));
+    }
+
+    #[test]
+    fn export_text() {
+        let history = vec![
+            Expr::functor("pair", vec![Expr::variable("a"), Expr::variable("b")]),
+            Expr::functor("pair", vec![Expr::variable("b"), Expr::variable("a")]),
+            Expr::functor("f", vec![Expr::variable("a")]),
+        ];
+        let pair = Expr::functor("pair", vec![Expr::variable("x"), Expr::variable("y")]);
+        let rules = vec![
+            AppliedRule::new(Some("swap".to_string()), pair.clone(), Expr::functor("pair", vec![Expr::variable("y"), Expr::variable("x")]), 0),
+            AppliedRule::new(None, pair, Expr::functor("f", vec![Expr::variable("y")]), 0),
+        ];
+
+        let text = ExportFormat::Text.render(&history, &rules, &ExportOptions::default());
+        assert!(text.contains("1. Applying rule swap: pair(x, y) => pair(y, x) at depth 0, results in:\n    pair(b, a)\n"));
+        assert!(text.contains("2. Applying in-line rule: pair(x, y) => f(y) at depth 0, results in:\n    f(a)\n"));
+        assert!(text.ends_with("Result: f(a)"));
+    }
+
+    #[test]
+    fn export_json_substitutions() {
+        let mut lexer = Lexer::new();
+        lexer.lex("
+            pair(f(a + b), f(c))
+                f(x) => g(x) at 1
+                g(x) => k(x) once
+                pair(k(x), rest..) => h(rest..) at 0
+        ");
+        let mut parser = Parser::new();
+        assert!(parser.parse(&mut lexer).is_ok());
+        let mut env = Env::new();
+        env.set_output(std::io::sink());
+        assert!(env.interpret(parser.stmts).is_ok());
+        assert_eq!(env.history.last().map(Expr::to_string), Some("h(g(c))".to_string()));
+
+        let json = Json::parse(&ExportFormat::Json.render(&env.history, &env.derivation_history, &ExportOptions::default())).unwrap();
+        let substitutions: Vec<String> = json.get("steps").and_then(Json::as_array).unwrap()[1..]
+            .iter()
+            .map(|step| step.get("rule").and_then(|rule| rule.get("substitutions")).unwrap().to_string())
+            .collect();
+        assert_eq!(substitutions, vec![
+            r#"[{"path":[0],"bindings":{"x":"a + b"}},{"path":[1],"bindings":{"x":"c"}}]"#,
+            r#"[{"path":[0],"bindings":{"x":"a + b"}}]"#,
+            r#"[{"path":[],"bindings":{"x":"a + b","rest..":["g(c)"]}}]"#,
+        ]);
+    }
+
+    #[test]
+    fn export_lean() {
+        let history = vec![
+            Expr::functor("pair", vec![Expr::variable("a"), Expr::variable("b")]),
+            Expr::functor("pair", vec![Expr::variable("b"), Expr::variable("a")]),
+            Expr::functor("f", vec![Expr::variable("a")]),
+        ];
+        let pair = Expr::functor("pair", vec![Expr::variable("x"), Expr::variable("y")]);
+        let rules = vec![
+            AppliedRule::new(Some("swap".to_string()), pair.clone(), Expr::functor("pair", vec![Expr::variable("y"), Expr::variable("x")]), 0),
+            AppliedRule::new(None, pair, Expr::functor("f", vec![Expr::variable("y")]), 0),
+        ];
+        let options = ExportOptions { preamble: "axiom swap : ∀ x y, pair x y = pair y x\n".to_string(), ..ExportOptions::default() };
+
+        assert_eq!(ExportFormat::Lean.render(&history[..2], &rules[..1], &options), [
+            "axiom swap : ∀ x y, pair x y = pair y x",
+            "",
+            "example : pair a b = pair b a := by",
+            "  rw [swap]",
+            ""
+        ].join("\n"));
+        assert_eq!(ExportFormat::Lean.render(&history, &rules, &ExportOptions::default()), [
+            "example : pair a b = f a := by",
+            "  rw [swap]",
+            "  -- in-line rule: pair x y => f y at depth 0",
+            "  sorry",
+            ""
+        ].join("\n"));
+    }
+
+    #[test]
+    fn export_source() {
+        // mul(add(a, b), c) => mul(c, add(a, b)) => mul(c, group(add(a, b)))
+        let sum = Expr::functor("add", vec![Expr::variable("a"), Expr::variable("b")]);
+        let history = vec![
+            Expr::functor("mul", vec![sum.clone(), Expr::variable("c")]),
+            Expr::functor("mul", vec![Expr::variable("c"), sum.clone()]),
+            Expr::functor("mul", vec![Expr::variable("c"), Expr::functor("group", vec![sum.clone()])]),
+        ];
+        let rules = vec![
+            AppliedRule::new(
+                Some("comm".to_string()),
+                Expr::functor("mul", vec![Expr::variable("x"), Expr::variable("y")]),
+                Expr::functor("mul", vec![Expr::variable("y"), Expr::variable("x")]),
+                0
+            ),
+            AppliedRule::new(None, sum.clone(), Expr::functor("group", vec![sum]), 1),
+        ];
+
+        let source = ExportFormat::Source.render(&history, &rules, &ExportOptions::default());
+        assert_eq!(source, [
+            "add(a, b) * c",
+            "    x * y => y * x at 0",
+            "    a + b => (a + b) at 1",
+            "    expect c * (a + b)",
+            "end",
+            ""
+        ].join("\n"));
+
+        let mut lexer = Lexer::new();
+        lexer.lex(&source);
+        let mut parser = Parser::new();
+        assert!(parser.parse(&mut lexer).is_ok());
+        let mut env = Env::new();
+        env.set_output(std::io::sink());
+        assert!(env.interpret(parser.stmts).is_ok());
+        assert!(env.warnings.is_empty());
+    }
+
+    fn interpret(env: &mut Env, input_string: &str) {
+        let mut lexer = Lexer::new();
+        lexer.lex(input_string);
+        let mut parser = Parser::new();
+        assert!(parser.parse(&mut lexer).is_ok());
+        assert!(env.interpret(parser.stmts).is_ok());
+    }
+
+    // The derivation of the environment as a script, which derives the same result in a new environment
+    fn export_and_rerun(env: &Env) -> String {
+        let source = ExportFormat::Source.render(&env.history, &env.derivation_history, &ExportOptions::default());
+        let mut rerun = Env::new();
+        rerun.set_output(std::io::sink());
+        interpret(&mut rerun, &source);
+        assert!(rerun.warnings.is_empty());
+        source
+    }
+
+    #[test]
+    fn export_source_once_and_chosen_match() {
+        let mut env = Env::new();
+        env.set_output(std::io::sink());
+        env.chooser = Some(Box::new(|_: &[(Vec<usize>, Expr)]| crate::event::Choice::One(1)));
+        interpret(&mut env, "
+            f(g(a), g(b), g(c))
+                g(x) => k(x) once
+                g(x) => m(x) at 1
+        ");
+        assert_eq!(export_and_rerun(&env), [
+            "f(g(a), g(b), g(c))",
+            "    g(x) => k(x) once",
+            "    focus path 2",
+            "        g(x) => m(x) at 0",
+            "    unfocus",
+            "    expect f(k(a), g(b), m(c))",
+            "end",
+            ""
+        ].join("\n"));
+    }
+
+    #[test]
+    fn export_source_innermost() {
+        let mut env = Env::new();
+        env.set_output(std::io::sink());
+        interpret(&mut env, "
+            def zero as add(x, 0) => x
+            f(add(add(a, 0), 0), add(b, 0))
+                apply zero innermost
+        ");
+        assert_eq!(export_and_rerun(&env), [
+            "f(a + 0 + 0, b + 0)",
+            "    x + 0 => x innermost",
+            "    expect f(a, b)",
+            "end",
+            ""
+        ].join("\n"));
+    }
+
+    #[test]
+    fn export_source_range() {
+        let mut env = Env::new();
+        env.set_output(std::io::sink());
+        interpret(&mut env, "
+            f(g(a), h(g(b)), h(h(g(c))))
+                g(x) => k(x) at 1..2
+        ");
+        assert_eq!(export_and_rerun(&env), [
+            "f(g(a), h(g(b)), h(h(g(c))))",
+            "    g(x) => k(x) at 1..2",
+            "    expect f(k(a), h(k(b)), h(h(g(c))))",
+            "end",
+            ""
+        ].join("\n"));
+    }
+
+    #[test]
+    fn export_source_auto_rule() {
+        let mut env = Env::new();
+        env.set_output(std::io::sink());
+        interpret(&mut env, "
+            def zero as x + 0 => x auto
+            h(k(g(a)), g(b))
+                k(x) => x + 0 + 0 at 1
+        ");
+        assert_eq!(export_and_rerun(&env), [
+            "h(k(g(a)), g(b))",
+            "    k(x) => x + 0 + 0 at 1",
+            "    x + 0 => x innermost",
+            "    expect h(g(a), g(b))",
+            "end",
+            ""
+        ].join("\n"));
+    }
+
+    #[test]
+    fn export_html() {
+        let history = vec![
+            Expr::functor("f", vec![Expr::variable("x")]),
+            Expr::functor("g", vec![Expr::variable("x"), Expr::variable("x")]),
+        ];
+        let rules = vec![AppliedRule::new(
+            None,
+            Expr::functor("f", vec![Expr::variable("x")]),
+            Expr::functor("g", vec![Expr::variable("x"), Expr::variable("x")]),
+            0
+        )];
+
+        let options = ExportOptions { elapsed: Some(Duration::from_millis(3)), ..ExportOptions::default() };
+        let res = ExportFormat::Html.render(&history, &rules, &options);
+        assert!(res.starts_with("<!DOCTYPE html>"));
+        assert!(res.contains("<p>Apply in-line rule <code class=\"rule\">f(x) =&gt; g(x, x)</code> at depth 0</p>"));
+        assert!(res.contains([
+            "<ul>",
+            "<li><details open><summary>g</summary>",
+            "<ul>",
+            "<li><span class=\"var\">x</span></li>",
+            "<li><span class=\"var\">x</span></li>",
+            "</ul>",
+            "</details></li>",
+            "</ul>",
+        ].join("\n").as_str()));
+        assert!(res.contains("<li>Steps: 1</li>\n<li>Rules: in-line rule (1)</li>\n<li>Nodes in the result: 3</li>\n<li>Elapsed: 3ms</li>"));
+        assert!(res.ends_with("<p><strong>Result:</strong> <code class=\"expr\">g(x, x)</code></p>\n</body>\n</html>\n"));
+    }
+}
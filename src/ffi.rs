@@ -0,0 +1,203 @@
+// C interface of the interpreter for embedding it in other languages, e.g., Python with ctypes or cffi.
+// The declarations are in include/raxio.h. Strings are UTF-8 and NUL-terminated, and every string that is
+// returned is owned by the caller, who frees it with raxio_string_free.
+
+use std::{ffi::{c_char, c_int, CStr, CString}, ptr};
+
+use crate::{
+    diagnostic::plain_message,
+    parser::Syntax,
+    runtime::Env
+};
+
+pub const RAXIO_OK: c_int = 0;
+pub const RAXIO_SYNTAX_ERROR: c_int = 1;
+pub const RAXIO_RUNTIME_ERROR: c_int = 2;
+// A null pointer, or a source that is not UTF-8
+pub const RAXIO_INVALID_ARGUMENT: c_int = 3;
+
+// An environment together with the outcome of the last evaluation
+pub struct RaxioEnv {
+    env: Env,
+    last_error: Option<String>,
+    // Warnings of the last evaluation, as 'CODE: message'
+    warnings: Vec<String>,
+}
+
+impl RaxioEnv {
+    fn eval(&mut self, source: &str) -> c_int {
+        self.last_error = None;
+        self.warnings.clear();
+
+        // Operators that earlier calls declared are known in this one, like their rules
+        let mut lexer = self.env.lex(source);
+        if let Some(err) = lexer.errors.first() {
+            self.last_error = Some(plain_message(err));
+            return RAXIO_SYNTAX_ERROR;
+        }
+        let mut parser = self.env.parser(Syntax::detect(&lexer));
+        if let Err(err) = parser.parse(&mut lexer) {
+            self.last_error = Some(plain_message(&err));
+            return RAXIO_SYNTAX_ERROR;
+        }
+
+        let res = self.env.interpret(parser.stmts);
+        self.warnings.extend(self.env.warnings.drain(..).map(|warn| format!("{}: {}", warn.code(), plain_message(&warn))));
+        match res {
+            Ok(()) => RAXIO_OK,
+            Err(err) => {
+                self.last_error = Some(plain_message(&err));
+                RAXIO_RUNTIME_ERROR
+            }
+        }
+    }
+}
+
+// A string for the caller, where interior NUL characters, which C strings cannot contain, are dropped
+fn to_c_string(s: &str) -> *mut c_char {
+    CString::new(s.replace('\0', "")).map_or(ptr::null_mut(), CString::into_raw)
+}
+
+/// Create an environment, which is freed with raxio_env_free.
+#[no_mangle]
+pub extern "C" fn raxio_env_new() -> *mut RaxioEnv {
+    Box::into_raw(Box::new(RaxioEnv { env: Env::new(), last_error: None, warnings: vec![] }))
+}
+
+/// Free an environment.
+///
+/// # Safety
+/// The environment must be created by raxio_env_new and not be freed before, or be null.
+#[no_mangle]
+pub unsafe extern "C" fn raxio_env_free(env: *mut RaxioEnv) {
+    if !env.is_null() {
+        drop(Box::from_raw(env));
+    }
+}
+
+/// Interpret the statements of the source in the environment, which keeps its rules and pattern matching
+/// context across calls. Returns RAXIO_OK, or the kind of error, whose message is given by raxio_last_error.
+///
+/// # Safety
+/// The environment must be created by raxio_env_new, and the source must be a NUL-terminated string, or null.
+#[no_mangle]
+pub unsafe extern "C" fn raxio_eval(env: *mut RaxioEnv, source: *const c_char) -> c_int {
+    let Some(env) = env.as_mut() else {
+        return RAXIO_INVALID_ARGUMENT;
+    };
+    if source.is_null() {
+        return RAXIO_INVALID_ARGUMENT;
+    }
+    match CStr::from_ptr(source).to_str() {
+        Ok(source) => env.eval(source),
+        Err(_) => {
+            env.last_error = Some("the source is not valid UTF-8".to_string());
+            RAXIO_INVALID_ARGUMENT
+        }
+    }
+}
+
+/// The current expression in infix notation, or null outside of a pattern matching context.
+///
+/// # Safety
+/// The environment must be created by raxio_env_new, or be null.
+#[no_mangle]
+pub unsafe extern "C" fn raxio_current_expr_string(env: *const RaxioEnv) -> *mut c_char {
+    match env.as_ref().and_then(|env| env.env.history.last().filter(|_| env.env.is_matching)) {
+        Some(expr) => to_c_string(&expr.to_string()),
+        None => ptr::null_mut(),
+    }
+}
+
+/// Whether the environment is in a pattern matching context, i.e., 1 if it is and 0 if not.
+///
+/// # Safety
+/// The environment must be created by raxio_env_new, or be null.
+#[no_mangle]
+pub unsafe extern "C" fn raxio_is_matching(env: *const RaxioEnv) -> c_int {
+    env.as_ref().is_some_and(|env| env.env.is_matching).into()
+}
+
+/// The message of the error of the last evaluation, or null if it succeeded.
+///
+/// # Safety
+/// The environment must be created by raxio_env_new, or be null.
+#[no_mangle]
+pub unsafe extern "C" fn raxio_last_error(env: *const RaxioEnv) -> *mut c_char {
+    match env.as_ref().and_then(|env| env.last_error.as_deref()) {
+        Some(message) => to_c_string(message),
+        None => ptr::null_mut(),
+    }
+}
+
+/// The warnings of the last evaluation, one 'CODE: message' per line, which is empty if there are none.
+///
+/// # Safety
+/// The environment must be created by raxio_env_new, or be null.
+#[no_mangle]
+pub unsafe extern "C" fn raxio_warnings(env: *const RaxioEnv) -> *mut c_char {
+    match env.as_ref() {
+        Some(env) => to_c_string(&env.warnings.join("\n")),
+        None => ptr::null_mut(),
+    }
+}
+
+/// Free a string that is returned by this interface.
+///
+/// # Safety
+/// The string must be returned by a raxio function and not be freed before, or be null.
+#[no_mangle]
+pub unsafe extern "C" fn raxio_string_free(s: *mut c_char) {
+    if !s.is_null() {
+        drop(CString::from_raw(s));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    unsafe fn take_string(s: *mut c_char) -> Option<String> {
+        if s.is_null() {
+            return None;
+        }
+        let res = CStr::from_ptr(s).to_str().unwrap().to_string();
+        raxio_string_free(s);
+        Some(res)
+    }
+
+    #[test]
+    fn embed_env() {
+        unsafe {
+            let env = raxio_env_new();
+            assert_eq!(raxio_eval(env, c"def swap as f(x, y) => f(y, x)".as_ptr()), RAXIO_OK);
+            assert_eq!(raxio_eval(env, c"f(a, b) apply swap at 0 apply swp at 0".as_ptr()), RAXIO_OK);
+            assert_eq!(raxio_is_matching(env), 1);
+            assert_eq!(take_string(raxio_current_expr_string(env)).as_deref(), Some("f(b, a)"));
+            assert!(take_string(raxio_warnings(env)).unwrap().starts_with("W005: cannot find rule 'swp'."));
+
+            assert_eq!(raxio_eval(env, c"expect f(a, b)".as_ptr()), RAXIO_RUNTIME_ERROR);
+            assert!(take_string(raxio_last_error(env)).unwrap().starts_with("expected the current expression"));
+            assert_eq!(raxio_eval(env, c"apply (at 0".as_ptr()), RAXIO_SYNTAX_ERROR);
+            assert_eq!(raxio_eval(env, c"end".as_ptr()), RAXIO_OK);
+            assert_eq!(take_string(raxio_last_error(env)), None);
+            assert_eq!(raxio_current_expr_string(env), ptr::null_mut());
+
+            assert_eq!(raxio_eval(env, ptr::null()), RAXIO_INVALID_ARGUMENT);
+            raxio_env_free(env);
+        }
+    }
+
+    #[test]
+    fn embed_env_operators() {
+        unsafe {
+            let env = raxio_env_new();
+            assert_eq!(raxio_eval(env, c"operator \"<+>\" as oplus, precedence 1".as_ptr()), RAXIO_OK);
+            // The operator of the earlier call is lexed and parsed in this one
+            assert_eq!(raxio_eval(env, c"a <+> b oplus(x, y) => oplus(y, x) at 0".as_ptr()), RAXIO_OK);
+            assert_eq!(take_string(raxio_last_error(env)), None);
+            assert_eq!(take_string(raxio_current_expr_string(env)).as_deref(), Some("oplus(b, a)"));
+            raxio_env_free(env);
+        }
+    }
+}
@@ -0,0 +1,288 @@
+use crate::ast::{quote, Expr, ExprNode};
+
+// A notation in which expressions can be printed with the show statement.
+// New notations are added by registering a formatter in the environment.
+pub trait Formatter: Send + Sync {
+    fn format(&self, expr: &Expr) -> String;
+}
+
+// Binary arithmetic operators in infix notation, e.g., c * (a + b)
+pub struct Infix;
+
+// Functor prefix notation, e.g., mul(c, group(add(a, b)))
+pub struct Prefix;
+
+// S-expressions, e.g., (mul c (group (add a b)))
+pub struct SExpr;
+
+// LaTeX math, e.g., c \cdot \left(a + b\right)
+pub struct Latex;
+
+// Lean terms, where functors are applied by juxtaposition, e.g., c * (a + b) / x_1 ^ 2 + foo y
+pub struct Lean;
+
+impl Formatter for Infix {
+    fn format(&self, expr: &Expr) -> String {
+        expr.to_string()
+    }
+}
+
+impl Formatter for Prefix {
+    fn format(&self, expr: &Expr) -> String {
+        format!("{}", expr)
+    }
+}
+
+// Output fragments of an expression that is printed with an explicit stack,
+// as expressions can be nested arbitrarily deep by repeated rewriting.
+enum Frame<'a> {
+    Expr(&'a Expr),
+    Str(&'static str),
+}
+
+impl Formatter for SExpr {
+    fn format(&self, expr: &Expr) -> String {
+        let mut res = String::new();
+        let mut stack = vec![Frame::Expr(expr)];
+        while let Some(frame) = stack.pop() {
+            match frame {
+                Frame::Str(s) => res.push_str(s),
+                Frame::Expr(expr) => match expr.node() {
+                    ExprNode::Variable { iden } => res.push_str(iden),
+                    ExprNode::Number(n) => res.push_str(&n.to_string()),
+                    ExprNode::Str(s) => res.push_str(&quote(s)),
+                    ExprNode::Segment { iden } => {
+                        res.push_str(iden);
+                        res.push_str("..");
+                    },
+                    ExprNode::Functor { iden, args } => {
+                        res.push('(');
+                        res.push_str(iden);
+                        stack.push(Frame::Str(")"));
+                        for arg in args.iter().rev() {
+                            stack.push(Frame::Expr(arg));
+                            stack.push(Frame::Str(" "));
+                        }
+                    }
+                }
+            }
+        }
+        res
+    }
+}
+
+impl Latex {
+    // Single letters are printed in math italics, and longer identifiers upright.
+    fn iden(iden: &str) -> String {
+        let iden = iden.replace('_', "\\_");
+        if iden.chars().count() == 1 || iden.chars().all(|c| c.is_ascii_digit()) {
+            iden
+        } else {
+            format!("\\mathrm{{{}}}", iden)
+        }
+    }
+
+    fn push_args<'a>(stack: &mut Vec<Frame<'a>>, args: &'a [Expr]) {
+        for (i, arg) in args.iter().enumerate().rev() {
+            stack.push(Frame::Expr(arg));
+            if i > 0 {
+                stack.push(Frame::Str(", "));
+            }
+        }
+    }
+}
+
+impl Formatter for Latex {
+    fn format(&self, expr: &Expr) -> String {
+        let mut res = String::new();
+        let mut stack = vec![Frame::Expr(expr)];
+        while let Some(frame) = stack.pop() {
+            match frame {
+                Frame::Str(s) => res.push_str(s),
+                Frame::Expr(expr) => match expr.node() {
+                    ExprNode::Variable { iden } => res.push_str(&Self::iden(iden)),
+                    ExprNode::Number(n) => res.push_str(&n.to_string()),
+                    ExprNode::Str(s) => res.push_str(&format!("\\text{{{}}}", quote(s))),
+                    ExprNode::Segment { iden } => {
+                        res.push_str(&Self::iden(iden));
+                        res.push_str("\\ldots");
+                    },
+                    ExprNode::Functor { iden, args } => match (iden.as_str(), args.as_slice()) {
+                        ("div", [left, right]) => {
+                            res.push_str("\\frac{");
+                            stack.extend([Frame::Str("}"), Frame::Expr(right), Frame::Str("}{"), Frame::Expr(left)]);
+                        },
+                        ("pow", [base, exponent]) => {
+                            res.push('{');
+                            stack.extend([Frame::Str("}"), Frame::Expr(exponent), Frame::Str("}^{"), Frame::Expr(base)]);
+                        },
+                        ("add" | "sub" | "mul", [left, right]) => {
+                            let op = match iden.as_str() {
+                                "add" => " + ",
+                                "sub" => " - ",
+                                _ => " \\cdot ",
+                            };
+                            stack.extend([Frame::Expr(right), Frame::Str(op), Frame::Expr(left)]);
+                        },
+                        ("group", _) => {
+                            res.push_str("\\left(");
+                            stack.push(Frame::Str("\\right)"));
+                            Self::push_args(&mut stack, args);
+                        },
+                        _ => {
+                            res.push_str(&Self::iden(iden));
+                            res.push_str("\\left(");
+                            stack.push(Frame::Str("\\right)"));
+                            Self::push_args(&mut stack, args);
+                        }
+                    }
+                }
+            }
+        }
+        res
+    }
+}
+
+impl Lean {
+    // The symbol and the precedence of an arithmetic operator in Lean, where only ^ is right-associative
+    fn operator(expr: &Expr) -> Option<(&'static str, u8)> {
+        match expr.node() {
+            ExprNode::Functor { iden, args } if args.len() == 2 => match iden.as_str() {
+                "add" => Some((" + ", 65)),
+                "sub" => Some((" - ", 65)),
+                "mul" => Some((" * ", 70)),
+                "div" => Some((" / ", 70)),
+                "pow" => Some((" ^ ", 75)),
+                _ => None
+            },
+            _ => None
+        }
+    }
+
+    // Push an argument of an application, in parentheses unless it is atomic
+    fn push_arg<'a>(stack: &mut Vec<Frame<'a>>, expr: &'a Expr) {
+        let is_atomic = match expr.node() {
+            ExprNode::Number(n) => !n.to_string().starts_with('-'),
+            ExprNode::Functor { iden, args } => iden == "group" || iden == "list" || args.is_empty(),
+            _ => true,
+        };
+        Self::push_parenthesized(stack, expr, !is_atomic);
+    }
+
+    // Push an operand of an operator with the precedence, in parentheses if it is an operator that binds weaker, 
+    // or as strong on the side that the operator does not associate to
+    fn push_operand<'a>(stack: &mut Vec<Frame<'a>>, expr: &'a Expr, precedence: u8, is_assoc_side: bool) {
+        let needs_parens = Self::operator(expr).is_some_and(|(_, operand_precedence)| {
+            operand_precedence < precedence || (operand_precedence == precedence && !is_assoc_side)
+        });
+        Self::push_parenthesized(stack, expr, needs_parens);
+    }
+
+    fn push_parenthesized<'a>(stack: &mut Vec<Frame<'a>>, expr: &'a Expr, needs_parens: bool) {
+        if needs_parens {
+            stack.extend([Frame::Str(")"), Frame::Expr(expr), Frame::Str("(")]);
+        } else {
+            stack.push(Frame::Expr(expr));
+        }
+    }
+}
+
+impl Formatter for Lean {
+    fn format(&self, expr: &Expr) -> String {
+        let mut res = String::new();
+        let mut stack = vec![Frame::Expr(expr)];
+        while let Some(frame) = stack.pop() {
+            match frame {
+                Frame::Str(s) => res.push_str(s),
+                Frame::Expr(expr) => match expr.node() {
+                    ExprNode::Variable { iden } | ExprNode::Segment { iden } => res.push_str(iden),
+                    ExprNode::Number(n) => res.push_str(&n.to_string()),
+                    ExprNode::Str(s) => res.push_str(&quote(s)),
+                    ExprNode::Functor { iden, args } => match (Self::operator(expr), args.as_slice()) {
+                        (Some((op, precedence)), [left, right]) => {
+                            let is_right_assoc = iden == "pow";
+                            Self::push_operand(&mut stack, right, precedence, is_right_assoc);
+                            stack.push(Frame::Str(op));
+                            Self::push_operand(&mut stack, left, precedence, !is_right_assoc);
+                        },
+                        _ if iden == "group" || iden == "list" => {
+                            let (open, close) = if iden == "group" { ("(", ")") } else { ("[", "]") };
+                            res.push_str(open);
+                            stack.push(Frame::Str(close));
+                            for (i, arg) in args.iter().enumerate().rev() {
+                                stack.push(Frame::Expr(arg));
+                                if i > 0 {
+                                    stack.push(Frame::Str(", "));
+                                }
+                            }
+                        },
+                        _ => {
+                            res.push_str(iden);
+                            for arg in args.iter().rev() {
+                                Self::push_arg(&mut stack, arg);
+                                stack.push(Frame::Str(" "));
+                            }
+                        }
+                    }
+                }
+            }
+        }
+        res
+    }
+}
+
+// The notations that are available by default, by the name used in 'show as NAME'.
+pub fn default_formatters() -> Vec<(&'static str, Box<dyn Formatter>)> {
+    vec![
+        ("infix", Box::new(Infix)),
+        ("prefix", Box::new(Prefix)),
+        ("sexpr", Box::new(SExpr)),
+        ("latex", Box::new(Latex)),
+        ("lean", Box::new(Lean)),
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lexer::Number;
+
+    #[test]
+    fn format_expr() {
+        // c * (a + b) / pow(x_1, 2) + foo(y)
+        let expr = Expr::functor("add", vec![
+            Expr::functor("div", vec![
+                Expr::functor("mul", vec![
+                    Expr::variable("c"),
+                    Expr::functor("group", vec![Expr::functor("add", vec![Expr::variable("a"), Expr::variable("b")])])
+                ]),
+                Expr::functor("pow", vec![Expr::variable("x_1"), Expr::variable("2")])
+            ]),
+            Expr::functor("foo", vec![Expr::variable("y")])
+        ]);
+
+        assert_eq!(Infix.format(&expr), "c * (a + b) / pow(x_1, 2) + foo(y)");
+        assert_eq!(SExpr.format(&expr), "(add (div (mul c (group (add a b))) (pow x_1 2)) (foo y))");
+        assert_eq!(
+            Latex.format(&expr),
+            "\\frac{c \\cdot \\left(a + b\\right)}{{\\mathrm{x\\_1}}^{2}} + \\mathrm{foo}\\left(y\\right)"
+        );
+        assert_eq!(Lean.format(&expr), "c * (a + b) / x_1 ^ 2 + foo y");
+
+        // f(g(x), -1, h(), [a, b])
+        let app = Expr::functor("f", vec![
+            Expr::functor("g", vec![Expr::variable("x")]),
+            Expr::number(Number::Int(-1)),
+            Expr::functor("h", vec![]),
+            Expr::functor("list", vec![Expr::variable("a"), Expr::variable("b")])
+        ]);
+        assert_eq!(Lean.format(&app), "f (g x) (-1) h [a, b]");
+
+        // sub(a, sub(b, c)) and pow(pow(a, b), c)
+        let (a, b, c) = (Expr::variable("a"), Expr::variable("b"), Expr::variable("c"));
+        let difference = Expr::functor("sub", vec![a.clone(), Expr::functor("sub", vec![b.clone(), c.clone()])]);
+        assert_eq!(Lean.format(&difference), "a - (b - c)");
+        let power = Expr::functor("pow", vec![Expr::functor("pow", vec![a, b]), c]);
+        assert_eq!(Lean.format(&power), "(a ^ b) ^ c");
+    }
+}
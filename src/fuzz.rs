@@ -0,0 +1,62 @@
+use std::io;
+
+use crate::{
+    lexer::Lexer,
+    parser::{Parser, Stmt, Syntax},
+    runtime::Env
+};
+
+// Entry point for fuzzers, e.g., the target in fuzz/, which interprets arbitrary input like a file, and only
+// fails by panicking. Any input that is not UTF-8 or has syntax errors is rejected, like the command line does.
+// The limits are lowered such that runaway rules fail fast, and exports are skipped, as they write files.
+pub fn parse_and_interpret(data: &[u8]) {
+    let Ok(source) = std::str::from_utf8(data) else {
+        return;
+    };
+    let mut lexer = Lexer::new();
+    lexer.lex(source);
+    if !lexer.errors.is_empty() {
+        return;
+    }
+    let mut parser = Parser::with_syntax(Syntax::detect(&lexer));
+    if parser.parse(&mut lexer).is_err() {
+        return;
+    }
+
+    let mut env = Env::new();
+    env.set_output(io::sink());
+    env.limits.max_depth = 64;
+    env.limits.max_nodes = 1000;
+    let stmts = parser.stmts.into_iter().filter_map(without_exports).collect();
+    let _ = env.interpret(stmts);
+}
+
+// The statement without writing a derivation to a file, or nothing if it exports rules
+fn without_exports(stmt: Stmt) -> Option<Stmt> {
+    match stmt {
+        Stmt::EndStmt { binding, .. } => Some(Stmt::EndStmt { path: None, append: false, format: None, binding }),
+        Stmt::ExportRulesStmt(_) => None,
+        Stmt::SilentStmt(stmts) => Some(Stmt::SilentStmt(stmts.into_iter().filter_map(without_exports).collect())),
+        stmt => Some(stmt),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn interpret_arbitrary_input() {
+        parse_and_interpret(b"def grow as x => f(x, x)\na\n    apply grow* at 0\nend \"fuzz_output.txt\"");
+        parse_and_interpret(b"a + \xff");
+        parse_and_interpret(b"silent { f(x) apply (at");
+        assert!(!std::path::Path::new("fuzz_output.txt").exists());
+    }
+
+    #[test]
+    fn interpret_without_exporting_rules() {
+        parse_and_interpret(b"def swap as f(x, y) => f(y, x)\nexport rules \"fuzz_rules.rxlib\"\nsilent { export rules \"fuzz_silent.rxlib\" }");
+        assert!(!std::path::Path::new("fuzz_rules.rxlib").exists());
+        assert!(!std::path::Path::new("fuzz_silent.rxlib").exists());
+    }
+}
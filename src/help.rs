@@ -0,0 +1,250 @@
+// A topic of the help in the REPL, which is printed by entering 'help' followed by its name, e.g., 'help def'
+pub struct HelpTopic {
+    pub name: &'static str,
+
+    // One line that describes the topic in the overview of all topics
+    pub summary: &'static str,
+
+    // The syntax of the constructs of the topic, each with what it does
+    pub lines: &'static [&'static str],
+
+    // A script that demonstrates the topic, which can be entered as is, and is interpreted by the tests to keep it working
+    pub example: &'static str,
+}
+
+pub const TOPICS: [HelpTopic; 20] = [
+    HelpTopic { name: "def", summary: "Define a named rule",
+        lines: &[
+            "def [RULE_NAME] as [LEFT_EXPR] => [RIGHT_EXPR]",
+            "    RULE_NAME is an alphanumeric identifier.",
+            "    LEFT_EXPR is the expression to match against.",
+            "    RIGHT_EXPR is the expression to produce if the left expression was matched.",
+            "def [RULE_NAME]([PARAMS]) as [LEFT_EXPR] => [RIGHT_EXPR]",
+            "    PARAMS is a comma-separated list of identifiers that are substituted when applying the rule.",
+            "def [RULE_NAME] as [LEFT_EXPR] => [RIGHT_EXPR] tags [[TAG], [TAG], ...]",
+            "    TAG groups the rule with other rules, which are applied together with 'tagged [TAG]' in a strategy.",
+            "def [RULE_NAME] as [LEFT_EXPR] => [RIGHT_EXPR] auto",
+            "    applies the rule after every rewrite of apply and in-line rules, until it no longer changes the expression.",
+            "'fresh y' in RIGHT_EXPR introduces a new variable y_1, y_2, etc., on each application.",
+            "'$1', '$2', etc., are numbered captures, which can be used like variables, e.g., f($1, $2) => g($2, $1)",
+            "'rest..' as an argument of a functor matches any number of arguments, e.g., add(x, rest..) => add(rest.., x)",
+            "'\"label\"' is a string constant, which only matches the same string, e.g., case(\"base\", x) => x",
+        ],
+        example: "def swap as pair(x, y) => pair(y, x)\npair(a, b)\napply swap at 0\nend" },
+    HelpTopic { name: "expressions", summary: "Start pattern matching an expression",
+        lines: &[
+            "[EXPR] starts a pattern matching context on the expression, which is either",
+            "    - a variable, e.g., x, foo, abc, etc.;",
+            "    - a functor, e.g., f(x), g(h(x, y)), foo(bar(baz)), etc.;",
+            "    - a number or a quoted string constant, e.g., 42 or \"label\";",
+            "    - a list, e.g., [a, b, c], which is the functor list(a, b, c); or",
+            "    - binary arithmetic with '+', '-', '*', '/', '^', e.g., a + b, which is the functor add(a, b).",
+            "end stops pattern matching, see 'help end' for keeping the derivation.",
+        ],
+        example: "f(x, [a, b]) + 2 * y\nend" },
+    HelpTopic { name: "apply", summary: "Apply a rule to the current expression",
+        lines: &[
+            "apply [RULE_NAME] at [DEPTH], or apply [RULE_NAME]([ARGS]) at [DEPTH]",
+            "    applies a defined rule at the depth, see 'help depth'.",
+            "[LEFT_EXPR] => [RIGHT_EXPR] at [DEPTH]",
+            "    applies an in-line rule without a name.",
+            "apply [STRATEGY] at [DEPTH]",
+            "    applies a combination of defined rules, see 'help strategies'.",
+            "If a rule matches several sub-expressions at the depth, the REPL lists them with their paths,",
+            "and asks for the number of the match to rewrite, or 'all' to rewrite every match.",
+            "preview [STRATEGY] at [DEPTH] shows the result without changing the current expression.",
+        ],
+        example: "def double as f(x) => g(x, x)\nf(a)\napply double at 0\ng(x, y) => h(y) at 0\nend" },
+    HelpTopic { name: "depth", summary: "Choose where in the expression a rule is applied",
+        lines: &[
+            "The depth counts the functors from the top of the expression, where the whole expression is at depth 0,",
+            "its arguments are at depth 1, their arguments at depth 2, etc. A rule rewrites every match at the depth.",
+            "at [NUMBER], e.g., at 1, applies the rule at exactly that depth;",
+            "at [FROM]..[TO], e.g., at 2..5, applies the rule at every depth from FROM up to and including TO;",
+            "at any applies the rule at the first depth where it matches;",
+            "innermost instead of 'at DEPTH' rewrites the deepest matching sub-expressions first; and",
+            "once instead of 'at DEPTH' rewrites only the outermost-leftmost match.",
+        ],
+        example: "def zero as add(x, 0) => x\nmul(add(a, 0), add(b, 0))\napply zero at 1\nend" },
+    HelpTopic { name: "strategies", summary: "Combine rules into one application",
+        lines: &[
+            "'a; b' applies a and then b, and 'a | b' applies a or else b;",
+            "'try a' succeeds even if a fails, and 'a*' repeats a until it fails; and",
+            "'tagged t' applies the first rule with tag t that changes the expression.",
+            "e.g., apply (try distribute; then collect)* at [DEPTH]",
+        ],
+        example: "def zero as add(x, 0) => x tags [simplify]\ndef one as mul(x, 1) => x tags [simplify]\nmul(add(a, 0), 1)\napply (tagged simplify)* at any\nend" },
+    HelpTopic { name: "operators", summary: "Declare arities and infix operators",
+        lines: &[
+            "functor [IDENTIFIER]/[ARITY], [IDENTIFIER]/[ARITY], ..., e.g., functor add/2, neg/1",
+            "    declares the number of arguments of functors.",
+            "infix [SYMBOL] as [IDENTIFIER] precedence [1-9], e.g., infix ⊕ as oplus precedence 4",
+            "    writes a binary functor with an infix operator.",
+            "operator \"[SYMBOL]\" as [IDENTIFIER], precedence [1-9], [left|right|nonassoc]",
+            "    also chooses the associativity, e.g., operator \"->\" as implies, precedence 1, right",
+        ],
+        example: "operator \"->\" as implies, precedence 1, right\na -> b -> c\nend" },
+    HelpTopic { name: "arguments", summary: "Flatten, unflatten and order arguments",
+        lines: &[
+            "flatten [IDENTIFIER] turns add(add(a, b), c) into add(a, b, c);",
+            "unflatten [IDENTIFIER] turns add(a, b, c) back into add(add(a, b), c);",
+            "commutative [IDENTIFIER], [IDENTIFIER], ... declares that the order of their arguments is irrelevant; and",
+            "canonicalize sorts the arguments of the declared functors in the current expression.",
+        ],
+        example: "commutative add\n(c + a) + b\nflatten add\ncanonicalize\nend" },
+    HelpTopic { name: "search", summary: "Replace, find and compare sub-expressions",
+        lines: &[
+            "replace [EXPR] with [EXPR] replaces every occurrence of a sub-expression literally, e.g., replace f(x) with y;",
+            "find [EXPR] lists the sub-expressions that match with their depths and paths, where _ matches any expression;",
+            "check [EXPR] warns if the current expression differs from the expression; and",
+            "expect [EXPR] stops with a runtime error if the current expression differs from the expression.",
+        ],
+        example: "g(f(x), f(y))\nfind f(_)\nreplace f(x) with z\nexpect g(z, f(y))\nend" },
+    HelpTopic { name: "history", summary: "Go back and fork derivations",
+        lines: &[
+            "undo, or undo [NUMBER], undoes the last steps in the REPL;",
+            "rollback [STEP] truncates the derivation to the step, where the first expression is step 0;",
+            "redo applies the next rolled back step again;",
+            "checkpoint [NAME] saves the derivation so far, and goto [NAME] jumps back to it; and",
+            "steps lists the steps of the derivation with the applied rules in the REPL.",
+        ],
+        example: "f(a)\nf(x) => g(x) at 0\ncheckpoint first\ng(x) => h(x) at 0\ngoto first\nrollback 0\nredo\nend" },
+    HelpTopic { name: "focus", summary: "Rewrite a sub-expression on its own",
+        lines: &[
+            "focus path [INDICES] narrows the current expression to the arguments at the indices, e.g., focus path 1.0,",
+            "    such that depths are relative to the sub-expression; and",
+            "unfocus puts the rewritten sub-expression back into the expression around it.",
+        ],
+        example: "f(a, g(b))\nfocus path 1\ng(x) => h(x) at 0\nunfocus\nend" },
+    HelpTopic { name: "contexts", summary: "Rewrite several expressions side by side",
+        lines: &[
+            "context [NAME] starts a new pattern matching context, where the first context is named main;",
+            "switch [NAME] resumes a context; and",
+            "equal? [NAME], [NAME] warns if the current expressions of two contexts differ.",
+        ],
+        example: "f(a)\ncontext other\nf(a)\nswitch main\nequal? main, other\nend" },
+    HelpTopic { name: "analysis", summary: "Check termination, overlaps and unification of rules",
+        lines: &[
+            "check-termination [RULE_NAME], [RULE_NAME], ... warns for each rule that does not decrease;",
+            "overlaps [RULE_NAME], [RULE_NAME], ... prints the critical pairs of the rules; and",
+            "unify [EXPR] with [EXPR] finds the most general substitution that makes two expressions identical.",
+        ],
+        example: "def zero as add(x, 0) => x\ncheck-termination zero\noverlaps zero\nunify f(x, b) with f(a, y)" },
+    HelpTopic { name: "prove", summary: "Search for a derivation",
+        lines: &[
+            "prove [LEFT_EXPR] == [RIGHT_EXPR] using [RULE_NAME], [RULE_NAME], ...",
+            "    searches for a derivation of one expression from another, where all rules are used without 'using';",
+            "goal [TEMPLATE] reports after each rule whether the current expression matches the template; and",
+            "prove [EXPR] == goal searches for a derivation of any expression that matches the goal.",
+        ],
+        example: "def swap as pair(x, y) => pair(y, x)\nprove pair(a, b) == pair(b, a) using swap" },
+    HelpTopic { name: "silent", summary: "Interpret statements without printing the steps",
+        lines: &[
+            "silent { [STMT] [STMT] ... } only prints the resulting expression.",
+        ],
+        example: "def zero as add(x, 0) => x\nsilent { add(add(a, 0), 0) apply zero at 0 apply zero at 0 }\nend" },
+    HelpTopic { name: "set", summary: "Change limits and what is printed",
+        lines: &[
+            "set max_depth [NUMBER] limits the depth of derived expressions (default 1000);",
+            "set max_nodes [NUMBER] limits the number of nodes of derived expressions (default 100000);",
+            "set timeout [DURATION], e.g., set timeout 5s or set timeout 500ms, stops statements that run too long,",
+            "    and set timeout off is the default. Ctrl-C also stops a statement, and keeps the environment intact;",
+            "set prompt \"[PROMPT]\" replaces the prompt '> ' of the REPL;",
+            "set preamble \"[TEXT]\" is written before the proof of a derivation in the lean format;",
+            "set verbosity [LEVEL], where LEVEL is quiet, normal (default) or verbose; and",
+            "set trace on prints why the left expression of each applied rule does (not) match.",
+        ],
+        example: "set max_depth 20\nset timeout 5s\nset verbosity verbose" },
+    HelpTopic { name: "end", summary: "End pattern matching and keep the derivation or result",
+        lines: &[
+            "end \"[PATH]\" as [FORMAT] writes the derivation, where FORMAT is one of text (default), markdown, html, raxio, json or lean;",
+            "end append \"[PATH]\" adds the derivation to the end of the file, where {n} in PATH is the next unused number;",
+            "end as [NAME] binds the resulting expression to NAME like a let statement; and",
+            "end as rule [NAME] defines the rule NAME from the initial to the resulting expression.",
+        ],
+        example: "f(a)\nf(x) => g(x) at 0\nend as rule f_to_g\nf(b)\napply f_to_g at 0\nend as result" },
+    HelpTopic { name: "libraries", summary: "Share rules between files",
+        lines: &[
+            "export rules \"[PATH]\" writes the rules, operators and commutative functors to a .rxlib file; and",
+            "import \"[PATH]\" defines the rules of a .rxlib file, or of a shipped library, e.g., import \"std:logic\".",
+        ],
+        example: "import \"std:logic\"" },
+    HelpTopic { name: "let", summary: "Name an expression",
+        lines: &[
+            "let [NAME] = [EXPR], after which NAME is replaced by EXPR when starting pattern matching or proving.",
+        ],
+        example: "let e = f(a, b)\ne\nend" },
+    HelpTopic { name: "show", summary: "Print the current expression in another notation",
+        lines: &[
+            "show as [NOTATION], where NOTATION is one of infix (default), prefix, sexpr, latex or lean.",
+        ],
+        example: "a + b * c\nshow as sexpr\nshow as latex\nend" },
+    HelpTopic { name: "warnings", summary: "Suppress warnings",
+        lines: &[
+            "allow [CODE], [CODE], ..., where each warning shows its code, e.g., Warning[W001].",
+        ],
+        example: "allow W001" },
+];
+
+// The topic with the name, if any
+pub fn find_topic(name: &str) -> Option<&'static HelpTopic> {
+    TOPICS.iter().find(|topic| topic.name == name)
+}
+
+// The names and summaries of all topics, and the commands of the REPL that are not statements
+pub fn overview() -> String {
+    let mut text = String::from("Enter \"help [TOPIC]\" for the syntax of a topic with an example, where TOPIC is one of\n");
+    for topic in TOPICS.iter() {
+        text.push_str(&format!("    {:<12} {}\n", topic.name, topic.summary));
+    }
+    text.push_str("\nThe REPL also has the commands quit, undo, edit, history, steps, examples and example [NAME].\n");
+    text
+}
+
+impl HelpTopic {
+    pub fn render(&self) -> String {
+        let mut text = format!("{}\n\n", self.summary);
+        for line in self.lines {
+            text.push_str(&format!("    {}\n", line));
+        }
+        text.push_str("\nExample:\n");
+        for line in self.example.lines() {
+            text.push_str(&format!("    {}\n", line));
+        }
+        text
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::HashSet, io};
+
+    use crate::{lexer::Lexer, parser::Parser, runtime::Env};
+
+    use super::*;
+
+    #[test]
+    fn help_examples_run() {
+        for topic in TOPICS.iter() {
+            let mut lexer = Lexer::new();
+            lexer.lex(topic.example);
+            let mut parser = Parser::new();
+            assert!(parser.parse(&mut lexer).is_ok(), "example of {}", topic.name);
+
+            let mut env = Env::new();
+            env.set_output(io::sink());
+            assert!(env.interpret(parser.stmts).is_ok(), "example of {}", topic.name);
+            assert!(env.warnings.is_empty(), "example of {}: {:?}", topic.name, env.warnings);
+        }
+    }
+
+    #[test]
+    fn help_topics() {
+        let names: HashSet<&str> = TOPICS.iter().map(|topic| topic.name).collect();
+        assert_eq!(names.len(), TOPICS.len());
+        assert!(find_topic("depth").unwrap().render().contains("at any"));
+        assert!(find_topic("operators").is_some());
+        assert!(find_topic("unknown").is_none());
+        assert!(overview().contains("    apply        Apply a rule to the current expression\n"));
+    }
+}
@@ -0,0 +1,254 @@
+use std::{fmt::Display, iter::Peekable, str::Chars};
+
+// A JSON value, as exchanged with editors and other tools. Object members keep their order.
+#[derive(Debug, PartialEq, Clone)]
+pub enum Json {
+    Null,
+    Bool(bool),
+    Number(f64),
+    Str(String),
+    Array(Vec<Json>),
+    Object(Vec<(String, Json)>),
+}
+
+impl Json {
+    pub fn object<const N: usize>(members: [(&str, Json); N]) -> Json {
+        Json::Object(members.into_iter().map(|(key, value)| (key.to_string(), value)).collect())
+    }
+
+    // The value of the member with the key, if this is an object that has it
+    pub fn get(&self, key: &str) -> Option<&Json> {
+        match self {
+            Json::Object(members) => members.iter().find(|(k, _)| k == key).map(|(_, value)| value),
+            _ => None
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Json::Str(s) => Some(s),
+            _ => None
+        }
+    }
+
+    pub fn as_usize(&self) -> Option<usize> {
+        match self {
+            Json::Number(n) if *n >= 0.0 && n.fract() == 0.0 => Some(*n as usize),
+            _ => None
+        }
+    }
+
+    pub fn as_array(&self) -> Option<&[Json]> {
+        match self {
+            Json::Array(values) => Some(values),
+            _ => None
+        }
+    }
+
+    // Parse a complete JSON text, with whitespace around the value
+    pub fn parse(text: &str) -> Option<Json> {
+        let mut chars = text.chars().peekable();
+        let value = parse_value(&mut chars)?;
+        skip_whitespace(&mut chars);
+        chars.peek().is_none().then_some(value)
+    }
+}
+
+impl From<&str> for Json {
+    fn from(s: &str) -> Self {
+        Json::Str(s.to_string())
+    }
+}
+
+impl From<String> for Json {
+    fn from(s: String) -> Self {
+        Json::Str(s)
+    }
+}
+
+impl From<usize> for Json {
+    fn from(n: usize) -> Self {
+        Json::Number(n as f64)
+    }
+}
+
+impl From<bool> for Json {
+    fn from(b: bool) -> Self {
+        Json::Bool(b)
+    }
+}
+
+impl From<Vec<Json>> for Json {
+    fn from(values: Vec<Json>) -> Self {
+        Json::Array(values)
+    }
+}
+
+// Compact JSON without whitespace between tokens
+impl Display for Json {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Json::Null => write!(f, "null"),
+            Json::Bool(b) => write!(f, "{}", b),
+            Json::Number(n) if n.is_finite() => write!(f, "{}", n),
+            // JSON has no infinities or NaN
+            Json::Number(_) => write!(f, "null"),
+            Json::Str(s) => write_str(f, s),
+            Json::Array(values) => {
+                write!(f, "[")?;
+                for (i, value) in values.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ",")?;
+                    }
+                    write!(f, "{}", value)?;
+                }
+                write!(f, "]")
+            },
+            Json::Object(members) => {
+                write!(f, "{{")?;
+                for (i, (key, value)) in members.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ",")?;
+                    }
+                    write_str(f, key)?;
+                    write!(f, ":{}", value)?;
+                }
+                write!(f, "}}")
+            },
+        }
+    }
+}
+
+fn write_str(f: &mut std::fmt::Formatter<'_>, s: &str) -> std::fmt::Result {
+    write!(f, "\"")?;
+    for c in s.chars() {
+        match c {
+            '"' => write!(f, "\\\"")?,
+            '\\' => write!(f, "\\\\")?,
+            '\n' => write!(f, "\\n")?,
+            '\r' => write!(f, "\\r")?,
+            '\t' => write!(f, "\\t")?,
+            c if c.is_control() => write!(f, "\\u{:04x}", c as u32)?,
+            c => write!(f, "{}", c)?,
+        }
+    }
+    write!(f, "\"")
+}
+
+fn skip_whitespace(chars: &mut Peekable<Chars>) {
+    while chars.next_if(|c| c.is_ascii_whitespace()).is_some() {}
+}
+
+fn parse_value(chars: &mut Peekable<Chars>) -> Option<Json> {
+    skip_whitespace(chars);
+    match chars.peek()? {
+        '{' => {
+            chars.next();
+            let mut members = vec![];
+            skip_whitespace(chars);
+            if chars.next_if_eq(&'}').is_some() {
+                return Some(Json::Object(members));
+            }
+            loop {
+                skip_whitespace(chars);
+                let key = parse_string(chars)?;
+                skip_whitespace(chars);
+                chars.next_if_eq(&':')?;
+                members.push((key, parse_value(chars)?));
+                skip_whitespace(chars);
+                match chars.next()? {
+                    ',' => continue,
+                    '}' => return Some(Json::Object(members)),
+                    _ => return None,
+                }
+            }
+        },
+        '[' => {
+            chars.next();
+            let mut values = vec![];
+            skip_whitespace(chars);
+            if chars.next_if_eq(&']').is_some() {
+                return Some(Json::Array(values));
+            }
+            loop {
+                values.push(parse_value(chars)?);
+                skip_whitespace(chars);
+                match chars.next()? {
+                    ',' => continue,
+                    ']' => return Some(Json::Array(values)),
+                    _ => return None,
+                }
+            }
+        },
+        '"' => parse_string(chars).map(Json::Str),
+        _ => {
+            let mut word = String::new();
+            while let Some(c) = chars.next_if(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '+' | '.')) {
+                word.push(c);
+            }
+            match word.as_str() {
+                "null" => Some(Json::Null),
+                "true" => Some(Json::Bool(true)),
+                "false" => Some(Json::Bool(false)),
+                _ => word.parse().ok().map(Json::Number),
+            }
+        }
+    }
+}
+
+fn parse_string(chars: &mut Peekable<Chars>) -> Option<String> {
+    chars.next_if_eq(&'"')?;
+    let mut s = String::new();
+    loop {
+        match chars.next()? {
+            '"' => return Some(s),
+            '\\' => match chars.next()? {
+                'n' => s.push('\n'),
+                'r' => s.push('\r'),
+                't' => s.push('\t'),
+                'b' => s.push('\u{8}'),
+                'f' => s.push('\u{c}'),
+                'u' => {
+                    let unit = parse_hex(chars)?;
+                    // Characters outside the basic multilingual plane are escaped as a surrogate pair
+                    let c = if (0xD800..0xDC00).contains(&unit) {
+                        chars.next_if_eq(&'\\')?;
+                        chars.next_if_eq(&'u')?;
+                        let low = parse_hex(chars)?;
+                        char::decode_utf16([unit as u16, low as u16]).next()?.ok()?
+                    } else {
+                        char::from_u32(unit)?
+                    };
+                    s.push(c);
+                },
+                c => s.push(c),
+            },
+            c => s.push(c),
+        }
+    }
+}
+
+fn parse_hex(chars: &mut Peekable<Chars>) -> Option<u32> {
+    let digits: String = (0..4).map(|_| chars.next()).collect::<Option<_>>()?;
+    u32::from_str_radix(&digits, 16).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_and_print_json() {
+        let text = r#" {"id": 1, "params": {"text": "a \"b\"\né😀", "items": [true, null, -2.5e1]}} "#;
+        let json = Json::parse(text).unwrap();
+        assert_eq!(json.get("id").and_then(Json::as_usize), Some(1));
+        let params = json.get("params").unwrap();
+        assert_eq!(params.get("text").and_then(Json::as_str), Some("a \"b\"\né😀"));
+        assert_eq!(params.get("items"), Some(&Json::Array(vec![Json::Bool(true), Json::Null, Json::Number(-25.0)])));
+        assert_eq!(json.to_string(), r#"{"id":1,"params":{"text":"a \"b\"\né😀","items":[true,null,-25]}}"#);
+        assert_eq!(Json::parse(&json.to_string()), Some(json));
+
+        assert_eq!(Json::parse("[1, 2"), None);
+        assert_eq!(Json::parse("{} {}"), None);
+    }
+}
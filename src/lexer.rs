@@ -1,18 +1,30 @@
 
-use std::error::Error;
+use std::{cmp::Ordering, error::Error, fmt::Display, hash::{Hash, Hasher}};
 
-use super::error::LexError;
+use super::{diagnostic::line_column, error::LexError, json::Json, operator::Operator};
 
 #[derive(Debug, PartialEq, Clone)]
 pub enum Token {
     Identifier(String)  , // alphabetic identifier
-    Number(usize)       , // unsigned integer
-    Path(String)        , // "/path/to/file"
+    Number(Number)      , // signed integer or floating point number
+    Str(String)         , // "string literal", which is a path, a prompt or a string constant
+    Symbol(String)      , // symbol of an operator statement, e.g., ->
+    Capture(usize)      , // numbered capture, e.g., $1
+    ArgPath(Vec<usize>) , // argument indices after 'focus path', e.g., 1.0
 
     OpenParen   , // (
     CloseParen  , // )
     Comma       , // ,
     Derive      , // =>
+    Equal       , // ==
+    Assign      , // =
+    Semicolon   , // ;
+    Pipe        , // |
+    Ellipsis    , // ..
+    OpenBrace   , // {
+    CloseBrace  , // }
+    OpenBracket , // [
+    CloseBracket, // ]
 
 
     Define      , // def
@@ -20,6 +32,46 @@ pub enum Token {
     End         , // end
     At          , // at
     Apply       , // apply
+    Try         , // try
+    Then        , // then
+    Prove       , // prove
+    Using       , // using
+    Set         , // set
+    Allow       , // allow
+    Show        , // show
+    Let         , // let
+    Preview     , // preview
+    Checkpoint  , // checkpoint
+    Goto        , // goto
+    Rollback    , // rollback
+    Redo        , // redo
+    Check       , // check
+    Expect      , // expect
+    Functor     , // functor
+    Flatten     , // flatten
+    Unflatten   , // unflatten
+    Commutative , // commutative
+    Canonicalize, // canonicalize
+    CheckTermination, // check-termination
+    Overlaps    , // overlaps
+    Unify       , // unify
+    Replace     , // replace
+    Find        , // find
+    Goal        , // goal
+    Export      , // export
+    Import      , // import
+    Focus       , // focus
+    Unfocus     , // unfocus
+    With        , // with
+    Silent      , // silent
+    Tags        , // tags
+    Auto        , // auto
+    Tagged      , // tagged
+    Context     , // context
+    Switch      , // switch
+    Infix       , // infix
+    Operator    , // operator
+    EqualQuery  , // equal?
 
     Add         , // +
     Sub         , // -
@@ -30,26 +82,162 @@ pub enum Token {
 const KEY_DEF: &str = "def";
 const KEY_END: &str = "end";
 const KEY_APP: &str = "apply";
+const KEY_AS: &str = "as";
+const KEY_AT: &str = "at";
+const KEY_TRY: &str = "try";
+const KEY_THEN: &str = "then";
+const KEY_PROVE: &str = "prove";
+const KEY_USING: &str = "using";
+const KEY_SET: &str = "set";
+const KEY_ALLOW: &str = "allow";
+const KEY_SHOW: &str = "show";
+const KEY_LET: &str = "let";
+const KEY_PREVIEW: &str = "preview";
+const KEY_CHECKPOINT: &str = "checkpoint";
+const KEY_GOTO: &str = "goto";
+const KEY_ROLLBACK: &str = "rollback";
+const KEY_REDO: &str = "redo";
+const KEY_CHECK: &str = "check";
+const KEY_EXPECT: &str = "expect";
+const KEY_FUNCTOR: &str = "functor";
+const KEY_FLATTEN: &str = "flatten";
+const KEY_UNFLATTEN: &str = "unflatten";
+const KEY_COMMUTATIVE: &str = "commutative";
+const KEY_CANONICALIZE: &str = "canonicalize";
+const KEY_CHECK_TERMINATION: &str = "check-termination";
+const KEY_OVERLAPS: &str = "overlaps";
+const KEY_UNIFY: &str = "unify";
+const KEY_REPLACE: &str = "replace";
+const KEY_FIND: &str = "find";
+const KEY_GOAL: &str = "goal";
+const KEY_EXPORT: &str = "export";
+const KEY_IMPORT: &str = "import";
+const KEY_FOCUS: &str = "focus";
+const KEY_UNFOCUS: &str = "unfocus";
+const KEY_WITH: &str = "with";
+const KEY_SILENT: &str = "silent";
+const KEY_TAGS: &str = "tags";
+const KEY_AUTO: &str = "auto";
+const KEY_TAGGED: &str = "tagged";
+const KEY_CONTEXT: &str = "context";
+const KEY_SWITCH: &str = "switch";
+const KEY_INFIX: &str = "infix";
+const KEY_OPERATOR: &str = "operator";
+const KEY_EQUAL_QUERY: &str = "equal?";
+
+// Numeric literals, e.g., 42, -3 or 1.5
+#[derive(Debug, Clone, Copy)]
+pub enum Number {
+    Int(i64),
+    Float(f64),
+}
+
+// Numbers are interned in expressions, so floats are compared and hashed by their bits,
+// i.e., 1 and 1.0 are different numbers, and so are 0.0 and -0.0.
+impl PartialEq for Number {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Number::Int(a), Number::Int(b)) => a == b,
+            (Number::Float(a), Number::Float(b)) => a.to_bits() == b.to_bits(),
+            _ => false
+        }
+    }
+}
+
+impl Eq for Number {}
+
+impl Hash for Number {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        match self {
+            Number::Int(n) => (0u8, *n).hash(state),
+            Number::Float(x) => (1u8, x.to_bits()).hash(state),
+        }
+    }
+}
+
+impl Number {
+    // Order by value, where an integer comes before a float with the same value
+    pub fn total_cmp(&self, other: &Number) -> Ordering {
+        match (self, other) {
+            (Number::Int(a), Number::Int(b)) => a.cmp(b),
+            (Number::Float(a), Number::Float(b)) => a.total_cmp(b),
+            (Number::Int(a), Number::Float(b)) => (*a as f64).total_cmp(b).then(Ordering::Less),
+            (Number::Float(a), Number::Int(b)) => a.total_cmp(&(*b as f64)).then(Ordering::Greater),
+        }
+    }
+
+    // Non-negative integers, which are used as depths, arities and settings
+    pub fn as_usize(&self) -> Option<usize> {
+        match self {
+            Number::Int(n) => usize::try_from(*n).ok(),
+            Number::Float(_) => None,
+        }
+    }
+}
+
+impl Display for Number {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Number::Int(n) => write!(f, "{}", n),
+            // Floats keep their decimal point, e.g., 2.0, to distinguish them from integers
+            Number::Float(x) => write!(f, "{:?}", x),
+        }
+    }
+}
+
+// Mathematical symbols that are not alphabetic, but can be used in identifiers like letters, e.g., ∂(f, x)
+const MATH_SYMBOLS: &str = "∂∇∞∅⊗⊕⊙∘";
+
+// Argument indices from the root of an expression to a sub-expression, e.g., 1.0 for the first argument of the second argument
+pub fn path_to_string(path: &[usize]) -> String {
+    path.iter().map(|idx| idx.to_string()).collect::<Vec<_>>().join(".")
+}
+
+// Characters that start an identifier
+fn is_identifier_start(c: char) -> bool {
+    c.is_alphabetic() || c == '_' || MATH_SYMBOLS.contains(c)
+}
+
+// Byte offsets of a token or statement in the source, from start up to (but excluding) end
+#[derive(Debug, PartialEq, Clone, Copy, Default)]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+}
+
+impl Span {
+    // The span from the start of this span up to the end of the other span
+    pub fn to(self, other: Span) -> Span {
+        Span { start: self.start, end: other.end.max(self.end) }
+    }
+}
 
 #[derive(Debug)]
 pub struct Lexer{
     pub tokens: Vec<Token>,
+    // Location of each token in the source
+    pub spans: Vec<Span>,
     pub errors: Vec<Box<dyn Error>>,
-    pub idx: usize
+    pub idx: usize,
+    // Symbols of operator statements that are not identifiers, e.g., ->, which are lexed before any other token
+    pub symbols: Vec<String>,
 }
 
 type PeekIter<'a> = std::iter::Peekable<std::str::CharIndices<'a>>;
 
-impl Token {
-    pub fn to_string(&self) -> String {
-        match self {
+impl Display for Token {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let s = match self {
             Token::Add => "add".to_string(),
             Token::Sub => "sub".to_string(),
             Token::Mul => "mul".to_string(),
             Token::Div => "div".to_string(),
             Token::Identifier(s) => format!("identifier literal '{}'", s),
             Token::Number(n) => format!("number literal '{}'", n),
-            Token::Path(s) => format!("path literal '{}'", s),
+            Token::Str(s) => format!("string literal '{}'", s),
+            Token::Symbol(s) => format!("operator symbol '{}'", s),
+            Token::Capture(n) => format!("capture '${}'", n),
+            Token::ArgPath(path) => format!("argument path '{}'", path_to_string(path)),
             Token::OpenParen => "open parenthesis ('(')".to_string(),   
             Token::CloseParen => "closed parenthesis (')')".to_string(),  
             Token::Comma => "comma (',')".to_string(),       
@@ -59,14 +247,90 @@ impl Token {
             Token::End => "end-keyword ('end')".to_string(),         
             Token::At => "at-keyword ('at')".to_string(),  
             Token::Apply => "apply-keyword ('apply')".to_string(),    
+            Token::Try => "try-keyword ('try')".to_string(),
+            Token::Then => "then-keyword ('then')".to_string(),
+            Token::Prove => "prove-keyword ('prove')".to_string(),
+            Token::Using => "using-keyword ('using')".to_string(),
+            Token::Set => "set-keyword ('set')".to_string(),
+            Token::Allow => "allow-keyword ('allow')".to_string(),
+            Token::Show => "show-keyword ('show')".to_string(),
+            Token::Let => "let-keyword ('let')".to_string(),
+            Token::Preview => "preview-keyword ('preview')".to_string(),
+            Token::Checkpoint => "checkpoint-keyword ('checkpoint')".to_string(),
+            Token::Goto => "goto-keyword ('goto')".to_string(),
+            Token::Rollback => "rollback-keyword ('rollback')".to_string(),
+            Token::Redo => "redo-keyword ('redo')".to_string(),
+            Token::Check => "check-keyword ('check')".to_string(),
+            Token::Expect => "expect-keyword ('expect')".to_string(),
+            Token::Functor => "functor-keyword ('functor')".to_string(),
+            Token::Flatten => "flatten-keyword ('flatten')".to_string(),
+            Token::Unflatten => "unflatten-keyword ('unflatten')".to_string(),
+            Token::Commutative => "commutative-keyword ('commutative')".to_string(),
+            Token::Canonicalize => "canonicalize-keyword ('canonicalize')".to_string(),
+            Token::CheckTermination => "check-termination-keyword ('check-termination')".to_string(),
+            Token::Overlaps => "overlaps-keyword ('overlaps')".to_string(),
+            Token::Unify => "unify-keyword ('unify')".to_string(),
+            Token::Replace => "replace-keyword ('replace')".to_string(),
+            Token::Find => "find-keyword ('find')".to_string(),
+            Token::Goal => "goal-keyword ('goal')".to_string(),
+            Token::Export => "export-keyword ('export')".to_string(),
+            Token::Import => "import-keyword ('import')".to_string(),
+            Token::Focus => "focus-keyword ('focus')".to_string(),
+            Token::Unfocus => "unfocus-keyword ('unfocus')".to_string(),
+            Token::Silent => "silent-keyword ('silent')".to_string(),
+            Token::Tags => "tags-keyword ('tags')".to_string(),
+            Token::Auto => "auto-keyword ('auto')".to_string(),
+            Token::Tagged => "tagged-keyword ('tagged')".to_string(),
+            Token::Context => "context-keyword ('context')".to_string(),
+            Token::Switch => "switch-keyword ('switch')".to_string(),
+            Token::Infix => "infix-keyword ('infix')".to_string(),
+            Token::Operator => "operator-keyword ('operator')".to_string(),
+            Token::EqualQuery => "equal-keyword ('equal?')".to_string(),
+            Token::With => "with-keyword ('with')".to_string(),
+            Token::Equal => "equality symbol ('==')".to_string(),
+            Token::Assign => "assignment symbol ('=')".to_string(),
+            Token::Semicolon => "semicolon (';')".to_string(),
+            Token::Pipe => "pipe ('|')".to_string(),
+            Token::Ellipsis => "ellipsis ('..')".to_string(),
+            Token::OpenBrace => "open brace ('{')".to_string(),
+            Token::CloseBrace => "close brace ('}')".to_string(),
+            Token::OpenBracket => "open bracket ('[')".to_string(),
+            Token::CloseBracket => "close bracket (']')".to_string(),
+        };
+        write!(f, "{}", s)
+    }
+}
+
+impl Token {
+    // Kind of the token for syntax highlighting, which is one of KINDS
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Token::Identifier(_) | Token::Capture(_) => "identifier",
+            Token::Number(_) | Token::ArgPath(_) => "number",
+            Token::Str(_) => "string",
+            Token::Add | Token::Sub | Token::Mul | Token::Div |
+            Token::Derive | Token::Equal | Token::Assign | 
+            Token::Semicolon | Token::Pipe | Token::Ellipsis | Token::Symbol(_) => "operator",
+            Token::OpenParen | Token::CloseParen | Token::Comma | 
+            Token::OpenBrace | Token::CloseBrace | 
+            Token::OpenBracket | Token::CloseBracket => "punctuation",
+            _ => "keyword",
         }
     }
+
+    pub const KINDS: [&'static str; 6] = ["keyword", "identifier", "number", "string", "operator", "punctuation"];
+}
+
+impl Default for Lexer {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl Lexer {
     
     pub fn new() -> Self {
-        Self { tokens: vec![], errors: vec![], idx: 0 }
+        Self { tokens: vec![], spans: vec![], errors: vec![], idx: 0, symbols: vec![] }
     }
 
     fn push_token(&mut self, token: Token, input_bytes: &mut PeekIter) {
@@ -74,190 +338,366 @@ impl Lexer {
         input_bytes.next();
     }
 
-    fn push_keyword(&mut self, 
-        token: Token, 
-        keyword: &str, 
-        input_bytes: &mut PeekIter, 
-        current_idx: usize, 
-        input_string: &str) 
-    {
-        let mut count = 0;
-
-        let res = input_string
-            .chars()
-            .skip(current_idx)
-            .zip(keyword.chars())
-            .inspect(|_| count +=1 )
-            .map(|(x, y)| x == y)
-            .reduce(|acc, b| acc & b)
-            .is_some_and(|x| x == true);
-
-        if res && count == keyword.len() {
-            let next_char = input_string
-                .chars()
-                .skip(current_idx + count)
-                .next();
-
-            if let Some(' ') | Some('\n') |
-                   Some('\t') | Some('\r') |
-                   None = next_char 
-            {
-                for _ in 0..keyword.len() {
-                    input_bytes.next();
+    fn push_identifier(&mut self, input_bytes: &mut PeekIter) {
+        let mut lexeme = String::new();
+        while let Some(&(_, c)) = input_bytes.peek() {
+            if !is_identifier_start(c) && !c.is_numeric() {
+                break;
+            }
+            lexeme.push(c);
+            input_bytes.next();
+        }
+
+        // Hyphenated keywords, e.g., check-termination, are only recognized as a whole, 
+        // such that check-x is still the subtraction of the variable x from check
+        if let Some((_, '-')) = input_bytes.peek() {
+            let mut lookahead = input_bytes.clone();
+            lookahead.next();
+            let mut hyphenated = format!("{}-", lexeme);
+            while let Some(&(_, c)) = lookahead.peek() {
+                if !is_identifier_start(c) && !c.is_numeric() {
+                    break;
                 }
-                self.tokens.push(token);
-            } else {
-                self.push_identifier(input_bytes);
+                hyphenated.push(c);
+                lookahead.next();
             }
+            if Self::get_keyword(&hyphenated).is_some() && Self::is_keyword_end(&mut lookahead) {
+                *input_bytes = lookahead;
+                lexeme = hyphenated;
+            }
+        }
 
-        } else {
-            // possibly identifier
-            self.push_identifier(input_bytes);
+        // Likewise, keywords that end with a question mark, e.g., equal?, are only recognized as a whole
+        if let Some((_, '?')) = input_bytes.peek() {
+            let mut lookahead = input_bytes.clone();
+            lookahead.next();
+            let query = format!("{}?", lexeme);
+            if Self::get_keyword(&query).is_some() && Self::is_keyword_end(&mut lookahead) {
+                *input_bytes = lookahead;
+                lexeme = query;
+            }
         }
+
+        // Keywords are only recognized if followed by whitespace or the end of the input
+        let token = match Self::get_keyword(&lexeme) {
+            Some(keyword) if Self::is_keyword_end(input_bytes) => keyword,
+            _ => Token::Identifier(lexeme)
+        };
+        self.tokens.push(token);
     }
 
-    fn push_identifier(&mut self, input_bytes: &mut PeekIter) {
-        let mut lexeme = String::new();
-        while let Some((_, c @ 'a'..='z')) |
-                  Some((_, c @ 'A'..='Z')) |
-                  Some((_, c @ '_')) | 
-                  Some((_, c @ '0'..='9')) = input_bytes.peek() 
-        {
-            lexeme.push(*c);
-            input_bytes.next();
-        }
+    fn is_keyword_end(input_bytes: &mut PeekIter) -> bool {
+        matches!(input_bytes.peek(), Some((_, ' ' | '\n' | '\t' | '\r')) | None)
+    }
 
-        self.tokens.push(Token::Identifier(lexeme));
+    fn get_keyword(lexeme: &str) -> Option<Token> {
+        match lexeme {
+            KEY_DEF => Some(Token::Define),
+            KEY_END => Some(Token::End),
+            KEY_APP => Some(Token::Apply),
+            KEY_AS => Some(Token::As),
+            KEY_AT => Some(Token::At),
+            KEY_TRY => Some(Token::Try),
+            KEY_THEN => Some(Token::Then),
+            KEY_PROVE => Some(Token::Prove),
+            KEY_USING => Some(Token::Using),
+            KEY_SET => Some(Token::Set),
+            KEY_ALLOW => Some(Token::Allow),
+            KEY_SHOW => Some(Token::Show),
+            KEY_LET => Some(Token::Let),
+            KEY_PREVIEW => Some(Token::Preview),
+            KEY_CHECKPOINT => Some(Token::Checkpoint),
+            KEY_GOTO => Some(Token::Goto),
+            KEY_ROLLBACK => Some(Token::Rollback),
+            KEY_REDO => Some(Token::Redo),
+            KEY_CHECK => Some(Token::Check),
+            KEY_EXPECT => Some(Token::Expect),
+            KEY_FUNCTOR => Some(Token::Functor),
+            KEY_FLATTEN => Some(Token::Flatten),
+            KEY_UNFLATTEN => Some(Token::Unflatten),
+            KEY_COMMUTATIVE => Some(Token::Commutative),
+            KEY_CANONICALIZE => Some(Token::Canonicalize),
+            KEY_CHECK_TERMINATION => Some(Token::CheckTermination),
+            KEY_OVERLAPS => Some(Token::Overlaps),
+            KEY_UNIFY => Some(Token::Unify),
+            KEY_REPLACE => Some(Token::Replace),
+            KEY_FIND => Some(Token::Find),
+            KEY_GOAL => Some(Token::Goal),
+            KEY_EXPORT => Some(Token::Export),
+            KEY_IMPORT => Some(Token::Import),
+            KEY_FOCUS => Some(Token::Focus),
+            KEY_UNFOCUS => Some(Token::Unfocus),
+            KEY_SILENT => Some(Token::Silent),
+            KEY_TAGS => Some(Token::Tags),
+            KEY_AUTO => Some(Token::Auto),
+            KEY_TAGGED => Some(Token::Tagged),
+            KEY_CONTEXT => Some(Token::Context),
+            KEY_SWITCH => Some(Token::Switch),
+            KEY_INFIX => Some(Token::Infix),
+            KEY_OPERATOR => Some(Token::Operator),
+            KEY_EQUAL_QUERY => Some(Token::EqualQuery),
+            KEY_WITH => Some(Token::With),
+            _ => None
+        }
     }
 
+    // A number with an optional minus sign, an optional fractional part and an optional exponent
     fn push_number(&mut self, input_bytes: &mut PeekIter) {
-        let mut collected_digits = String::new();
-        while let Some((_, d @ '0'..='9')) = input_bytes.peek() {
-            collected_digits.push(*d);
-            input_bytes.next();
+        let start = input_bytes.peek().map_or(0, |(i, _)| *i);
+        let mut end = start;
+        let mut lexeme = String::new();
+        let mut take = |lexeme: &mut String, input_bytes: &mut PeekIter, c: char| {
+            let (i, got) = input_bytes.next().unwrap();
+            end = i + got.len_utf8();
+            lexeme.push(c);
+        };
+        if let Some((_, '-' | '−')) = input_bytes.peek() {
+            take(&mut lexeme, input_bytes, '-');
         }
-        
-        match collected_digits.parse::<usize>() {
+        while let Some(&(_, d @ '0'..='9')) = input_bytes.peek() {
+            take(&mut lexeme, input_bytes, d);
+        }
+
+        let mut is_float = false;
+        let mut error = None;
+        // Two dots after a number are an ellipsis, e.g., in the depth range 2..5
+        let is_ellipsis = input_bytes.clone().nth(1).is_some_and(|(_, c)| c == '.');
+        if let (Some((_, '.')), false) = (input_bytes.peek(), is_ellipsis) {
+            is_float = true;
+            take(&mut lexeme, input_bytes, '.');
+            if !matches!(input_bytes.peek(), Some((_, '0'..='9'))) {
+                error = Some("expected digits after the decimal point");
+            }
+            while let Some(&(_, d @ '0'..='9')) = input_bytes.peek() {
+                take(&mut lexeme, input_bytes, d);
+            }
+        }
+
+        // An exponent is only lexed when digits follow, so that 2e still lexes as 2 followed by e
+        let mut ahead = input_bytes.clone();
+        let has_exponent = matches!(ahead.next(), Some((_, 'e' | 'E'))) && match ahead.next() {
+            Some((_, '+' | '-')) => matches!(ahead.next(), Some((_, '0'..='9'))),
+            Some((_, c)) => c.is_ascii_digit(),
+            None => false,
+        };
+        if has_exponent {
+            is_float = true;
+            take(&mut lexeme, input_bytes, 'e');
+            if let Some(&(_, s @ ('+' | '-'))) = input_bytes.peek() {
+                take(&mut lexeme, input_bytes, s);
+            }
+            while let Some(&(_, d @ '0'..='9')) = input_bytes.peek() {
+                take(&mut lexeme, input_bytes, d);
+            }
+        }
+
+        let number = match (error, is_float) {
+            (Some(reason), _) => Err(reason),
+            (None, true) => match lexeme.parse::<f64>() {
+                Ok(x) if x.is_finite() => Ok(Number::Float(x)),
+                _ => Err("the number is out of range"),
+            },
+            (None, false) => lexeme.parse::<i64>()
+                .map(Number::Int)
+                .map_err(|_| "the number is out of range"),
+        };
+        match number {
             Ok(n) => self.tokens.push(Token::Number(n)),
-            Err(msg) => self.errors.push(Box::new(msg))
+            Err(reason) => self.errors.push(Box::new(LexError::InvalidNumber { start, end, lexeme, reason })),
         }
+    }
 
-        // match input_bytes.peek() {
-        //     Some((_, ' ')) | Some((_, '\n')) |
-        //     Some((_, '\t')) | Some((_, '\r')) | 
-        //     None => { input_bytes.next(); },
-        //     Some((i, c)) => { self.errors.push(format!("Expected whitespace or number, but found '{}' at position {} during lexing.", *c, *i)); }
-        // }
+    // Argument indices separated by dots, e.g., 1.0, which are only lexed after 'focus path', as they look like a number
+    fn is_arg_path(&self) -> bool {
+        matches!(self.tokens.as_slice(), [.., Token::Focus, Token::Identifier(s)] if s == "path")
     }
 
-    fn push_path(&mut self, input_bytes: &mut PeekIter) {
-        input_bytes.next();
+    fn push_arg_path(&mut self, input_bytes: &mut PeekIter) {
+        let mut path = vec![];
+        while let Some(&(start, _)) = input_bytes.peek() {
+            let mut lexeme = String::new();
+            while let Some((_, d @ '0'..='9')) = input_bytes.peek() {
+                lexeme.push(*d);
+                input_bytes.next();
+            }
+            match lexeme.parse::<usize>() {
+                Ok(idx) => path.push(idx),
+                Err(_) => {
+                    self.errors.push(Box::new(LexError::InvalidIndex { start, end: start + lexeme.len(), lexeme }));
+                    return;
+                }
+            }
+            let mut lookahead = input_bytes.clone();
+            match (lookahead.next(), lookahead.peek()) {
+                (Some((_, '.')), Some((_, '0'..='9'))) => { input_bytes.next(); },
+                _ => break
+            }
+        }
+        self.tokens.push(Token::ArgPath(path));
+    }
+
+    // A dollar sign directly followed by digits, e.g., $1, which is a variable of a rule that is named by position
+    fn push_capture(&mut self, input_bytes: &mut PeekIter) {
+        let Some((pos, _)) = input_bytes.next() else { return; };
         let mut lexeme = String::new();
-        if let Some((_, '/')) = input_bytes.peek() {
-            lexeme.push('/');
+        while let Some((_, d @ '0'..='9')) = input_bytes.peek() {
+            lexeme.push(*d);
             input_bytes.next();
         }
-        while let Some((_, c @ 'a'..='z')) |
-                  Some((_, c @ 'A'..='Z')) |
-                  Some((_, c @ '0'..='9')) | 
-                  Some((_, c @ '_')) | Some((_, c @ '.')) |
-                  Some((_, c @ ' ')) | Some((_, c @ '\n')) |
-                  Some((_, c @ '\t')) | Some((_, c @ '\r')) = input_bytes.peek() 
-        {
-            lexeme.push(*c);
-            input_bytes.next();
+        if lexeme.is_empty() {
+            self.errors.push(Box::new(LexError::UnknownChar { pos, got: '$' }));
+            return;
+        }
+        match lexeme.parse::<usize>() {
+            Ok(n) => self.tokens.push(Token::Capture(n)),
+            Err(_) => {
+                let lexeme = format!("${}", lexeme);
+                self.errors.push(Box::new(LexError::InvalidIndex { start: pos, end: pos + lexeme.len(), lexeme }));
+            }
         }
+    }
 
-        
-        while let Some((_, '/')) = input_bytes.peek() {
-            lexeme.push('/');
-            input_bytes.next();
-            while let Some((_, c @ 'a'..='z')) |
-                    Some((_, c @ 'A'..='Z')) |
-                    Some((_, c @ '0'..='9')) |
-                    Some((_, c @ '_')) | Some((_, c @ '.')) |
-                    Some((_, c @ ' ')) | Some((_, c @ '\n')) |
-                    Some((_, c @ '\t')) | Some((_, c @ '\r')) = input_bytes.peek() 
-            {
-                lexeme.push(*c);
-                input_bytes.next();
+    // A minus sign directly followed by a digit is a negative number, unless it follows an operand, e.g., a -1
+    fn is_negative_number(&self, input_bytes: &PeekIter) -> bool {
+        let mut ahead = input_bytes.clone();
+        ahead.next();
+        let follows_operand = matches!(
+            self.tokens.last(), 
+            Some(Token::Identifier(_) | Token::Capture(_) | Token::Number(_) | Token::CloseParen | Token::CloseBracket | Token::Str(_))
+        );
+        matches!(ahead.peek(), Some((_, '0'..='9'))) && !follows_operand
+    }
+
+    // A quoted literal contains any character up to the closing quote, where the parser decides whether it is a path, 
+    // a prompt or a string constant. The escape sequences \", \\, \n and \t stand for a quote, a backslash, a newline 
+    // and a tab, and any other backslash is kept as is, such that Windows paths such as "C:\Users\me" need no escaping.
+    fn push_string(&mut self, input_bytes: &mut PeekIter) {
+        let Some((start, _)) = input_bytes.next() else { return; };
+        let mut lexeme = String::new();
+        loop {
+            match input_bytes.next() {
+                Some((_, '"')) => {
+                    self.tokens.push(Token::Str(lexeme));
+                    return;
+                },
+                Some((_, '\\')) => match input_bytes.peek() {
+                    Some(&(_, c @ ('"' | '\\'))) => {
+                        lexeme.push(c);
+                        input_bytes.next();
+                    },
+                    Some((_, 'n')) => {
+                        lexeme.push('\n');
+                        input_bytes.next();
+                    },
+                    Some((_, 't')) => {
+                        lexeme.push('\t');
+                        input_bytes.next();
+                    },
+                    _ => lexeme.push('\\')
+                },
+                Some((_, c)) => lexeme.push(c),
+                None => {
+                    self.errors.push(Box::new(LexError::UnterminatedStringLiteral { pos: start }));
+                    return;
+                }
             }
         }
+    }
 
-        match input_bytes.peek() {
-            Some((_, '"')) => { self.tokens.push(Token::Path(lexeme)); },
-            Some((i, _)) => { self.errors.push(Box::new(LexError::UnterminatedStringLiteral { pos: *i })); },
-            None => { self.errors.push(Box::new(LexError::UnterminatedStringLiteralAtEnd)); }
+    // The quoted symbol of an operator statement extends the symbols
+    fn push_symbol(&mut self) {
+        if let [.., Token::Operator, Token::Str(symbol)] = self.tokens.as_slice() {
+            let symbol = symbol.clone();
+            self.add_symbol(&symbol);
         }
-        input_bytes.next();
+    }
 
+    // Lex the symbol as a single token from now on, unless it already is one, e.g., an identifier such as ∘ or +
+    pub fn add_symbol(&mut self, symbol: &str) {
+        let mut lexer = Lexer::new();
+        lexer.lex(symbol);
+        let is_token = lexer.tokens.len() == 1 && lexer.errors.is_empty();
+        if Operator::is_valid_symbol(symbol) && !is_token && !self.symbols.iter().any(|other| other == symbol) {
+            self.symbols.push(symbol.to_string());
+        }
     }
 
-    pub fn lex<'a>(&mut self, input_string: &'a str) {
+    // The longest symbol at the start of the input
+    fn symbol_at(&self, input: &str) -> Option<String> {
+        self.symbols
+            .iter()
+            .filter(|symbol| input.starts_with(symbol.as_str()))
+            .max_by_key(|symbol| symbol.len())
+            .cloned()
+    }
+
+    pub fn lex(&mut self, input_string: &str) {
         let mut input_bytes: PeekIter = input_string.char_indices().peekable();
 
-        while input_bytes.peek().is_some() {
+        while let Some(&(start, _)) = input_bytes.peek() {
+
+            if let Some(symbol) = self.symbol_at(&input_string[start..]) {
+                for _ in symbol.chars() {
+                    input_bytes.next();
+                }
+                self.spans.push(Span { start, end: start + symbol.len() });
+                self.tokens.push(Token::Symbol(symbol));
+                continue;
+            }
 
+            let num_tokens = self.tokens.len();
+            let is_negative_number = self.is_negative_number(&input_bytes);
             match input_bytes.peek() {
                 Some((_, ',')) => { self.push_token(Token::Comma,      &mut input_bytes); },
                 Some((_, '(')) => { self.push_token(Token::OpenParen,  &mut input_bytes); },
                 Some((_, ')')) => { self.push_token(Token::CloseParen, &mut input_bytes); },
+                Some((_, '{')) => { self.push_token(Token::OpenBrace,  &mut input_bytes); },
+                Some((_, '}')) => { self.push_token(Token::CloseBrace, &mut input_bytes); },
+                Some((_, '[')) => { self.push_token(Token::OpenBracket,  &mut input_bytes); },
+                Some((_, ']')) => { self.push_token(Token::CloseBracket, &mut input_bytes); },
                 Some((_, '+')) => { self.push_token(Token::Add, &mut input_bytes); },
-                Some((_, '-')) => { self.push_token(Token::Sub, &mut input_bytes); },
-                Some((_, '*')) => { self.push_token(Token::Mul, &mut input_bytes); },
-                Some((_, '/')) => { self.push_token(Token::Div, &mut input_bytes); },
-                Some((_, '"')) => { self.push_path(&mut input_bytes); },
+                Some((_, '-' | '−')) if is_negative_number => { self.push_number(&mut input_bytes); },
+                Some((_, '-' | '−')) => { self.push_token(Token::Sub, &mut input_bytes); },
+                // Multiplication, division and minus signs are the same as their ASCII counterparts
+                Some((_, '*' | '×' | '·')) => { self.push_token(Token::Mul, &mut input_bytes); },
+                Some((_, '/' | '÷')) => { self.push_token(Token::Div, &mut input_bytes); },
+                Some((_, ';')) => { self.push_token(Token::Semicolon, &mut input_bytes); },
+                Some((_, '$')) => { self.push_capture(&mut input_bytes); },
+                Some((_, '|')) => { self.push_token(Token::Pipe, &mut input_bytes); },
+                Some((_, '"')) => { 
+                    self.push_string(&mut input_bytes); 
+                    self.push_symbol();
+                },
+                Some((i, '.')) => {
+                    let pos = *i;
+                    input_bytes.next();
+                    match input_bytes.peek() {
+                        Some((_, '.')) => {
+                            self.push_token(Token::Ellipsis, &mut input_bytes);
+                        },
+                        _ => { self.errors.push(Box::new(LexError::UnknownChar { pos, got: '.' })); }
+                    }
+                },
                 Some((_, '=')) => {
                     input_bytes.next();
                     match input_bytes.peek() {
                         Some((_, '>')) => {
                             self.push_token(Token::Derive, &mut input_bytes);
                         },
-                        Some((i, c)) => {
-                            self.errors.push(Box::new(LexError::ExpectCharAfter {
-                                pos: *i, 
-                                expected: '>', 
-                                after: '=', 
-                                got: *c 
-                            }));
-                            input_bytes.next();
+                        Some((_, '=')) => {
+                            self.push_token(Token::Equal, &mut input_bytes);
                         },
-                        None => {
-                            self.errors.push(Box::new(LexError::ExpectCharAfter {
-                                pos: input_string.len()-1, 
-                                expected: '>', 
-                                after: '=', 
-                                got: ' ' 
-                            }));
-                            input_bytes.next();
-                        }
+                        _ => { self.tokens.push(Token::Assign); }
                     }
                 },
                 Some((_, ' ')) | Some((_, '\t')) | 
                 Some((_, '\r')) | Some((_, '\n')) => { input_bytes.next(); },
-                Some((i, 'd')) => {
-                    let current_idx = *i;
-                    self.push_keyword(Token::Define, KEY_DEF, &mut input_bytes, current_idx, input_string); 
-                },
-                Some((i, 'e')) => {
-                    let current_idx = *i;
-                    self.push_keyword(Token::End, KEY_END, &mut input_bytes, current_idx, input_string); 
-                },
-                Some((i, 'a')) => {
-                    match input_string.chars().nth(*i + 1) {
-                        Some('s') => { self.push_token(Token::As, &mut input_bytes); input_bytes.next(); },
-                        Some('t') => { self.push_token(Token::At, &mut input_bytes); input_bytes.next(); },
-                        Some('p') => { 
-                            let current_idx = *i;
-                            self.push_keyword(Token::Apply, KEY_APP, &mut input_bytes, current_idx, input_string);
-                        },
-                        Some(_) => { self.push_identifier(&mut input_bytes); },
-                        None => { input_bytes.next(); }
-                    } 
-                },
-                Some((_, 'a'..='z')) | Some((_, 'A'..='Z')) | Some((_, '_'))=> {
+                Some((_, c)) if is_identifier_start(*c) => {
                     self.push_identifier(&mut input_bytes);
                 },
+                Some((_, '0'..='9')) if self.is_arg_path() => {
+                    self.push_arg_path(&mut input_bytes);
+                },
                 Some((_, '0'..='9')) => {
                     self.push_number(&mut input_bytes);
                 },
@@ -270,6 +710,12 @@ impl Lexer {
                 }
                 _ => {unreachable!()}
             }        
+
+            // A token spans from its first character up to the next character that is not consumed
+            if self.tokens.len() > num_tokens {
+                let end = input_bytes.peek().map_or(input_string.len(), |(i, _)| *i);
+                self.spans.push(Span { start, end });
+            }
         } 
     }
 
@@ -281,15 +727,78 @@ impl Lexer {
         self.tokens.get(self.idx + n)
     }
 
+    #[allow(clippy::should_implement_trait)]
     pub fn next(&mut self) -> Option<&Token> {
         self.idx += 1;
         self.tokens.get(self.idx-1)
     }
 
+    // Location of the n-th next token. At the end of the input, this is the empty span after the last token.
+    pub fn span(&self, n: usize) -> Span {
+        match self.spans.get(self.idx + n) {
+            Some(span) => *span,
+            None => {
+                let end = self.spans.last().map_or(0, |span| span.end);
+                Span { start: end, end }
+            }
+        }
+    }
+
+    // Location of the token that was consumed last
+    pub fn prev_span(&self) -> Span {
+        match self.idx.checked_sub(1).and_then(|idx| self.spans.get(idx)) {
+            Some(span) => *span,
+            None => self.span(0)
+        }
+    }
+
     pub fn is_at_end(&self) -> bool {
         self.tokens.get(self.idx).is_none()
     }
 
+    // The lexed tokens of the source for editors, each with its kind, text, byte offsets and line and column 
+    // starting at 1, followed by the lexical errors, e.g., for an unknown character.
+    pub fn to_json(&self, source: &str) -> Json {
+        let tokens = self.tokens
+            .iter()
+            .zip(self.spans.iter())
+            .map(|(token, span)| {
+                let (line, column) = line_column(source, span.start);
+                Json::object([
+                    ("kind", token.kind().into()),
+                    ("text", source[span.start..span.end].into()),
+                    ("start", span.start.into()),
+                    ("end", span.end.into()),
+                    ("line", line.into()),
+                    ("column", column.into()),
+                ])
+            })
+            .collect();
+        let errors = self.errors
+            .iter()
+            .map(|err| {
+                let span = err.downcast_ref::<LexError>().map_or(Span::default(), |err| err.span(source));
+                Json::object([
+                    ("message", err.to_string().trim_end().into()),
+                    ("start", span.start.into()),
+                    ("end", span.end.into()),
+                ])
+            })
+            .collect();
+        Json::object([("tokens", Json::Array(tokens)), ("errors", Json::Array(errors))])
+    }
+
+    // Whether the lexed input is an unfinished statement, i.e., it has unclosed parentheses, braces or brackets, or
+    // ends with a token that must be followed by an expression, such that more input should be read.
+    pub fn is_incomplete(&self) -> bool {
+        let open_parens = self.tokens.iter().fold(0isize, |open, token| match token {
+            Token::OpenParen | Token::OpenBrace | Token::OpenBracket => open + 1,
+            Token::CloseParen | Token::CloseBrace | Token::CloseBracket => open - 1,
+            _ => open
+        });
+        open_parens > 0 || matches!(self.tokens.last(), Some(Token::Derive | Token::Comma))
+    }
+
 }
 
 #[cfg(test)]
@@ -332,7 +841,7 @@ mod tests {
         lexer.lex(input_string);
 
         let iter = lexer.tokens.iter();
-        let test = vec![
+        let test = [
             Token::Identifier("defas".to_string()),
             Token::Identifier("enddef".to_string()),
             Token::As,
@@ -341,30 +850,25 @@ mod tests {
     }
 
     #[test]
-    fn trigger_equal_sign_and_numeric_error() {
+    fn lex_assign() {
         let input_string = " a = a + a";
         let mut lexer = Lexer::new();
         lexer.lex(input_string);
 
-        dbg!(&lexer.errors);
-        
-        assert!(lexer.errors.len() == 1);
-        let e = lexer.errors.swap_remove(0);
-        assert!(e.is::<LexError>());
-        assert_eq!(
-            *e.downcast::<LexError>().unwrap().clone(), 
-            LexError::ExpectCharAfter { 
-                pos: 4, 
-                expected: '>', 
-                after: '=', 
-                got: ' ' 
-            }
-        );
+        assert!(lexer.errors.is_empty());
+        assert_eq!(lexer.tokens, vec![
+            Token::Identifier("a".to_string()),
+            Token::Assign,
+            Token::Identifier("a".to_string()),
+            Token::Add,
+            Token::Identifier("a".to_string()),
+        ]);
+        assert_eq!(lexer.spans[1], Span { start: 3, end: 4 });
     }
 
     #[test]
     fn trigger_unterminated_string_literal_error() {
-        let input_string = "abc \"path ";
+        let input_string = "abc \"path\n";
         let mut lexer = Lexer::new();
         lexer.lex(input_string);
         
@@ -373,10 +877,291 @@ mod tests {
         assert!(e.is::<LexError>());
         assert_eq!(
             *e.downcast::<LexError>().unwrap().clone(), 
-            LexError::UnterminatedStringLiteralAtEnd
+            LexError::UnterminatedStringLiteral { pos: 4 }
         );
     }
 
+    #[test]
+    fn lex_number_exponents() {
+        let input_string = "f(1e5, 2.5E-3, -1e+2) 2e";
+        let mut lexer = Lexer::new();
+        lexer.lex(input_string);
+
+        assert!(lexer.errors.is_empty());
+        assert_eq!(lexer.tokens, vec![
+            Token::Identifier("f".to_string()),
+            Token::OpenParen,
+            Token::Number(Number::Float(1e5)),
+            Token::Comma,
+            Token::Number(Number::Float(2.5e-3)),
+            Token::Comma,
+            Token::Number(Number::Float(-1e2)),
+            Token::CloseParen,
+            Token::Number(Number::Int(2)),
+            Token::Identifier("e".to_string()),
+        ]);
+        assert_eq!(lexer.spans[2], Span { start: 2, end: 5 });
+    }
+
+    #[test]
+    fn trigger_invalid_number_errors() {
+        for (input_string, error) in [
+            ("1.x", LexError::InvalidNumber { 
+                start: 0, end: 2, lexeme: "1.".to_string(), reason: "expected digits after the decimal point" 
+            }),
+            ("a + 3.", LexError::InvalidNumber { 
+                start: 4, end: 6, lexeme: "3.".to_string(), reason: "expected digits after the decimal point" 
+            }),
+            ("f(99999999999999999999)", LexError::InvalidNumber { 
+                start: 2, end: 22, lexeme: "99999999999999999999".to_string(), reason: "the number is out of range" 
+            }),
+            ("1e999", LexError::InvalidNumber { 
+                start: 0, end: 5, lexeme: "1e999".to_string(), reason: "the number is out of range" 
+            }),
+        ] {
+            let mut lexer = Lexer::new();
+            lexer.lex(input_string);
+
+            assert_eq!(lexer.errors.len(), 1, "{}", input_string);
+            let e = *lexer.errors.swap_remove(0).downcast::<LexError>().unwrap();
+            let span = e.span(input_string);
+            assert_eq!(e, error);
+            if let LexError::InvalidNumber { lexeme, .. } = e {
+                assert_eq!(input_string[span.start..span.end], lexeme);
+            }
+        }
+    }
+
+    #[test]
+    fn trigger_invalid_index_errors() {
+        for (input_string, error) in [
+            ("focus path 0.99999999999999999999", LexError::InvalidIndex { 
+                start: 13, end: 33, lexeme: "99999999999999999999".to_string() 
+            }),
+            ("f($99999999999999999999)", LexError::InvalidIndex { 
+                start: 2, end: 23, lexeme: "$99999999999999999999".to_string() 
+            }),
+        ] {
+            let mut lexer = Lexer::new();
+            lexer.lex(input_string);
+
+            assert_eq!(lexer.errors.len(), 1, "{}", input_string);
+            let e = *lexer.errors.swap_remove(0).downcast::<LexError>().unwrap();
+            let span = e.span(input_string);
+            assert_eq!(e, error);
+            if let LexError::InvalidIndex { lexeme, .. } = e {
+                assert_eq!(input_string[span.start..span.end], lexeme);
+            }
+        }
+    }
+
+    #[test]
+    fn lex_string_literals() {
+        let input_string = r#"end "../out/my-file.v2.md" "C:\Users\me" "say \"hi\"\n" "\\""#;
+        let mut lexer = Lexer::new();
+        lexer.lex(input_string);
+
+        assert!(lexer.errors.is_empty());
+        assert_eq!(lexer.tokens, vec![
+            Token::End,
+            Token::Str("../out/my-file.v2.md".to_string()),
+            Token::Str("C:\\Users\\me".to_string()),
+            Token::Str("say \"hi\"\n".to_string()),
+            Token::Str("\\".to_string()),
+        ]);
+    }
+
+    #[test]
+    fn lex_spans() {
+        let input_string = "f(x) =>\n  \"out.txt\"";
+        let mut lexer = Lexer::new();
+        lexer.lex(input_string);
+
+        let spans: Vec<(usize, usize)> = lexer.spans.iter().map(|span| (span.start, span.end)).collect();
+        assert_eq!(spans, vec![(0, 1), (1, 2), (2, 3), (3, 4), (5, 7), (10, 19)]);
+        assert_eq!(lexer.span(6), Span { start: 19, end: 19 });
+    }
+
+    #[test]
+    fn lex_hyphenated_keyword() {
+        let input_string = "check-termination foo check-x";
+        let mut lexer = Lexer::new();
+        lexer.lex(input_string);
+
+        assert!(lexer.errors.is_empty());
+        assert_eq!(lexer.tokens, vec![
+            Token::CheckTermination,
+            Token::Identifier("foo".to_string()),
+            Token::Identifier("check".to_string()),
+            Token::Sub,
+            Token::Identifier("x".to_string()),
+        ]);
+        assert_eq!(lexer.spans[0], Span { start: 0, end: 17 });
+    }
+
+    #[test]
+    fn lex_query_keyword() {
+        let input_string = "equal? lhs, equal";
+        let mut lexer = Lexer::new();
+        lexer.lex(input_string);
+
+        assert!(lexer.errors.is_empty());
+        assert_eq!(lexer.tokens, vec![
+            Token::EqualQuery,
+            Token::Identifier("lhs".to_string()),
+            Token::Comma,
+            Token::Identifier("equal".to_string()),
+        ]);
+        assert_eq!(lexer.spans[0], Span { start: 0, end: 6 });
+    }
+
+    #[test]
+    fn lex_operator_symbols() {
+        let input_string = "a->b operator \"->\" as implies, precedence 1 a->-1 operator \"+\" as plus, precedence 1";
+        let mut lexer = Lexer::new();
+        lexer.lex(input_string);
+
+        // Symbols are lexed after their operator statement, and symbols that are already a token are kept
+        assert_eq!(lexer.errors.len(), 1);
+        assert_eq!(lexer.symbols, vec!["->"]);
+        assert_eq!(lexer.tokens[10..13], [
+            Token::Identifier("a".to_string()),
+            Token::Symbol("->".to_string()),
+            Token::Number(Number::Int(-1)),
+        ]);
+        assert_eq!(lexer.spans[11], Span { start: 45, end: 47 });
+    }
+
+    #[test]
+    fn lex_captures() {
+        let input_string = "f($1, $23) $1 -1 $";
+        let mut lexer = Lexer::new();
+        lexer.lex(input_string);
+
+        assert_eq!(lexer.tokens, vec![
+            Token::Identifier("f".to_string()),
+            Token::OpenParen,
+            Token::Capture(1),
+            Token::Comma,
+            Token::Capture(23),
+            Token::CloseParen,
+            Token::Capture(1),
+            Token::Sub,
+            Token::Number(Number::Int(1)),
+        ]);
+        assert_eq!(lexer.spans[4], Span { start: 6, end: 9 });
+        assert_eq!(lexer.errors.len(), 1);
+    }
+
+    #[test]
+    fn lex_unicode() {
+        let input_string = "∂(α × β, x₀) − γ÷2";
+        let mut lexer = Lexer::new();
+        lexer.lex(input_string);
+
+        assert!(lexer.errors.is_empty());
+        assert_eq!(lexer.tokens, vec![
+            Token::Identifier("∂".to_string()),
+            Token::OpenParen,
+            Token::Identifier("α".to_string()),
+            Token::Mul,
+            Token::Identifier("β".to_string()),
+            Token::Comma,
+            Token::Identifier("x₀".to_string()),
+            Token::CloseParen,
+            Token::Sub,
+            Token::Identifier("γ".to_string()),
+            Token::Div,
+            Token::Number(Number::Int(2)),
+        ]);
+        assert_eq!(lexer.spans[2], Span { start: 4, end: 6 });
+    }
+
+    #[test]
+    fn lex_numbers() {
+        let input_string = "f(-3, 1.5) - 2 * -0.25 x -1";
+        let mut lexer = Lexer::new();
+        lexer.lex(input_string);
+
+        assert!(lexer.errors.is_empty());
+        assert_eq!(lexer.tokens, vec![
+            Token::Identifier("f".to_string()),
+            Token::OpenParen,
+            Token::Number(Number::Int(-3)),
+            Token::Comma,
+            Token::Number(Number::Float(1.5)),
+            Token::CloseParen,
+            Token::Sub,
+            Token::Number(Number::Int(2)),
+            Token::Mul,
+            Token::Number(Number::Float(-0.25)),
+            Token::Identifier("x".to_string()),
+            Token::Sub,
+            Token::Number(Number::Int(1)),
+        ]);
+        assert_eq!(lexer.spans[2], Span { start: 2, end: 4 });
+        assert_eq!(Number::Float(2.0).to_string(), "2.0");
+
+        let mut lexer = Lexer::new();
+        lexer.lex("2..5");
+        assert!(lexer.errors.is_empty());
+        assert_eq!(lexer.tokens, vec![Token::Number(Number::Int(2)), Token::Ellipsis, Token::Number(Number::Int(5))]);
+
+        // A minus after a list is a subtraction
+        let mut lexer = Lexer::new();
+        lexer.lex("[a] -1");
+        assert!(lexer.errors.is_empty());
+        assert_eq!(lexer.tokens[3..], [Token::Sub, Token::Number(Number::Int(1))]);
+
+        // And so is a minus after a string constant
+        let mut lexer = Lexer::new();
+        lexer.lex("\"s\" -1");
+        assert!(lexer.errors.is_empty());
+        assert_eq!(lexer.tokens, vec![Token::Str("s".to_string()), Token::Sub, Token::Number(Number::Int(1))]);
+    }
+
+    #[test]
+    fn lex_tokens_json() {
+        let input_string = "def swap as f(x, y) => f(y, x)\n  apply swap at 0 $";
+        let mut lexer = Lexer::new();
+        lexer.lex(input_string);
+        let json = lexer.to_json(input_string);
+
+        let tokens = json.get("tokens").and_then(Json::as_array).unwrap();
+        let kinds: Vec<&str> = tokens.iter().filter_map(|token| token.get("kind").and_then(Json::as_str)).collect();
+        assert_eq!(&kinds[..7], ["keyword", "identifier", "keyword", "identifier", "punctuation", "identifier", "punctuation"]);
+        assert_eq!(tokens[16].to_string(), r#"{"kind":"keyword","text":"apply","start":33,"end":38,"line":2,"column":3}"#);
+        assert_eq!(tokens[16].get("text").and_then(Json::as_str), Some(&input_string[33..38]));
+
+        let errors = json.get("errors").and_then(Json::as_array).unwrap();
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].get("start").and_then(Json::as_usize), Some(input_string.len() - 1));
+    }
+
+    #[test]
+    fn lex_incomplete() {
+        let mut lexer = Lexer::new();
+        lexer.lex("def distribute as mul(x, add(y, z)) =>");
+        assert!(lexer.is_incomplete());
+
+        let mut lexer = Lexer::new();
+        lexer.lex("f(a,\n    g(b)");
+        assert!(lexer.is_incomplete());
+
+        let mut lexer = Lexer::new();
+        lexer.lex("f(a,\n    g(b))");
+        assert!(!lexer.is_incomplete());
+
+        let mut lexer = Lexer::new();
+        lexer.lex("silent {\n    f(a)");
+        assert!(lexer.is_incomplete());
+
+        // Too many closing parentheses are a syntax error instead
+        let mut lexer = Lexer::new();
+        lexer.lex("f(a))");
+        assert!(!lexer.is_incomplete());
+    }
+
     #[test]
     fn lex_infix_math_ops() {
         let input_string = "(5 + 6) * 3-1";
@@ -386,14 +1171,14 @@ mod tests {
         let iter = lexer.tokens.iter();
         let test = vec![
             Token::OpenParen,
-            Token::Number(5),
+            Token::Number(Number::Int(5)),
             Token::Add,
-            Token::Number(6),
+            Token::Number(Number::Int(6)),
             Token::CloseParen,
             Token::Mul,
-            Token::Number(3),
+            Token::Number(Number::Int(3)),
             Token::Sub,
-            Token::Number(1),
+            Token::Number(Number::Int(1)),
         ];
         assert!(iter.eq(test.iter()));
     }
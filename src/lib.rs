@@ -0,0 +1,27 @@
+pub mod ast;
+pub mod lexer;
+pub mod parser;
+pub mod runtime;
+pub mod strategy;
+pub mod search;
+pub mod error;
+pub mod diagnostic;
+pub mod format;
+pub mod export;
+pub mod termination;
+pub mod unify;
+pub mod config;
+pub mod check;
+pub mod printer;
+pub mod json;
+pub mod lsp;
+pub mod wasm;
+pub mod event;
+pub mod fuzz;
+pub mod operator;
+pub mod lint;
+pub mod replay;
+pub mod library;
+pub mod help;
+#[cfg(feature = "ffi")]
+pub mod ffi;
@@ -0,0 +1,305 @@
+use std::{collections::{HashMap, HashSet}, fs, path::Path};
+
+use crate::{
+    error::LibraryError,
+    export::parse_expr,
+    json::Json,
+    lexer::Lexer,
+    operator::{Assoc, Operator, Operators},
+    parser::{Parser, Stmt},
+    runtime::Rule
+};
+
+// Version of the '.rxlib' format of rule libraries, which is increased whenever the format changes incompatibly
+pub const LIBRARY_VERSION: usize = 1;
+
+// Libraries that are shipped with raxio as source, which are imported by name instead of a path, e.g., 'import "std:logic"'
+pub const STD_LIBRARIES: [(&str, &str); 2] = [
+    ("std:logic", include_str!("../std/logic.rx")),
+    ("std:calculus", include_str!("../std/calculus.rx")),
+];
+
+// The scripts of examples/, which are compiled into the binary, such that the REPL lists them with 'examples' and
+// shows and runs them with 'example NAME'
+pub const EXAMPLES: [(&str, &str); 5] = [
+    ("limit_power_rule_calculus", include_str!("../examples/limit_power_rule_calculus.rx")),
+    ("peano", include_str!("../examples/peano.rx")),
+    ("product_rule_calculus", include_str!("../examples/product_rule_calculus.rx")),
+    ("simple_power_rule_calculus", include_str!("../examples/simple_power_rule_calculus.rx")),
+    ("swap_pair", include_str!("../examples/swap_pair.rx")),
+];
+
+// The source of the bundled example with the name
+pub fn find_example(name: &str) -> Option<&'static str> {
+    EXAMPLES.iter().find(|(example, _)| *example == name).map(|(_, source)| *source)
+}
+
+// The path of the library that an import statement reads, where the shipped libraries are imported by name,
+// and other relative paths are relative to the directory of the importing file, if any.
+pub fn resolve_library_path(path: &str, base_dir: Option<&Path>) -> String {
+    match base_dir {
+        Some(base_dir) if !path.starts_with("std:") => base_dir.join(path).display().to_string(),
+        _ => path.to_string(),
+    }
+}
+
+// The statements of a shipped library, or of a '.rxlib' file, where an error is the reason that it cannot be read.
+pub fn read_library(path: &str) -> Result<Vec<Stmt>, String> {
+    if let Some((_, source)) = STD_LIBRARIES.iter().find(|(name, _)| *name == path) {
+        let mut lexer = Lexer::new();
+        lexer.lex(source);
+        let mut parser = Parser::new();
+        parser.parse(&mut lexer).map_err(|err| err.to_string())?;
+        return Ok(parser.stmts);
+    }
+    if path.starts_with("std:") {
+        let available = STD_LIBRARIES.iter().map(|(name, _)| name.to_string()).collect();
+        return Err(LibraryError::UnknownStdLibrary { available }.to_string());
+    }
+    let text = fs::read_to_string(path).map_err(|err| err.to_string())?;
+    import_library(&text).map_err(|err| err.to_string())
+}
+
+// A rule library with the declared operators, the commutative functors, and every defined rule with its meta-parameters
+// and tags, in order of identifier. Expressions are written in source notation with the arithmetic operators only, so
+// a library can be read without knowing its operators first.
+pub fn export_library(rules: &HashMap<String, Rule>, operators: &Operators, commutative: &HashSet<String>) -> String {
+    let operators: Vec<Json> = operators
+        .iter()
+        .filter(|op| !Operators::arithmetic().iter().any(|arithmetic| arithmetic == *op))
+        .map(|op| Json::object([
+            ("symbol", op.symbol.as_str().into()),
+            ("functor", op.iden.as_str().into()),
+            ("precedence", (op.precedence as usize).into()),
+            ("assoc", op.assoc.name().into()),
+        ]))
+        .collect();
+    let mut commutative: Vec<&String> = commutative.iter().collect();
+    commutative.sort();
+    let mut idens: Vec<&String> = rules.keys().collect();
+    idens.sort();
+    let rules: Vec<Json> = idens
+        .into_iter()
+        .map(|iden| {
+            let rule = &rules[iden];
+            Json::object([
+                ("name", iden.as_str().into()),
+                ("params", strings(&rule.params)),
+                ("left", rule.left.to_source().into()),
+                ("right", rule.right.to_source().into()),
+                ("tags", strings(&rule.tags)),
+                ("auto", rule.auto.into()),
+            ])
+        })
+        .collect();
+    format!("{}\n", Json::object([
+        ("version", LIBRARY_VERSION.into()),
+        ("operators", operators.into()),
+        ("commutative", commutative.into_iter().map(|iden| Json::from(iden.as_str())).collect::<Vec<_>>().into()),
+        ("rules", rules.into()),
+    ]))
+}
+
+fn strings(values: &[String]) -> Json {
+    values.iter().map(|value| Json::from(value.as_str())).collect::<Vec<_>>().into()
+}
+
+// The statements that declare the operators and commutative functors of a rule library and define its rules, such
+// that importing it is interpreted like the statements that were exported, with the same warnings.
+pub fn import_library(text: &str) -> Result<Vec<Stmt>, LibraryError> {
+    let json = Json::parse(text).ok_or(LibraryError::InvalidJson)?;
+    let version = json.get("version").ok_or(LibraryError::InvalidJson)?;
+    if version.as_usize() != Some(LIBRARY_VERSION) {
+        return Err(LibraryError::UnsupportedVersion { version: version.to_string(), supported: LIBRARY_VERSION });
+    }
+    let array = |key| json.get(key).and_then(Json::as_array).ok_or(LibraryError::InvalidJson);
+    let (operators, commutative, rules) = (array("operators")?, array("commutative")?, array("rules")?);
+
+    let mut stmts = vec![];
+    for (index, op) in operators.iter().enumerate() {
+        let field = |key| op.get(key).and_then(Json::as_str).map(str::to_string);
+        let precedence = op.get("precedence").and_then(Json::as_usize).and_then(|n| u8::try_from(n).ok());
+        let assoc = op.get("assoc").and_then(Json::as_str).and_then(Assoc::from_name);
+        let (Some(symbol), Some(iden), Some(precedence), Some(assoc)) = (field("symbol"), field("functor"), precedence, assoc) else {
+            return Err(LibraryError::InvalidOperator { index });
+        };
+        stmts.push(Stmt::OperatorStmt(Operator { symbol, iden, precedence, assoc }));
+    }
+    let commutative: Option<Vec<String>> = commutative.iter().map(|iden| iden.as_str().map(str::to_string)).collect();
+    match commutative {
+        Some(idens) if idens.is_empty() => {},
+        Some(idens) => stmts.push(Stmt::CommutativeStmt(idens)),
+        None => return Err(LibraryError::InvalidJson),
+    }
+    for (index, rule) in rules.iter().enumerate() {
+        let invalid = |reason: &str| LibraryError::InvalidRule { index, reason: reason.to_string() };
+        let iden = rule.get("name").and_then(Json::as_str).ok_or_else(|| invalid("expected the name"))?;
+        let names = |key| -> Option<Vec<String>> {
+            rule.get(key)?.as_array()?.iter().map(|value| value.as_str().map(str::to_string)).collect()
+        };
+        let (Some(params), Some(tags)) = (names("params"), names("tags")) else {
+            return Err(invalid("expected the meta-parameters and tags"));
+        };
+        let side = |key| rule.get(key).and_then(Json::as_str).and_then(parse_expr);
+        let (Some(left), Some(right)) = (side("left"), side("right")) else {
+            return Err(invalid("expected the left and right expression"));
+        };
+        // Libraries that were exported before auto rules have no auto field
+        let auto = matches!(rule.get("auto"), Some(Json::Bool(true)));
+        stmts.push(Stmt::DefineStmt { iden: iden.to_string(), params, left, right, tags, auto });
+    }
+    Ok(stmts)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{ast::{Expr, ExprNode}, runtime::{ast_traverse_match, Env}};
+
+    #[test]
+    fn examples_are_bundled() {
+        let mut names: Vec<String> = fs::read_dir("examples").unwrap()
+            .filter_map(|entry| entry.unwrap().file_name().to_str()?.strip_suffix(".rx").map(String::from))
+            .collect();
+        names.sort();
+        assert_eq!(names, EXAMPLES.iter().map(|(name, _)| name.to_string()).collect::<Vec<_>>());
+        assert_eq!(find_example("peano"), Some(include_str!("../examples/peano.rx")));
+        assert_eq!(find_example("unknown"), None);
+    }
+
+    fn interpret(env: &mut Env, input_string: &str) {
+        let mut lexer = Lexer::new();
+        lexer.lex(input_string);
+        let mut parser = Parser::new();
+        assert!(parser.parse(&mut lexer).is_ok());
+        assert!(env.interpret(parser.stmts).is_ok());
+    }
+
+    #[test]
+    fn library_round_trip() {
+        let mut env = Env::new();
+        env.set_output(std::io::sink());
+        interpret(&mut env, "
+            operator \"->\" as implies, precedence 1, right
+            commutative add
+            def swap as pair(x, y) => pair(y, x) tags [comm] auto
+            def scale(k) as mul(k, add(x, y)) => k * x + k * y
+        ");
+        let text = export_library(&env.rules, &env.operators, &env.commutative);
+        assert!(text.starts_with(concat!(
+            "{\"version\":1,",
+            "\"operators\":[{\"symbol\":\"->\",\"functor\":\"implies\",\"precedence\":1,\"assoc\":\"right\"}],",
+            "\"commutative\":[\"add\"],",
+            "\"rules\":[{\"name\":\"scale\",\"params\":[\"k\"],\"left\":\"k * add(x, y)\"",
+        )));
+
+        let mut imported = Env::new();
+        imported.set_output(std::io::sink());
+        assert!(imported.interpret(import_library(&text).unwrap()).is_ok());
+        assert_eq!(imported.rules, env.rules);
+        assert_eq!(imported.operators, env.operators);
+        assert_eq!(imported.commutative, env.commutative);
+        assert_eq!(export_library(&imported.rules, &imported.operators, &imported.commutative), text);
+    }
+
+    // Each rule of std:logic with a canonical example and its result, from which the inverse rule, if any, derives the example again
+    const LOGIC_EXAMPLES: [(&str, &str, &str, Option<&str>); 7] = [
+        ("double_negation", "not(not(a))", "a", None),
+        ("de_morgan_and", "not(and(a, b))", "or(not(a), not(b))", Some("de_morgan_and_rev")),
+        ("de_morgan_or", "not(or(a, b))", "and(not(a), not(b))", Some("de_morgan_or_rev")),
+        ("distribute_and", "and(a, or(b, c))", "or(and(a, b), and(a, c))", Some("factor_and")),
+        ("distribute_or", "or(a, and(b, c))", "and(or(a, b), or(a, c))", Some("factor_or")),
+        ("implication", "implies(a, b)", "or(not(a), b)", Some("implication_rev")),
+        ("implication_rev", "or(not(a), b)", "implies(a, b)", Some("implication")),
+    ];
+
+    #[test]
+    fn std_logic() {
+        let mut env = Env::new();
+        env.set_output(std::io::sink());
+        assert!(env.interpret(read_library("std:logic").unwrap()).is_ok());
+        assert!(env.warnings.is_empty());
+
+        let apply = |iden: &str, expr: &Expr| {
+            let rule = &env.rules[iden];
+            ast_traverse_match(expr, &rule.left, &rule.right, 0, &env).unwrap()
+        };
+        for (iden, example, result, inverse) in LOGIC_EXAMPLES {
+            let (example, result) = (parse_expr(example).unwrap(), parse_expr(result).unwrap());
+            assert_eq!(apply(iden, &example), result, "{}", iden);
+            if let Some(inverse) = inverse {
+                assert_eq!(apply(inverse, &result), example, "{}", inverse);
+            }
+        }
+        // Every rule is covered by an example
+        for iden in env.rules.keys() {
+            assert!(LOGIC_EXAMPLES.iter().any(|(rule, _, _, inverse)| rule == iden || *inverse == Some(iden)), "{}", iden);
+        }
+        assert_eq!(env.tagged_rules("negation"), vec!["de_morgan_and", "de_morgan_or", "double_negation"]);
+
+        assert_eq!(read_library("std:nope"), Err("Library error: unknown standard library, expected one of std:logic, std:calculus".to_string()));
+    }
+
+    // Each rule of std:calculus, with the arguments of a rule schema, together with a canonical example and its result
+    const CALCULUS_EXAMPLES: [(&str, &str, &str); 19] = [
+        ("derivative_sum", "d(add(pow(x, 2), x), x)", "add(d(pow(x, 2), x), d(x, x))"),
+        ("derivative_difference", "d(sub(pow(x, 2), x), x)", "sub(d(pow(x, 2), x), d(x, x))"),
+        ("derivative_scale(3)", "d(mul(3, pow(x, 2)), x)", "mul(3, d(pow(x, 2), x))"),
+        ("derivative_constant(3)", "d(3, x)", "0"),
+        ("derivative_variable", "d(y, y)", "1"),
+        ("power_rule", "d(pow(y, 2), y)", "mul(2, pow(y, sub(2, 1)))"),
+        ("product_rule", "d(mul(x, sin(x)), x)", "add(mul(d(x, x), sin(x)), mul(x, d(sin(x), x)))"),
+        ("quotient_rule", "d(div(1, x), x)", "div(sub(mul(d(1, x), x), mul(1, d(x, x))), pow(x, 2))"),
+        ("chain_power", "d(pow(sin(x), 2), x)", "mul(mul(2, pow(sin(x), sub(2, 1))), d(sin(x), x))"),
+        ("chain_sin", "d(sin(pow(x, 2)), x)", "mul(cos(pow(x, 2)), d(pow(x, 2), x))"),
+        ("chain_cos", "d(cos(pow(x, 2)), x)", "mul(neg(sin(pow(x, 2))), d(pow(x, 2), x))"),
+        ("chain_exp", "d(exp(pow(x, 2)), x)", "mul(exp(pow(x, 2)), d(pow(x, 2), x))"),
+        ("chain_ln", "d(ln(pow(x, 2)), x)", "div(d(pow(x, 2), x), pow(x, 2))"),
+        ("limit_sum", "lim(h, 0, add(x, h))", "add(lim(h, 0, x), lim(h, 0, h))"),
+        ("limit_difference", "lim(h, 0, sub(x, h))", "sub(lim(h, 0, x), lim(h, 0, h))"),
+        ("limit_scale(2)", "lim(h, 0, mul(2, h))", "mul(2, lim(h, 0, h))"),
+        ("limit_product", "lim(h, 0, mul(h, h))", "mul(lim(h, 0, h), lim(h, 0, h))"),
+        ("limit_constant(x)", "lim(h, 0, x)", "x"),
+        ("limit_variable", "lim(h, 0, h)", "0"),
+    ];
+
+    #[test]
+    fn std_calculus() {
+        let mut env = Env::new();
+        env.set_output(std::io::sink());
+        assert!(env.interpret(read_library("std:calculus").unwrap()).is_ok());
+        assert!(env.warnings.is_empty());
+
+        // A rule is written like an expression, where the arguments of a rule schema are its arguments
+        for (rule, example, result) in CALCULUS_EXAMPLES {
+            let rule = parse_expr(rule).unwrap();
+            let (iden, args) = match rule.node() {
+                ExprNode::Functor { iden, args } => (iden.clone(), args.clone()),
+                _ => (rule.to_string(), vec![]),
+            };
+            let (left, right) = env.rules[&iden].instantiate(&args);
+            let (example, result) = (parse_expr(example).unwrap(), parse_expr(result).unwrap());
+            assert_eq!(ast_traverse_match(&example, &left, &right, 0, &env).unwrap(), result, "{}", iden);
+        }
+        for iden in env.rules.keys() {
+            assert!(CALCULUS_EXAMPLES.iter().any(|(rule, _, _)| rule.split('(').next() == Some(iden.as_str())), "{}", iden);
+        }
+    }
+
+    #[test]
+    fn library_errors() {
+        assert_eq!(import_library("{\"rules\":[]}"), Err(LibraryError::InvalidJson));
+        assert_eq!(
+            import_library("{\"version\":0,\"operators\":[],\"commutative\":[],\"rules\":[]}"),
+            Err(LibraryError::UnsupportedVersion { version: "0".to_string(), supported: 1 })
+        );
+        assert_eq!(
+            import_library("{\"version\":1,\"operators\":[{\"symbol\":\"->\"}],\"commutative\":[],\"rules\":[]}"),
+            Err(LibraryError::InvalidOperator { index: 0 })
+        );
+        assert_eq!(
+            import_library("{\"version\":1,\"operators\":[],\"commutative\":[],\"rules\":[{\"name\":\"r\",\"params\":[],\"tags\":[],\"left\":\"f(x\",\"right\":\"x\"}]}"),
+            Err(LibraryError::InvalidRule { index: 0, reason: "expected the left and right expression".to_string() })
+        );
+    }
+}
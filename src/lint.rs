@@ -0,0 +1,182 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::{ast::{Expr, ExprNode}, error::Warning, runtime::{Rule, WILDCARD}};
+
+// Mistakes in the definition of a rule that are found without applying it: variables of the right expression that
+// are not bound, a right expression that equals the left one, a left expression that is a bare variable, and a rule
+// that is another defined rule with its variables renamed, of which the first in order of identifier is reported.
+pub fn lint_rule<'a>(iden: &str, rule: &Rule, others: impl IntoIterator<Item = (&'a String, &'a Rule)>) -> Vec<Warning> {
+    let mut warnings = vec![];
+    let vars = unbound_variables(&rule.left, &rule.right, &rule.params);
+    if !vars.is_empty() {
+        warnings.push(Warning::UnboundVariables { rule: iden.to_string(), vars });
+    }
+    if rule.left == rule.right {
+        warnings.push(Warning::RuleHasNoEffect(iden.to_string()));
+    }
+    if let ExprNode::Variable { iden: var } = rule.left.node() {
+        warnings.push(Warning::LeftExprIsVariable { rule: iden.to_string(), var: var.clone() });
+    }
+    let duplicate = others
+        .into_iter()
+        .filter(|(other, other_rule)| *other != iden && is_renaming(rule, other_rule))
+        .map(|(other, _)| other)
+        .min();
+    if let Some(other) = duplicate {
+        warnings.push(Warning::DuplicateRule { rule: iden.to_string(), other: other.clone() });
+    }
+    warnings
+}
+
+// The variables and segments of the right expression of a rule that are neither bound by the left expression, nor
+// meta-parameters, nor introduced by 'fresh v', in order of their first occurrence. They are produced literally.
+pub fn unbound_variables(left: &Expr, right: &Expr, params: &[String]) -> Vec<String> {
+    let mut bound = HashSet::new();
+    let mut stack = vec![left];
+    while let Some(expr) = stack.pop() {
+        match expr.node() {
+            ExprNode::Functor { args, .. } => stack.extend(args.iter()),
+            // Wildcards match without binding
+            ExprNode::Variable { iden } if iden == WILDCARD => {},
+            _ => { bound.insert(expr); }
+        }
+    }
+    let mut stack = vec![right];
+    while let Some(expr) = stack.pop() {
+        if let ExprNode::Functor { iden, args } = expr.node() {
+            match args.as_slice() {
+                [var] if iden == "fresh" => { bound.insert(var); },
+                _ => stack.extend(args.iter()),
+            }
+        }
+    }
+
+    let mut unbound = vec![];
+    let mut stack = vec![right];
+    while let Some(expr) = stack.pop() {
+        match expr.node() {
+            ExprNode::Functor { args, .. } => stack.extend(args.iter().rev()),
+            ExprNode::Variable { iden } if params.contains(iden) => {},
+            ExprNode::Variable { .. } | ExprNode::Segment { .. } if !bound.contains(expr) => {
+                let var = expr.to_string();
+                if !unbound.contains(&var) {
+                    unbound.push(var);
+                }
+            },
+            _ => {}
+        }
+    }
+    unbound
+}
+
+// Whether the rule is the other rule with its variables, segments and meta-parameters renamed one-to-one, e.g.,
+// f(x, y) => f(y, x) is f(a, b) => f(b, a). Wildcards and unbound variables are not renamed, as they are literal.
+fn is_renaming(rule: &Rule, other: &Rule) -> bool {
+    let literals = unbound_variables(&rule.left, &rule.right, &rule.params);
+    let other_literals = unbound_variables(&other.left, &other.right, &other.params);
+    let mut renaming: HashMap<&Expr, &Expr> = HashMap::new();
+    let mut renamed = HashSet::new();
+    let mut stack = vec![(&rule.left, &other.left), (&rule.right, &other.right)];
+    while let Some((expr, other)) = stack.pop() {
+        match (expr.node(), other.node()) {
+            (ExprNode::Functor { iden, args }, ExprNode::Functor { iden: other_iden, args: other_args }) => {
+                if iden != other_iden || args.len() != other_args.len() {
+                    return false;
+                }
+                stack.extend(args.iter().zip(other_args.iter()));
+            },
+            (ExprNode::Variable { .. }, ExprNode::Variable { .. }) | (ExprNode::Segment { .. }, ExprNode::Segment { .. }) => {
+                let is_literal = expr.to_string() == WILDCARD || literals.contains(&expr.to_string());
+                let other_is_literal = other.to_string() == WILDCARD || other_literals.contains(&other.to_string());
+                if is_literal || other_is_literal {
+                    if expr != other || is_literal != other_is_literal {
+                        return false;
+                    }
+                    continue;
+                }
+                match renaming.get(expr) {
+                    Some(renamed_to) if *renamed_to != other => return false,
+                    Some(_) => {},
+                    None if renamed.contains(other) => return false,
+                    None => {
+                        renaming.insert(expr, other);
+                        renamed.insert(other);
+                    },
+                }
+            },
+            _ => if expr != other {
+                return false;
+            },
+        }
+    }
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{lexer::Lexer, parser::{Parser, Stmt}};
+
+    fn parse_rules(input_string: &str) -> Vec<(String, Rule)> {
+        let mut lexer = Lexer::new();
+        lexer.lex(input_string);
+
+        let mut parser = Parser::new();
+        let _ = parser.parse(&mut lexer);
+        parser.stmts.into_iter().filter_map(|stmt| match stmt {
+            Stmt::DefineStmt { iden, params, left, right, tags, auto } => Some((iden, Rule { params, left, right, tags, auto })),
+            _ => None,
+        }).collect()
+    }
+
+    #[test]
+    fn lint_rules() {
+        let rules = parse_rules("
+            def swap as f(x, y) => f(y, x)
+            def noop as g(x, 1) => g(x, 1)
+            def anything as x => 0
+            def flip as f(a, b) => f(b, a)
+            def collapse as f(a, a) => f(a, a)
+            def merge as f(a, b) => f(b, b)
+            def swap as f(x, y) => f(y, x)
+        ");
+        let lint = |idx: usize| {
+            let (iden, rule) = &rules[idx];
+            lint_rule(iden, rule, rules[..idx].iter().map(|(iden, rule)| (iden, rule)))
+        };
+        assert_eq!(lint(0), vec![]);
+        assert_eq!(lint(1), vec![Warning::RuleHasNoEffect("noop".to_string())]);
+        assert_eq!(lint(2), vec![Warning::LeftExprIsVariable { rule: "anything".to_string(), var: "x".to_string() }]);
+        assert_eq!(lint(3), vec![Warning::DuplicateRule { rule: "flip".to_string(), other: "swap".to_string() }]);
+        assert_eq!(lint(4), vec![Warning::RuleHasNoEffect("collapse".to_string())]);
+        assert_eq!(lint(5), vec![]);
+        // Redefining a rule does not duplicate the rule it replaces
+        assert_eq!(lint(6), vec![Warning::DuplicateRule { rule: "swap".to_string(), other: "flip".to_string() }]);
+    }
+
+    #[test]
+    fn renamings() {
+        let rules = parse_rules("
+            def r0 as f(x, y..) => g(y.., x)
+            def r1 as f(a, b..) => g(b.., a)
+            def r2 as f(a, b..) => g(a, b..)
+            def r3 as f(x, _) => x
+            def r4 as f(_, x) => x
+            def r5 as f(x) => g(x, c)
+            def r6 as f(x) => g(x, d)
+            def r7 as f(x) => g(fresh v, v, x)
+            def r8 as f(y) => g(fresh w, w, y)
+            def r9(c) as f(x) => mul(c, x)
+            def r10(k) as f(y) => mul(k, y)
+        ");
+        let renaming = |i: usize, j: usize| is_renaming(&rules[i].1, &rules[j].1);
+        assert!(renaming(0, 1));
+        assert!(!renaming(0, 2));
+        assert!(!renaming(3, 4));
+        assert!(renaming(3, 3));
+        // Unbound variables are produced literally, so renaming them changes the rule
+        assert!(!renaming(5, 6));
+        assert!(renaming(7, 8));
+        assert!(renaming(9, 10));
+    }
+}
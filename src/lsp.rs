@@ -0,0 +1,414 @@
+use std::{collections::HashMap, error::Error, io::{self, BufRead, Write}};
+
+use crate::{
+    check::Checker,
+    diagnostic::{plain_message, Severity},
+    error::LexError,
+    json::Json,
+    lexer::{Lexer, Span, Token},
+    parser::{Binding, Parser, Stmt, Syntax},
+    printer::print_stmts
+};
+
+// JSON-RPC error code of a request whose method the server does not implement
+const METHOD_NOT_FOUND: i32 = -32601;
+
+// LSP kind of a completion item that is a function, which is the closest kind to a rule
+const COMPLETION_KIND_FUNCTION: usize = 3;
+
+// A syntax error or warning in a document
+#[derive(Debug, PartialEq)]
+pub struct Diagnostic {
+    pub span: Span,
+    pub severity: Severity,
+    // Warning code, e.g., W005
+    pub code: Option<&'static str>,
+    pub message: String,
+}
+
+// A rule that is defined in a document, by 'def' or by 'end as rule'
+#[derive(Debug, PartialEq)]
+pub struct RuleDefinition {
+    pub iden: String,
+    // Location of the defining statement
+    pub span: Span,
+    // The statement as it is shown on hover, e.g., 'def swap as f(x, y) => f(y, x)'
+    pub source: String,
+}
+
+// What the editor features need to know about a document, which is analyzed again after every change.
+// The statements before a syntax error are still analyzed, so a document that is being edited keeps its rules.
+#[derive(Debug)]
+pub struct Analysis {
+    pub diagnostics: Vec<Diagnostic>,
+    pub rules: Vec<RuleDefinition>,
+    tokens: Vec<(Token, Span)>,
+}
+
+impl Analysis {
+    pub fn new(source: &str) -> Self {
+        let mut lexer = Lexer::new();
+        lexer.lex(source);
+        let mut diagnostics: Vec<Diagnostic> = lexer.errors
+            .iter()
+            .map(|err| Diagnostic {
+                span: err.downcast_ref::<LexError>().map_or(Span::default(), |err| err.span(source)),
+                severity: Severity::Error,
+                code: None,
+                message: plain_message(err),
+            })
+            .collect();
+
+        let mut parser = Parser::with_syntax(Syntax::detect(&lexer));
+        if let Err(err) = parser.parse(&mut lexer) {
+            diagnostics.push(Diagnostic { span: lexer.span(0), severity: Severity::Error, code: None, message: plain_message(&err) });
+        }
+
+        let warnings = Checker::new().check(&parser.stmts);
+        diagnostics.extend(warnings.into_iter().map(|(idx, warn)| Diagnostic {
+            span: parser.spans.get(idx).copied().unwrap_or_default(),
+            severity: Severity::Warning,
+            code: Some(warn.code()),
+            message: plain_message(&warn),
+        }));
+
+        // The statements of a silent block have the span of the block
+        let mut rules = vec![];
+        let mut stack: Vec<(&Stmt, Span)> = parser.stmts.iter().zip(parser.spans.iter().copied()).rev().collect();
+        let mut context_start = None;
+        while let Some((stmt, span)) = stack.pop() {
+            match stmt {
+                Stmt::DefineStmt { iden, .. } => rules.push(RuleDefinition {
+                    iden: iden.clone(),
+                    span,
+                    source: print_stmts(std::slice::from_ref(stmt), parser.syntax).trim_end().to_string()
+                }),
+                Stmt::ExprStmt(expr) if context_start.is_none() => context_start = Some(expr.to_string()),
+                Stmt::EndStmt { binding, .. } => {
+                    if let (Some(Binding::Rule(iden)), Some(start)) = (binding, &context_start) {
+                        rules.push(RuleDefinition {
+                            iden: iden.clone(),
+                            span,
+                            source: format!("rule {} from {} to the result of its pattern matching context", iden, start)
+                        });
+                    }
+                    context_start = None;
+                },
+                Stmt::SilentStmt(stmts) => stack.extend(stmts.iter().rev().map(|stmt| (stmt, span))),
+                _ => {}
+            }
+        }
+
+        let tokens = lexer.tokens.into_iter().zip(lexer.spans).collect();
+        Self { diagnostics, rules, tokens }
+    }
+
+    // The last definition of the rule, which is the one that applies after it
+    pub fn definition(&self, iden: &str) -> Option<&RuleDefinition> {
+        self.rules.iter().rev().find(|rule| rule.iden == iden)
+    }
+
+    // The identifier at the byte offset, including an offset directly after it, where the cursor is while typing
+    pub fn identifier_at(&self, offset: usize) -> Option<&str> {
+        self.tokens.iter().find_map(|(token, span)| match token {
+            Token::Identifier(iden) if span.start <= offset && offset <= span.end => Some(iden.as_str()),
+            _ => None
+        })
+    }
+}
+
+// LSP positions are a line and a column in UTF-16 code units
+fn position(source: &str, offset: usize) -> Json {
+    let offset = offset.min(source.len());
+    let line_start = source[..offset].rfind('\n').map_or(0, |i| i + 1);
+    let line = source[..line_start].matches('\n').count();
+    let character: usize = source[line_start..offset].chars().map(char::len_utf16).sum();
+    Json::object([("line", line.into()), ("character", character.into())])
+}
+
+fn offset(source: &str, position: &Json) -> Option<usize> {
+    let line = position.get("line")?.as_usize()?;
+    let character = position.get("character")?.as_usize()?;
+    let line_start = if line == 0 {
+        0
+    } else {
+        source.match_indices('\n').nth(line - 1)?.0 + 1
+    };
+    let mut units = 0;
+    for (i, c) in source[line_start..].char_indices() {
+        if units >= character || c == '\n' {
+            return Some(line_start + i);
+        }
+        units += c.len_utf16();
+    }
+    Some(source.len())
+}
+
+fn range(source: &str, span: Span) -> Json {
+    Json::object([("start", position(source, span.start)), ("end", position(source, span.end))])
+}
+
+struct Document {
+    source: String,
+    analysis: Analysis,
+}
+
+// A language server for the open documents, which are synchronized in full on every change.
+#[derive(Default)]
+pub struct Server {
+    documents: HashMap<String, Document>,
+    is_exiting: bool,
+}
+
+impl Server {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // Handle a request or notification from the client, and return the messages to send back.
+    pub fn handle(&mut self, message: &Json) -> Vec<Json> {
+        let method = message.get("method").and_then(Json::as_str).unwrap_or_default();
+        let params = message.get("params").unwrap_or(&Json::Null);
+        let uri = params.get("textDocument").and_then(|doc| doc.get("uri")).and_then(Json::as_str);
+        let Some(id) = message.get("id").cloned() else {
+            return self.handle_notification(method, params, uri);
+        };
+
+        let result = match method {
+            "initialize" => Json::object([
+                ("capabilities", Json::object([
+                    ("textDocumentSync", 1.into()),
+                    ("definitionProvider", true.into()),
+                    ("hoverProvider", true.into()),
+                    ("completionProvider", Json::object([])),
+                ])),
+                ("serverInfo", Json::object([("name", "raxio".into()), ("version", env!("CARGO_PKG_VERSION").into())])),
+            ]),
+            "shutdown" => Json::Null,
+            "textDocument/definition" => self.definition(uri, params).map_or(Json::Null, |(uri, doc, rule)| Json::object([
+                ("uri", uri.into()),
+                ("range", range(&doc.source, rule.span)),
+            ])),
+            "textDocument/hover" => self.definition(uri, params).map_or(Json::Null, |(_, _, rule)| Json::object([
+                ("contents", Json::object([
+                    ("kind", "markdown".into()),
+                    ("value", format!("```\n{}\n```", rule.source).into()),
+                ])),
+            ])),
+            "textDocument/completion" => self.completion(uri),
+            _ => return vec![Json::object([
+                ("jsonrpc", "2.0".into()),
+                ("id", id),
+                ("error", Json::object([
+                    ("code", Json::Number(METHOD_NOT_FOUND.into())),
+                    ("message", format!("method '{}' is not supported", method).into()),
+                ])),
+            ])],
+        };
+        vec![Json::object([("jsonrpc", "2.0".into()), ("id", id), ("result", result)])]
+    }
+
+    fn handle_notification(&mut self, method: &str, params: &Json, uri: Option<&str>) -> Vec<Json> {
+        let Some(uri) = uri else {
+            self.is_exiting |= method == "exit";
+            return vec![];
+        };
+        let source = match method {
+            "textDocument/didOpen" => params.get("textDocument").and_then(|doc| doc.get("text")),
+            // The last change has the full text, as the server only supports full synchronization
+            "textDocument/didChange" => params
+                .get("contentChanges")
+                .and_then(Json::as_array)
+                .and_then(|changes| changes.last())
+                .and_then(|change| change.get("text")),
+            "textDocument/didClose" => {
+                self.documents.remove(uri);
+                return vec![publish_diagnostics(uri, vec![])];
+            },
+            _ => None
+        };
+        let Some(source) = source.and_then(Json::as_str) else {
+            return vec![];
+        };
+        let analysis = Analysis::new(source);
+        let diagnostics = analysis.diagnostics
+            .iter()
+            .map(|diagnostic| {
+                let severity: usize = match diagnostic.severity {
+                    Severity::Error => 1,
+                    Severity::Warning => 2,
+                };
+                let mut json = Json::object([
+                    ("range", range(source, diagnostic.span)),
+                    ("severity", severity.into()),
+                    ("source", "raxio".into()),
+                    ("message", diagnostic.message.as_str().into()),
+                ]);
+                if let (Some(code), Json::Object(members)) = (diagnostic.code, &mut json) {
+                    members.push(("code".to_string(), code.into()));
+                }
+                json
+            })
+            .collect();
+        self.documents.insert(uri.to_string(), Document { source: source.to_string(), analysis });
+        vec![publish_diagnostics(uri, diagnostics)]
+    }
+
+    // The definition of the rule at the position, which is looked up in the same document first
+    fn definition<'a>(&'a self, uri: Option<&str>, params: &Json) -> Option<(&'a str, &'a Document, &'a RuleDefinition)> {
+        let (uri, doc) = self.documents.get_key_value(uri?)?;
+        let iden = doc.analysis.identifier_at(offset(&doc.source, params.get("position")?)?)?;
+        if let Some(rule) = doc.analysis.definition(iden) {
+            return Some((uri, doc, rule));
+        }
+        self.documents
+            .iter()
+            .find_map(|(uri, doc)| doc.analysis.definition(iden).map(|rule| (uri.as_str(), doc, rule)))
+    }
+
+    // The rules that are defined in the open documents
+    fn completion(&self, uri: Option<&str>) -> Json {
+        let mut items: Vec<(&str, &str)> = vec![];
+        let docs = uri.and_then(|uri| self.documents.get(uri)).into_iter().chain(self.documents.values());
+        for rule in docs.flat_map(|doc| doc.analysis.rules.iter()) {
+            if !items.iter().any(|(iden, _)| *iden == rule.iden) {
+                items.push((&rule.iden, &rule.source));
+            }
+        }
+        Json::Array(items
+            .into_iter()
+            .map(|(iden, source)| Json::object([
+                ("label", iden.into()),
+                ("kind", COMPLETION_KIND_FUNCTION.into()),
+                ("detail", source.into()),
+            ]))
+            .collect())
+    }
+}
+
+fn publish_diagnostics(uri: &str, diagnostics: Vec<Json>) -> Json {
+    Json::object([
+        ("jsonrpc", "2.0".into()),
+        ("method", "textDocument/publishDiagnostics".into()),
+        ("params", Json::object([("uri", uri.into()), ("diagnostics", diagnostics.into())])),
+    ])
+}
+
+// Read the next message, which has a header with its length in bytes followed by a blank line.
+// Returns None at the end of the input.
+fn read_message(input: &mut impl BufRead) -> Result<Option<Json>, Box<dyn Error>> {
+    let mut length = None;
+    loop {
+        let mut line = String::new();
+        if input.read_line(&mut line)? == 0 {
+            return Ok(None);
+        }
+        let line = line.trim_end();
+        if line.is_empty() {
+            break;
+        }
+        if let Some((name, value)) = line.split_once(':') {
+            if name.eq_ignore_ascii_case("Content-Length") {
+                length = Some(value.trim().parse::<usize>()?);
+            }
+        }
+    }
+    let mut body = vec![0; length.ok_or("missing Content-Length header")?];
+    input.read_exact(&mut body)?;
+    let body = String::from_utf8(body)?;
+    Ok(Some(Json::parse(&body).ok_or("invalid JSON message")?))
+}
+
+fn write_message(output: &mut impl Write, message: &Json) -> io::Result<()> {
+    let body = message.to_string();
+    write!(output, "Content-Length: {}\r\n\r\n{}", body.len(), body)?;
+    output.flush()
+}
+
+// Serve the language server protocol over the input and output, e.g., stdin and stdout, until the client exits.
+pub fn serve(mut input: impl BufRead, mut output: impl Write) -> Result<(), Box<dyn Error>> {
+    let mut server = Server::new();
+    while let Some(message) = read_message(&mut input)? {
+        for response in server.handle(&message) {
+            write_message(&mut output, &response)?;
+        }
+        if server.is_exiting {
+            break;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request(id: usize, method: &str, uri: &str, line: usize, character: usize) -> Json {
+        Json::object([
+            ("jsonrpc", "2.0".into()),
+            ("id", id.into()),
+            ("method", method.into()),
+            ("params", Json::object([
+                ("textDocument", Json::object([("uri", uri.into())])),
+                ("position", Json::object([("line", line.into()), ("character", character.into())])),
+            ])),
+        ])
+    }
+
+    #[test]
+    fn language_server() {
+        let source = "def swap as f(x, y) => f(y, x)\nf(a, b)\n    apply swap at 0\n    apply swp at 0\nend as rule lemma\n";
+        let mut server = Server::new();
+        let open = Json::object([
+            ("jsonrpc", "2.0".into()),
+            ("method", "textDocument/didOpen".into()),
+            ("params", Json::object([
+                ("textDocument", Json::object([("uri", "file:///a.rx".into()), ("text", source.into())])),
+            ])),
+        ]);
+        let messages = server.handle(&open);
+        let diagnostics = messages[0].get("params").and_then(|params| params.get("diagnostics")).unwrap();
+        assert_eq!(diagnostics.to_string(), concat!(
+            r#"[{"range":{"start":{"line":3,"character":4},"end":{"line":3,"character":18}},"severity":2,"source":"raxio","#,
+            r#""message":"cannot find rule 'swp'. First define the rule before applying it like "#,
+            r#"'def YOUR_RULE_NAME as LEFT_EXPR => RIGHT_EXPR'. Thus this statement is ignored.","code":"W005"}]"#
+        ));
+
+        let definition = server.handle(&request(1, "textDocument/definition", "file:///a.rx", 2, 12));
+        assert_eq!(definition[0].get("result").unwrap().to_string(),
+            r#"{"uri":"file:///a.rx","range":{"start":{"line":0,"character":0},"end":{"line":0,"character":30}}}"#);
+
+        let hover = server.handle(&request(2, "textDocument/hover", "file:///a.rx", 2, 10));
+        let contents = hover[0].get("result").and_then(|result| result.get("contents")).unwrap();
+        assert_eq!(contents.get("value").and_then(Json::as_str), Some("```\ndef swap as f(x, y) => f(y, x)\n```"));
+        let hover = server.handle(&request(3, "textDocument/hover", "file:///a.rx", 1, 0));
+        assert_eq!(hover[0].get("result"), Some(&Json::Null));
+
+        let completion = server.handle(&request(4, "textDocument/completion", "file:///a.rx", 3, 9));
+        let labels: Vec<&str> = completion[0]
+            .get("result")
+            .and_then(Json::as_array)
+            .unwrap()
+            .iter()
+            .filter_map(|item| item.get("label").and_then(Json::as_str))
+            .collect();
+        assert_eq!(labels, vec!["swap", "lemma"]);
+
+        let unknown = server.handle(&request(5, "textDocument/rename", "file:///a.rx", 0, 0));
+        assert!(unknown[0].get("error").is_some());
+    }
+
+    #[test]
+    fn serve_messages() {
+        let messages = [
+            r#"{"jsonrpc":"2.0","id":0,"method":"initialize","params":{}}"#,
+            r#"{"jsonrpc":"2.0","method":"exit"}"#,
+        ];
+        let input: String = messages.iter().map(|body| format!("Content-Length: {}\r\n\r\n{}", body.len(), body)).collect();
+        let mut output = vec![];
+        serve(input.as_bytes(), &mut output).unwrap();
+        let output = String::from_utf8(output).unwrap();
+        assert!(output.starts_with("Content-Length: "));
+        assert!(output.contains(r#""definitionProvider":true"#));
+    }
+}
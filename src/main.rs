@@ -1,149 +1,726 @@
+use std::error::Error;
 use std::io::{self, Write};
-use std::{env, fs};
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+use std::sync::{atomic::{AtomicBool, Ordering}, Arc};
+use std::time::{Duration, SystemTime};
+use std::{env, fs, thread};
 
-use lexer::Lexer;
-use parser::Parser;
-use runtime::Env;
+mod cli;
 
-mod lexer;
-mod parser;
-mod runtime;
-mod error;
+use cli::{CliError, Command};
+use raxio::{
+    ast::Expr,
+    check::Checker,
+    config::{History, UserConfig, CONFIG_FILE_NAME, HISTORY_FILE_NAME},
+    diagnostic::{Renderer, Severity},
+    error::LexError,
+    json::Json,
+    lexer::Lexer,
+    lsp,
+    parser::{Parser, Stmt, Syntax, Verbosity},
+    printer::print_stmts,
+    replay::{replay, Derivation},
+    event::Choice,
+    help,
+    library,
+    runtime::Env
+};
 
-fn main() {
+// Command line options that configure the environment
+struct Options {
+    stats: bool,
+    strict: bool,
+    quiet: bool,
+    diff: bool,
+    jobs: usize,
+    watch: bool,
+    color: bool,
+    allowed_warnings: Vec<String>,
+    // Syntax of expressions, which is detected per source if None
+    syntax: Option<Syntax>,
+    // Parse number literals as variables, as they were before numbers were constants
+    numbers_as_variables: bool,
+    // Skip the user configuration file
+    no_config: bool,
+    // Prompt of the REPL from the user configuration, if any
+    prompt: Option<String>,
+    // Files from the user configuration that are interpreted before all other files
+    prelude: Vec<String>,
+    // Path and maximum size of the REPL history, which is not kept without a home or data directory
+    history: Option<(PathBuf, usize)>,
+}
 
-    let mut args: Vec<String> = env::args().collect();
+impl Options {
+    fn new_env(&self) -> Env {
+        let mut env = Env::new();
+        env.stats.enabled = self.stats;
+        env.strict = self.strict;
+        if self.quiet {
+            env.config.verbosity = Verbosity::Quiet;
+        }
+        if let Some(prompt) = &self.prompt {
+            env.config.prompt = prompt.clone();
+        }
+        env.diff = self.diff;
+        env.jobs = self.jobs;
+        for code in self.allowed_warnings.iter() {
+            env.allow_warning(code.clone());
+        }
+        env
+    }
 
-    match args.len() {
-        1 => { start_repl(); },
-        2 => { interpret_file(args.swap_remove(1)); },
-        _ => { usage(); }
+    fn new_parser(&self, lexer: &Lexer) -> Parser {
+        let mut parser = Parser::with_syntax(self.syntax.unwrap_or_else(|| Syntax::detect(lexer)));
+        parser.numbers_as_variables = self.numbers_as_variables;
+        parser
+    }
+
+    // A parser that knows the operators that the environment has defined so far, e.g., in earlier files
+    fn new_env_parser(&self, lexer: &Lexer, env: &Env) -> Parser {
+        let mut parser = env.parser(self.syntax.unwrap_or_else(|| Syntax::detect(lexer)));
+        parser.numbers_as_variables = self.numbers_as_variables;
+        parser
+    }
+
+    // Read the user configuration unless it is skipped, and return false if it is invalid.
+    fn load_config(&mut self) -> bool {
+        let Some(dir) = UserConfig::dir().filter(|_| !self.no_config) else {
+            return true;
+        };
+        match UserConfig::load(&dir) {
+            Ok(config) => {
+                self.apply_config(config);
+                true
+            },
+            Err(e) => {
+                println!("{}: {}", dir.join(CONFIG_FILE_NAME).display(), e);
+                false
+            }
+        }
     }
-}
 
-fn usage() {
-    println!("Usage:");
-    println!("    Provide file for interpretation");
-    println!("        $ ./raxio [FILE_NAME]");
-    println!("    When no arguments are provided, enter REPL mode");
-    println!("        $ ./raxio");
+    // Command line options take precedence over the user configuration, e.g., '--no-color' over 'color = true'.
+    fn apply_config(&mut self, config: UserConfig) {
+        self.color &= config.color;
+        self.prompt = config.prompt;
+        self.prelude = config.prelude.iter().map(|path| path.to_string_lossy().into_owned()).collect();
+        if config.history_size > 0 {
+            self.history = History::dir().map(|dir| (dir.join(HISTORY_FILE_NAME), config.history_size));
+        }
+    }
 }
 
-fn interpret_file(file_name: String) {
-    
-    let input_string = match fs::read_to_string(file_name) {
-        Ok(s) => s,
-        Err(msg) => panic!("{}", msg)
+fn main() -> ExitCode {
+
+    let (command, mut options) = match cli::parse_args(env::args().skip(1).collect()) {
+        Ok(res) => res,
+        Err(e) => return usage(e)
     };
+    let (file_names, eval_source) = match command {
+        Command::Help(command) => {
+            cli::print_help(command);
+            return ExitCode::SUCCESS;
+        },
+        Command::Completions(shell) => {
+            print!("{}", cli::completions(&shell));
+            return ExitCode::SUCCESS;
+        },
+        Command::Check { file_names } => {
+            return exit_code(options.load_config() && check_files(&file_names, &options));
+        },
+        Command::Tokens { file_name, json } => return exit_code(print_tokens(&file_name, json)),
+        Command::Replay { derivation, file_names } => {
+            return exit_code(options.load_config() && replay_derivation(&derivation, &file_names, &options));
+        },
+        Command::Fmt { file_names, check } => return exit_code(format_files(&file_names, check, &options)),
+        // Messages are exchanged on stdout, so errors are printed to stderr
+        Command::Lsp => match lsp::serve(io::stdin().lock(), io::stdout().lock()) {
+            Ok(()) => return ExitCode::SUCCESS,
+            Err(e) => {
+                eprintln!("raxio lsp: {}", e);
+                return ExitCode::from(EXIT_FAILURE);
+            }
+        },
+        Command::Repl => (vec![], None),
+        Command::Run { file_names, eval_source } => (file_names, eval_source),
+    };
+
+    if !options.load_config() {
+        return ExitCode::from(EXIT_FAILURE);
+    }
+
+    if options.jobs > 1 {
+        rayon::ThreadPoolBuilder::new()
+            .num_threads(options.jobs)
+            .build_global()
+            .expect("Failed to build thread pool");
+    }
 
-    let mut env = Env::new();
+    if options.watch {
+        watch(&file_names, eval_source.as_deref(), &options);
+    }
+
+    if file_names.is_empty() && eval_source.is_none() {
+        start_repl(options.new_env(), &options);
+        return ExitCode::SUCCESS;
+    }
 
+    exit_code(run(&file_names, eval_source.as_deref(), &options).0)
+}
+
+fn exit_code(is_ok: bool) -> ExitCode {
+    if is_ok {
+        ExitCode::SUCCESS
+    } else {
+        ExitCode::from(EXIT_FAILURE)
+    }
+}
+
+// Print the tokens of the file, one per line as 'LINE:COLUMN KIND TEXT', or as JSON.
+// Returns whether the file could be read and lexed without errors.
+fn print_tokens(file_name: &str, json: bool) -> bool {
+    let Some(input_string) = read_source(file_name) else {
+        return false;
+    };
     let mut lexer = Lexer::new();
     lexer.lex(&input_string);
+    let tokens = lexer.to_json(&input_string);
+    if json {
+        println!("{}", tokens);
+        return lexer.errors.is_empty();
+    }
 
-    
-    for err in lexer.errors.iter() {
-        println!("{}", err);
+    for token in tokens.get("tokens").and_then(Json::as_array).unwrap_or_default() {
+        let field = |key| token.get(key).map(|value| value.to_string()).unwrap_or_default();
+        let text = token.get("text").and_then(Json::as_str).unwrap_or_default();
+        println!("{}:{} {} {}", field("line"), field("column"), token.get("kind").and_then(Json::as_str).unwrap_or_default(), text);
     }
+    let renderer = Renderer::new(file_name, &input_string, false);
+    report_syntax_errors(&lexer, Ok(()), &renderer)
+}
 
-    let mut parser = Parser::new();
-    let res = parser.parse(&mut lexer);
+// Rewrite the files in the canonical layout, or only report the files that differ from it if check is true.
+// Files with syntax errors are left unchanged. Returns whether all files could be formatted, or are formatted.
+fn format_files(file_names: &[String], check: bool, options: &Options) -> bool {
+    let mut is_ok = true;
+    for file_name in file_names {
+        let Some(input_string) = read_source(file_name) else {
+            is_ok = false;
+            continue;
+        };
+        let renderer = Renderer::new(file_name, &input_string, options.color);
+
+        let mut lexer = Lexer::new();
+        lexer.lex(&input_string);
+
+        let mut parser = options.new_parser(&lexer);
+        let res = parser.parse(&mut lexer);
+        if !report_syntax_errors(&lexer, res, &renderer) {
+            is_ok = false;
+            continue;
+        }
+
+        let formatted = print_stmts(&parser.stmts, parser.syntax);
+        if check {
+            if formatted != input_string {
+                println!("{}: not formatted", file_name);
+                is_ok = false;
+            }
+        } else if file_name == "-" {
+            print!("{}", formatted);
+        } else if formatted != input_string {
+            if let Err(msg) = fs::write(file_name, formatted) {
+                println!("{}: {}", file_name, msg);
+                is_ok = false;
+            }
+        }
+    }
+    is_ok
+}
 
-    if let Err(e) = res {
-        println!("{}", e);
+// Check the files in order, as they would be interpreted, without stopping at the first file with errors.
+// Returns whether there are no syntax errors, and in strict mode also no warnings.
+fn check_files(file_names: &[String], options: &Options) -> bool {
+    let mut checker = Checker::new();
+    for code in options.allowed_warnings.iter() {
+        checker.allow_warning(code.clone());
     }
+    let mut is_ok = true;
+    for file_name in options.prelude.iter().chain(file_names) {
+        let Some(input_string) = read_source(file_name) else {
+            is_ok = false;
+            continue;
+        };
+        let renderer = Renderer::new(file_name, &input_string, options.color);
 
-    let res = env.interpret(parser.stmts);
-    
-    if env.warnings.len() > 0 {
-        for warn in env.warnings.iter() {
-            println!("{}", warn);
+        let mut lexer = Lexer::new();
+        lexer.lex(&input_string);
+
+        let mut parser = options.new_parser(&lexer);
+        let res = parser.parse(&mut lexer);
+        is_ok &= report_syntax_errors(&lexer, res, &renderer);
+
+        checker.base_dir = (file_name != "-").then(|| Path::new(file_name).parent().unwrap_or(Path::new("")).to_path_buf());
+        let warnings = checker.check(&parser.stmts);
+        is_ok &= !options.strict || warnings.is_empty();
+        for (idx, warn) in warnings {
+            println!("{}", renderer.render(&warn, parser.spans.get(idx).copied(), Severity::Warning));
+        }
+    }
+    is_ok
+}
+
+// Apply every step of the exported derivation again with the rules that the files define, and print whether it
+// derives the recorded expression. Returns whether every step does.
+fn replay_derivation(derivation_name: &str, file_names: &[String], options: &Options) -> bool {
+    let Some(text) = read_source(derivation_name) else {
+        return false;
+    };
+    let derivation = match Derivation::from_json(&text) {
+        Ok(derivation) => derivation,
+        Err(e) => {
+            println!("{}: {}", derivation_name, e);
+            return false;
+        }
+    };
+    let mut env = options.new_env();
+    if !interpret_all(file_names, None, &mut env, options) {
+        return false;
+    }
+    let steps = match replay(&derivation, &env) {
+        Ok(steps) => steps,
+        Err(e) => {
+            println!("{}: {}", derivation_name, e);
+            return false;
+        }
+    };
+
+    println!("Replaying {} step(s) from: {}", steps.len(), derivation.history[0].to_string_with(&env.operators));
+    let mut num_failed = 0;
+    for (i, step) in steps.iter().enumerate() {
+        let rule = match &step.rule.name {
+            Some(name) => name.clone(),
+            None => format!("{} => {}", step.rule.left.to_string_with(&env.operators), step.rule.right.to_string_with(&env.operators)),
+        };
+        if step.is_ok {
+            if !options.quiet {
+                println!("[{}] {} at depth {}: ok", i+1, rule, step.rule.depth);
+            }
+            continue;
+        }
+        num_failed += 1;
+        println!("[{}] {} at depth {}: expected {}, but got {}",
+            i+1,
+            rule,
+            step.rule.depth,
+            step.expected.to_string_with(&env.operators),
+            step.got.to_string_with(&env.operators)
+        );
+    }
+    match num_failed {
+        0 => println!("Every step derives the recorded expression"),
+        _ => println!("{} of {} step(s) no longer derive the recorded expression", num_failed, steps.len()),
+    }
+    num_failed == 0
+}
+
+// All files share the environment, such that rules defined in one file can be applied in 
+// the next, starting with the prelude. Interpretation stops at the first file that fails.
+fn interpret_all(file_names: &[String], eval_source: Option<&str>, env: &mut Env, options: &Options) -> bool {
+    options.prelude
+        .iter()
+        .chain(file_names)
+        .all(|file_name| interpret_file(file_name, env, options)) && 
+        eval_source.is_none_or(|source| interpret_source("eval", source, env, options))
+}
+
+// Interpret the prelude, the files and the evaluated source in a fresh environment, which is returned with whether 
+// all of them were interpreted without errors.
+fn run(file_names: &[String], eval_source: Option<&str>, options: &Options) -> (bool, Env) {
+    let mut env = options.new_env();
+    let is_ok = interpret_all(file_names, eval_source, &mut env, options);
+    (is_ok, env)
+}
+
+// Time between two checks for changes of the watched files
+const WATCH_INTERVAL: Duration = Duration::from_millis(250);
+
+// Run the files like the run command whenever one of them, a prelude file, or an imported rule library changes on disk,
+// until the process is interrupted. Changes are detected by polling the modification times.
+fn watch(file_names: &[String], eval_source: Option<&str>, options: &Options) -> ! {
+    let modification_times = |watched: &[String]| -> Vec<Option<SystemTime>> {
+        watched
+            .iter()
+            .map(|file_name| fs::metadata(file_name).and_then(|meta| meta.modified()).ok())
+            .collect()
+    };
+    let mut watched: Vec<String> = options.prelude.iter().chain(file_names).cloned().collect();
+    let mut last_modified = vec![];
+    loop {
+        let modified = modification_times(&watched);
+        if modified != last_modified {
+            // Clear the terminal before printing the fresh derivation
+            print!("\x1B[2J\x1B[1;1H");
+            println!("Watching {} for changes. Press Ctrl-C to stop.\n", file_names.join(", "));
+            let (_, env) = run(file_names, eval_source, options);
+            // The imported libraries are only known after the files are interpreted, and may differ from the last run
+            watched = options.prelude.iter().chain(file_names).chain(env.imports.iter()).cloned().collect();
+            last_modified = modification_times(&watched);
+            io::stdout().flush().expect("Failed to flush stdout");
         }
-        env.warnings.clear();
+        thread::sleep(WATCH_INTERVAL);
     }
+}
+
+// Exit code of a program that failed with a syntax or runtime error, 
+// or that produced warnings in strict mode.
+const EXIT_FAILURE: u8 = 1;
+
+// Exit code of invalid command line arguments
+const EXIT_USAGE: u8 = 2;
 
-    if let Err(e) = res {
-        println!("{}", e);
+fn usage(e: CliError) -> ExitCode {
+    println!("Error: {}\n", e);
+    match e.command() {
+        Some(command) => println!("Enter 'raxio help {}' to see its usage and options.", command.name),
+        None => println!("Enter 'raxio help' to see the commands and their usage."),
     }
+    ExitCode::from(EXIT_USAGE)
 }
 
-fn start_repl() {
-    let mut env = Env::new();
+// Relative paths in the file are resolved against its directory, rather than the working directory
+fn interpret_file(file_name: &str, env: &mut Env, options: &Options) -> bool {
+    let Some(input_string) = read_source(file_name) else {
+        return false;
+    };
+    let base_dir = (file_name != "-").then(|| Path::new(file_name).parent().unwrap_or(Path::new("")).to_path_buf());
+    let prev_base_dir = std::mem::replace(&mut env.base_dir, base_dir);
+    let is_ok = interpret_source(file_name, &input_string, env, options);
+    env.base_dir = prev_base_dir;
+    is_ok
+}
+
+// Read the file, or print why it cannot be read
+fn read_source(file_name: &str) -> Option<String> {
+    // By convention, '-' denotes the standard input
+    let res = if file_name == "-" {
+        io::read_to_string(io::stdin())
+    } else {
+        fs::read_to_string(file_name)
+    };
+    match res {
+        Ok(s) => Some(s),
+        Err(msg) => {
+            println!("{}: {}", file_name, msg);
+            None
+        }
+    }
+}
+
+// Returns whether the source was interpreted without errors.
+fn interpret_source(source_name: &str, input_string: &str, env: &mut Env, options: &Options) -> bool {
+
+    let renderer = Renderer::new(source_name, input_string, options.color);
+
+    // Operators that earlier files defined are known in this one, like their rules
+    let mut lexer = env.lex(input_string);
+
+    let mut parser = options.new_env_parser(&lexer, env);
+    let res = parser.parse(&mut lexer);
+
+    let is_ok = report_syntax_errors(&lexer, res, &renderer);
+    interpret_stmts(parser, env, &renderer) && is_ok
+}
+
+// Print the lexing and parsing errors, and return whether there are none.
+fn report_syntax_errors(lexer: &Lexer, parse_res: Result<(), Box<dyn Error>>, renderer: &Renderer) -> bool {
+    for err in lexer.errors.iter() {
+        let span = err.downcast_ref::<LexError>().map(|err| err.span(renderer.source));
+        println!("{}", renderer.render(err, span, Severity::Error));
+    }
+
+    // The parser stops at the offending token
+    if let Err(e) = &parse_res {
+        println!("{}", renderer.render(e, Some(lexer.span(0)), Severity::Error));
+    }
+
+    lexer.errors.is_empty() && parse_res.is_ok()
+}
+
+// Interpret the statements one at a time, such that warnings and errors are reported at 
+// the statement that caused them. Returns whether the statements were interpreted without errors.
+fn interpret_stmts(parser: Parser, env: &mut Env, renderer: &Renderer) -> bool {
+    for (stmt, span) in parser.stmts.into_iter().zip(parser.spans) {
+        let res = env.interpret(vec![stmt]);
+
+        // Warnings still stop the interpretation in strict mode, even if they are not printed
+        let is_quiet = env.is_quiet();
+        for warn in env.warnings.drain(..) {
+            if !is_quiet {
+                println!("{}", renderer.render(&warn, Some(span), Severity::Warning));
+            }
+        }
+
+        if let Err(e) = res {
+            println!("{}", renderer.render(&e, Some(span), Severity::Error));
+            return false;
+        }
+    }
+    true
+}
+
+fn start_repl(mut env: Env, options: &Options) {
     println!("Welcome to the REPL environment of raxio.");
     println!("Enter \"quit\" to stop the REPL environment.");
-    println!("Enter \"help\" to see an overview of raxio syntax.");
-    println!("Enter \"undo\" during mattern patching to undo the current expression.");
+    println!("Enter \"help\" to list the help topics, or e.g. \"help def\" for the syntax of a topic.");
+    println!("Enter \"undo\" during mattern patching to undo the current expression, or \"undo 3\" to undo the last 3 steps.");
+    println!("Enter \"edit\" during pattern matching to change the current expression in $EDITOR.");
+    println!("Enter \"history\" to list previous inputs, and \"!3\" or \"!!\" to enter the third or the last input again.");
+    println!("Enter \"steps\" during pattern matching to list the steps of the derivation with the names of their rules.");
+    println!("Enter \"examples\" to list the bundled example scripts, and e.g. \"example peano\" to show and run one.");
+
+    // The rules of the prelude are available in the REPL, even if a prelude file fails
+    for file_name in options.prelude.iter() {
+        interpret_file(file_name, &mut env, options);
+    }
+    let mut history = options.history.clone().map(|(path, max_size)| History::load(path, max_size));
+    env.chooser = Some(Box::new(choose_match));
+    let busy = handle_interrupts(&env);
 
     loop {
         let mut input_string = String::new();
         
         env.print_prefix();
-
-        io::stdout().flush().expect("Failed to flush stdout");
         io::stdin().read_line(&mut input_string).expect("Failed to read input line");
         
-        let input_string = input_string.trim();
-        
-        if input_string.len() == 0 {
+        if input_string.trim().is_empty() {
             continue;
         }
+
+        // A recalled entry of the history is echoed, and then handled as if it was entered again
+        if input_string.trim().starts_with('!') {
+            match history.as_ref().and_then(|history| history.recall(input_string.trim())) {
+                Some(entry) => {
+                    println!("{}", entry);
+                    input_string = entry.to_string();
+                },
+                None => {
+                    println!("There is no entry {} in the history, which is listed by entering \"history\".", input_string.trim());
+                    continue;
+                }
+            }
+        }
         
-        match input_string {
+        match input_string.trim() {
             "quit" => { return; }, 
-            "help" => { print_help(); continue; },
-            "undo" => { env.pop_expr(); continue; },
+            "help" => { print_help(None); continue; },
+            "edit" => { edit_current_expr(&mut env, options); continue; },
+            "steps" => { env.print_steps(); continue; },
+            "examples" => {
+                for (name, _) in library::EXAMPLES.iter() {
+                    println!("    {}", name);
+                }
+                continue;
+            },
+            "history" => { 
+                for (i, entry) in history.iter().flat_map(|history| history.entries.iter()).enumerate() {
+                    println!("{:>5}  {}", i + 1, entry);
+                }
+                continue; 
+            },
             _ => {}
         }
-
-        let mut lexer = Lexer::new();
-        lexer.lex(input_string);
-        
-        let mut parser = Parser::new();
-        let res = parser.parse(&mut lexer);
-    
-        if lexer.errors.len() > 0 {
-            for err in lexer.errors.iter() {
-                println!("{}", err);
+        if let Some(topic) = input_string.trim().strip_prefix("help ") {
+            print_help(Some(topic.trim()));
+            continue;
+        }
+        if let Some(name) = input_string.trim().strip_prefix("example ") {
+            run_example(name.trim(), &mut env, options);
+            continue;
+        }
+        if let Some(num_steps) = parse_undo(input_string.trim()) {
+            if env.undo(num_steps) > 0 {
+                env.print_current_expr("    ");
             }
             continue;
         }
+
+        let mut lexer = env.lex(&input_string);
+
+        // Keep reading lines until the statement is complete, or the input ends
+        while lexer.is_incomplete() {
+            env.print_continuation_prefix();
+            let num_bytes = io::stdin().read_line(&mut input_string).expect("Failed to read input line");
+            if num_bytes == 0 {
+                break;
+            }
+            lexer = env.lex(&input_string);
+        }
+
+        let input_string = input_string.trim_end();
+        // The history is a convenience, so the REPL continues if it cannot be written
+        if let Some(history) = history.as_mut() {
+            let _ = history.push(input_string);
+        }
+        let renderer = Renderer::new("repl", input_string, options.color);
         
-        if let Err(e) = res {
-            println!("{}", e);
+        let mut parser = options.new_env_parser(&lexer, &env);
+        let res = parser.parse(&mut lexer);
+    
+        if !report_syntax_errors(&lexer, res, &renderer) {
             continue;
         }
 
-        let res = env.interpret(parser.stmts);
+        env.limits.interrupted.store(false, Ordering::Relaxed);
+        busy.store(true, Ordering::Relaxed);
+        interpret_stmts(parser, &mut env, &renderer);
+        busy.store(false, Ordering::Relaxed);
+    }
+}
 
-        if env.warnings.len() > 0 {
-            for warn in env.warnings.iter() {
-                println!("{}", warn);
+// Ctrl-C stops the statement that is interpreted, and keeps the environment, while it still quits the REPL at the prompt.
+// Returns whether a statement is interpreted, which the REPL sets around each input.
+fn handle_interrupts(env: &Env) -> Arc<AtomicBool> {
+    let busy = Arc::new(AtomicBool::new(false));
+    #[cfg(not(target_arch = "wasm32"))]
+    {
+        let (is_busy, interrupted) = (busy.clone(), env.limits.interrupted.clone());
+        // Without a handler, Ctrl-C stops the process as usual
+        let _ = ctrlc::set_handler(move || {
+            if is_busy.load(Ordering::Relaxed) {
+                interrupted.store(true, Ordering::Relaxed);
+            } else {
+                std::process::exit(130);
             }
-            env.warnings.clear();
+        });
+    }
+    busy
+}
+
+// Open the current expression in $EDITOR, and derive the edited expression from it in a single step, which can be undone.
+fn edit_current_expr(env: &mut Env, options: &Options) {
+    let Some(expr) = env.history.last().filter(|_| env.is_matching).cloned() else {
+        println!("There is no current expression to edit, enter an expression to start pattern matching.");
+        return;
+    };
+    let source = match options.syntax {
+        Some(Syntax::SExpr) => print_stmts(&[Stmt::ExprStmt(expr.clone())], Syntax::SExpr),
+        _ => expr.to_source_with(&env.operators),
+    };
+    let path = env::temp_dir().join(format!("raxio-edit-{}.rx", std::process::id()));
+    let res = open_in_editor(&path, &source);
+    let _ = fs::remove_file(&path);
+    let source = match res {
+        Ok(source) => source,
+        Err(e) => {
+            println!("Cannot edit the current expression in {}: {}", path.display(), e);
+            return;
         }
+    };
 
-        if let Err(e) = res {
-            println!("{}", e);
+    let renderer = Renderer::new("edit", &source, options.color);
+    let mut lexer = env.lex(&source);
+    let mut parser = options.new_env_parser(&lexer, env);
+    let res = parser.parse(&mut lexer);
+    if !report_syntax_errors(&lexer, res, &renderer) {
+        return;
+    }
+    match parser.stmts.as_slice() {
+        [Stmt::ExprStmt(new_expr)] if *new_expr == expr => env.print_current_expr("    "),
+        [Stmt::ExprStmt(new_expr)] => {
+            let res = env.edit(new_expr.clone());
+            for warn in env.warnings.drain(..) {
+                println!("{}", renderer.render(&warn, None, Severity::Warning));
+            }
+            if let Err(e) = res {
+                println!("{}", renderer.render(&e, None, Severity::Error));
+            }
+        },
+        _ => println!("The edited file must contain a single expression, so the current expression is left unchanged."),
+    }
+}
+
+// Write the source to the file, and return its contents after the editor is closed. The editor can have 
+// arguments, e.g., EDITOR="code --wait", and is vi if EDITOR is not set.
+fn open_in_editor(path: &Path, source: &str) -> io::Result<String> {
+    fs::write(path, source)?;
+    let editor = env::var("EDITOR").ok().filter(|editor| !editor.trim().is_empty()).unwrap_or_else(|| "vi".to_string());
+    let mut args = editor.split_whitespace();
+    let status = std::process::Command::new(args.next().unwrap())
+        .args(args)
+        .arg(path)
+        .status()?;
+    if !status.success() {
+        return Err(io::Error::other(format!("'{}' exited with {}", editor, status)));
+    }
+    fs::read_to_string(path)
+}
+
+// The number of steps of an undo command, 'undo' or 'undo N', or None if the input is a statement instead.
+fn parse_undo(input: &str) -> Option<usize> {
+    match input.split_whitespace().collect::<Vec<_>>()[..] {
+        ["undo"] => Some(1),
+        ["undo", num_steps] => num_steps.parse().ok(),
+        _ => None,
+    }
+}
+
+// Ask which of the listed matches of a rule to rewrite, by its number or 'all'. The end of the input chooses all matches.
+fn choose_match(candidates: &[(Vec<usize>, Expr)]) -> Choice {
+    loop {
+        print!("    Choose a match (1-{}, or all): ", candidates.len());
+        let _ = io::stdout().flush();
+        let mut input_string = String::new();
+        if io::stdin().read_line(&mut input_string).unwrap_or(0) == 0 {
+            return Choice::All;
+        }
+        match input_string.trim() {
+            "all" => return Choice::All,
+            input => match input.parse::<usize>() {
+                Ok(n) if (1..=candidates.len()).contains(&n) => return Choice::One(n - 1),
+                _ => println!("    Expected a number from 1 to {}, or all.", candidates.len()),
+            }
         }
+    }
+}
+
+// Print the bundled example, and interpret it in the environment of the REPL, such that its rules can be used afterwards.
+// The derivations and rules are not written to files, as the paths of the examples are relative to the examples directory.
+fn run_example(name: &str, env: &mut Env, options: &Options) {
+    let Some(source) = library::find_example(name) else {
+        println!("There is no example '{}', the examples are listed by entering \"examples\".", name);
+        return;
+    };
+    for line in source.lines() {
+        println!("    | {}", line);
+    }
+    println!();
 
+    let mut lexer = env.lex(source);
+    let mut parser = options.new_env_parser(&lexer, env);
+    let res = parser.parse(&mut lexer);
+    let renderer = Renderer::new(name, source, options.color);
+    if !report_syntax_errors(&lexer, res, &renderer) {
+        return;
+    }
+    let stmts = std::mem::take(&mut parser.stmts).into_iter().zip(std::mem::take(&mut parser.spans));
+    (parser.stmts, parser.spans) = stmts.filter(|(stmt, _)| !matches!(stmt, Stmt::ExportRulesStmt(_))).unzip();
+    for stmt in parser.stmts.iter_mut() {
+        if let Stmt::EndStmt { path, append, format, .. } = stmt {
+            (*path, *append, *format) = (None, false, None);
+        }
     }
+    interpret_stmts(parser, env, &renderer);
 }
 
-fn print_help() {
-    println!("Raxio syntax:");
-    println!("To define a rule, use");
-    println!("    - def [YOUR_RULE_NAME] as [LEFT_EXPR] => [RIGHT_EXPR]");
-    println!("      YOUR_RULE_NAME is an alphanumeric identifier.");
-    println!("      LEFT_EXPR is the expression to match against.");
-    println!("      RIGHT_EXPR is the expression to produce if left expression was matched.\n");
-    println!("To start pattern matching an expression, use either");
-    println!("    - a variable, e.g., x, foo, abc, etc.; or");
-    println!("    - a functor, e.g., f(x), g(h(x, y)), foo(bar(baz)), etc.; or");
-    println!("    - binary arithmetic in-fix operations, '+', '-', '*', '/'.");
-    println!("      e.g., a + b (which gets translated to the functor add(a, b))\n");
-    println!("To apply a rule during pattern matching of expession, enter either");
-    println!("    - predefined identifier of a rule followed by a number indicating at which depth to apply the rule");
-    println!("      e.g., apply [YOUR_RULE_NAME] at [DEPTH]; or");
-    println!("    - an in-line rule without an identifier followed by a number indicating at which depth to apply the rule");
-    println!("      e.g., [LEFT_EXPR] => [RIGHT_EXPR] at [DEPTH]\n");
+// The overview of the help topics, or the syntax and example of one topic
+fn print_help(topic: Option<&str>) {
+    match topic {
+        None => print!("{}", help::overview()),
+        Some(name) => match help::find_topic(name) {
+            Some(topic) => print!("{}", topic.render()),
+            None => println!("There is no help topic '{}', the topics are listed by entering \"help\".", name),
+        }
+    }
 }
@@ -0,0 +1,161 @@
+use std::sync::OnceLock;
+
+use crate::error::Warning;
+
+// Punctuation of the language, which cannot be the symbol of an operator
+const RESERVED_SYMBOLS: [&str; 6] = ["=>", "==", "=", ";", "|", ".."];
+
+// Whether consecutive operators of the same precedence group to the left, e.g., a - b - c is (a - b) - c, to the right,
+// or not at all, such that a == b == c is a parsing error
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Assoc {
+    Left,
+    Right,
+    NonAssoc,
+}
+
+impl Assoc {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "left" => Some(Assoc::Left),
+            "right" => Some(Assoc::Right),
+            "nonassoc" => Some(Assoc::NonAssoc),
+            _ => None
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Assoc::Left => "left",
+            Assoc::Right => "right",
+            Assoc::NonAssoc => "nonassoc",
+        }
+    }
+}
+
+// An infix operator, which is notation for a binary functor, e.g., a + b for add(a, b)
+#[derive(Debug, Clone, PartialEq)]
+pub struct Operator {
+    pub symbol: String,
+    pub iden: String,
+    // Operators of a higher precedence bind stronger
+    pub precedence: u8,
+    pub assoc: Assoc,
+}
+
+impl Operator {
+    // A left-associative operator, like the arithmetic operators
+    pub fn new(symbol: impl Into<String>, iden: impl Into<String>, precedence: u8) -> Self {
+        Self { symbol: symbol.into(), iden: iden.into(), precedence, assoc: Assoc::Left }
+    }
+
+    // The minimum precedences of the left and right operand, where the operand on the side of the associativity,
+    // if any, can contain the operator itself without parentheses
+    pub fn operand_precedences(&self) -> (u8, u8) {
+        match self.assoc {
+            Assoc::Left => (self.precedence, self.precedence + 1),
+            Assoc::Right => (self.precedence + 1, self.precedence),
+            Assoc::NonAssoc => (self.precedence + 1, self.precedence + 1),
+        }
+    }
+
+    // Symbols are written without whitespace, and do not contain the punctuation around expressions
+    pub fn is_valid_symbol(symbol: &str) -> bool {
+        !symbol.is_empty() 
+            && !RESERVED_SYMBOLS.contains(&symbol)
+            && symbol.chars().all(|c| !c.is_whitespace() && !c.is_control() && !"(),{}[]\"".contains(c))
+    }
+}
+
+// Symbol table of the infix operators, which tracks the functors that are backed by an operator, such that
+// they are parsed and printed consistently. It starts with the arithmetic operators, and 'infix SYMBOL as
+// IDENTIFIER precedence N' adds an operator, unless its symbol or identifier already belongs to another one.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Operators(Vec<Operator>);
+
+impl Default for Operators {
+    fn default() -> Self {
+        Self(vec![
+            Operator::new("+", "add", 1),
+            Operator::new("-", "sub", 1),
+            Operator::new("*", "mul", 2),
+            Operator::new("/", "div", 2),
+        ])
+    }
+}
+
+impl Operators {
+    // Precedences of defined operators range from 1 up to and including this one
+    pub const MAX_PRECEDENCE: u8 = 9;
+
+    // Only the arithmetic operators, which are used wherever no symbol table is given
+    pub fn arithmetic() -> &'static Self {
+        static ARITHMETIC: OnceLock<Operators> = OnceLock::new();
+        ARITHMETIC.get_or_init(Self::default)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Operator> {
+        self.0.iter()
+    }
+
+    pub fn by_symbol(&self, symbol: &str) -> Option<&Operator> {
+        self.0.iter().find(|op| op.symbol == symbol)
+    }
+
+    pub fn by_iden(&self, iden: &str) -> Option<&Operator> {
+        self.0.iter().find(|op| op.iden == iden)
+    }
+
+    // The operator of a functor with the given number of arguments, as only binary functors are printed infix
+    pub fn infix(&self, iden: &str, num_args: usize) -> Option<&Operator> {
+        self.by_iden(iden).filter(|_| num_args == 2)
+    }
+
+    pub fn define(&mut self, op: Operator) -> Result<(), Warning> {
+        if let Some(other) = self.by_symbol(&op.symbol) {
+            return Err(Warning::OperatorSymbolExists { symbol: op.symbol, iden: other.iden.clone() });
+        }
+        if let Some(other) = self.by_iden(&op.iden) {
+            return Err(Warning::OperatorFunctorExists { iden: op.iden, symbol: other.symbol.clone() });
+        }
+        self.0.push(op);
+        Ok(())
+    }
+
+    // A functor that is backed by an operator is binary, so declaring it with another number of arguments collides.
+    pub fn check_arity(&self, iden: &str, arity: usize) -> Option<Warning> {
+        self.by_iden(iden).filter(|_| arity != 2).map(|op| Warning::OperatorArityMismatch {
+            iden: iden.to_string(),
+            symbol: op.symbol.clone(),
+            arity
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn define_operators() {
+        let mut operators = Operators::default();
+        assert_eq!(operators.define(Operator::new("⊕", "oplus", 4)), Ok(()));
+        assert_eq!(operators.by_symbol("⊕").map(|op| op.iden.as_str()), Some("oplus"));
+        assert_eq!(operators.infix("oplus", 2).map(|op| op.precedence), Some(4));
+        assert_eq!(operators.infix("oplus", 3), None);
+
+        assert_eq!(
+            operators.define(Operator::new("+", "plus", 1)),
+            Err(Warning::OperatorSymbolExists { symbol: "+".to_string(), iden: "add".to_string() })
+        );
+        assert_eq!(
+            operators.define(Operator::new("⊗", "add", 3)),
+            Err(Warning::OperatorFunctorExists { iden: "add".to_string(), symbol: "+".to_string() })
+        );
+        assert_eq!(operators.check_arity("oplus", 2), None);
+        assert_eq!(
+            operators.check_arity("add", 3),
+            Some(Warning::OperatorArityMismatch { iden: "add".to_string(), symbol: "+".to_string(), arity: 3 })
+        );
+    }
+}
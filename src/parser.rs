@@ -1,25 +1,228 @@
-use std::{fmt::Display, error::Error};
+use std::{error::Error, fmt::Display, time::Duration};
 
-use crate::{lexer::{Token, Lexer}, error::ParsingError};
-
-#[derive(Debug, PartialEq, Eq, Clone, Hash)]
-pub enum Expr {
-    Functor { iden: String, args: Vec<Expr> },
-    Variable { iden: String },
-}
+use crate::{ast::Expr, lexer::{Token, Lexer, Number, Span}, error::ParsingError, operator::{Assoc, Operator, Operators}};
 
 #[derive(Debug, PartialEq)]
+#[allow(clippy::enum_variant_names)]
 pub enum Stmt {
-    RuleStmt {left: Expr, right: Expr, depth: usize},
-    DefineStmt {iden: String, left: Expr, right: Expr}, 
+    RuleStmt {left: Expr, right: Expr, depth: Depth},
+    // An auto rule is applied after every rewrite until it no longer changes the expression
+    DefineStmt {iden: String, params: Vec<String>, left: Expr, right: Expr, tags: Vec<String>, auto: bool}, 
     ExprStmt(Expr),
-    ApplyStmt { iden: String, depth: usize },
-    EndStmt(Option<String>)
+    ApplyStmt { strategy: Strategy, depth: Depth },
+    EndStmt { path: Option<String>, append: bool, format: Option<String>, binding: Option<Binding> },
+    // Without a right expression, the proof searches for an expression that matches the goal
+    ProveStmt { left: Expr, right: Option<Expr>, rules: Vec<String> },
+    SetStmt(Setting),
+    AllowStmt(Vec<String>),
+    ShowStmt(String),
+    LetStmt { iden: String, expr: Expr },
+    PreviewStmt { strategy: Strategy, depth: Depth },
+    CheckpointStmt(String),
+    GotoStmt(String),
+    RollbackStmt(usize),
+    RedoStmt,
+    ContextStmt(String),
+    SwitchStmt(String),
+    EqualStmt { left: String, right: String },
+    FlattenStmt(String),
+    UnflattenStmt(String),
+    CommutativeStmt(Vec<String>),
+    CanonicalizeStmt,
+    CheckTerminationStmt(Vec<String>),
+    OverlapsStmt(Vec<String>),
+    UnifyStmt { left: Expr, right: Expr },
+    ReplaceStmt { old: Expr, new: Expr },
+    FindStmt(Expr),
+    // The shape that a derivation aims for, where 'goal off' is None
+    GoalStmt(Option<Expr>),
+    // Paths of rule libraries, which are written and read in the versioned '.rxlib' format
+    ExportRulesStmt(String),
+    ImportStmt(String),
+    FocusStmt(Vec<usize>),
+    UnfocusStmt,
+    SilentStmt(Vec<Stmt>),
+    CheckStmt(Expr),
+    ExpectStmt(Expr),
+    FunctorStmt(Vec<(String, usize)>),
+    InfixStmt(Operator),
+    OperatorStmt(Operator),
+}
+
+impl Stmt {
+    // The expressions that occur in the statement, excluding the arguments of strategies
+    pub fn exprs(&self) -> Vec<&Expr> {
+        match self {
+            Stmt::RuleStmt { left, right, .. } |
+            Stmt::DefineStmt { left, right, .. } |
+            Stmt::UnifyStmt { left, right } => vec![left, right],
+            Stmt::ProveStmt { left, right, .. } => std::iter::once(left).chain(right).collect(),
+            Stmt::GoalStmt(goal) => goal.iter().collect(),
+            Stmt::ReplaceStmt { old, new } => vec![old, new],
+            Stmt::ExprStmt(expr) |
+            Stmt::LetStmt { expr, .. } |
+            Stmt::CheckStmt(expr) |
+            Stmt::FindStmt(expr) |
+            Stmt::ExpectStmt(expr) => vec![expr],
+            _ => vec![]
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum Setting {
+    MaxDepth(usize),
+    MaxNodes(usize),
+    Prompt(String),
+    // Text before the proof of a derivation that is written in the lean format
+    Preamble(String),
+    Verbosity(Verbosity),
+    // Whether every match attempt of a rule application is printed
+    Trace(bool),
+    // Maximum duration of a statement, or None if it is unlimited
+    Timeout(Option<Duration>),
+}
+
+// How much of the interpretation is printed
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Copy, Default)]
+pub enum Verbosity {
+    // Only the start and result of each pattern matching context
+    Quiet,
+    // Also every intermediate expression, in functor notation if it has operators, and warnings
+    #[default]
+    Normal,
+    // Also the rule that derived each intermediate expression
+    Verbose,
+}
+
+impl Verbosity {
+    pub const NAMES: [&'static str; 3] = ["quiet", "normal", "verbose"];
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "quiet" => Some(Verbosity::Quiet),
+            "normal" => Some(Verbosity::Normal),
+            "verbose" => Some(Verbosity::Verbose),
+            _ => None
+        }
+    }
+}
+
+// Depth at which a rule is applied, where the entire expression is at depth 0
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Depth {
+    At(usize),
+    // at any: the first depth from the top where the rule matches
+    Any,
+    // at N..M: every depth from N up to and including M, from the top
+    Range(usize, usize),
+    // innermost: every sub-expression after its arguments, from the bottom
+    Innermost,
+    // once: only the outermost-leftmost sub-expression that matches
+    Once,
+}
+
+impl Display for Depth {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Depth::At(n) => write!(f, "{}", n),
+            Depth::Any => write!(f, "any"),
+            Depth::Range(start, end) => write!(f, "{}..{}", start, end),
+            Depth::Innermost => write!(f, "innermost"),
+            Depth::Once => write!(f, "once"),
+        }
+    }
+}
+
+impl Depth {
+    // The depth that is written as a name, e.g., in the json format, which is the inverse of Display
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "any" => Some(Depth::Any),
+            "innermost" => Some(Depth::Innermost),
+            "once" => Some(Depth::Once),
+            _ => match name.split_once("..") {
+                Some((start, end)) => match (start.parse().ok()?, end.parse().ok()?) {
+                    (start, end) if start <= end => Some(Depth::Range(start, end)),
+                    _ => None,
+                },
+                None => name.parse().ok().map(Depth::At),
+            }
+        }
+    }
+
+    // Innermost and single-step rewriting are written instead of a depth, rather than after the at-keyword
+    pub fn to_source(&self) -> String {
+        match self {
+            Depth::Innermost | Depth::Once => self.to_string(),
+            depth => format!("at {}", depth),
+        }
+    }
+}
+
+// What the result of a pattern matching context is kept as, by 'end as ...'
+#[derive(Debug, PartialEq, Clone)]
+pub enum Binding {
+    // end as NAME: the resulting expression, like a let statement
+    Expr(String),
+    // end as rule NAME: the rule from the initial to the resulting expression
+    Rule(String),
+}
+
+// Strategies combine predefined rules into a single apply statement.
+#[derive(Debug, PartialEq, Clone)]
+pub enum Strategy {
+    // A predefined rule, with arguments for its meta-parameters
+    Rule { iden: String, args: Vec<Expr> },
+    // try s: apply s, but succeed even if s fails
+    Try(Box<Strategy>),
+    // s1; s2: apply s1 and then s2
+    Sequence(Vec<Strategy>),
+    // s1 | s2: apply s1, or s2 if s1 fails
+    Choice(Vec<Strategy>),
+    // s*: apply s until it fails
+    Repeat(Box<Strategy>),
+    // tagged t: apply the first rule with tag t that changes the expression, in the order of the rule identifiers
+    Tagged(String),
+}
+
+// Syntax of expressions. Statements have the same syntax in both cases.
+#[derive(Debug, PartialEq, Clone, Copy, Default)]
+pub enum Syntax {
+    // Functors with infix arithmetic operators, e.g., f(g(x), y) + z
+    #[default]
+    Functor,
+    // S-expressions, where the head of a list is the functor identifier, e.g., (add (f (g x) y) z)
+    SExpr,
+}
+
+impl Syntax {
+    // The source uses S-expressions if a list starts with an identifier or operator that is 
+    // followed by an argument, e.g., '(f x' or '(+', which is invalid in functor syntax.
+    pub fn detect(lexer: &Lexer) -> Syntax {
+        let is_sexpr = lexer.tokens.windows(3).any(|tokens| matches!(tokens, 
+            [Token::OpenParen, Token::Identifier(_), Token::Identifier(_) | Token::Number(_)] |
+            [Token::OpenParen, Token::Add | Token::Sub | Token::Mul | Token::Div, _]
+        ));
+        if is_sexpr {
+            Syntax::SExpr
+        } else {
+            Syntax::Functor
+        }
+    }
 }
 
 #[derive(Debug)]
 pub struct Parser {
     pub stmts: Vec<Stmt>,
+    // Location of each statement in the source
+    pub spans: Vec<Span>,
+    pub syntax: Syntax,
+    // If true, number literals are parsed as variables like before numbers were constants, 
+    // such that old rules where numbers match any expression keep working
+    pub numbers_as_variables: bool,
+    // Infix operators of the expressions, which infix statements add to for the statements after them
+    pub operators: Operators,
 }
 
 macro_rules! expect {
@@ -71,404 +274,1989 @@ macro_rules! expect {
     };
 }
 
-impl Display for Expr {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        match self {
-            Expr::Variable { iden } => write!(f, "{}", iden),
-            Expr::Functor { iden, args }  => {
-                write!(f, "{}(", iden)?;
-                for (i, arg) in args.iter().enumerate() {
-                    write!(f, "{}", arg)?;
-                    if i < args.len() - 1 {
-                        write!(f, ", ")?;
-                    }
-                }
-                write!(f, ")")?;
-
-                Ok(())
-            }
-        }
-    }
-}
-
-impl Expr {
-    pub fn to_string(&self) -> String {
-        match self {
-            Expr::Variable { iden } => format!("{}", iden),
-            Expr::Functor { iden, args }  => {
-                let mut res = String::new();
-                if let (Some(op), 2) = (Self::get_binary_operator_str(iden.as_str()), args.len()) {
-                    res.push_str(&format!("{} {} {}", &args[0].to_string(), op, &args[1].to_string()));
-                } else {
-                    if iden.as_str() == "group" {
-                        res.push('(');
-                    } else {
-                        res.push_str(&format!("{}(", iden));
-                    }
-    
-                    for (i, arg) in args.iter().enumerate() {
-                        res.push_str(&arg.to_string());
-                        if i < args.len() - 1 {
-                            res.push_str(&", ");
-                        }
-                    }
-                    res.push(')');
-                }
-                
-                res
-            }
-        }
-    }
-
-    pub fn get_binary_operator_str(iden: &str) -> Option<&str> {
-        match iden {
-            "add" => Some("+"),
-            "sub" => Some("-"),
-            "mul" => Some("*"),
-            "div" => Some("/"),
-            _ => None
-        }
+impl Default for Parser {
+    fn default() -> Self {
+        Self::new()
     }
 }
 
 impl Parser{
     pub fn new() -> Self {
-        Self { stmts: vec![] }
+        Self { stmts: vec![], spans: vec![], syntax: Syntax::Functor, numbers_as_variables: false, operators: Operators::default() }
+    }
+
+    pub fn with_syntax(syntax: Syntax) -> Self {
+        Self { syntax, ..Self::new() }
     }
 
+    // On a parsing error, the lexer is left at the offending token, 
+    // such that the location of the error is given by lexer.span(0).
     pub fn parse(&mut self, lexer: &mut Lexer) -> Result<(), Box<dyn Error>>{
         lexer.reset_iter();
         
         while !lexer.is_at_end() {
-            match lexer.peek(0) {
-                Some(Token::Define) => { self.parse_definition(lexer)?; },
-                Some(Token::End) => { self.parse_end_stmt(lexer)?; },
-                Some(Token::Apply) => { self.parse_apply_stmt(lexer)?; },
-                Some(_) => { self.parse_rule(lexer)?; },
-                _ => unreachable!()
+            self.parse_stmt(lexer)?;
+        }
+        Ok(())
+    }
+
+    // Parses the next statement, and records the span from its first to its last token
+    fn parse_stmt(&mut self, lexer: &mut Lexer) -> Result<(), Box<dyn Error>> {
+        let start = lexer.span(0);
+        match lexer.peek(0) {
+            Some(Token::Define) => { self.parse_definition(lexer)?; },
+            Some(Token::End) => { self.parse_end_stmt(lexer)?; },
+            Some(Token::Apply) => { self.parse_apply_stmt(lexer)?; },
+            Some(Token::Prove) => { self.parse_prove_stmt(lexer)?; },
+            Some(Token::Unify) => { self.parse_unify_stmt(lexer)?; },
+            Some(Token::Replace) => { self.parse_replace_stmt(lexer)?; },
+            Some(Token::Find) => { self.parse_find_stmt(lexer)?; },
+            Some(Token::Goal) => { self.parse_goal_stmt(lexer)?; },
+            Some(Token::Export) | Some(Token::Import) => { self.parse_library_stmt(lexer)?; },
+            Some(Token::Focus) => { self.parse_focus_stmt(lexer)?; },
+            Some(Token::Unfocus) => {
+                lexer.next();
+                self.stmts.push(Stmt::UnfocusStmt);
+            },
+            Some(Token::Set) => { self.parse_set_stmt(lexer)?; },
+            Some(Token::Allow) => { self.parse_allow_stmt(lexer)?; },
+            Some(Token::Show) => { self.parse_show_stmt(lexer)?; },
+            Some(Token::Let) => { self.parse_let_stmt(lexer)?; },
+            Some(Token::Preview) => { self.parse_preview_stmt(lexer)?; },
+            Some(Token::Checkpoint) | Some(Token::Goto) => { self.parse_checkpoint_stmt(lexer)?; },
+            Some(Token::Rollback) => { self.parse_rollback_stmt(lexer)?; },
+            Some(Token::Context) | Some(Token::Switch) | Some(Token::EqualQuery) => { self.parse_context_stmt(lexer)?; },
+            Some(Token::Redo) => {
+                lexer.next();
+                self.stmts.push(Stmt::RedoStmt);
+            },
+            Some(Token::Check) | Some(Token::Expect) => { self.parse_check_stmt(lexer)?; },
+            Some(Token::Functor) => { self.parse_functor_stmt(lexer)?; },
+            Some(Token::Infix) => { self.parse_infix_stmt(lexer)?; },
+            Some(Token::Operator) => { self.parse_operator_stmt(lexer)?; },
+            Some(Token::Flatten) | Some(Token::Unflatten) => { self.parse_flatten_stmt(lexer)?; },
+            Some(Token::Commutative) => { self.parse_commutative_stmt(lexer)?; },
+            Some(Token::CheckTermination) | Some(Token::Overlaps) => { self.parse_rule_analysis_stmt(lexer)?; },
+            Some(Token::Silent) => { self.parse_silent_block(lexer)?; },
+            Some(Token::Canonicalize) => { 
+                lexer.next();
+                self.stmts.push(Stmt::CanonicalizeStmt); 
+            },
+            Some(_) => { self.parse_rule(lexer)?; },
+            _ => unreachable!()
+        }
+        self.spans.push(start.to(lexer.prev_span()));
+        Ok(())
+    }
+
+    // Parses the statements between braces, e.g., 'silent { apply swap at 0 apply swap at 1 }'
+    fn parse_silent_block(&mut self, lexer: &mut Lexer) -> Result<(), Box<dyn Error>> {
+
+        lexer.next();
+        expect!(Token::OpenBrace, lexer)?;
+        lexer.next();
+        let num_stmts = self.stmts.len();
+        while !matches!(lexer.peek(0), Some(Token::CloseBrace)) {
+            let res = if lexer.is_at_end() {
+                Err(Box::new(ParsingError::ExpectToken { 
+                    expected: Token::CloseBrace.to_string(), 
+                    got: None 
+                }).into())
+            } else {
+                self.parse_stmt(lexer)
+            };
+            // An unfinished block is not interpreted at all, rather than interpreting its statements loudly
+            if res.is_err() {
+                self.stmts.truncate(num_stmts);
+                self.spans.truncate(num_stmts);
+                return res;
             }
         }
+        lexer.next();
+        // The block is reported as a whole, so the spans of its statements are dropped
+        self.spans.truncate(num_stmts);
+        let stmts = self.stmts.split_off(num_stmts);
+        self.stmts.push(Stmt::SilentStmt(stmts));
         Ok(())
     }
 
     fn parse_apply_stmt(&mut self, lexer: &mut Lexer) -> Result<(), Box<dyn Error>> {
     
         lexer.next();
-        expect!(Token::Identifier(_), "identifier".to_string(), lexer)?;
-        let Token::Identifier(iden) = lexer.next().unwrap().clone() else { unreachable!(); };
-        expect!(Token::At, lexer)?;
+        let strategy = self.parse_strategy(lexer)?;
+        let depth = self.parse_depth(lexer)?;
+        self.stmts.push(Stmt::ApplyStmt { strategy, depth });
+        Ok(())
+    }
+
+    // Parses a strategy that is applied without changing the current expression, e.g., 'preview swap at 0'
+    fn parse_preview_stmt(&mut self, lexer: &mut Lexer) -> Result<(), Box<dyn Error>> {
+    
         lexer.next();
-        if let Some(Token::Number(n)) = lexer.peek(0){
-            self.stmts.push(
-                Stmt::ApplyStmt { 
-                    iden, 
-                    depth: *n
-                }
-            );
-            lexer.next();
-            Ok(())
+        let strategy = self.parse_strategy(lexer)?;
+        let depth = self.parse_depth(lexer)?;
+        self.stmts.push(Stmt::PreviewStmt { strategy, depth });
+        Ok(())
+    }
+
+    // Parses 'checkpoint NAME' and 'goto NAME', which save and restore a derivation
+    fn parse_checkpoint_stmt(&mut self, lexer: &mut Lexer) -> Result<(), Box<dyn Error>> {
+
+        let keyword = lexer.next().unwrap().clone();
+        expect!(Token::Identifier(_), "checkpoint name".to_string(), lexer)?;
+        let Token::Identifier(name) = lexer.next().unwrap().clone() else { unreachable!(); };
+        if keyword == Token::Checkpoint {
+            self.stmts.push(Stmt::CheckpointStmt(name));
         } else {
-            Err(Box::new(ParsingError::ExpectDepthValue))
+            self.stmts.push(Stmt::GotoStmt(name));
         }
+        Ok(())
     }
 
-    fn parse_end_stmt(&mut self, lexer: &mut Lexer) -> Result<(), Box<dyn Error>> {
-        
+    // Parses 'rollback N', which truncates the derivation to step N
+    fn parse_rollback_stmt(&mut self, lexer: &mut Lexer) -> Result<(), Box<dyn Error>> {
+
         lexer.next();
-        let path = if let Some(Token::Path(s)) = lexer.peek(0) {
-            let res = Some(s.to_owned());
-            lexer.next();
-            res
-        } else {
-            None
+        let Some(step) = lexer.peek(0).and_then(Self::as_usize) else {
+            return Err(Box::new(ParsingError::ExpectToken { 
+                expected: "step number".to_string(), 
+                got: lexer.peek(0).map(|tok| tok.to_string()) 
+            }));
         };
-        self.stmts.push(Stmt::EndStmt(path));
+        lexer.next();
+        self.stmts.push(Stmt::RollbackStmt(step));
         Ok(())
     }
 
-    fn parse_definition(&mut self, lexer: &mut Lexer) -> Result<(), Box<dyn Error>>{
-        lexer.next();
+    // Parses 'context NAME' and 'switch NAME', which start and resume a named pattern matching context, 
+    // and 'equal? NAME, NAME', which compares the current expressions of two contexts
+    fn parse_context_stmt(&mut self, lexer: &mut Lexer) -> Result<(), Box<dyn Error>> {
+
+        let keyword = lexer.next().unwrap().clone();
+        expect!(Token::Identifier(_), "context name".to_string(), lexer)?;
+        let Token::Identifier(name) = lexer.next().unwrap().clone() else { unreachable!(); };
+        match keyword {
+            Token::Context => self.stmts.push(Stmt::ContextStmt(name)),
+            Token::Switch => self.stmts.push(Stmt::SwitchStmt(name)),
+            _ => {
+                expect!(Token::Comma, lexer)?;
+                lexer.next();
+                expect!(Token::Identifier(_), "context name".to_string(), lexer)?;
+                let Token::Identifier(right) = lexer.next().unwrap().clone() else { unreachable!(); };
+                self.stmts.push(Stmt::EqualStmt { left: name, right });
+            }
+        }
+        Ok(())
+    }
+
+    // Parses a functor identifier after the given token, where the arithmetic operators 
+    // are the identifiers add, sub, mul and div, e.g., 'flatten add' or 'flatten +'
+    fn parse_functor_iden(lexer: &mut Lexer, after: &Token) -> Result<String, Box<dyn Error>> {
         let iden = match lexer.peek(0) {
-            Some(Token::Identifier(s)) => s.as_str().to_owned(),
-            Some(tok) => return Err(
-                Box::new(ParsingError::ExpectTokenAfter { 
-                    expected: "identifier".to_string(), 
-                    after: Token::Define.to_string(), 
-                    got: Some(tok.to_string()) 
-                })),
-            None => return Err(Box::new(ParsingError::ExpectTokenAfter { 
-                expected: "identifier".to_string(), 
-                after: Token::Define.to_string(), 
-                got: None
+            Some(Token::Identifier(s)) => s.to_owned(),
+            Some(tok @ (Token::Add | Token::Sub | Token::Mul | Token::Div)) => tok.to_string(),
+            tok => return Err(Box::new(ParsingError::ExpectTokenAfter { 
+                expected: "functor identifier".to_string(), 
+                after: after.to_string(), 
+                got: tok.map(|tok| tok.to_string()) 
             }))
         };
         lexer.next();
-        expect!(Token::As, lexer)?;
-        lexer.next();
+        Ok(iden)
+    }
 
-        let left= self.parse_term(lexer)?;
-        expect!(Token::Derive, lexer)?;
-        lexer.next();
-        let right= self.parse_term(lexer)?;
-        
-        self.stmts.push(Stmt::DefineStmt { 
-            iden, 
-            left, 
-            right
-        });
+    fn parse_flatten_stmt(&mut self, lexer: &mut Lexer) -> Result<(), Box<dyn Error>> {
 
+        let keyword = lexer.next().unwrap().clone();
+        let iden = Self::parse_functor_iden(lexer, &keyword)?;
+        if keyword == Token::Flatten {
+            self.stmts.push(Stmt::FlattenStmt(iden));
+        } else {
+            self.stmts.push(Stmt::UnflattenStmt(iden));
+        }
         Ok(())
     }
 
-    fn parse_rule(&mut self, lexer: &mut Lexer) -> Result<(), Box<dyn Error>> {
-        
-        let left = self.parse_term(lexer)?;
-        if let Some(Token::Derive) = lexer.peek(0) {
-            lexer.next();
-            let right = self.parse_term(lexer)?;
-            expect!(Token::At, lexer)?;
+    // Parses a comma-separated list of functors whose arguments can be ordered arbitrarily, e.g., 'commutative add, mul'
+    fn parse_commutative_stmt(&mut self, lexer: &mut Lexer) -> Result<(), Box<dyn Error>> {
+
+        let keyword = lexer.next().unwrap().clone();
+        let mut idens = vec![Self::parse_functor_iden(lexer, &keyword)?];
+        while let Some(Token::Comma) = lexer.peek(0) {
             lexer.next();
-            if let Some(Token::Number(n)) = lexer.peek(0){
-                self.stmts.push(Stmt::RuleStmt {
-                    left, 
-                    right,
-                    depth: *n 
-                });
+            idens.push(Self::parse_functor_iden(lexer, &Token::Comma)?);
+        }
+        self.stmts.push(Stmt::CommutativeStmt(idens));
+        Ok(())
+    }
+
+    // Parses a comma-separated list of rules to analyze, e.g., 'check-termination distribute, collect' or 'overlaps distribute, collect'
+    fn parse_rule_analysis_stmt(&mut self, lexer: &mut Lexer) -> Result<(), Box<dyn Error>> {
+
+        let keyword = lexer.next().unwrap().clone();
+        let mut idens = vec![];
+        loop {
+            expect!(Token::Identifier(_), "rule identifier".to_string(), lexer)?;
+            let Token::Identifier(iden) = lexer.next().unwrap().clone() else { unreachable!(); };
+            idens.push(iden);
+            if let Some(Token::Comma) = lexer.peek(0) {
                 lexer.next();
-                Ok(())
             } else {
-                Err(Box::new(ParsingError::ExpectDepthValue))
+                break;
             }
+        }
+        if keyword == Token::CheckTermination {
+            self.stmts.push(Stmt::CheckTerminationStmt(idens));
         } else {
-            self.stmts.push(Stmt::ExprStmt(left));
-            Ok(())
+            self.stmts.push(Stmt::OverlapsStmt(idens));
         }
+        Ok(())
     }
 
-    fn parse_term(&mut self, lexer: &mut Lexer) -> Result<Expr, Box<dyn Error>> {
-        let mut left = self.parse_factor(lexer)?;
-
-        while let Some(Token::Add) | Some(Token::Sub) = lexer.peek(0) {
-            let op = lexer.next().unwrap().clone();
-            let right = self.parse_factor(lexer)?;
-            left = Expr::Functor{
-                iden: op.to_string(),
-                args: vec![left, right]
-            };    
-        } 
-        Ok(left)
-    }
-
-    fn parse_factor(&mut self, lexer: &mut Lexer) -> Result<Expr, Box<dyn Error>> {
-        let mut left = self.parse_expr(lexer)?;
+    // Parses the expression that the current expression is compared with, e.g., 'check g(x, x)' or 'expect g(x, x)'
+    fn parse_check_stmt(&mut self, lexer: &mut Lexer) -> Result<(), Box<dyn Error>> {
 
-        while let Some(Token::Mul) | Some(Token::Div) = lexer.peek(0) {
-            let op = lexer.next().unwrap().clone();
-            let right = self.parse_expr(lexer)?;
-            left = Expr::Functor{
-                iden: op.to_string(),
-                args: vec![left, right]
-            };    
-        } 
-        Ok(left)
+        let keyword = lexer.next().unwrap().clone();
+        let expr = self.parse_term(lexer)?;
+        if keyword == Token::Check {
+            self.stmts.push(Stmt::CheckStmt(expr));
+        } else {
+            self.stmts.push(Stmt::ExpectStmt(expr));
+        }
+        Ok(())
     }
 
-    fn parse_expr(&mut self, lexer: &mut Lexer) -> Result<Expr, Box<dyn Error>> {
+    // Parses the arity declarations of functors, e.g., 'functor add/2, neg/1'
+    fn parse_functor_stmt(&mut self, lexer: &mut Lexer) -> Result<(), Box<dyn Error>> {
 
-        match lexer.peek(0) {
-            Some(Token::OpenParen) => {
-                // group
-                let args = self.parse_functor_args(lexer)?;
-                Ok(Expr::Functor { 
-                    iden: "group".to_string(), 
-                    args
-                })
-            },
-            Some(Token::Identifier(s)) => {
-                let iden = s.to_owned();
-                lexer.next();
-                if let Some(Token::OpenParen) = lexer.peek(0) {
-                    
-                    let args = self.parse_functor_args(lexer)?;
-                    Ok(Expr::Functor { 
-                        iden, 
-                        args
-                    })
-                } else {
-                    // let depth = if let Some(Token::At) = lexer.peek(0) {
-                    //     lexer.next();
-                    //     match lexer.peek(0) {
-                    //         Some(Token::Number(n)) => {
-                    //             let depth = Some(*n);
-                    //             lexer.next();
-                    //             depth
-                    //         },
-                    //         Some(tok) => {
-                    //             return Err(Box::new(ParsingError::ExpectTokenAfter { 
-                    //                 expected: "number".to_string(), 
-                    //                 after: Token::At.to_string(), 
-                    //                 got: Some(tok.to_string())
-                    //             }));
-                    //         },
-                    //         None => {
-                    //             return Err(Box::new(ParsingError::ExpectTokenAfter { 
-                    //                 expected: "number".to_string(), 
-                    //                 after: Token::At.to_string(), 
-                    //                 got: None
-                    //             }));
-                    //         }
-                    //     }
-                    // } else {
-                    //     None
-                    // };
-                    Ok(Expr::Variable { iden })
-                }
-            },
-            Some(Token::Number(n)) => {
-                let res = Ok(Expr::Variable { iden: n.to_string() });
+        lexer.next();
+        let mut arities = vec![];
+        loop {
+            expect!(Token::Identifier(_), "functor identifier".to_string(), lexer)?;
+            let Token::Identifier(iden) = lexer.next().unwrap().clone() else { unreachable!(); };
+            expect!(Token::Div, lexer)?;
+            lexer.next();
+            let Some(arity) = lexer.peek(0).and_then(Self::as_usize) else {
+                return Err(Box::new(ParsingError::ExpectToken { 
+                    expected: "arity".to_string(), 
+                    got: lexer.peek(0).map(|tok| tok.to_string()) 
+                }));
+            };
+            lexer.next();
+            arities.push((iden, arity));
+            if let Some(Token::Comma) = lexer.peek(0) {
                 lexer.next();
-                res
+            } else {
+                break;
             }
-            Some(tok) => Err(Box::new(ParsingError::UnexpectedToken { 
-                got: Some(tok.to_string()) 
-            })),
-            None => Err(Box::new(ParsingError::UnexpectedToken { 
-                got: None
-            }))
         }
+
+        self.stmts.push(Stmt::FunctorStmt(arities));
+        Ok(())
     }
 
-    fn parse_functor_args(&mut self, lexer: &mut Lexer) -> Result<Vec<Expr>, Box<dyn Error>> {
+    // Parses the definition of an infix operator for a binary functor, e.g., 'infix ⊕ as oplus precedence 4'.
+    // An operator whose symbol or functor is taken is still parsed, and is ignored with a warning at runtime.
+    fn parse_infix_stmt(&mut self, lexer: &mut Lexer) -> Result<(), Box<dyn Error>> {
+
         lexer.next();
-        let mut args = vec![];
-        loop {
-            match lexer.peek(0) {
-                Some(Token::CloseParen) => {
-                    lexer.next();
-                    break;
-                },
-                _ => {
-                    args.push(self.parse_term(lexer)?);
-                    if let Some(Token::Comma) = lexer.peek(0) {
-                        lexer.next();
-                    }
-                }
-            }
+        let symbol = match lexer.peek(0) {
+            Some(Token::Identifier(symbol)) => symbol.clone(),
+            Some(tok @ (Token::Add | Token::Sub | Token::Mul | Token::Div)) => {
+                Operators::arithmetic().by_iden(&tok.to_string()).unwrap().symbol.clone()
+            },
+            tok => return Err(Box::new(ParsingError::ExpectToken { 
+                expected: "operator symbol".to_string(), 
+                got: tok.map(|tok| tok.to_string()) 
+            }))
         };
-        Ok(args)
-    }   
-
-}
-
-#[cfg(test)]
-mod tests {
-    use super::*;
-
-    #[test]
-    fn parse_demorgan() {
-        let input_string = "def demorgan as neg(or(p, q)) => and(neg(p), neg(q))";
-        let mut lexer = Lexer::new();
-        lexer.lex(input_string);
-
-        dbg!(&lexer.tokens);
+        lexer.next();
+        expect!(Token::As, lexer)?;
+        lexer.next();
+        expect!(Token::Identifier(_), "functor identifier".to_string(), lexer)?;
+        let Token::Identifier(iden) = lexer.next().unwrap().clone() else { unreachable!(); };
+        let precedence = self.parse_precedence(lexer)?;
 
-        let mut parser = Parser::new();
-        let res = parser.parse(&mut lexer);
+        let op = Operator::new(symbol, iden, precedence);
+        let _ = self.operators.define(op.clone());
+        self.stmts.push(Stmt::InfixStmt(op));
+        Ok(())
+    }
 
-        assert!(!res.is_err());
-        assert!(parser.stmts.len() == 1);
+    // Parses the definition of an operator with a quoted symbol, which need not be an identifier, 
+    // and an optional associativity, e.g., 'operator "->" as implies, precedence 1, right'
+    fn parse_operator_stmt(&mut self, lexer: &mut Lexer) -> Result<(), Box<dyn Error>> {
 
-        let test_stmt = Stmt::DefineStmt { 
-            iden: "demorgan".to_string(), 
-            left: Expr::Functor { 
-                iden: "neg".to_string(), 
-                args: vec![
-                    Expr::Functor {
-                        iden: "or".to_string(), 
-                        args: vec![
-                            Expr::Variable { iden: "p".to_string() },
-                            Expr::Variable { iden: "q".to_string() }
-                        ]
-                    }
-                ] 
-            }, 
-            right: Expr::Functor { 
-                iden: "and".to_string(), 
-                args: vec![
-                    Expr::Functor {
-                        iden: "neg".to_string(), 
-                        args: vec![
-                            Expr::Variable { iden: "p".to_string() }
-                        ]
-                    },
-                    Expr::Functor {
-                        iden: "neg".to_string(), 
-                        args: vec![
-                            Expr::Variable { iden: "q".to_string() }
-                        ]
-                    },
-                ] 
-            }, 
+        lexer.next();
+        let symbol = match lexer.peek(0) {
+            Some(Token::Str(symbol)) if Operator::is_valid_symbol(symbol) => symbol.clone(),
+            tok => return Err(Box::new(ParsingError::ExpectToken { 
+                expected: "quoted operator symbol".to_string(), 
+                got: tok.map(|tok| tok.to_string()) 
+            }))
         };
-
+        lexer.next();
+        expect!(Token::As, lexer)?;
+        lexer.next();
+        expect!(Token::Identifier(_), "functor identifier".to_string(), lexer)?;
+        let Token::Identifier(iden) = lexer.next().unwrap().clone() else { unreachable!(); };
+        expect!(Token::Comma, lexer)?;
+        lexer.next();
+        let precedence = self.parse_precedence(lexer)?;
+        let mut op = Operator::new(symbol, iden, precedence);
+        if let Some(Token::Comma) = lexer.peek(0) {
+            lexer.next();
+            op.assoc = match lexer.peek(0) {
+                Some(Token::Identifier(name)) if Assoc::from_name(name).is_some() => Assoc::from_name(name).unwrap(),
+                tok => return Err(Box::new(ParsingError::ExpectToken { 
+                    expected: "'left', 'right' or 'nonassoc'".to_string(), 
+                    got: tok.map(|tok| tok.to_string()) 
+                }))
+            };
+            lexer.next();
+        }
+
+        let _ = self.operators.define(op.clone());
+        self.stmts.push(Stmt::OperatorStmt(op));
+        Ok(())
+    }
+
+    // Parses the precedence of an operator, e.g., 'precedence 4'
+    fn parse_precedence(&mut self, lexer: &mut Lexer) -> Result<u8, Box<dyn Error>> {
+        match lexer.peek(0) {
+            Some(Token::Identifier(s)) if s == "precedence" => { lexer.next(); },
+            tok => return Err(Box::new(ParsingError::ExpectToken { 
+                expected: "'precedence'".to_string(), 
+                got: tok.map(|tok| tok.to_string()) 
+            }))
+        }
+        let precedence = match lexer.peek(0).and_then(Self::as_usize) {
+            Some(n @ 1..=9) => n as u8,
+            _ => return Err(Box::new(ParsingError::ExpectToken { 
+                expected: format!("precedence from 1 to {}", Operators::MAX_PRECEDENCE), 
+                got: lexer.peek(0).map(|tok| tok.to_string()) 
+            }))
+        };
+        lexer.next();
+        Ok(precedence)
+    }
+
+    // Non-negative integers, which are used as depths, arities and settings
+    fn as_usize(tok: &Token) -> Option<usize> {
+        match tok {
+            Token::Number(n) => n.as_usize(),
+            _ => None
+        }
+    }
+
+    // A non-negative number of seconds or milliseconds, e.g., 1.5 s or 500 ms
+    fn as_duration(n: &Number, unit: &str) -> Option<Duration> {
+        let n = match n {
+            Number::Int(n) => *n as f64,
+            Number::Float(n) => *n,
+        };
+        match unit {
+            "s" => Duration::try_from_secs_f64(n).ok(),
+            "ms" => Duration::try_from_secs_f64(n / 1000.0).ok(),
+            _ => None
+        }
+    }
+
+    // A numbered capture is the variable $n, which cannot be written as an identifier, so it never collides with one
+    fn capture(n: usize) -> Expr {
+        Expr::variable(format!("${}", n))
+    }
+
+    fn number(&self, n: Number) -> Expr {
+        if self.numbers_as_variables {
+            Expr::variable(n.to_string())
+        } else {
+            Expr::number(n)
+        }
+    }
+
+    // Parses either a number, a range of numbers or 'any' after the at-keyword, or 'innermost' or 'once' instead of a depth
+    fn parse_depth(&mut self, lexer: &mut Lexer) -> Result<Depth, Box<dyn Error>> {
+        if let Some(Token::Identifier(s)) = lexer.peek(0) {
+            let depth = match s.as_str() {
+                "innermost" => Some(Depth::Innermost),
+                "once" => Some(Depth::Once),
+                _ => None
+            };
+            if let Some(depth) = depth {
+                lexer.next();
+                return Ok(depth);
+            }
+        }
+        expect!(Token::At, lexer)?;
+        lexer.next();
+        let depth = match (lexer.peek(0), lexer.peek(0).and_then(Self::as_usize)) {
+            (_, Some(start)) if lexer.peek(1) == Some(&Token::Ellipsis) => {
+                lexer.next();
+                lexer.next();
+                match lexer.peek(0).and_then(Self::as_usize) {
+                    Some(end) if start <= end => Depth::Range(start, end),
+                    Some(end) => return Err(Box::new(ParsingError::EmptyDepthRange { start, end })),
+                    None => return Err(Box::new(ParsingError::ExpectDepthValue)),
+                }
+            },
+            (_, Some(n)) => Depth::At(n),
+            (Some(Token::Identifier(s)), _) if s == "any" => Depth::Any,
+            _ => return Err(Box::new(ParsingError::ExpectDepthValue))
+        };
+        lexer.next();
+        Ok(depth)
+    }
+
+    // Choice has the lowest precedence, followed by sequencing, 'try' and repetition.
+    fn parse_strategy(&mut self, lexer: &mut Lexer) -> Result<Strategy, Box<dyn Error>> {
+        let mut alternatives = vec![self.parse_strategy_sequence(lexer)?];
+        while let Some(Token::Pipe) = lexer.peek(0) {
+            lexer.next();
+            alternatives.push(self.parse_strategy_sequence(lexer)?);
+        }
+
+        if alternatives.len() == 1 {
+            Ok(alternatives.pop().unwrap())
+        } else {
+            Ok(Strategy::Choice(alternatives))
+        }
+    }
+
+    fn parse_strategy_sequence(&mut self, lexer: &mut Lexer) -> Result<Strategy, Box<dyn Error>> {
+        let mut steps = vec![self.parse_strategy_unary(lexer)?];
+        // Steps are separated by ';', 'then' or both, e.g., 'a; b', 'a then b' or 'a; then b'
+        while let Some(Token::Semicolon) | Some(Token::Then) = lexer.peek(0) {
+            if let Some(Token::Semicolon) = lexer.next() {
+                if let Some(Token::Then) = lexer.peek(0) {
+                    lexer.next();
+                }
+            }
+            steps.push(self.parse_strategy_unary(lexer)?);
+        }
+
+        if steps.len() == 1 {
+            Ok(steps.pop().unwrap())
+        } else {
+            Ok(Strategy::Sequence(steps))
+        }
+    }
+
+    fn parse_strategy_unary(&mut self, lexer: &mut Lexer) -> Result<Strategy, Box<dyn Error>> {
+        if let Some(Token::Try) = lexer.peek(0) {
+            lexer.next();
+            let strategy = self.parse_strategy_unary(lexer)?;
+            return Ok(Strategy::Try(Box::new(strategy)));
+        }
+
+        let mut strategy = match lexer.peek(0) {
+            Some(Token::OpenParen) => {
+                lexer.next();
+                let strategy = self.parse_strategy(lexer)?;
+                expect!(Token::CloseParen, lexer)?;
+                lexer.next();
+                strategy
+            },
+            Some(Token::Identifier(s)) => {
+                let iden = s.to_owned();
+                lexer.next();
+                // Arguments that instantiate the meta-parameters of a rule schema
+                let args = if let Some(Token::OpenParen) = lexer.peek(0) {
+                    self.parse_functor_args(lexer, Token::CloseParen)?
+                } else {
+                    vec![]
+                };
+                Strategy::Rule { iden, args }
+            },
+            Some(Token::Tagged) => {
+                lexer.next();
+                expect!(Token::Identifier(_), "tag".to_string(), lexer)?;
+                let Token::Identifier(tag) = lexer.next().unwrap().clone() else { unreachable!(); };
+                Strategy::Tagged(tag)
+            },
+            Some(tok) => return Err(Box::new(ParsingError::ExpectTokenAfter { 
+                expected: "rule identifier or strategy".to_string(), 
+                after: Token::Apply.to_string(), 
+                got: Some(tok.to_string()) 
+            })),
+            None => return Err(Box::new(ParsingError::ExpectTokenAfter { 
+                expected: "rule identifier or strategy".to_string(), 
+                after: Token::Apply.to_string(), 
+                got: None 
+            })),
+        };
+
+        while let Some(Token::Mul) = lexer.peek(0) {
+            lexer.next();
+            strategy = Strategy::Repeat(Box::new(strategy));
+        }
+        Ok(strategy)
+    }
+
+    fn parse_prove_stmt(&mut self, lexer: &mut Lexer) -> Result<(), Box<dyn Error>> {
+        
+        lexer.next();
+        let left = self.parse_term(lexer)?;
+        expect!(Token::Equal, lexer)?;
+        lexer.next();
+        let right = match lexer.peek(0) {
+            Some(Token::Goal) => {
+                lexer.next();
+                None
+            },
+            _ => Some(self.parse_term(lexer)?),
+        };
+        
+        // Without a list of rules, all predefined rules are used.
+        let mut rules = vec![];
+        if let Some(Token::Using) = lexer.peek(0) {
+            lexer.next();
+            loop {
+                expect!(Token::Identifier(_), "rule identifier".to_string(), lexer)?;
+                let Token::Identifier(iden) = lexer.next().unwrap().clone() else { unreachable!(); };
+                rules.push(iden);
+                if let Some(Token::Comma) = lexer.peek(0) {
+                    lexer.next();
+                } else {
+                    break;
+                }
+            }
+        }
+
+        self.stmts.push(Stmt::ProveStmt { left, right, rules });
+        Ok(())
+    }
+
+    // Parses the two expressions to unify, e.g., 'unify f(x, g(y)) with f(g(z), x)'
+    fn parse_unify_stmt(&mut self, lexer: &mut Lexer) -> Result<(), Box<dyn Error>> {
+
+        lexer.next();
+        let left = self.parse_term(lexer)?;
+        expect!(Token::With, lexer)?;
+        lexer.next();
+        let right = self.parse_term(lexer)?;
+        self.stmts.push(Stmt::UnifyStmt { left, right });
+        Ok(())
+    }
+
+    // Parses the argument indices of the sub-expression to focus on, e.g., 'focus path 1.0'
+    fn parse_focus_stmt(&mut self, lexer: &mut Lexer) -> Result<(), Box<dyn Error>> {
+
+        lexer.next();
+        match lexer.peek(0) {
+            Some(Token::Identifier(s)) if s == "path" => { lexer.next(); },
+            tok => return Err(Box::new(ParsingError::ExpectToken { 
+                expected: "'path'".to_string(), 
+                got: tok.map(|tok| tok.to_string()) 
+            }))
+        }
+        expect!(Token::ArgPath(_), "argument indices separated by dots".to_string(), lexer)?;
+        let Token::ArgPath(path) = lexer.next().unwrap().clone() else { unreachable!(); };
+        self.stmts.push(Stmt::FocusStmt(path));
+        Ok(())
+    }
+
+    // Parses the sub-expression to replace literally and its replacement, e.g., 'replace f(x) with y'
+    fn parse_replace_stmt(&mut self, lexer: &mut Lexer) -> Result<(), Box<dyn Error>> {
+
+        lexer.next();
+        let old = self.parse_term(lexer)?;
+        expect!(Token::With, lexer)?;
+        lexer.next();
+        let new = self.parse_term(lexer)?;
+        self.stmts.push(Stmt::ReplaceStmt { old, new });
+        Ok(())
+    }
+
+    // Parses the template that derivations aim for, e.g., 'goal g(_, f(_))', or 'goal off'
+    fn parse_goal_stmt(&mut self, lexer: &mut Lexer) -> Result<(), Box<dyn Error>> {
+
+        lexer.next();
+        let goal = self.parse_term(lexer)?;
+        let off = Expr::variable("off");
+        self.stmts.push(Stmt::GoalStmt(Some(goal).filter(|goal| *goal != off)));
+        Ok(())
+    }
+
+    // Parses the path of a rule library to write or read, e.g., 'export rules "algebra.rxlib"' or 'import "algebra.rxlib"'
+    fn parse_library_stmt(&mut self, lexer: &mut Lexer) -> Result<(), Box<dyn Error>> {
+
+        let is_export = matches!(lexer.next(), Some(Token::Export));
+        if is_export {
+            match lexer.peek(0) {
+                Some(Token::Identifier(s)) if s == "rules" => { lexer.next(); },
+                tok => return Err(Box::new(ParsingError::ExpectToken { 
+                    expected: "'rules'".to_string(), 
+                    got: tok.map(|tok| tok.to_string()) 
+                }))
+            }
+        }
+        expect!(Token::Str(_), "path".to_string(), lexer)?;
+        let Token::Str(path) = lexer.next().unwrap().clone() else { unreachable!(); };
+        self.stmts.push(if is_export { Stmt::ExportRulesStmt(path) } else { Stmt::ImportStmt(path) });
+        Ok(())
+    }
+
+    // Parses the pattern to search for in the current expression, e.g., 'find f(x, _)'
+    fn parse_find_stmt(&mut self, lexer: &mut Lexer) -> Result<(), Box<dyn Error>> {
+
+        lexer.next();
+        let pattern = self.parse_term(lexer)?;
+        self.stmts.push(Stmt::FindStmt(pattern));
+        Ok(())
+    }
+
+    fn parse_set_stmt(&mut self, lexer: &mut Lexer) -> Result<(), Box<dyn Error>> {
+
+        lexer.next();
+        let name = match lexer.peek(0) {
+            Some(Token::Identifier(s)) if ["max_depth", "max_nodes", "prompt", "preamble", "verbosity", "trace", "timeout"].contains(&s.as_str()) => s.to_owned(),
+            tok => return Err(Box::new(ParsingError::ExpectTokenAfter { 
+                expected: "setting 'max_depth', 'max_nodes', 'prompt', 'preamble', 'verbosity', 'trace' or 'timeout'".to_string(), 
+                after: Token::Set.to_string(), 
+                got: tok.map(|tok| tok.to_string()) 
+            }))
+        };
+        lexer.next();
+
+        let value = lexer.peek(0);
+        let setting = match name.as_str() {
+            "max_depth" => value.and_then(Self::as_usize).map(Setting::MaxDepth),
+            "max_nodes" => value.and_then(Self::as_usize).map(Setting::MaxNodes),
+            "prompt" => match value {
+                Some(Token::Str(prompt)) => Some(Setting::Prompt(prompt.to_owned())),
+                _ => None
+            },
+            "preamble" => match value {
+                Some(Token::Str(preamble)) => Some(Setting::Preamble(preamble.to_owned())),
+                _ => None
+            },
+            "trace" => match value {
+                Some(Token::Identifier(s)) if s == "on" => Some(Setting::Trace(true)),
+                Some(Token::Identifier(s)) if s == "off" => Some(Setting::Trace(false)),
+                _ => None
+            },
+            "timeout" => match (value, lexer.peek(1)) {
+                (Some(Token::Identifier(s)), _) if s == "off" => Some(Setting::Timeout(None)),
+                (Some(Token::Number(n)), Some(Token::Identifier(unit))) => Self::as_duration(n, unit).map(|timeout| Setting::Timeout(Some(timeout))),
+                _ => None
+            },
+            _ => match value {
+                Some(Token::Identifier(level)) => Verbosity::from_name(level).map(Setting::Verbosity),
+                _ => None
+            },
+        };
+        let Some(setting) = setting else {
+            let expected = match name.as_str() {
+                "prompt" => "quoted prompt, e.g., \"> \"".to_string(),
+                "preamble" => "quoted preamble, e.g., \"import Mathlib\"".to_string(),
+                "verbosity" => format!("verbosity '{}'", Verbosity::NAMES.join("', '")),
+                "trace" => "'on' or 'off'".to_string(),
+                "timeout" => "duration, e.g., 5s or 500ms, or 'off'".to_string(),
+                _ => "non-negative integer".to_string()
+            };
+            return Err(Box::new(ParsingError::ExpectTokenAfter { 
+                expected, 
+                after: format!("setting '{}'", name), 
+                got: value.map(|tok| tok.to_string()) 
+            }));
+        };
+        // A duration is followed by its unit
+        if let Setting::Timeout(Some(_)) = setting {
+            lexer.next();
+        }
+        lexer.next();
+        self.stmts.push(Stmt::SetStmt(setting));
+        Ok(())
+    }
+
+    // Parses the warning codes to suppress, e.g., 'allow W001, W003'
+    fn parse_allow_stmt(&mut self, lexer: &mut Lexer) -> Result<(), Box<dyn Error>> {
+
+        lexer.next();
+        let mut codes = vec![];
+        loop {
+            expect!(Token::Identifier(_), "warning code".to_string(), lexer)?;
+            let Token::Identifier(code) = lexer.next().unwrap().clone() else { unreachable!(); };
+            codes.push(code);
+            if let Some(Token::Comma) = lexer.peek(0) {
+                lexer.next();
+            } else {
+                break;
+            }
+        }
+
+        self.stmts.push(Stmt::AllowStmt(codes));
+        Ok(())
+    }
+
+    // Parses the notation to print the current expression in, which is infix notation by default
+    fn parse_show_stmt(&mut self, lexer: &mut Lexer) -> Result<(), Box<dyn Error>> {
+
+        lexer.next();
+        let notation = if let Some(Token::As) = lexer.peek(0) {
+            lexer.next();
+            // The infix notation is named by the keyword of infix operators
+            if let Some(Token::Infix) = lexer.peek(0) {
+                lexer.next();
+                "infix".to_string()
+            } else {
+                expect!(Token::Identifier(_), "notation".to_string(), lexer)?;
+                let Token::Identifier(notation) = lexer.next().unwrap().clone() else { unreachable!(); };
+                notation
+            }
+        } else {
+            "infix".to_string()
+        };
+
+        self.stmts.push(Stmt::ShowStmt(notation));
+        Ok(())
+    }
+
+    // Parses a named expression, e.g., 'let e1 = integral(mul(x, x), x)'
+    fn parse_let_stmt(&mut self, lexer: &mut Lexer) -> Result<(), Box<dyn Error>> {
+
+        lexer.next();
+        expect!(Token::Identifier(_), "identifier".to_string(), lexer)?;
+        let Token::Identifier(iden) = lexer.next().unwrap().clone() else { unreachable!(); };
+        expect!(Token::Assign, lexer)?;
+        lexer.next();
+        let expr = self.parse_term(lexer)?;
+
+        self.stmts.push(Stmt::LetStmt { iden, expr });
+        Ok(())
+    }
+
+    fn parse_end_stmt(&mut self, lexer: &mut Lexer) -> Result<(), Box<dyn Error>> {
+        
+        lexer.next();
+        // The derivation is added to the end of the file instead of replacing it, e.g., end append "log.txt"
+        let append = matches!(
+            (lexer.peek(0), lexer.peek(1)), 
+            (Some(Token::Identifier(s)), Some(Token::Str(_))) if s == "append"
+        );
+        if append {
+            lexer.next();
+        }
+        let path = if let Some(Token::Str(s)) = lexer.peek(0) {
+            let res = Some(s.to_owned());
+            lexer.next();
+            res
+        } else {
+            None
+        };
+        // The format of the written derivation, e.g., end "path/to/file.md" as markdown
+        let format = if let (Some(_), Some(Token::As)) = (&path, lexer.peek(0)) {
+            lexer.next();
+            expect!(Token::Identifier(_), "format".to_string(), lexer)?;
+            let Token::Identifier(format) = lexer.next().unwrap().clone() else { unreachable!(); };
+            Some(format)
+        } else {
+            None
+        };
+        // The name the result is bound to, e.g., end as lemma1 or end as rule lemma1
+        let binding = if let Some(Token::As) = lexer.peek(0) {
+            lexer.next();
+            expect!(Token::Identifier(_), "identifier".to_string(), lexer)?;
+            let is_rule = matches!(
+                (lexer.peek(0), lexer.peek(1)), 
+                (Some(Token::Identifier(s)), Some(Token::Identifier(_))) if s == "rule"
+            );
+            if is_rule {
+                lexer.next();
+            }
+            let Token::Identifier(iden) = lexer.next().unwrap().clone() else { unreachable!(); };
+            Some(if is_rule { Binding::Rule(iden) } else { Binding::Expr(iden) })
+        } else {
+            None
+        };
+        self.stmts.push(Stmt::EndStmt { path, append, format, binding });
+        Ok(())
+    }
+
+    fn parse_definition(&mut self, lexer: &mut Lexer) -> Result<(), Box<dyn Error>>{
+        lexer.next();
+        let iden = match lexer.peek(0) {
+            Some(Token::Identifier(s)) => s.as_str().to_owned(),
+            Some(tok) => return Err(
+                Box::new(ParsingError::ExpectTokenAfter { 
+                    expected: "identifier".to_string(), 
+                    after: Token::Define.to_string(), 
+                    got: Some(tok.to_string()) 
+                })),
+            None => return Err(Box::new(ParsingError::ExpectTokenAfter { 
+                expected: "identifier".to_string(), 
+                after: Token::Define.to_string(), 
+                got: None
+            }))
+        };
+        lexer.next();
+        let params = if let Some(Token::OpenParen) = lexer.peek(0) {
+            self.parse_rule_params(lexer)?
+        } else {
+            vec![]
+        };
+        expect!(Token::As, lexer)?;
+        lexer.next();
+
+        let left= self.parse_term(lexer)?;
+        expect!(Token::Derive, lexer)?;
+        lexer.next();
+        let right= self.parse_term(lexer)?;
+        Self::check_captures(&left, &right)?;
+        let tags = if let Some(Token::Tags) = lexer.peek(0) {
+            self.parse_rule_tags(lexer)?
+        } else {
+            vec![]
+        };
+        let auto = matches!(lexer.peek(0), Some(Token::Auto));
+        if auto {
+            lexer.next();
+            if !params.is_empty() {
+                return Err(Box::new(ParsingError::AutoRuleWithParams { iden }));
+            }
+        }
+        
+        self.stmts.push(Stmt::DefineStmt { 
+            iden, 
+            params,
+            left, 
+            right,
+            tags,
+            auto
+        });
+
+        Ok(())
+    }
+
+    // Parses the tags of a rule, e.g., the 'tags [algebra, ring]' in 'def r as LEFT_EXPR => RIGHT_EXPR tags [algebra, ring]'
+    fn parse_rule_tags(&mut self, lexer: &mut Lexer) -> Result<Vec<String>, Box<dyn Error>> {
+        lexer.next();
+        expect!(Token::OpenBracket, lexer)?;
+        lexer.next();
+        let mut tags = vec![];
+        loop {
+            expect!(Token::Identifier(_), "tag".to_string(), lexer)?;
+            let Token::Identifier(tag) = lexer.next().unwrap().clone() else { unreachable!(); };
+            tags.push(tag);
+            if let Some(Token::Comma) = lexer.peek(0) {
+                lexer.next();
+            } else {
+                break;
+            }
+        }
+        expect!(Token::CloseBracket, lexer)?;
+        lexer.next();
+        Ok(tags)
+    }
+
+    // Parses the meta-parameters of a rule schema, e.g., the (n) in 'def power_rule(n) as ...'
+    fn parse_rule_params(&mut self, lexer: &mut Lexer) -> Result<Vec<String>, Box<dyn Error>> {
+        lexer.next();
+        let mut params = vec![];
+        loop {
+            match lexer.peek(0) {
+                Some(Token::CloseParen) => {
+                    lexer.next();
+                    break;
+                },
+                Some(Token::Identifier(s)) => {
+                    params.push(s.to_owned());
+                    lexer.next();
+                    if let Some(Token::Comma) = lexer.peek(0) {
+                        lexer.next();
+                    }
+                },
+                Some(tok) => return Err(Box::new(ParsingError::ExpectToken { 
+                    expected: "identifier as rule parameter".to_string(), 
+                    got: Some(tok.to_string()) 
+                })),
+                None => return Err(Box::new(ParsingError::ExpectToken { 
+                    expected: Token::CloseParen.to_string(), 
+                    got: None 
+                })),
+            }
+        }
+        Ok(params)
+    }
+
+    fn parse_rule(&mut self, lexer: &mut Lexer) -> Result<(), Box<dyn Error>> {
+        
+        let left = self.parse_term(lexer)?;
+        // Depths belong to rules, as an expression statement only starts a pattern matching context
+        if let Some(Token::At) = lexer.peek(0) {
+            return Err(Box::new(ParsingError::UnexpectedDepth { expr: left.to_string() }));
+        }
+        if let Some(Token::Derive) = lexer.peek(0) {
+            lexer.next();
+            let right = self.parse_term(lexer)?;
+            Self::check_captures(&left, &right)?;
+            let depth = self.parse_depth(lexer)?;
+            self.stmts.push(Stmt::RuleStmt { left, right, depth });
+            Ok(())
+        } else {
+            self.stmts.push(Stmt::ExprStmt(left));
+            Ok(())
+        }
+    }
+
+    // Captures are only bound by matching the left expression, unlike other variables of the right expression,
+    // which are kept as they are, e.g., the y in f(x) => g(x, y)
+    fn check_captures(left: &Expr, right: &Expr) -> Result<(), Box<dyn Error>> {
+        let bound = left.variables();
+        match right.variables().into_iter().find(|var| var.starts_with('$') && !bound.contains(var)) {
+            Some(capture) => Err(Box::new(ParsingError::UnboundCapture { capture: capture.to_string(), left: left.to_string() })),
+            None => Ok(())
+        }
+    }
+
+    fn parse_term(&mut self, lexer: &mut Lexer) -> Result<Expr, Box<dyn Error>> {
+        if self.syntax == Syntax::SExpr {
+            return self.parse_sexpr(lexer);
+        }
+        self.parse_binary(lexer, 1)
+    }
+
+    // Parses the infix operators that bind at least as strong as the minimum precedence, by precedence climbing,
+    // where the right operand of a left-associative operator binds stronger than the operator itself.
+    fn parse_binary(&mut self, lexer: &mut Lexer, min_precedence: u8) -> Result<Expr, Box<dyn Error>> {
+        let mut left = self.parse_expr(lexer)?;
+        let mut prev_op: Option<Operator> = None;
+
+        while let Some(op) = lexer.peek(0).and_then(|tok| self.binary_operator(tok)) {
+            if op.precedence < min_precedence {
+                break;
+            }
+            // Operands of a non-associative operator cannot contain an operator of the same precedence without a group
+            if let Some(prev_op) = prev_op.filter(|prev_op| prev_op.precedence == op.precedence) {
+                if prev_op.assoc == Assoc::NonAssoc || op.assoc == Assoc::NonAssoc {
+                    let symbol = if op.assoc == Assoc::NonAssoc { op.symbol } else { prev_op.symbol };
+                    return Err(Box::new(ParsingError::NonAssociativeOperator { symbol }));
+                }
+            }
+            lexer.next();
+            let (_, right_precedence) = op.operand_precedences();
+            let right = self.parse_binary(lexer, right_precedence)?;
+            left = Expr::functor(op.iden.clone(), vec![left, right]);    
+            prev_op = Some(op);
+        } 
+        Ok(left)
+    }
+
+    // The operator of an arithmetic token, or of an identifier that is the symbol of a defined operator
+    fn binary_operator(&self, tok: &Token) -> Option<Operator> {
+        match tok {
+            Token::Add | Token::Sub | Token::Mul | Token::Div => self.operators.by_iden(&tok.to_string()).cloned(),
+            Token::Identifier(symbol) | Token::Symbol(symbol) => self.operators.by_symbol(symbol).cloned(),
+            _ => None
+        }
+    }
+
+    fn parse_expr(&mut self, lexer: &mut Lexer) -> Result<Expr, Box<dyn Error>> {
+
+        match lexer.peek(0) {
+            Some(Token::OpenParen) => {
+                // group
+                let args = self.parse_functor_args(lexer, Token::CloseParen)?;
+                Ok(Expr::functor("group", args))
+            },
+            Some(Token::OpenBracket) => {
+                // list, which is the functor list(args) like parentheses are a group
+                let args = self.parse_functor_args(lexer, Token::CloseBracket)?;
+                Self::check_segments("list", &args)?;
+                Ok(Expr::functor("list", args))
+            },
+            Some(Token::Identifier(s)) => {
+                let iden = s.to_owned();
+                lexer.next();
+                // The arguments start right after the identifier, as a parenthesis after whitespace starts a group, 
+                // e.g., the in-line rule (a + b) => c on the line after the expression statement x.
+                if matches!(lexer.peek(0), Some(Token::OpenParen)) && lexer.span(0).start == lexer.prev_span().end {
+                    let args = self.parse_functor_args(lexer, Token::CloseParen)?;
+                    Self::check_segments(&iden, &args)?;
+                    Ok(Expr::functor(iden, args))
+                } else if let (Some(Token::Identifier(var)), "fresh") = (lexer.peek(0), iden.as_str()) {
+                    // fresh variable, which is the functor fresh(var) like parentheses are a group
+                    let var = Expr::variable(var.as_str());
+                    lexer.next();
+                    Ok(Expr::functor(iden, vec![var]))
+                } else {
+                    Ok(Expr::variable(iden))
+                }
+            },
+            Some(Token::Number(n)) => {
+                let res = Ok(self.number(*n));
+                lexer.next();
+                res
+            }
+            Some(Token::Capture(n)) => {
+                let res = Ok(Self::capture(*n));
+                lexer.next();
+                res
+            }
+            // A quoted string in an expression is a string constant, while statements such as end consume it as a path
+            Some(Token::Str(s)) => {
+                let res = Ok(Expr::string(s.as_str()));
+                lexer.next();
+                res
+            }
+            Some(tok) => Err(Box::new(ParsingError::UnexpectedToken { 
+                got: Some(tok.to_string()) 
+            })),
+            None => Err(Box::new(ParsingError::UnexpectedToken { 
+                got: None
+            }))
+        }
+    }
+
+    // Parses a list as a functor with the head of the list as identifier, where the 
+    // arithmetic operators are the identifiers add, sub, mul and div, e.g., (+ x (f y)).
+    fn parse_sexpr(&mut self, lexer: &mut Lexer) -> Result<Expr, Box<dyn Error>> {
+
+        match lexer.peek(0) {
+            Some(Token::OpenParen) => {
+                lexer.next();
+                let iden = match lexer.peek(0) {
+                    Some(Token::Identifier(s)) => s.to_owned(),
+                    Some(tok @ (Token::Add | Token::Sub | Token::Mul | Token::Div)) => tok.to_string(),
+                    tok => return Err(Box::new(ParsingError::ExpectTokenAfter { 
+                        expected: "functor identifier".to_string(), 
+                        after: Token::OpenParen.to_string(), 
+                        got: tok.map(|tok| tok.to_string()) 
+                    }))
+                };
+                lexer.next();
+
+                let mut args = vec![];
+                loop {
+                    match lexer.peek(0) {
+                        Some(Token::CloseParen) => {
+                            lexer.next();
+                            break;
+                        },
+                        None => return Err(Box::new(ParsingError::ExpectToken { 
+                            expected: Token::CloseParen.to_string(), 
+                            got: None 
+                        })),
+                        _ => match Self::parse_segment(lexer) {
+                            Some(segment) => args.push(segment),
+                            None => args.push(self.parse_sexpr(lexer)?)
+                        }
+                    }
+                }
+                Self::check_segments(&iden, &args)?;
+                Ok(Expr::functor(iden, args))
+            },
+            Some(Token::Identifier(s)) => {
+                let res = Ok(Expr::variable(s.as_str()));
+                lexer.next();
+                res
+            },
+            Some(Token::Number(n)) => {
+                let res = Ok(self.number(*n));
+                lexer.next();
+                res
+            },
+            Some(Token::Capture(n)) => {
+                let res = Ok(Self::capture(*n));
+                lexer.next();
+                res
+            },
+            Some(Token::Str(s)) => {
+                let res = Ok(Expr::string(s.as_str()));
+                lexer.next();
+                res
+            },
+            tok => Err(Box::new(ParsingError::UnexpectedToken { 
+                got: tok.map(|tok| tok.to_string()) 
+            }))
+        }
+    }
+
+    // Parses the arguments after the opening token up to the closing token, e.g., the closing parenthesis of a functor
+    fn parse_functor_args(&mut self, lexer: &mut Lexer, close: Token) -> Result<Vec<Expr>, Box<dyn Error>> {
+        lexer.next();
+        let mut args = vec![];
+        loop {
+            match lexer.peek(0) {
+                Some(tok) if *tok == close => {
+                    lexer.next();
+                    break;
+                },
+                _ => {
+                    match Self::parse_segment(lexer) {
+                        Some(segment) => args.push(segment),
+                        None => args.push(self.parse_term(lexer)?)
+                    }
+                    if let Some(Token::Comma) = lexer.peek(0) {
+                        lexer.next();
+                    }
+                }
+            }
+        };
+        Ok(args)
+    }   
+
+    // Parses a segment, e.g., xs.., which can only occur as an argument of a functor
+    fn parse_segment(lexer: &mut Lexer) -> Option<Expr> {
+        let (Some(Token::Identifier(iden)), Some(Token::Ellipsis)) = (lexer.peek(0), lexer.peek(1)) else {
+            return None;
+        };
+        let segment = Expr::segment(iden.as_str());
+        lexer.next();
+        lexer.next();
+        Some(segment)
+    }
+
+    fn check_segments(iden: &str, args: &[Expr]) -> Result<(), Box<dyn Error>> {
+        if args.iter().filter(|arg| arg.is_segment()).count() > 1 {
+            return Err(Box::new(ParsingError::MultipleSegments { functor: iden.to_string() }));
+        }
+        Ok(())
+    }
+
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_demorgan() {
+        let input_string = "def demorgan as neg(or(p, q)) => and(neg(p), neg(q))";
+        let mut lexer = Lexer::new();
+        lexer.lex(input_string);
+
+        dbg!(&lexer.tokens);
+
+        let mut parser = Parser::new();
+        let res = parser.parse(&mut lexer);
+
+        assert!(res.is_ok());
+        assert!(parser.stmts.len() == 1);
+
+        let test_stmt = Stmt::DefineStmt { 
+            iden: "demorgan".to_string(), 
+            params: vec![],
+            left: Expr::functor("neg", vec![
+                Expr::functor("or", vec![
+                    Expr::variable("p"),
+                    Expr::variable("q")
+                ])
+            ]), 
+            right: Expr::functor("and", vec![
+                Expr::functor("neg", vec![Expr::variable("p")]),
+                Expr::functor("neg", vec![Expr::variable("q")]),
+            ]), 
+            tags: vec![],
+            auto: false,
+        };
+
         let parsed_stmt = parser.stmts.swap_remove(0);
         assert_eq!(parsed_stmt, test_stmt);
     }
 
-    #[test] 
-    fn parse_multiple_stmts() {
-        let input_string = "f(x, y, z) def x as x(z) => z(x) end \"hello world\"";
+    #[test] 
+    fn parse_multiple_stmts() {
+        let input_string = "f(x, y, z) def x as x(z) => z(x) end \"hello world\"";
+        let mut lexer = Lexer::new();
+        lexer.lex(input_string);
+
+        let mut parser = Parser::new();
+        let res = parser.parse(&mut lexer);
+        
+        dbg!(&res);
+        assert!(res.is_ok());
+        assert!(parser.stmts.len() == 3);
+
+        assert_eq!(
+            parser.stmts[0], 
+            Stmt::ExprStmt(Expr::functor("f", vec![
+                Expr::variable("x"),
+                Expr::variable("y"),
+                Expr::variable("z")
+            ]))
+        );
+        assert_eq!(
+            parser.stmts[1], 
+            Stmt::DefineStmt { 
+                iden: "x".to_string(), 
+                params: vec![],
+                left:  Expr::functor("x", vec![Expr::variable("z")]), 
+                right: Expr::functor("z", vec![Expr::variable("x")]), 
+                tags: vec![],
+                auto: false,
+            }
+        );
+
+        assert_eq!(
+            parser.stmts[2], 
+            Stmt::EndStmt { path: Some("hello world".to_string()), append: false, format: None, binding: None }
+        );
+    }
+
+    #[test]
+    fn trigger_definition_error() {
+        let input_string = "def x x(z) => z(x)";
+        let mut lexer = Lexer::new();
+        lexer.lex(input_string);
+
+        let mut parser = Parser::new();
+        let res = parser.parse(&mut lexer);
+
+        assert!(res.is_err());//
+    }
+
+    #[test]
+    fn trigger_rule_error() {
+        let input_string = "def f as x(z) = z(x)";
+        let mut lexer = Lexer::new();
+        lexer.lex(input_string);
+
+        let mut parser = Parser::new();
+        let res = parser.parse(&mut lexer);
+
+        assert!(res.is_err());
+    }
+
+    #[test]
+    fn parse_rule_schema() {
+        let input_string = "def power_rule(n) as pow(x, n) => n apply power_rule(3) at 0";
+        let mut lexer = Lexer::new();
+        lexer.lex(input_string);
+
+        let mut parser = Parser::new();
+        let res = parser.parse(&mut lexer);
+
+        assert!(res.is_ok());
+        assert_eq!(
+            parser.stmts[0], 
+            Stmt::DefineStmt { 
+                iden: "power_rule".to_string(), 
+                params: vec!["n".to_string()],
+                left: Expr::functor("pow", vec![Expr::variable("x"), Expr::variable("n")]), 
+                right: Expr::variable("n"), 
+                tags: vec![],
+                auto: false,
+            }
+        );
+        assert_eq!(
+            parser.stmts[1], 
+            Stmt::ApplyStmt { 
+                strategy: Strategy::Rule {
+                    iden: "power_rule".to_string(), 
+                    args: vec![Expr::number(Number::Int(3))],
+                },
+                depth: Depth::At(0)
+            }
+        );
+    }
+
+    #[test]
+    fn parse_rule_tags() {
+        let input_string = "def swap as pair(x, y) => pair(y, x) tags [algebra, ring] apply (tagged ring)* | swap at 0";
+        let mut lexer = Lexer::new();
+        lexer.lex(input_string);
+
+        let mut parser = Parser::new();
+        let res = parser.parse(&mut lexer);
+
+        assert!(res.is_ok());
+        assert_eq!(parser.stmts, vec![
+            Stmt::DefineStmt { 
+                iden: "swap".to_string(), 
+                params: vec![],
+                left: Expr::functor("pair", vec![Expr::variable("x"), Expr::variable("y")]), 
+                right: Expr::functor("pair", vec![Expr::variable("y"), Expr::variable("x")]), 
+                tags: vec!["algebra".to_string(), "ring".to_string()],
+                auto: false,
+            },
+            Stmt::ApplyStmt { 
+                strategy: Strategy::Choice(vec![
+                    Strategy::Repeat(Box::new(Strategy::Tagged("ring".to_string()))),
+                    Strategy::Rule { iden: "swap".to_string(), args: vec![] },
+                ]),
+                depth: Depth::At(0)
+            }
+        ]);
+    }
+
+    #[test]
+    fn parse_auto_rule() {
+        let input_string = "def zero as add(x, 0) => x auto (a + 0) * b def one as mul(x, 1) => x auto f(a) => g at 0 def scale(k) as x => mul(k, x) auto";
+        let mut lexer = Lexer::new();
+        lexer.lex(input_string);
+
+        let mut parser = Parser::new();
+        let res = parser.parse(&mut lexer);
+
+        assert!(matches!(
+            res.unwrap_err().downcast_ref::<ParsingError>(), 
+            Some(ParsingError::AutoRuleWithParams { iden }) if iden == "scale"
+        ));
+        assert_eq!(parser.stmts, vec![
+            Stmt::DefineStmt { 
+                iden: "zero".to_string(), 
+                params: vec![],
+                left: Expr::functor("add", vec![Expr::variable("x"), Expr::number(Number::Int(0))]), 
+                right: Expr::variable("x"), 
+                tags: vec![],
+                auto: true,
+            },
+            Stmt::ExprStmt(Expr::functor("mul", vec![
+                Expr::functor("group", vec![Expr::functor("add", vec![Expr::variable("a"), Expr::number(Number::Int(0))])]),
+                Expr::variable("b")
+            ])),
+            Stmt::DefineStmt { 
+                iden: "one".to_string(), 
+                params: vec![],
+                left: Expr::functor("mul", vec![Expr::variable("x"), Expr::number(Number::Int(1))]), 
+                right: Expr::variable("x"), 
+                tags: vec![],
+                auto: true,
+            },
+            Stmt::RuleStmt { 
+                left: Expr::functor("f", vec![Expr::variable("a")]), 
+                right: Expr::variable("g"), 
+                depth: Depth::At(0) 
+            },
+        ]);
+    }
+
+    #[test]
+    fn parse_strategy() {
+        let input_string = "apply (try distribute; then collect)* | swap at 0";
+        let mut lexer = Lexer::new();
+        lexer.lex(input_string);
+
+        let mut parser = Parser::new();
+        let res = parser.parse(&mut lexer);
+
+        assert!(res.is_ok());
+        let rule = |iden: &str| Strategy::Rule { iden: iden.to_string(), args: vec![] };
+        assert_eq!(
+            parser.stmts[0], 
+            Stmt::ApplyStmt { 
+                strategy: Strategy::Choice(vec![
+                    Strategy::Repeat(Box::new(Strategy::Sequence(vec![
+                        Strategy::Try(Box::new(rule("distribute"))),
+                        rule("collect"),
+                    ]))),
+                    rule("swap"),
+                ]),
+                depth: Depth::At(0)
+            }
+        );
+    }
+
+    #[test]
+    fn trigger_expr_depth_error() {
+        let input_string = "f(x) x at 3";
+        let mut lexer = Lexer::new();
+        lexer.lex(input_string);
+
+        let mut parser = Parser::new();
+        let res = parser.parse(&mut lexer);
+
+        assert!(matches!(
+            *res.unwrap_err().downcast::<ParsingError>().unwrap(),
+            ParsingError::UnexpectedDepth { .. }
+        ));
+        assert_eq!(parser.stmts.len(), 1);
+        assert_eq!(lexer.peek(0), Some(&Token::At));
+    }
+
+    #[test]
+    fn parse_show_stmt() {
+        let input_string = "show show as latex show as infix";
+        let mut lexer = Lexer::new();
+        lexer.lex(input_string);
+
+        let mut parser = Parser::new();
+        let res = parser.parse(&mut lexer);
+
+        assert!(res.is_ok());
+        assert_eq!(parser.stmts, vec![
+            Stmt::ShowStmt("infix".to_string()),
+            Stmt::ShowStmt("latex".to_string()),
+            Stmt::ShowStmt("infix".to_string())
+        ]);
+    }
+
+    #[test]
+    fn parse_end_stmt() {
+        let input_string = "end end \"derivation.md\" as markdown end as lemma1 end as rule lemma2 end as rule end append \"log_{n}.txt\"";
+        let mut lexer = Lexer::new();
+        lexer.lex(input_string);
+
+        let mut parser = Parser::new();
+        let res = parser.parse(&mut lexer);
+
+        assert!(res.is_ok());
+        assert_eq!(parser.stmts, vec![
+            Stmt::EndStmt { path: None, append: false, format: None, binding: None },
+            Stmt::EndStmt { path: Some("derivation.md".to_string()), append: false, format: Some("markdown".to_string()), binding: None },
+            Stmt::EndStmt { path: None, append: false, format: None, binding: Some(Binding::Expr("lemma1".to_string())) },
+            Stmt::EndStmt { path: None, append: false, format: None, binding: Some(Binding::Rule("lemma2".to_string())) },
+            Stmt::EndStmt { path: None, append: false, format: None, binding: Some(Binding::Expr("rule".to_string())) },
+            Stmt::EndStmt { path: Some("log_{n}.txt".to_string()), append: true, format: None, binding: None }
+        ]);
+    }
+
+    #[test]
+    fn parse_let_stmt() {
+        let input_string = "let e1 = integral(mul(x, x), x) e1 + 3";
+        let mut lexer = Lexer::new();
+        lexer.lex(input_string);
+
+        let mut parser = Parser::new();
+        let res = parser.parse(&mut lexer);
+
+        assert!(res.is_ok());
+        assert_eq!(parser.stmts, vec![
+            Stmt::LetStmt { 
+                iden: "e1".to_string(), 
+                expr: Expr::functor("integral", vec![
+                    Expr::functor("mul", vec![Expr::variable("x"), Expr::variable("x")]), 
+                    Expr::variable("x")
+                ])
+            },
+            Stmt::ExprStmt(Expr::functor("add", vec![Expr::variable("e1"), Expr::number(Number::Int(3))]))
+        ]);
+    }
+
+    #[test]
+    fn parse_fresh_variable() {
+        let input_string = "integral(f(x), x) => lambda(fresh y, f(y)) at 0";
+        let mut lexer = Lexer::new();
+        lexer.lex(input_string);
+
+        let mut parser = Parser::new();
+        let res = parser.parse(&mut lexer);
+
+        assert!(res.is_ok());
+        assert!(matches!(&parser.stmts[0], Stmt::RuleStmt { right, .. } if right.to_string() == "lambda(fresh(y), f(y))"));
+    }
+
+    #[test]
+    fn parse_segment() {
+        let input_string = "add(x, rest..) => add(rest.., x) at 0";
+        let mut lexer = Lexer::new();
+        lexer.lex(input_string);
+
+        let mut parser = Parser::new();
+        let res = parser.parse(&mut lexer);
+
+        assert!(res.is_ok());
+        assert_eq!(parser.stmts[0], Stmt::RuleStmt { 
+            left: Expr::functor("add", vec![Expr::variable("x"), Expr::segment("rest")]),
+            right: Expr::functor("add", vec![Expr::segment("rest"), Expr::variable("x")]),
+            depth: Depth::At(0)
+        });
+
+        let input_string = "f(xs.., ys..)";
+        let mut lexer = Lexer::new();
+        lexer.lex(input_string);
+
+        let mut parser = Parser::new();
+        let res = parser.parse(&mut lexer);
+
+        assert!(res.is_err_and(|e| e.is::<ParsingError>()));
+    }
+
+    #[test]
+    fn parse_list() {
+        let input_string = "map(f, [x, rest..]) => [] at 0";
+        let mut lexer = Lexer::new();
+        lexer.lex(input_string);
+
+        let mut parser = Parser::new();
+        let res = parser.parse(&mut lexer);
+
+        assert!(res.is_ok());
+        assert_eq!(parser.stmts[0], Stmt::RuleStmt { 
+            left: Expr::functor("map", vec![
+                Expr::variable("f"),
+                Expr::functor("list", vec![Expr::variable("x"), Expr::segment("rest")])
+            ]),
+            right: Expr::functor("list", vec![]),
+            depth: Depth::At(0)
+        });
+
+        let input_string = "[xs.., ys..]";
+        let mut lexer = Lexer::new();
+        lexer.lex(input_string);
+
+        let mut parser = Parser::new();
+        let res = parser.parse(&mut lexer);
+
+        assert!(res.is_err_and(|e| e.is::<ParsingError>()));
+    }
+
+    #[test]
+    fn parse_string() {
+        let input_string = "case(\"base\", x) => x at 0 end \"out.txt\"";
+        let mut lexer = Lexer::new();
+        lexer.lex(input_string);
+
+        let mut parser = Parser::new();
+        let res = parser.parse(&mut lexer);
+
+        assert!(res.is_ok());
+        assert_eq!(parser.stmts[0], Stmt::RuleStmt { 
+            left: Expr::functor("case", vec![Expr::string("base"), Expr::variable("x")]),
+            right: Expr::variable("x"),
+            depth: Depth::At(0)
+        });
+        assert!(matches!(&parser.stmts[1], Stmt::EndStmt { path: Some(path), .. } if path == "out.txt"));
+    }
+
+    #[test]
+    fn parse_any_depth() {
+        let input_string = "apply swap at any f(x) => g(x) at any";
+        let mut lexer = Lexer::new();
+        lexer.lex(input_string);
+
+        let mut parser = Parser::new();
+        let res = parser.parse(&mut lexer);
+
+        assert!(res.is_ok());
+        assert!(matches!(parser.stmts[0], Stmt::ApplyStmt { depth: Depth::Any, .. }));
+        assert!(matches!(parser.stmts[1], Stmt::RuleStmt { depth: Depth::Any, .. }));
+    }
+
+    #[test]
+    fn parse_depth_range() {
+        let input_string = "apply swap at 2..5 f(x) => g(x) at 0..0";
+        let mut lexer = Lexer::new();
+        lexer.lex(input_string);
+
+        let mut parser = Parser::new();
+        let res = parser.parse(&mut lexer);
+
+        assert!(res.is_ok());
+        assert!(matches!(parser.stmts[0], Stmt::ApplyStmt { depth: Depth::Range(2, 5), .. }));
+        assert!(matches!(parser.stmts[1], Stmt::RuleStmt { depth: Depth::Range(0, 0), .. }));
+        assert_eq!(Depth::Range(2, 5).to_string(), "2..5");
+
+        let mut lexer = Lexer::new();
+        lexer.lex("apply (swap | flip)* innermost");
+        let mut parser = Parser::new();
+        assert!(parser.parse(&mut lexer).is_ok());
+        assert!(matches!(parser.stmts[0], Stmt::ApplyStmt { depth: Depth::Innermost, .. }));
+
+        let mut lexer = Lexer::new();
+        lexer.lex("f(x) => g(x) once");
+        let mut parser = Parser::new();
+        assert!(parser.parse(&mut lexer).is_ok());
+        assert!(matches!(parser.stmts[0], Stmt::RuleStmt { depth: Depth::Once, .. }));
+
+        for input_string in ["apply swap at 5..2", "apply swap at 2..", "apply swap at 2..any"] {
+            let mut lexer = Lexer::new();
+            lexer.lex(input_string);
+            assert!(Parser::new().parse(&mut lexer).is_err());
+        }
+    }
+
+    #[test]
+    fn parse_preview_stmt() {
+        let input_string = "preview swap; then foo at 1";
         let mut lexer = Lexer::new();
         lexer.lex(input_string);
 
         let mut parser = Parser::new();
         let res = parser.parse(&mut lexer);
-        
-        dbg!(&res);
-        assert!(!res.is_err());
-        assert!(parser.stmts.len() == 3);
 
-        assert_eq!(
-            parser.stmts[0], 
-            Stmt::ExprStmt(Expr::Functor { 
-                iden: "f".to_string(), 
-                args: vec![
-                    Expr::Variable { iden: "x".to_string() },
-                    Expr::Variable { iden: "y".to_string() },
-                    Expr::Variable { iden: "z".to_string() }
-                ]
-            })
-        );
-        assert_eq!(
-            parser.stmts[1], 
-            Stmt::DefineStmt { 
-                iden: "x".to_string(), 
-                left:  Expr::Functor { iden: "x".to_string(), args: vec![Expr::Variable { iden: "z".to_string() }] }, 
-                right: Expr::Functor { iden: "z".to_string(), args: vec![Expr::Variable { iden: "x".to_string() }] }, 
-            }
-        );
+        assert!(res.is_ok());
+        let rule = |iden: &str| Strategy::Rule { iden: iden.to_string(), args: vec![] };
+        assert_eq!(parser.stmts, vec![Stmt::PreviewStmt { 
+            strategy: Strategy::Sequence(vec![rule("swap"), rule("foo")]), 
+            depth: Depth::At(1) 
+        }]);
+    }
 
-        assert_eq!(
-            parser.stmts[2], 
-            Stmt::EndStmt(Some("hello world".to_string()))
-        );
+    #[test]
+    fn parse_checkpoint_stmt() {
+        let input_string = "checkpoint left goto left goto";
+        let mut lexer = Lexer::new();
+        lexer.lex(input_string);
+
+        let mut parser = Parser::new();
+        let res = parser.parse(&mut lexer);
+
+        assert!(res.is_err());
+        assert_eq!(parser.stmts, vec![
+            Stmt::CheckpointStmt("left".to_string()),
+            Stmt::GotoStmt("left".to_string())
+        ]);
     }
 
     #[test]
-    fn trigger_definition_error() {
-        let input_string = "def x x(z) => z(x)";
+    fn parse_rollback_stmt() {
+        let input_string = "rollback 3 redo rollback x";
         let mut lexer = Lexer::new();
         lexer.lex(input_string);
 
         let mut parser = Parser::new();
         let res = parser.parse(&mut lexer);
 
-        assert!(res.is_err());//
+        assert!(res.is_err());
+        assert_eq!(parser.stmts, vec![
+            Stmt::RollbackStmt(3),
+            Stmt::RedoStmt
+        ]);
     }
 
     #[test]
-    fn trigger_rule_error() {
-        let input_string = "def f as x(z) = z(x)";
+    fn parse_context_stmt() {
+        let input_string = "context lhs switch main equal? lhs, rhs equal? lhs";
+        let mut lexer = Lexer::new();
+        lexer.lex(input_string);
+
+        let mut parser = Parser::new();
+        let res = parser.parse(&mut lexer);
+
+        assert!(res.is_err());
+        assert_eq!(parser.stmts, vec![
+            Stmt::ContextStmt("lhs".to_string()),
+            Stmt::SwitchStmt("main".to_string()),
+            Stmt::EqualStmt { left: "lhs".to_string(), right: "rhs".to_string() }
+        ]);
+    }
+
+    #[test]
+    fn parse_flatten_stmt() {
+        let input_string = "flatten add unflatten * flatten";
+        let mut lexer = Lexer::new();
+        lexer.lex(input_string);
+
+        let mut parser = Parser::new();
+        let res = parser.parse(&mut lexer);
+
+        assert!(res.is_err());
+        assert_eq!(parser.stmts, vec![
+            Stmt::FlattenStmt("add".to_string()),
+            Stmt::UnflattenStmt("mul".to_string())
+        ]);
+    }
+
+    #[test]
+    fn parse_commutative_stmt() {
+        let input_string = "commutative add, *, f canonicalize";
+        let mut lexer = Lexer::new();
+        lexer.lex(input_string);
+
+        let mut parser = Parser::new();
+        let res = parser.parse(&mut lexer);
+
+        assert!(res.is_ok());
+        assert_eq!(parser.stmts, vec![
+            Stmt::CommutativeStmt(vec!["add".to_string(), "mul".to_string(), "f".to_string()]),
+            Stmt::CanonicalizeStmt
+        ]);
+    }
+
+    #[test]
+    fn parse_check_termination_stmt() {
+        let input_string = "check-termination swap, distribute overlaps swap check-termination";
+        let mut lexer = Lexer::new();
+        lexer.lex(input_string);
+
+        let mut parser = Parser::new();
+        let res = parser.parse(&mut lexer);
+
+        assert!(res.is_err());
+        assert_eq!(parser.stmts, vec![
+            Stmt::CheckTerminationStmt(vec!["swap".to_string(), "distribute".to_string()]),
+            Stmt::OverlapsStmt(vec!["swap".to_string()])
+        ]);
+    }
+
+    #[test]
+    fn parse_check_stmt() {
+        let input_string = "check a + f(b) expect x";
+        let mut lexer = Lexer::new();
+        lexer.lex(input_string);
+
+        let mut parser = Parser::new();
+        let res = parser.parse(&mut lexer);
+
+        assert!(res.is_ok());
+        assert_eq!(parser.stmts, vec![
+            Stmt::CheckStmt(Expr::functor("add", vec![
+                Expr::variable("a"), 
+                Expr::functor("f", vec![Expr::variable("b")])
+            ])),
+            Stmt::ExpectStmt(Expr::variable("x"))
+        ]);
+    }
+
+    #[test]
+    fn parse_functor_stmt() {
+        let input_string = "functor add/2, neg/1 functor f";
+        let mut lexer = Lexer::new();
+        lexer.lex(input_string);
+
+        let mut parser = Parser::new();
+        let res = parser.parse(&mut lexer);
+
+        assert!(res.is_err());
+        assert_eq!(parser.stmts, vec![Stmt::FunctorStmt(vec![("add".to_string(), 2), ("neg".to_string(), 1)])]);
+    }
+
+    #[test]
+    fn parse_infix_stmt() {
+        let input_string = "infix ⊕ as oplus precedence 4 a + b ⊕ c * d ⊕ e infix ⊗ as otimes precedence 10";
+        let mut lexer = Lexer::new();
+        lexer.lex(input_string);
+
+        let mut parser = Parser::new();
+        let res = parser.parse(&mut lexer);
+
+        // The operator binds stronger than multiplication, and it is left-associative
+        assert!(res.is_err());
+        assert_eq!(parser.stmts, vec![
+            Stmt::InfixStmt(Operator::new("⊕", "oplus", 4)),
+            Stmt::ExprStmt(Expr::functor("add", vec![
+                Expr::variable("a"),
+                Expr::functor("mul", vec![
+                    Expr::functor("oplus", vec![Expr::variable("b"), Expr::variable("c")]),
+                    Expr::functor("oplus", vec![Expr::variable("d"), Expr::variable("e")]),
+                ])
+            ])),
+        ]);
+    }
+
+    #[test]
+    fn parse_operator_stmt() {
+        let input_string = "
+            operator \"->\" as implies, precedence 1, right
+            operator \"∘\" as compose, precedence 7
+            p -> q ∘ f ∘ g -> r
+            operator \"=>\" as derives, precedence 1
+        ";
+        let mut lexer = Lexer::new();
+        lexer.lex(input_string);
+
+        let mut parser = Parser::new();
+        let res = parser.parse(&mut lexer);
+
+        assert!(res.is_err());
+        assert_eq!(parser.stmts, vec![
+            Stmt::OperatorStmt(Operator { assoc: Assoc::Right, ..Operator::new("->", "implies", 1) }),
+            Stmt::OperatorStmt(Operator::new("∘", "compose", 7)),
+            Stmt::ExprStmt(Expr::functor("implies", vec![
+                Expr::variable("p"),
+                Expr::functor("implies", vec![
+                    Expr::functor("compose", vec![
+                        Expr::functor("compose", vec![Expr::variable("q"), Expr::variable("f")]),
+                        Expr::variable("g"),
+                    ]),
+                    Expr::variable("r"),
+                ])
+            ])),
+        ]);
+    }
+
+    #[test]
+    fn parse_captures() {
+        let input_string = "f($1, $2) => g($2, $1, x) at 0 f($1) => $2 at any";
+        let mut lexer = Lexer::new();
+        lexer.lex(input_string);
+
+        let mut parser = Parser::new();
+        let res = parser.parse(&mut lexer);
+
+        assert!(matches!(
+            res.unwrap_err().downcast_ref::<ParsingError>(),
+            Some(ParsingError::UnboundCapture { capture, left }) if capture == "$2" && left == "f($1)"
+        ));
+        assert_eq!(parser.stmts[0], Stmt::RuleStmt { 
+            left: Expr::functor("f", vec![Expr::variable("$1"), Expr::variable("$2")]), 
+            right: Expr::functor("g", vec![Expr::variable("$2"), Expr::variable("$1"), Expr::variable("x")]), 
+            depth: Depth::At(0)
+        });
+
+        let mut lexer = Lexer::new();
+        lexer.lex("(+ $1 y) => $1 at 0");
+        let mut parser = Parser::with_syntax(Syntax::SExpr);
+        assert!(parser.parse(&mut lexer).is_ok());
+        assert_eq!(parser.stmts[0], Stmt::RuleStmt { 
+            left: Expr::functor("add", vec![Expr::variable("$1"), Expr::variable("y")]), 
+            right: Expr::variable("$1"), 
+            depth: Depth::At(0)
+        });
+    }
+
+    #[test]
+    fn parse_associativity() {
+        let input_string = "
+            operator \"^\" as pow, precedence 3, right
+            operator \"<\" as lt, precedence 1, nonassoc
+            a ^ b ^ c * d
+            a < b * c
+            a < b < c
+        ";
+        let mut lexer = Lexer::new();
+        lexer.lex(input_string);
+
+        let mut parser = Parser::new();
+        let res = parser.parse(&mut lexer);
+
+        let (a, b, c, d) = (Expr::variable("a"), Expr::variable("b"), Expr::variable("c"), Expr::variable("d"));
+        assert_eq!(parser.stmts[2..], [
+            Stmt::ExprStmt(Expr::functor("mul", vec![
+                Expr::functor("pow", vec![a.clone(), Expr::functor("pow", vec![b.clone(), c.clone()])]),
+                d
+            ])),
+            Stmt::ExprStmt(Expr::functor("lt", vec![a, Expr::functor("mul", vec![b, c])])),
+        ]);
+        assert!(matches!(
+            res.unwrap_err().downcast_ref::<ParsingError>(),
+            Some(ParsingError::NonAssociativeOperator { symbol }) if symbol == "<"
+        ));
+        assert_eq!(lexer.span(0), Span { start: 175, end: 176 });
+    }
+
+    #[test]
+    fn parse_sexpr() {
+        let input_string = "def swap as (pair x y) => (pair y x) (* (f (g x) 2) (h))";
+        let mut lexer = Lexer::new();
+        lexer.lex(input_string);
+
+        assert_eq!(Syntax::detect(&lexer), Syntax::SExpr);
+        let mut parser = Parser::with_syntax(Syntax::SExpr);
+        let res = parser.parse(&mut lexer);
+
+        assert!(res.is_ok());
+        assert_eq!(parser.stmts[1], Stmt::ExprStmt(Expr::functor("mul", vec![
+            Expr::functor("f", vec![Expr::functor("g", vec![Expr::variable("x")]), Expr::number(Number::Int(2))]),
+            Expr::functor("h", vec![])
+        ])));
+
+        let mut lexer = Lexer::new();
+        lexer.lex("(a + b) * f(x) apply (plus | times)* at 0");
+        assert_eq!(Syntax::detect(&lexer), Syntax::Functor);
+    }
+
+    #[test]
+    fn parse_allow_stmt() {
+        let input_string = "allow W001, W003 allow";
+        let mut lexer = Lexer::new();
+        lexer.lex(input_string);
+
+        let mut parser = Parser::new();
+        let res = parser.parse(&mut lexer);
+
+        assert!(res.is_err());
+        assert_eq!(parser.stmts, vec![Stmt::AllowStmt(vec!["W001".to_string(), "W003".to_string()])]);
+    }
+
+    #[test]
+    fn parse_set_stmt() {
+        let input_string = "set max_depth 20 set prompt \"λ> \" set preamble \"import Mathlib\\n\" set verbosity quiet set trace on set timeout 1.5s set timeout off set prompt 5";
+        let mut lexer = Lexer::new();
+        lexer.lex(input_string);
+
+        let mut parser = Parser::new();
+        let res = parser.parse(&mut lexer);
+
+        assert!(res.is_err());
+        assert_eq!(parser.stmts, vec![
+            Stmt::SetStmt(Setting::MaxDepth(20)),
+            Stmt::SetStmt(Setting::Prompt("λ> ".to_string())),
+            Stmt::SetStmt(Setting::Preamble("import Mathlib\n".to_string())),
+            Stmt::SetStmt(Setting::Verbosity(Verbosity::Quiet)),
+            Stmt::SetStmt(Setting::Trace(true)),
+            Stmt::SetStmt(Setting::Timeout(Some(Duration::from_millis(1500)))),
+            Stmt::SetStmt(Setting::Timeout(None)),
+        ]);
+    }
+
+    #[test]
+    fn parse_silent_block() {
+        let input_string = "silent { f(a) apply swap at 0 } end silent { f(a)";
+        let mut lexer = Lexer::new();
+        lexer.lex(input_string);
+
+        let mut parser = Parser::new();
+        let res = parser.parse(&mut lexer);
+
+        assert!(res.is_err());
+        assert_eq!(parser.stmts, vec![
+            Stmt::SilentStmt(vec![
+                Stmt::ExprStmt(Expr::functor("f", vec![Expr::variable("a")])),
+                Stmt::ApplyStmt { strategy: Strategy::Rule { iden: "swap".to_string(), args: vec![] }, depth: Depth::At(0) },
+            ]),
+            Stmt::EndStmt { path: None, append: false, format: None, binding: None },
+        ]);
+        assert_eq!(parser.spans.len(), 2);
+    }
+
+    #[test]
+    fn parse_unify_stmt() {
+        let input_string = "unify f(x) with f(a + b) unify f(x) f(y)";
+        let mut lexer = Lexer::new();
+        lexer.lex(input_string);
+
+        let mut parser = Parser::new();
+        let res = parser.parse(&mut lexer);
+
+        assert!(res.is_err());
+        assert_eq!(parser.stmts, vec![Stmt::UnifyStmt { 
+            left: Expr::functor("f", vec![Expr::variable("x")]),
+            right: Expr::functor("f", vec![Expr::functor("add", vec![Expr::variable("a"), Expr::variable("b")])])
+        }]);
+    }
+
+    #[test]
+    fn parse_replace_stmt() {
+        let input_string = "replace f(x) with a + b replace f(x)";
+        let mut lexer = Lexer::new();
+        lexer.lex(input_string);
+
+        let mut parser = Parser::new();
+        let res = parser.parse(&mut lexer);
+
+        assert!(res.is_err());
+        assert_eq!(parser.stmts, vec![Stmt::ReplaceStmt { 
+            old: Expr::functor("f", vec![Expr::variable("x")]),
+            new: Expr::functor("add", vec![Expr::variable("a"), Expr::variable("b")])
+        }]);
+    }
+
+    #[test]
+    fn parse_focus_stmt() {
+        let input_string = "focus path 1.0.12 unfocus focus path 2 focus 1";
+        let mut lexer = Lexer::new();
+        lexer.lex(input_string);
+
+        let mut parser = Parser::new();
+        let res = parser.parse(&mut lexer);
+
+        assert!(res.is_err());
+        assert_eq!(parser.stmts, vec![Stmt::FocusStmt(vec![1, 0, 12]), Stmt::UnfocusStmt, Stmt::FocusStmt(vec![2])]);
+    }
+
+    #[test]
+    fn parse_library_stmts() {
+        let input_string = "export rules \"algebra.rxlib\" import \"algebra.rxlib\" export \"algebra.rxlib\"";
+        let mut lexer = Lexer::new();
+        lexer.lex(input_string);
+
+        let mut parser = Parser::new();
+        let res = parser.parse(&mut lexer);
+
+        assert!(res.is_err());
+        assert_eq!(parser.stmts, vec![
+            Stmt::ExportRulesStmt("algebra.rxlib".to_string()),
+            Stmt::ImportStmt("algebra.rxlib".to_string()),
+        ]);
+    }
+
+    #[test]
+    fn parse_find_stmt() {
+        let input_string = "find f(x, _) find";
         let mut lexer = Lexer::new();
         lexer.lex(input_string);
 
@@ -476,5 +2264,65 @@ mod tests {
         let res = parser.parse(&mut lexer);
 
         assert!(res.is_err());
+        assert_eq!(parser.stmts, vec![Stmt::FindStmt(Expr::functor("f", vec![Expr::variable("x"), Expr::variable("_")]))]);
+    }
+
+    #[test]
+    fn parse_group_after_whitespace() {
+        let input_string = "x\n    (x) + x => x at 0";
+        let mut lexer = Lexer::new();
+        lexer.lex(input_string);
+
+        let mut parser = Parser::new();
+        let res = parser.parse(&mut lexer);
+
+        assert!(res.is_ok());
+        let x = Expr::variable("x");
+        assert_eq!(parser.stmts, vec![
+            Stmt::ExprStmt(x.clone()),
+            Stmt::RuleStmt { 
+                left: Expr::functor("add", vec![Expr::functor("group", vec![x.clone()]), x.clone()]), 
+                right: x, 
+                depth: Depth::At(0) 
+            }
+        ]);
+    }
+
+    #[test]
+    fn parse_prove_stmt() {
+        let input_string = "prove f(x) == g(x, x) using foo, bar";
+        let mut lexer = Lexer::new();
+        lexer.lex(input_string);
+
+        let mut parser = Parser::new();
+        let res = parser.parse(&mut lexer);
+
+        assert!(res.is_ok());
+        assert_eq!(
+            parser.stmts[0], 
+            Stmt::ProveStmt { 
+                left: Expr::functor("f", vec![Expr::variable("x")]), 
+                right: Some(Expr::functor("g", vec![Expr::variable("x"), Expr::variable("x")])), 
+                rules: vec!["foo".to_string(), "bar".to_string()]
+            }
+        );
+    }
+
+    #[test]
+    fn parse_goal_stmt() {
+        let input_string = "goal g(_, f(_)) prove f(x) == goal goal off";
+        let mut lexer = Lexer::new();
+        lexer.lex(input_string);
+
+        let mut parser = Parser::new();
+        let res = parser.parse(&mut lexer);
+
+        assert!(res.is_ok());
+        let wildcard = Expr::variable("_");
+        assert_eq!(parser.stmts, vec![
+            Stmt::GoalStmt(Some(Expr::functor("g", vec![wildcard.clone(), Expr::functor("f", vec![wildcard])]))),
+            Stmt::ProveStmt { left: Expr::functor("f", vec![Expr::variable("x")]), right: None, rules: vec![] },
+            Stmt::GoalStmt(None),
+        ]);
     }
-}
\ No newline at end of file
+}
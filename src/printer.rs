@@ -0,0 +1,298 @@
+use crate::{
+    ast::{quote, Expr},
+    format::{Formatter, SExpr},
+    lexer::path_to_string,
+    operator::Operators,
+    parser::{Binding, Setting, Stmt, Strategy, Syntax, Verbosity}
+};
+
+// Number of spaces per indentation level
+const INDENT: usize = 4;
+
+// Prints parsed statements back as source code in a canonical layout: one statement per line, the statements of
+// a pattern matching context and of a silent block indented, and a blank line around each pattern matching context.
+// Parsing the printed source gives the same statements, so formatting never changes the meaning of a script.
+pub fn print_stmts(stmts: &[Stmt], syntax: Syntax) -> String {
+    let mut printer = Printer { syntax, res: String::new(), level: 0, is_matching: false, operators: Operators::default() };
+    printer.print_block(stmts);
+    printer.trim_blank_lines();
+    printer.res
+}
+
+struct Printer {
+    syntax: Syntax,
+    res: String,
+    // Number of enclosing silent blocks
+    level: usize,
+    is_matching: bool,
+    // Infix operators that are defined by the statements so far, which expressions are printed with
+    operators: Operators,
+}
+
+impl Printer {
+    fn print_block(&mut self, stmts: &[Stmt]) {
+        for stmt in stmts {
+            match stmt {
+                Stmt::ExprStmt(_) if !self.is_matching => {
+                    self.blank_line();
+                    self.line(stmt);
+                    self.is_matching = true;
+                },
+                Stmt::EndStmt { .. } if self.is_matching => {
+                    self.is_matching = false;
+                    self.line(stmt);
+                    self.blank_line();
+                },
+                Stmt::SilentStmt(stmts) => {
+                    self.push_indent();
+                    self.res.push_str("silent {\n");
+                    self.level += 1;
+                    self.print_block(stmts);
+                    self.level -= 1;
+                    self.trim_blank_lines();
+                    self.push_indent();
+                    self.res.push_str("}\n");
+                },
+                _ => self.line(stmt),
+            }
+        }
+    }
+
+    fn line(&mut self, stmt: &Stmt) {
+        self.push_indent();
+        let line = self.stmt(stmt);
+        self.res.push_str(&line);
+        self.res.push('\n');
+        if let Stmt::InfixStmt(op) | Stmt::OperatorStmt(op) = stmt {
+            let _ = self.operators.define(op.clone());
+        }
+    }
+
+    fn push_indent(&mut self) {
+        let level = self.level + usize::from(self.is_matching);
+        self.res.push_str(&" ".repeat(level * INDENT));
+    }
+
+    // A blank line separates a pattern matching context from the previous statement, unless it is the first one
+    fn blank_line(&mut self) {
+        if !self.res.is_empty() && !self.res.ends_with("\n\n") && !self.res.ends_with("{\n") {
+            self.res.push('\n');
+        }
+    }
+
+    fn trim_blank_lines(&mut self) {
+        while self.res.ends_with("\n\n") {
+            self.res.pop();
+        }
+    }
+
+    fn stmt(&self, stmt: &Stmt) -> String {
+        match stmt {
+            Stmt::RuleStmt { left, right, depth } => {
+                format!("{} => {} {}", self.expr(left), self.expr(right), depth.to_source())
+            },
+            Stmt::DefineStmt { iden, params, left, right, tags, auto } => {
+                let params = if params.is_empty() { String::new() } else { format!("({})", params.join(", ")) };
+                let tags = if tags.is_empty() { String::new() } else { format!(" tags [{}]", tags.join(", ")) };
+                let auto = if *auto { " auto" } else { "" };
+                format!("def {}{} as {} => {}{}{}", iden, params, self.expr(left), self.expr(right), tags, auto)
+            },
+            Stmt::ExprStmt(expr) => self.expr(expr),
+            Stmt::ApplyStmt { strategy, depth } => format!("apply {} {}", self.strategy(strategy), depth.to_source()),
+            Stmt::PreviewStmt { strategy, depth } => format!("preview {} {}", self.strategy(strategy), depth.to_source()),
+            Stmt::EndStmt { path, append, format, binding } => {
+                let mut res = "end".to_string();
+                if *append {
+                    res.push_str(" append");
+                }
+                if let Some(path) = path {
+                    res.push_str(&format!(" {}", quote(path)));
+                }
+                if let Some(format) = format {
+                    res.push_str(&format!(" as {}", format));
+                }
+                match binding {
+                    Some(Binding::Expr(iden)) => res.push_str(&format!(" as {}", iden)),
+                    Some(Binding::Rule(iden)) => res.push_str(&format!(" as rule {}", iden)),
+                    None => {}
+                }
+                res
+            },
+            Stmt::ProveStmt { left, right, rules } => {
+                let right = right.as_ref().map_or("goal".to_string(), |right| self.expr(right));
+                let mut res = format!("prove {} == {}", self.expr(left), right);
+                if !rules.is_empty() {
+                    res.push_str(&format!(" using {}", rules.join(", ")));
+                }
+                res
+            },
+            Stmt::SetStmt(setting) => match setting {
+                Setting::MaxDepth(n) => format!("set max_depth {}", n),
+                Setting::MaxNodes(n) => format!("set max_nodes {}", n),
+                Setting::Prompt(prompt) => format!("set prompt {}", quote(prompt)),
+                Setting::Preamble(preamble) => format!("set preamble {}", quote(preamble)),
+                Setting::Verbosity(verbosity) => {
+                    let idx = [Verbosity::Quiet, Verbosity::Normal, Verbosity::Verbose]
+                        .iter()
+                        .position(|v| v == verbosity)
+                        .unwrap();
+                    format!("set verbosity {}", Verbosity::NAMES[idx])
+                },
+                Setting::Trace(on) => format!("set trace {}", if *on { "on" } else { "off" }),
+                Setting::Timeout(None) => "set timeout off".to_string(),
+                Setting::Timeout(Some(timeout)) if timeout.subsec_nanos() == 0 => format!("set timeout {}s", timeout.as_secs()),
+                Setting::Timeout(Some(timeout)) => format!("set timeout {}ms", timeout.as_millis()),
+            },
+            Stmt::AllowStmt(codes) => format!("allow {}", codes.join(", ")),
+            // Infix notation is the default
+            Stmt::ShowStmt(notation) if notation == "infix" => "show".to_string(),
+            Stmt::ShowStmt(notation) => format!("show as {}", notation),
+            Stmt::LetStmt { iden, expr } => format!("let {} = {}", iden, self.expr(expr)),
+            Stmt::CheckpointStmt(name) => format!("checkpoint {}", name),
+            Stmt::GotoStmt(name) => format!("goto {}", name),
+            Stmt::RollbackStmt(step) => format!("rollback {}", step),
+            Stmt::RedoStmt => "redo".to_string(),
+            Stmt::ContextStmt(name) => format!("context {}", name),
+            Stmt::SwitchStmt(name) => format!("switch {}", name),
+            Stmt::EqualStmt { left, right } => format!("equal? {}, {}", left, right),
+            Stmt::FlattenStmt(iden) => format!("flatten {}", iden),
+            Stmt::UnflattenStmt(iden) => format!("unflatten {}", iden),
+            Stmt::CommutativeStmt(idens) => format!("commutative {}", idens.join(", ")),
+            Stmt::CanonicalizeStmt => "canonicalize".to_string(),
+            Stmt::CheckTerminationStmt(idens) => format!("check-termination {}", idens.join(", ")),
+            Stmt::OverlapsStmt(idens) => format!("overlaps {}", idens.join(", ")),
+            Stmt::UnifyStmt { left, right } => format!("unify {} with {}", self.expr(left), self.expr(right)),
+            Stmt::ReplaceStmt { old, new } => format!("replace {} with {}", self.expr(old), self.expr(new)),
+            Stmt::FindStmt(pattern) => format!("find {}", self.expr(pattern)),
+            Stmt::GoalStmt(Some(goal)) => format!("goal {}", self.expr(goal)),
+            Stmt::GoalStmt(None) => "goal off".to_string(),
+            Stmt::ExportRulesStmt(path) => format!("export rules \"{}\"", path),
+            Stmt::ImportStmt(path) => format!("import \"{}\"", path),
+            Stmt::FocusStmt(path) => format!("focus path {}", path_to_string(path)),
+            Stmt::UnfocusStmt => "unfocus".to_string(),
+            Stmt::CheckStmt(expr) => format!("check {}", self.expr(expr)),
+            Stmt::ExpectStmt(expr) => format!("expect {}", self.expr(expr)),
+            Stmt::FunctorStmt(arities) => {
+                let arities: Vec<String> = arities.iter().map(|(iden, arity)| format!("{}/{}", iden, arity)).collect();
+                format!("functor {}", arities.join(", "))
+            },
+            Stmt::InfixStmt(op) => format!("infix {} as {} precedence {}", op.symbol, op.iden, op.precedence),
+            Stmt::OperatorStmt(op) => format!("operator \"{}\" as {}, precedence {}, {}", op.symbol, op.iden, op.precedence, op.assoc.name()),
+            Stmt::SilentStmt(_) => unreachable!("silent blocks span multiple lines"),
+        }
+    }
+
+    fn expr(&self, expr: &Expr) -> String {
+        match self.syntax {
+            Syntax::Functor => expr.to_source_with(&self.operators),
+            Syntax::SExpr => SExpr.format(expr),
+        }
+    }
+
+    // Choice binds weakest, followed by sequencing, 'try' and repetition, so that
+    // parentheses are only printed around a strategy that would otherwise bind differently.
+    fn strategy(&self, strategy: &Strategy) -> String {
+        fn precedence(strategy: &Strategy) -> u8 {
+            match strategy {
+                Strategy::Choice(_) => 0,
+                Strategy::Sequence(_) => 1,
+                Strategy::Try(_) => 2,
+                Strategy::Repeat(_) | Strategy::Rule { .. } | Strategy::Tagged(_) => 3,
+            }
+        }
+
+        let wrap = |strategy: &Strategy, min_precedence: u8| {
+            let res = self.strategy(strategy);
+            if precedence(strategy) < min_precedence { format!("({})", res) } else { res }
+        };
+        let join = |strategies: &[Strategy], sep: &str, min_precedence: u8| {
+            strategies.iter().map(|strategy| wrap(strategy, min_precedence)).collect::<Vec<_>>().join(sep)
+        };
+        match strategy {
+            Strategy::Rule { iden, args } if args.is_empty() => iden.clone(),
+            Strategy::Rule { iden, args } => {
+                let args: Vec<String> = args.iter().map(|arg| self.expr(arg)).collect();
+                format!("{}({})", iden, args.join(", "))
+            },
+            Strategy::Choice(strategies) => join(strategies, " | ", 1),
+            Strategy::Sequence(strategies) => join(strategies, "; ", 2),
+            Strategy::Try(strategy) => format!("try {}", wrap(strategy, 2)),
+            Strategy::Repeat(strategy) => format!("{}*", wrap(strategy, 3)),
+            Strategy::Tagged(tag) => format!("tagged {}", tag),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{lexer::Lexer, parser::Parser};
+
+    fn parse(input_string: &str, syntax: Syntax) -> Vec<Stmt> {
+        let mut lexer = Lexer::new();
+        lexer.lex(input_string);
+        let mut parser = Parser::with_syntax(syntax);
+        parser.parse(&mut lexer).unwrap();
+        parser.stmts
+    }
+
+    #[test]
+    fn print_source() {
+        let input_string = "
+            def  swap as f(x,y)=>f(y,x)   def scale(k) as mul(k,x) => k*x
+            functor neg/1 allow W001,W003
+            add(a, mul(b, c)) + sub(a, sub(b, c)) * (d - e)
+            apply (try swap ; scale(2))* | swap at any
+            silent { a => b at 0 show as latex }
+            apply swap innermost apply swap once
+            end \"out.md\" as markdown as rule lemma
+            prove a + 0 == a using swap
+            set prompt \"λ> \"
+        ";
+        let stmts = parse(input_string, Syntax::Functor);
+        let source = print_stmts(&stmts, Syntax::Functor);
+        assert_eq!(source, [
+            "def swap as f(x, y) => f(y, x)",
+            "def scale(k) as k * x => k * x",
+            "functor neg/1",
+            "allow W001, W003",
+            "",
+            "a + b * c + sub(a, b - c) * (d - e)",
+            "    apply (try swap; scale(2))* | swap at any",
+            "    silent {",
+            "        a => b at 0",
+            "        show as latex",
+            "    }",
+            "    apply swap innermost",
+            "    apply swap once",
+            "end \"out.md\" as markdown as rule lemma",
+            "",
+            "prove a + 0 == a using swap",
+            "set prompt \"λ> \"",
+            ""
+        ].join("\n"));
+
+        // Formatting is idempotent and preserves the statements
+        assert_eq!(parse(&source, Syntax::Functor), stmts);
+        assert_eq!(print_stmts(&parse(&source, Syntax::Functor), Syntax::Functor), source);
+
+        // Expressions after an infix statement are printed with its operator
+        let stmts = parse("oplus(a, b) infix ⊕ as oplus precedence 3 oplus(a, b) * oplus(c, oplus(d, e))", Syntax::Functor);
+        let source = print_stmts(&stmts, Syntax::Functor);
+        assert_eq!(source, "oplus(a, b)\n    infix ⊕ as oplus precedence 3\n    a ⊕ b * c ⊕ oplus(d, e)\n");
+        assert_eq!(parse(&source, Syntax::Functor), stmts);
+
+        let stmts = parse("operator \"->\" as implies, precedence 1, right implies(implies(a, b), implies(c, d))", Syntax::Functor);
+        let source = print_stmts(&stmts, Syntax::Functor);
+        assert_eq!(source, "operator \"->\" as implies, precedence 1, right\n\nimplies(a, b) -> c -> d\n");
+        assert_eq!(parse(&source, Syntax::Functor), stmts);
+
+        let stmts = parse("operator \"<\" as lt, precedence 1, nonassoc lt(lt(a, b), c * d)", Syntax::Functor);
+        let source = print_stmts(&stmts, Syntax::Functor);
+        assert_eq!(source, "operator \"<\" as lt, precedence 1, nonassoc\n\nlt(a, b) < c * d\n");
+        assert_eq!(parse(&source, Syntax::Functor), stmts);
+
+        let stmts = parse("(f (+ x 1))  apply swap at 0 end", Syntax::SExpr);
+        assert_eq!(print_stmts(&stmts, Syntax::SExpr), "(f (add x 1))\n    apply swap at 0\nend\n");
+    }
+}
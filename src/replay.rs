@@ -0,0 +1,232 @@
+use crate::{
+    ast::Expr,
+    error::{ReplayError, RuntimeError},
+    export::{parse_expr, JSON_VERSION},
+    json::Json,
+    parser::Depth,
+    runtime::{AppliedRule, Env}
+};
+
+// A derivation that is read back from a file written with 'end "PATH" as json', where each expression
+// after the first results from the rule at the same step.
+#[derive(Debug, PartialEq)]
+pub struct Derivation {
+    pub history: Vec<Expr>,
+    pub rules: Vec<AppliedRule>,
+}
+
+impl Derivation {
+    pub fn from_json(text: &str) -> Result<Self, ReplayError> {
+        let json = Json::parse(text).ok_or(ReplayError::InvalidJson)?;
+        let version = json.get("version").ok_or(ReplayError::InvalidJson)?;
+        if version.as_usize() != Some(JSON_VERSION) {
+            return Err(ReplayError::UnsupportedVersion { version: version.to_string(), supported: JSON_VERSION });
+        }
+        let steps = json.get("steps").and_then(Json::as_array).filter(|steps| !steps.is_empty()).ok_or(ReplayError::InvalidJson)?;
+
+        let mut history = vec![];
+        let mut rules = vec![];
+        for (step, value) in steps.iter().enumerate() {
+            let invalid = |reason: &str| ReplayError::InvalidStep { step, reason: reason.to_string() };
+            let expr = value.get("expr").and_then(Json::as_str).and_then(parse_expr).ok_or_else(|| invalid("expected an expression"))?;
+            history.push(expr);
+            match value.get("rule") {
+                Some(Json::Null) if step == 0 => {},
+                Some(rule @ Json::Object(_)) if step > 0 => {
+                    let name = match rule.get("name") {
+                        Some(Json::Null) => None,
+                        Some(Json::Str(name)) => Some(name.clone()),
+                        _ => return Err(invalid("expected the name of the rule, or null for an in-line rule")),
+                    };
+                    let side = |key| rule.get(key).and_then(Json::as_str).and_then(parse_expr);
+                    let (Some(left), Some(right)) = (side("left"), side("right")) else {
+                        return Err(invalid("expected the left and right expression of the rule"));
+                    };
+                    let depth = rule.get("depth").and_then(Json::as_usize).ok_or_else(|| invalid("expected the depth of the rule"))?;
+                    // Derivations that were exported before steps recorded how they were applied were applied at the depth
+                    let path = match rule.get("path") {
+                        None => Some(vec![]),
+                        Some(path) => path.as_array().and_then(|path| path.iter().map(Json::as_usize).collect()),
+                    };
+                    let at = match rule.get("at") {
+                        None => Some(Depth::At(depth)),
+                        Some(at) => at.as_str().and_then(Depth::from_name),
+                    };
+                    let (Some(path), Some(at)) = (path, at) else {
+                        return Err(invalid("expected the path and the depth that the rule was applied at"));
+                    };
+                    rules.push(AppliedRule { name, left, right, depth, path, at });
+                },
+                _ if step == 0 => return Err(invalid("expected a null rule for the starting expression")),
+                _ => return Err(invalid("expected the rule that derived the expression")),
+            }
+        }
+        Ok(Derivation { history, rules })
+    }
+}
+
+// A step of a replayed derivation, with the expression that the rule derives now from the recorded expression before it.
+#[derive(Debug, PartialEq)]
+pub struct ReplayedStep<'a> {
+    pub rule: &'a AppliedRule,
+    pub expected: &'a Expr,
+    pub got: Expr,
+    pub is_ok: bool,
+}
+
+// Apply the rule of every step to the recorded expression before it, where and how it was applied. A rule that is defined in
+// the environment is applied with its current definition, such that a changed definition is caught, while in-line rules,
+// rule schemas and built-in steps, e.g., edits and focused derivations, are applied as they were recorded.
+pub fn replay<'a>(derivation: &'a Derivation, env: &Env) -> Result<Vec<ReplayedStep<'a>>, RuntimeError> {
+    let mut steps = vec![];
+    for ((prev_expr, expected), rule) in derivation.history.iter().zip(&derivation.history[1..]).zip(&derivation.rules) {
+        let defined = rule.name.as_ref().and_then(|name| env.rules.get(name)).filter(|defined| defined.params.is_empty());
+        let (left, right) = match defined {
+            Some(defined) => (&defined.left, &defined.right),
+            None => (&rule.left, &rule.right),
+        };
+        let got = rule.reapply(prev_expr, left, right, env)?;
+        let is_ok = env.is_equivalent(&got, expected);
+        steps.push(ReplayedStep { rule, expected, got, is_ok });
+    }
+    Ok(steps)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{event::Choice, export::{ExportFormat, ExportOptions}, lexer::Lexer, parser::Parser};
+
+    fn interpret(env: &mut Env, input_string: &str) {
+        let mut lexer = Lexer::new();
+        lexer.lex(input_string);
+        let mut parser = Parser::new();
+        assert!(parser.parse(&mut lexer).is_ok());
+        assert!(env.interpret(parser.stmts).is_ok());
+    }
+
+    #[test]
+    fn replay_derivation() {
+        let mut env = Env::new();
+        env.set_output(std::io::sink());
+        interpret(&mut env, "
+            def swap as pair(x, y) => pair(y, x)
+            pair(a + b, c)
+                apply swap at 0
+                pair(x, y) => f(y) at 0
+                replace a with d
+        ");
+        let json = ExportFormat::Json.render(&env.history, &env.derivation_history, &ExportOptions::default());
+        assert!(json.starts_with("{\"version\":1,\"steps\":[{\"expr\":\"pair(a + b, c)\",\"rule\":null},"));
+
+        let derivation = Derivation::from_json(&json).unwrap();
+        assert_eq!(derivation.history, env.history);
+        assert_eq!(derivation.rules, env.derivation_history);
+        let steps = replay(&derivation, &env).unwrap();
+        assert_eq!(steps.len(), 3);
+        assert!(steps.iter().all(|step| step.is_ok));
+
+        // A changed definition no longer derives the recorded expression
+        interpret(&mut env, "def swap as pair(x, y) => pair(x, y)");
+        let steps = replay(&derivation, &env).unwrap();
+        assert!(!steps[0].is_ok);
+        assert_eq!(steps[0].got.to_string(), "pair(a + b, c)");
+        assert!(steps[1].is_ok);
+    }
+
+    // Export the derivation of the environment as json, and replay it with the rules of the environment
+    fn replay_exported(env: &Env) -> Vec<bool> {
+        let json = ExportFormat::Json.render(&env.history, &env.derivation_history, &ExportOptions::default());
+        let derivation = Derivation::from_json(&json).unwrap();
+        assert_eq!(derivation.rules, env.derivation_history);
+        replay(&derivation, env).unwrap().iter().map(|step| step.is_ok).collect()
+    }
+
+    #[test]
+    fn replay_chosen_match() {
+        let mut env = Env::new();
+        env.set_output(std::io::sink());
+        // The second of several matches is chosen, which replaying at the depth of the match would not rewrite alone
+        env.chooser = Some(Box::new(|_: &[(Vec<usize>, Expr)]| Choice::One(1)));
+        interpret(&mut env, "
+            def wrap as g(x) => k(x)
+            f(g(a), g(b), g(c))
+                apply wrap at 1
+                g(x) => h(x) at any
+        ");
+        assert_eq!(env.history.last().unwrap().to_string(), "f(g(a), k(b), h(c))");
+        assert_eq!(env.derivation_history[0].path, vec![1]);
+        assert_eq!(env.derivation_history[1].path, vec![2]);
+        assert_eq!(replay_exported(&env), vec![true, true]);
+    }
+
+    #[test]
+    fn replay_innermost() {
+        let mut env = Env::new();
+        env.set_output(std::io::sink());
+        interpret(&mut env, "
+            def zero as add(x, 0) => x
+            f(add(add(a, 0), 0), add(b, 0))
+                apply zero innermost
+                s(x) => t(x) innermost
+        ");
+        // At its deepest depth alone, the rule would only rewrite add(a, 0)
+        assert_eq!(env.history[1].to_string(), "f(a, b)");
+        assert_eq!(env.derivation_history[0].at, Depth::Innermost);
+        assert_eq!(replay_exported(&env), vec![true, true]);
+    }
+
+    #[test]
+    fn replay_range() {
+        let mut env = Env::new();
+        env.set_output(std::io::sink());
+        interpret(&mut env, "
+            f(g(a), h(g(b)), h(h(g(c))))
+                g(x) => k(x) at 1..2
+        ");
+        // At its first changed depth alone, the rule would only rewrite g(a)
+        assert_eq!(env.history[1].to_string(), "f(k(a), h(k(b)), h(h(g(c))))");
+        assert_eq!(env.derivation_history[0].at, Depth::Range(1, 2));
+        assert_eq!(replay_exported(&env), vec![true]);
+    }
+
+    #[test]
+    fn replay_auto_rule() {
+        let mut env = Env::new();
+        env.set_output(std::io::sink());
+        interpret(&mut env, "
+            def zero as x + 0 => x auto
+            h(k(g(a)), g(b))
+                k(x) => x + 0 + 0 at 1
+        ");
+        // At its deepest depth alone, the rule would only rewrite g(a) + 0
+        assert_eq!(env.history[2].to_string(), "h(g(a), g(b))");
+        assert_eq!(env.derivation_history[1].at, Depth::Innermost);
+        assert_eq!(replay_exported(&env), vec![true, true]);
+    }
+
+    #[test]
+    fn replay_errors() {
+        assert_eq!(Derivation::from_json("[1, 2]"), Err(ReplayError::InvalidJson));
+        assert_eq!(Derivation::from_json("{\"version\":2,\"steps\":[]}"), Err(ReplayError::UnsupportedVersion { version: "2".to_string(), supported: 1 }));
+        assert_eq!(
+            Derivation::from_json("{\"version\":1,\"steps\":[{\"expr\":\"f(a\",\"rule\":null}]}"),
+            Err(ReplayError::InvalidStep { step: 0, reason: "expected an expression".to_string() })
+        );
+        assert_eq!(
+            Derivation::from_json("{\"version\":1,\"steps\":[{\"expr\":\"f(a)\",\"rule\":null},{\"expr\":\"g(a)\",\"rule\":null}]}"),
+            Err(ReplayError::InvalidStep { step: 1, reason: "expected the rule that derived the expression".to_string() })
+        );
+        let step = |fields: &str| format!(
+            "{{\"version\":1,\"steps\":[{{\"expr\":\"f(a)\",\"rule\":null}},{{\"expr\":\"g(a)\",\"rule\":{{\"name\":null,\"left\":\"f(x)\",\"right\":\"g(x)\",\"depth\":0{}}}}}]}}", 
+            fields
+        );
+        // Steps without a path and a depth mode were applied at their depth
+        assert_eq!(Derivation::from_json(&step("")).unwrap().rules[0].at, Depth::At(0));
+        assert_eq!(Derivation::from_json(&step(",\"path\":[],\"at\":\"innermost\"")).unwrap().rules[0].at, Depth::Innermost);
+        assert_eq!(
+            Derivation::from_json(&step(",\"path\":[],\"at\":\"5..2\"")),
+            Err(ReplayError::InvalidStep { step: 1, reason: "expected the path and the depth that the rule was applied at".to_string() })
+        );
+    }
+}
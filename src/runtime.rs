@@ -1,23 +1,484 @@
-use std::{collections::HashMap, fs, error::Error};
+use std::{collections::{HashMap, HashSet}, fmt::Display, hash::{DefaultHasher, Hash, Hasher}, fs, io::{self, Write}, path::{Path, PathBuf}, sync::{atomic::{AtomicBool, AtomicUsize, Ordering}, Arc, Mutex}, time::{Duration, Instant, SystemTime, UNIX_EPOCH}};
 
-use crate::{parser::{Expr, Stmt}, error::Warning};
+use rayon::prelude::*;
 
+use crate::{ast::{Expr, ExprNode}, event::{Choice, Chooser, Event, Observer}, lexer::{path_to_string, Lexer}, operator::Operators, format::{Formatter, default_formatters}, export::{ExportFormat, ExportOptions}, library::{export_library, read_library, resolve_library_path}, parser::{Parser, Syntax, Stmt, Setting, Binding, Depth, Strategy, Verbosity}, error::{MatchFailure, Warning, RuntimeError}, strategy::StrategyRunner, search::{prove, prove_goal, expr_depth, MAX_PROOF_STEPS}, termination::{check_termination, Termination}, lint::lint_rule, unify::{critical_pairs, substitute, unify}};
+
+// Presentation of the interpretation, which can be changed by set statements.
+#[derive(Debug, Clone)]
+pub struct Config {
+    // Prompt of the REPL outside of a pattern matching context
+    pub prompt: String,
+
+    pub verbosity: Verbosity,
+
+    // Text before the proof of a derivation that is written in the lean format
+    pub preamble: String,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self { 
+            prompt: "> ".to_string(), 
+            verbosity: Verbosity::Normal,
+            preamble: String::new(),
+        }
+    }
+}
+
+// Printed output that is captured in memory, which stays readable after a clone is given to an environment.
+#[derive(Debug, Clone, Default)]
+pub struct OutputBuffer(Arc<Mutex<Vec<u8>>>);
+
+impl OutputBuffer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // Everything that is written so far
+    pub fn contents(&self) -> String {
+        String::from_utf8_lossy(&self.0.lock().unwrap()).into_owned()
+    }
+}
+
+impl Write for OutputBuffer {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.lock().unwrap().extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+// A rule maps a left expression to a right expression. Rule schemas also carry 
+// meta-parameters, which are substituted by the arguments of an apply statement.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Rule {
+    pub params: Vec<String>,
+    pub left: Expr,
+    pub right: Expr,
+    // Tags that group the rule with others, which are applied together with 'apply tagged TAG'
+    pub tags: Vec<String>,
+    // Whether the rule is applied after every rewrite until it no longer changes the expression
+    pub auto: bool,
+}
+
+impl Rule {
+    // Substitute the meta-parameters in both sides of the rule with the given arguments.
+    pub fn instantiate(&self, args: &[Expr]) -> (Expr, Expr) {
+        let args_table: HashMap<Expr, Expr> = self.params
+            .iter()
+            .map(|param| Expr::variable(param.as_str()))
+            .zip(args.iter().cloned())
+            .collect();
+
+        (construct_rhs(&self.left, &args_table), construct_rhs(&self.right, &args_table))
+    }
+}
+
+// A rule application in the derivation of an expression
+#[derive(Debug, Clone, PartialEq)]
+pub struct AppliedRule {
+    // Identifier of a defined rule or name of a built-in step, e.g., flatten add, or None for an in-line rule
+    pub name: Option<String>,
+    pub left: Expr,
+    pub right: Expr,
+    // Depth of the rewrite from the top, which is the first depth at which the expression changed if the rule was
+    // applied at several depths, or the deepest one if it was applied innermost
+    pub depth: usize,
+    // Path of the sub-expression that the rule was applied to, e.g., the match that the chooser picked
+    pub path: Vec<usize>,
+    // How the rule was applied to that sub-expression, such that the step can be applied again
+    pub at: Depth,
+}
+
+impl AppliedRule {
+    // A rule that was applied at a single depth of the entire expression
+    pub fn new(name: Option<String>, left: Expr, right: Expr, depth: usize) -> Self {
+        Self { name, left, right, depth, path: vec![], at: Depth::At(depth) }
+    }
+
+    // Apply the left and right expression where and how the rule of the step was applied, 
+    // such that the expression before the step derives the expression of the step again
+    pub fn reapply(&self, expr: &Expr, left: &Expr, right: &Expr, env: &Env) -> Result<Expr, RuntimeError> {
+        let Some(sub_expr) = expr.at_path(&self.path) else {
+            return Ok(expr.clone());
+        };
+        let (new_expr, _) = ast_traverse_match_at(sub_expr, left, right, self.at, env)?;
+        let new_expr = expr.replace_at(&self.path, new_expr);
+        env.limits.check(&new_expr)?;
+        Ok(new_expr)
+    }
+}
+
+// Safety rails against runaway rules, which are checked for every derived expression.
+#[derive(Debug, Clone)]
+pub struct Limits {
+    // Maximum depth of an expression, where the entire expression is at depth 0
+    pub max_depth: usize,
+
+    // Maximum number of variables and functors in an expression
+    pub max_nodes: usize,
+
+    // Maximum duration of a statement, which is unlimited by default
+    pub timeout: Option<Duration>,
+
+    // When the current statement exceeds the timeout
+    pub deadline: Option<Instant>,
+
+    // Set from another thread to stop the current statement, e.g., by Ctrl-C in the REPL
+    pub interrupted: Arc<AtomicBool>,
+}
+
+impl Default for Limits {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Limits {
+    pub fn new() -> Self {
+        Self { 
+            max_depth: 1000, 
+            max_nodes: 100_000,
+            timeout: None,
+            deadline: None,
+            interrupted: Arc::new(AtomicBool::new(false)),
+        }
+    }
+
+    // Start the clock of the timeout for the next statement. The clock is only read if there is a timeout, 
+    // as it is unavailable in WebAssembly without JavaScript.
+    pub fn start_clock(&mut self) {
+        self.deadline = self.timeout.map(|timeout| Instant::now() + timeout);
+    }
+
+    // Rule applications and searches check the clock between steps, and traversals every so many sub-expressions, 
+    // such that a statement that runs too long, or is interrupted, stops with an error instead of hanging the REPL.
+    pub fn check_time(&self) -> Result<(), RuntimeError> {
+        if self.interrupted.load(Ordering::Relaxed) {
+            return Err(RuntimeError::Interrupted);
+        }
+        match (self.deadline, self.timeout) {
+            (Some(deadline), Some(timeout)) if Instant::now() >= deadline => Err(RuntimeError::TimeoutExceeded { timeout }),
+            _ => Ok(())
+        }
+    }
+
+    // Iteratively count the nodes and depth of an expression, 
+    // stopping as soon as one of the limits is exceeded.
+    pub fn check(&self, expr: &Expr) -> Result<(), RuntimeError> {
+        self.check_time()?;
+        let mut num_nodes = 0;
+        let mut stack = vec![(expr, 0)];
+        while let Some((expr, depth)) = stack.pop() {
+            num_nodes += 1;
+            if num_nodes > self.max_nodes {
+                return Err(RuntimeError::MaxNodesExceeded { max_nodes: self.max_nodes });
+            }
+            if depth > self.max_depth {
+                return Err(RuntimeError::MaxDepthExceeded { max_depth: self.max_depth });
+            }
+            if let ExprNode::Functor { args, .. } = expr.node() {
+                stack.extend(args.iter().map(|arg| (arg, depth + 1)));
+            }
+        }
+        Ok(())
+    }
+}
+
+// Maximum number of memoized sub-expressions, as the entries of the cache keep expressions alive
+const MATCH_CACHE_CAPACITY: usize = 1 << 16;
+
+// Number of separately locked parts of the match cache, such that traversals that share an environment rarely wait for each other
+const MATCH_CACHE_SHARDS: usize = 16;
+
+type MatchKey = (Expr, Expr, Expr, usize);
+
+// The rewritten sub-expression for a sub-expression, the left and right expression of a rule, and the depth below the
+// sub-expression at which the rule was applied, together with the number of match attempts that the rewrite took.
+// As expressions are hash-consed, identical sub-expressions, e.g., of repeated applications to a large expression,
+// are then rewritten once, and a key is hashed in constant time. Each key belongs to the shard of its sub-expression.
+#[derive(Debug)]
+pub struct MatchCache {
+    shards: Vec<Mutex<MatchCacheShard>>,
+}
+
+// The entries of a shard in two generations. Once the recent entries are full, they become the old entries, 
+// and an old entry that is used again moves back to the recent ones, such that only entries that were not used 
+// for a whole generation are dropped.
+#[derive(Debug, Default)]
+struct MatchCacheShard {
+    recent: HashMap<MatchKey, (Expr, usize)>,
+    old: HashMap<MatchKey, (Expr, usize)>,
+}
+
+impl MatchCacheShard {
+    fn insert(&mut self, key: MatchKey, value: (Expr, usize)) {
+        if self.recent.len() >= MATCH_CACHE_CAPACITY / MATCH_CACHE_SHARDS / 2 {
+            self.old = std::mem::take(&mut self.recent);
+        }
+        self.recent.insert(key, value);
+    }
+}
+
+impl Default for MatchCache {
+    fn default() -> Self {
+        Self { shards: (0..MATCH_CACHE_SHARDS).map(|_| Mutex::default()).collect() }
+    }
+}
+
+impl MatchCache {
+    fn shard(&self, expr: &Expr) -> &Mutex<MatchCacheShard> {
+        let mut hasher = DefaultHasher::new();
+        expr.hash(&mut hasher);
+        &self.shards[hasher.finish() as usize % MATCH_CACHE_SHARDS]
+    }
+
+    fn get(&self, expr: &Expr, left: &Expr, right: &Expr, depth: usize) -> Option<(Expr, usize)> {
+        let key = (expr.clone(), left.clone(), right.clone(), depth);
+        let mut shard = self.shard(expr).lock().unwrap();
+        if let Some(value) = shard.recent.get(&key) {
+            return Some(value.clone());
+        }
+        let value = shard.old.remove(&key)?;
+        shard.insert(key, value.clone());
+        Some(value)
+    }
+
+    fn insert(&self, expr: &Expr, left: &Expr, right: &Expr, depth: usize, result: Expr, match_attempts: usize) {
+        let key = (expr.clone(), left.clone(), right.clone(), depth);
+        self.shard(expr).lock().unwrap().insert(key, (result, match_attempts));
+    }
+
+    pub fn len(&self) -> usize {
+        self.shards.iter().map(|shard| {
+            let shard = shard.lock().unwrap();
+            shard.recent.len() + shard.old.len()
+        }).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+// Measurements of the statements that apply rules, which are reported with --stats.
+#[derive(Debug, Default)]
+pub struct Stats {
+    // Whether to print the measurements after each statement that applies rules
+    pub enabled: bool,
+
+    // Number of sub-expressions matched against the left expression of a rule,
+    // since the start of the statement that is currently interpreted
+    pub match_attempts: AtomicUsize,
+
+    // Number of rewritten sub-expressions that the match cache returned instead of traversing them again, whose
+    // match attempts are counted as well, such that the match attempts are the same with or without the cache
+    pub cache_hits: AtomicUsize,
+
+    // Measurements of each apply statement and in-line rule, in order of interpretation
+    pub applies: Vec<ApplyStats>,
+
+    // Number of measurements before the current pattern matching context started
+    pub context_start: usize,
+}
+
+#[derive(Debug, Clone)]
+pub struct ApplyStats {
+    pub elapsed: Duration,
+
+    // Number of variables and functors in the resulting expression
+    pub num_nodes: usize,
+
+    pub match_attempts: usize,
+
+    pub cache_hits: usize,
+}
+
+impl Display for ApplyStats {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f, "Stats: {:?} elapsed, {} nodes, {} match attempts, {} cache hits", 
+            self.elapsed, self.num_nodes, self.match_attempts, self.cache_hits
+        )
+    }
+}
+
+// Why applying the rule at the depth, or at any depth, leaves the expression unchanged. As all sub-expressions 
+// at the depth failed to match, the reason is the first failure, which is at depth 0 for any depth.
+pub fn no_match_reason(current_expr: &Expr, left: &Expr, depth: Depth) -> String {
+    let attempts: Vec<MatchAttempt> = depth_range(current_expr, depth)
+        .map(|depth| trace_match(current_expr, left, depth))
+        .collect();
+    let failures: Option<Vec<MatchFailure>> = attempts.iter().map(MatchAttempt::failure).collect();
+    match failures.and_then(|failures| failures.into_iter().next()) {
+        Some(failure) => failure.to_string(),
+        None => "the rule derives the same expression".to_string(),
+    }
+}
+
+// Match attempts of the statement that is currently interpreted, which are printed with 'set trace on'.
+#[derive(Debug, Default)]
+pub struct Trace {
+    pub enabled: bool,
+
+    // Each traversal of the current expression with the left expression of a rule, in order of application
+    pub attempts: Mutex<Vec<MatchAttempt>>,
+}
+
+// The expressions that the variables and segments of a left expression are bound to by a match, in order of their 
+// first occurrence, where a variable is bound to a single expression and a segment to any number of arguments
+pub type Bindings = Vec<(Expr, Vec<Expr>)>;
+
+// The outcome of matching the left expression of a rule against every sub-expression at a depth
+#[derive(Debug, Clone, PartialEq)]
+pub struct MatchAttempt {
+    pub left: Expr,
+    pub depth: usize,
+    pub outcomes: Vec<(Expr, Result<Bindings, MatchFailure>)>,
+}
+
+impl MatchAttempt {
+    // Why the rule did not change any sub-expression, which is the first failure, as every sub-expression at the depth failed
+    pub fn failure(&self) -> Option<MatchFailure> {
+        match self.outcomes.first() {
+            None => Some(MatchFailure::NoExprAtDepth { depth: self.depth }),
+            Some(_) if self.outcomes.iter().any(|(_, outcome)| outcome.is_ok()) => None,
+            Some((_, outcome)) => outcome.clone().err(),
+        }
+    }
+}
+
+// Name of the pattern matching context that is current until another context is started
+pub const DEFAULT_CONTEXT: &str = "main";
+
+// The state of a pattern matching context that is not current, which is restored when switching to it.
+#[derive(Debug, Default)]
+pub struct Context {
+    pub history: Vec<Expr>,
+    pub derivation_history: Vec<AppliedRule>,
+    pub seen_exprs: HashSet<Expr>,
+    pub checkpoints: HashMap<String, (Vec<Expr>, Vec<AppliedRule>)>,
+    pub redo_steps: Vec<(Expr, AppliedRule)>,
+    pub focus: Vec<Focus>,
+    pub is_matching: bool,
+}
+
+// A sub-expression that is rewritten on its own, with the pattern matching context of the expression around it,
+// which is restored by unfocus with the rewritten sub-expression spliced in at the path.
+#[derive(Debug)]
+pub struct Focus {
+    pub path: Vec<usize>,
+    pub outer: Context,
+}
 
 pub struct Env {
     // History of all expressions after applying transformations.
     pub history: Vec<Expr>,
 
     // History of all expressions after applying transformations.
-    pub derivation_history: Vec<(Expr, Expr, usize)>,
+    pub derivation_history: Vec<AppliedRule>,
+
+    // All expressions derived in the current pattern matching context, to detect cycles
+    pub seen_exprs: HashSet<Expr>,
     
     // True if in pattern matching state and false if in global state
     pub is_matching: bool,
 
-    // Rules hashmap from (string -> (lhs-expr, rhs-expr))
-    pub rules: HashMap<String, (Expr, Expr)>,
+    // Rules hashmap from (string -> rule)
+    pub rules: HashMap<String, Rule>,
 
     // Warnings that need to be printed to the user
-    pub warnings: Vec<Warning>
+    pub warnings: Vec<Warning>,
+
+    // Limits on the size of derived expressions
+    pub limits: Limits,
+
+    // Measurements of rule applications
+    pub stats: Stats,
+
+    // Rewritten sub-expressions of earlier rule applications, which are shared by all pattern matching contexts
+    pub match_cache: MatchCache,
+
+    // Match attempts of rule applications
+    pub trace: Trace,
+
+    // Number of threads that rewrite sibling sub-expressions of large expressions
+    pub jobs: usize,
+
+    // If true, a statement that produces warnings stops the interpretation with an error
+    pub strict: bool,
+
+    // Prompt and verbosity of the printed output
+    pub config: Config,
+
+    // If true, only the rewritten regions of an intermediate expression are printed, instead of the entire expression
+    pub diff: bool,
+
+    // Codes of the warnings that are suppressed
+    pub allowed_warnings: HashSet<String>,
+
+    // Notations of the show statement, by name
+    pub formatters: HashMap<String, Box<dyn Formatter>>,
+
+    // Expressions named by let statements, which replace the variables with the same name
+    pub bindings: HashMap<Expr, Expr>,
+
+    // Template that derivations aim for, which is reported after each rule application, and which 'prove EXPR == goal' searches for
+    pub goal: Option<Expr>,
+
+    // Snapshots of the history and derivation history of the current pattern matching context, by name
+    pub checkpoints: HashMap<String, (Vec<Expr>, Vec<AppliedRule>)>,
+
+    // Steps that were undone or rolled back, with the last undone step on top, until another step is derived
+    pub redo_steps: Vec<(Expr, AppliedRule)>,
+
+    // Enclosing pattern matching contexts of the focused sub-expression, with the innermost focus on top
+    pub focus: Vec<Focus>,
+
+    // Declared number of arguments of functors, by identifier
+    pub arities: HashMap<String, usize>,
+
+    // Functors whose arguments are sorted by canonicalize, and compared regardless of order by check, expect and prove
+    pub commutative: HashSet<String>,
+
+    // Infix operators of the binary functors, which are printed with their symbol
+    pub operators: Operators,
+
+    // Receivers of the events of the interpretation, in the order they subscribed
+    pub observers: Vec<Box<dyn Observer>>,
+
+    // Picks the match of a rule if it matches several sub-expressions, which only the REPL does
+    pub chooser: Option<Box<dyn Chooser>>,
+
+    // Destination of the printed output, which is stdout unless it is captured, e.g., by a library or a test
+    pub output: Box<dyn Write + Send>,
+
+    // Number of the last fresh variables, which are introduced by 'fresh v' in the right expression of a rule
+    pub fresh_count: AtomicUsize,
+
+    // Name of the current pattern matching context
+    pub context: String,
+
+    // Pattern matching contexts that are not current, by name, such that expressions can be rewritten side by side
+    pub contexts: HashMap<String, Context>,
+
+    // Directory of the interpreted file, which relative paths of end, export and import are resolved against, 
+    // or the working directory if there is none, e.g., in the REPL
+    pub base_dir: Option<PathBuf>,
+
+    // Paths of the rule libraries that were imported from files, e.g., such that watch mode also interprets 
+    // the files again when one of the libraries changes
+    pub imports: Vec<String>,
+}
+
+impl Default for Env {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl Env {
@@ -25,315 +486,1779 @@ impl Env {
         Self { 
             history: vec![],
             derivation_history: vec![],
+            seen_exprs: HashSet::new(),
             is_matching: false, 
             rules: HashMap::new(),
-            warnings: vec![]
+            warnings: vec![],
+            limits: Limits::new(),
+            stats: Stats::default(),
+            match_cache: MatchCache::default(),
+            trace: Trace::default(),
+            jobs: 1,
+            strict: false,
+            config: Config::default(),
+            diff: false,
+            allowed_warnings: HashSet::new(),
+            formatters: default_formatters()
+                .into_iter()
+                .map(|(name, formatter)| (name.to_string(), formatter))
+                .collect(),
+            bindings: HashMap::new(),
+            goal: None,
+            fresh_count: AtomicUsize::new(0),
+            context: DEFAULT_CONTEXT.to_string(),
+            contexts: HashMap::new(),
+            base_dir: None,
+            imports: vec![],
+            checkpoints: HashMap::new(),
+            redo_steps: vec![],
+            focus: vec![],
+            arities: HashMap::new(),
+            commutative: HashSet::new(),
+            operators: Operators::default(),
+            observers: vec![],
+            chooser: None,
+            output: Box::new(io::stdout()),
         }
     }
 
-    pub fn print_prefix(&self) {
-        if self.is_matching {
-            print!("    ~> ");
-        } else {
-            print!("> ");
+    // Print to the given destination instead of stdout. Output that cannot be written is dropped, 
+    // as it does not affect the interpretation.
+    pub fn set_output(&mut self, output: impl Write + Send + 'static) {
+        self.output = Box::new(output);
+    }
+
+    // Lex the source with the symbols of the operators that the environment has defined so far
+    pub fn lex(&self, input_string: &str) -> Lexer {
+        let mut lexer = Lexer::new();
+        for op in self.operators.iter() {
+            lexer.add_symbol(&op.symbol);
         }
+        lexer.lex(input_string);
+        lexer
     }
 
-    fn get_expr(&self) -> Option<&Expr> {
-        if self.history.len() == 0 {
-            None
+    // A parser of the syntax that knows the operators that the environment has defined so far, like their rules
+    pub fn parser(&self, syntax: Syntax) -> Parser {
+        let mut parser = Parser::with_syntax(syntax);
+        parser.operators = self.operators.clone();
+        parser
+    }
+
+    // Receive the events of the interpretation, in addition to the printed output.
+    pub fn subscribe(&mut self, observer: Box<dyn Observer>) {
+        self.observers.push(observer);
+    }
+
+    fn emit(&mut self, event: Event) {
+        for observer in &mut self.observers {
+            observer.notify(&event);
+        }
+    }
+
+    // Number of steps of the derivation in the current pattern matching context, which numbers the current expression
+    // like the steps of an export, where the expression that started the context is step 0.
+    pub fn step_index(&self) -> usize {
+        self.history.len().saturating_sub(1)
+    }
+
+    pub fn print_prefix(&mut self) {
+        let _ = if self.is_matching {
+            write!(self.output, "[{}] ~> ", self.step_index())
+        } else {
+            write!(self.output, "{}", self.config.prompt)
+        };
+        let _ = self.output.flush();
+    }
+
+    // In quiet mode, only the start and result of each pattern matching context are printed, without warnings.
+    pub fn is_quiet(&self) -> bool {
+        self.config.verbosity == Verbosity::Quiet
+    }
+
+    // Prompt for the next line of an unfinished statement, aligned with the prompt of its first line
+    pub fn print_continuation_prefix(&mut self) {
+        let _ = if self.is_matching {
+            let width = format!("[{}]", self.step_index()).len();
+            write!(self.output, "{}... ", " ".repeat(width))
         } else {
-            self.history.get(self.history.len()-1)
+            write!(self.output, "... ")
+        };
+        let _ = self.output.flush();
+    }
+
+    fn get_expr(&self) -> Option<&Expr> {
+        self.history.last()
+    }
+
+    // The current expression, which exists in every pattern matching context.
+    fn current_expr(&self) -> Result<&Expr, RuntimeError> {
+        self.get_expr().ok_or(RuntimeError::NoCurrentExpr)
+    }
+
+    pub fn print_current_expr(&mut self, prefix: &str) {
+        let echo_functors = !self.is_quiet();
+        if let Some(expr) = self.history.last() {
+            print_expr(&mut self.output, prefix, expr, &self.operators, echo_functors);
+        }
+    }
+
+    // Print each step of the current derivation with the name of its rule, which the REPL does for "steps"
+    pub fn print_steps(&mut self) {
+        let Some(first_expr) = self.history.first() else {
+            let _ = writeln!(self.output, "There are no steps, as there is no current expression.");
+            return;
+        };
+        let _ = writeln!(self.output, "Start matching on: {}", first_expr.to_string_with(&self.operators));
+        for (i, (expr, rule)) in self.history[1..].iter().zip(&self.derivation_history).enumerate() {
+            let _ = writeln!(self.output, "[{}] {}: {} => {} at depth {}", 
+                i, 
+                rule.name.as_deref().unwrap_or("in-line rule"), 
+                rule.left.to_string_with(&self.operators), 
+                rule.right.to_string_with(&self.operators), 
+                rule.depth
+            );
+            let _ = writeln!(self.output, "    {}", expr.to_string_with(&self.operators));
+        }
+    }
+
+    // Identifiers of the rules with the tag, in order, except for rule schemas, as a tag does not give them arguments
+    pub fn tagged_rules(&self, tag: &str) -> Vec<String> {
+        let mut idens: Vec<String> = self.rules
+            .iter()
+            .filter(|(_, rule)| rule.params.is_empty() && rule.tags.iter().any(|rule_tag| rule_tag == tag))
+            .map(|(iden, _)| iden.clone())
+            .collect();
+        idens.sort();
+        idens
+    }
+
+    // Store the current pattern matching context under its name, and continue with the given context.
+    fn switch_context(&mut self, name: String, context: Context) {
+        let prev_context = self.replace_state(context);
+        let prev_name = std::mem::replace(&mut self.context, name);
+        self.contexts.insert(prev_name, prev_context);
+    }
+
+    // Continue with the state of the given pattern matching context, and return the state so far
+    fn replace_state(&mut self, context: Context) -> Context {
+        Context {
+            history: std::mem::replace(&mut self.history, context.history),
+            derivation_history: std::mem::replace(&mut self.derivation_history, context.derivation_history),
+            seen_exprs: std::mem::replace(&mut self.seen_exprs, context.seen_exprs),
+            checkpoints: std::mem::replace(&mut self.checkpoints, context.checkpoints),
+            redo_steps: std::mem::replace(&mut self.redo_steps, context.redo_steps),
+            focus: std::mem::replace(&mut self.focus, context.focus),
+            is_matching: std::mem::replace(&mut self.is_matching, context.is_matching),
         }
     }
 
-    pub fn print_current_expr(&self, prefix: &str) {
-        if let Some(expr) = self.get_expr() {
-            println!("{}{}", prefix, expr.to_string());
-            // For readability, also print as functor prefix notation
-            if find_binary_ops(expr) {
-                println!("{:indent$}As functor: {}", "", expr, indent=prefix.len());
+    // Rewrite the sub-expression at the path of the current expression on its own, as a new derivation
+    fn focus(&mut self, path: Vec<usize>) -> Result<(), RuntimeError> {
+        let current_expr = self.current_expr()?;
+        let Some(expr) = current_expr.at_path(&path).cloned() else {
+            self.warnings.push(Warning::PathDoesNotExist { path: path_to_string(&path), expr: current_expr.to_string() });
+            return Ok(());
+        };
+        let outer = self.replace_state(Context {
+            history: vec![expr.clone()],
+            seen_exprs: HashSet::from([expr]),
+            is_matching: true,
+            ..Context::default()
+        });
+        self.focus.push(Focus { path, outer });
+        self.print_current_expr("Focus on: ");
+        Ok(())
+    }
+
+    // Splice the focused sub-expression back into the expression around it, where its entire derivation is a single step
+    fn unfocus(&mut self) -> Result<(), RuntimeError> {
+        let Some(Focus { path, outer }) = self.focus.pop() else {
+            self.warnings.push(Warning::NotFocused);
+            return Ok(());
+        };
+        let inner = self.replace_state(outer);
+        let (left, right) = (inner.history.first().unwrap().clone(), inner.history.last().unwrap().clone());
+        if left == right {
+            if !self.is_quiet() {
+                self.print_current_expr("    ");
+            }
+            return Ok(());
+        }
+        let expr = self.current_expr()?.replace_at(&path, right.clone());
+        self.limits.check(&expr)?;
+        let name = Some(format!("focus path {}", path_to_string(&path)));
+        let rule = AppliedRule { name, left, right, depth: path.len(), path, at: Depth::At(0) };
+        self.push_step(expr, rule);
+        Ok(())
+    }
+
+    // The current expression of a pattern matching context, which is None if it is not pattern matching
+    fn context_expr(&self, name: &str) -> Result<Option<&Expr>, Warning> {
+        if name == self.context {
+            return Ok(self.get_expr());
+        }
+        match self.contexts.get(name) {
+            Some(context) => Ok(context.history.last()),
+            None => Err(Warning::ContextDoesNotExist(name.to_string())),
+        }
+    }
+
+    // Undo the last steps of the derivation, at most back to the expression that started the pattern matching context,
+    // such that they can be redone. Returns the number of steps that were undone.
+    pub fn undo(&mut self, num_steps: usize) -> usize {
+        let num_steps = num_steps.min(self.step_index());
+        for _ in 0..num_steps {
+            let expr = self.history.pop().unwrap();
+            if !self.history.contains(&expr) {
+                self.seen_exprs.remove(&expr);
+            }
+            let rule = self.derivation_history.pop().unwrap();
+            self.redo_steps.push((expr, rule));
+        }
+        num_steps
+    }
+
+    // Replace the current expression by an expression that was changed outside of the interpreter, e.g., in an editor,
+    // which is a single step of the derivation.
+    pub fn edit(&mut self, expr: Expr) -> Result<(), RuntimeError> {
+        self.push_entire_step("edit".to_string(), expr)
+    }
+
+    // Push a derived expression to the history, and warn if the expression was already 
+    // derived earlier in the pattern matching context. 
+    fn push_step(&mut self, expr: Expr, rule: AppliedRule) {
+        self.redo_steps.clear();
+        if self.get_expr() != Some(&expr) && !self.seen_exprs.insert(expr.clone()) {
+            self.warnings.push(Warning::RewriteCycleDetected(expr.to_string()));
+        }
+        let prev_expr = self.get_expr().cloned();
+        self.history.push(expr);
+        if !self.is_quiet() {
+            match prev_expr {
+                Some(prev_expr) if self.diff => self.print_diff(&prev_expr),
+                _ => self.print_current_expr("    "),
+            }
+        }
+        if self.config.verbosity == Verbosity::Verbose {
+            let _ = match &rule.name {
+                Some(name) => writeln!(self.output, "      (by {} at depth {})", name, rule.depth),
+                None => writeln!(self.output, "      (by {} => {} at depth {})", rule.left.to_string(), rule.right.to_string(), rule.depth),
+            };
+        }
+        if !self.observers.is_empty() {
+            self.emit(Event::StepApplied { expr: self.history.last().unwrap().clone(), rule: rule.clone() });
+        }
+        self.derivation_history.push(rule);
+    }
+
+    // Rewrite the current expression with the auto rules, innermost first, until none of them changes it. Each rewrite is
+    // a step named after its rule, and a rewrite back to an expression of the derivation stops with a warning instead.
+    fn apply_auto_rules(&mut self) -> Result<(), RuntimeError> {
+        let mut auto_rules: Vec<(String, Expr, Expr)> = self.rules
+            .iter()
+            .filter(|(_, rule)| rule.auto)
+            .map(|(iden, rule)| (iden.clone(), rule.left.clone(), rule.right.clone()))
+            .collect();
+        // The rules are tried in order of their names, such that the same steps are derived every time
+        auto_rules.sort_by(|(iden, ..), (other, ..)| iden.cmp(other));
+        'fixpoint: loop {
+            let current_expr = self.current_expr()?.clone();
+            for (iden, left, right) in auto_rules.iter() {
+                let (expr, depth) = ast_innermost_match(&current_expr, left, right, self)?;
+                if expr == current_expr {
+                    continue;
+                }
+                if self.seen_exprs.contains(&expr) {
+                    self.warnings.push(Warning::RewriteCycleDetected(expr.to_string()));
+                    return Ok(());
+                }
+                let rule = AppliedRule {
+                    name: Some(iden.clone()),
+                    left: left.clone(),
+                    right: right.clone(),
+                    depth,
+                    path: vec![],
+                    at: Depth::Innermost,
+                };
+                self.push_step(expr, rule);
+                continue 'fixpoint;
             }
+            return Ok(());
         }
     }
 
-    pub fn pop_expr(&mut self) {
-        if self.history.len() > 1 {
-            self.history.pop();
-            self.derivation_history.pop();
+    // Whether two expressions are identical up to the order of the arguments of commutative functors.
+    pub fn is_equivalent(&self, left: &Expr, right: &Expr) -> bool {
+        left == right || left.canonicalize(&self.commutative) == right.canonicalize(&self.commutative)
+    }
+
+    // Push an expression that is derived from the entire current expression by a built-in 
+    // transformation, which is recorded as a rule from the current to the derived expression.
+    fn push_entire_step(&mut self, name: String, expr: Expr) -> Result<(), RuntimeError> {
+        self.limits.check(&expr)?;
+        let left = self.current_expr()?.clone();
+        let rule = AppliedRule::new(Some(name), left, expr.clone(), 0);
+        self.push_step(expr, rule);
+        Ok(())
+    }
+
+    // Print each rewritten region of the current expression before and after the last step.
+    fn print_diff(&mut self, prev_expr: &Expr) {
+        let Some(expr) = self.get_expr().cloned() else { return; };
+        let regions = prev_expr.diff(&expr);
+        if regions.is_empty() {
             self.print_current_expr("    ");
         }
-    } 
+        for (old, new) in regions {
+            let _ = writeln!(self.output, "    - {}", old.to_string());
+            let _ = writeln!(self.output, "    + {}", new.to_string());
+        }
+    }
+
+    fn check_arities(&mut self, stmt: &Stmt) {
+        if !self.arities.is_empty() {
+            let mut warnings = arity_mismatches(stmt, &self.arities);
+            self.warnings.append(&mut warnings);
+        }
+    }
+
+    // Report the depth that was found for a rule applied 'at any' depth.
+    // Let the chooser pick one of the sub-expressions that the left expression matches at the depth, if it matches more than one.
+    // Returns the path of the chosen sub-expression and the depth to match it at, which is the given depth if all matches are chosen.
+    fn choose_match(&mut self, current_expr: &Expr, left: &Expr, depth: Depth) -> Result<(Vec<usize>, Depth), RuntimeError> {
+        // Innermost and single-step rewriting do not match at a single depth to choose from
+        if self.chooser.is_none() || matches!(depth, Depth::Innermost | Depth::Once) {
+            return Ok((vec![], depth));
+        }
+        let (match_depth, paths) = depth_range(current_expr, depth)
+            .map(|depth| (depth, match_paths(current_expr, left, depth)))
+            .find(|(_, paths)| !paths.is_empty())
+            .unwrap_or((0, vec![]));
+        if paths.len() < 2 {
+            return Ok((vec![], depth));
+        }
+
+        let candidates: Vec<(Vec<usize>, Expr)> = paths
+            .into_iter()
+            .map(|path| { let expr = current_expr.at_path(&path).unwrap().clone(); (path, expr) })
+            .collect();
+        let _ = writeln!(self.output, "    {} matches of {} at depth {}:", candidates.len(), left.to_string_with(&self.operators), match_depth);
+        for (i, (path, expr)) in candidates.iter().enumerate() {
+            let _ = writeln!(self.output, "        {}) path {}: {}", i + 1, path_to_string(path), expr.to_string_with(&self.operators));
+        }
+        let _ = self.output.flush();
+        match self.chooser.as_mut().unwrap().choose(&candidates) {
+            Choice::One(i) if i < candidates.len() => Ok((candidates[i].0.clone(), Depth::At(0))),
+            _ => Ok((vec![], depth))
+        }
+    }
+
+    // Report whether the current expression has the shape of the goal, if there is one.
+    fn print_goal(&mut self) -> Result<(), RuntimeError> {
+        let Some(goal) = self.goal.as_ref().filter(|_| !self.is_quiet()) else {
+            return Ok(());
+        };
+        let reached = if matches_goal(self.current_expr()?, goal) { "reached" } else { "not reached yet" };
+        let _ = writeln!(self.output, "    (goal {} {})", goal.to_string_with(&self.operators), reached);
+        Ok(())
+    }
+
+    // Report the depth that was found for a rule that is not applied at a single depth, and for a rule applied 'once',
+    // the path of the sub-expression it rewrote in the previous expression.
+    fn print_matched_depth(&mut self, depth: Depth, prev_expr: &Expr, rule: &AppliedRule) {
+        if matches!(depth, Depth::At(_)) || self.is_quiet() {
+            return;
+        }
+        let path = Some(depth)
+            .filter(|depth| *depth == Depth::Once)
+            .and_then(|_| first_match_path(prev_expr, &rule.left))
+            .filter(|path| !path.is_empty());
+        let _ = match path {
+            Some(path) => writeln!(self.output, "    (matched at depth {}, path {})", rule.depth, path_to_string(&path)),
+            None => writeln!(self.output, "    (matched at depth {})", rule.depth),
+        };
+    }
+
+    // Print the match attempts of the statement, which are only recorded with 'set trace on'.
+    fn print_trace(&mut self) {
+        let attempts = std::mem::take(self.trace.attempts.get_mut().unwrap());
+        for attempt in attempts {
+            let _ = writeln!(self.output, "    Trace of {} at depth {}:", attempt.left.to_string(), attempt.depth);
+            if attempt.outcomes.is_empty() {
+                let _ = writeln!(self.output, "        {}", MatchFailure::NoExprAtDepth { depth: attempt.depth });
+            }
+            for (expr, outcome) in attempt.outcomes {
+                let _ = match outcome {
+                    Ok(bindings) if bindings.is_empty() => writeln!(self.output, "        {}: matched", expr.to_string()),
+                    Ok(bindings) => writeln!(self.output, "        {}: matched with {}", expr.to_string(), bindings_to_string(&bindings)),
+                    Err(failure) => writeln!(self.output, "        {}: {}", expr.to_string(), failure),
+                };
+            }
+        }
+    }
+
+    // Record the measurements of a statement that applied rules, which started at the given instant if it was measured.
+    fn record_stats(&mut self, start: Option<Instant>) {
+        let stats = ApplyStats {
+            elapsed: start.map_or(Duration::ZERO, |start| start.elapsed()),
+            num_nodes: self.get_expr().map_or(0, Expr::num_nodes),
+            match_attempts: self.stats.match_attempts.swap(0, Ordering::Relaxed),
+            cache_hits: self.stats.cache_hits.swap(0, Ordering::Relaxed),
+        };
+        if self.stats.enabled {
+            let _ = writeln!(self.output, "    {}", stats);
+        }
+        self.stats.applies.push(stats);
+    }
 
-    pub fn interpret(&mut self, stmts: Vec<Stmt>) -> Result<(), Box<dyn Error>> {
+    pub fn interpret(&mut self, stmts: Vec<Stmt>) -> Result<(), RuntimeError> {
 
         // interpret each parsed statement.
         for stmt in stmts {
+            // The clock is only read for --stats, as it is unavailable in WebAssembly without JavaScript
+            let start = self.stats.enabled.then(Instant::now);
+            self.limits.start_clock();
+            self.stats.match_attempts.store(0, Ordering::Relaxed);
+            self.stats.cache_hits.store(0, Ordering::Relaxed);
+            self.trace.attempts.get_mut().unwrap().clear();
+            self.check_arities(&stmt);
+            // Warnings before this statement are already emitted
+            let mut num_warnings = self.warnings.len();
             // match on a statement and global/matching state.
             match (stmt, self.is_matching) {
                 // These cases have no effect, and thus produce warnings
                 (Stmt::ExprStmt(_), true) => self.warnings.push(Warning::ExprHasNoEffect),
                 (Stmt::ApplyStmt { .. }, false) => self.warnings.push(Warning::ApplyRuleNoEffect),
                 (Stmt::RuleStmt { .. }, false) => self.warnings.push(Warning::InLineRuleNoEffect),
-                (Stmt::EndStmt(_), false) => self.warnings.push(Warning::EndStmtHasNoEffect),
+                (Stmt::EndStmt { .. }, false) => self.warnings.push(Warning::EndStmtHasNoEffect),
+                (Stmt::ShowStmt(_), false) => self.warnings.push(Warning::ShowStmtHasNoEffect),
+                (Stmt::PreviewStmt { .. }, false) => self.warnings.push(Warning::PreviewStmtHasNoEffect),
+                (Stmt::CheckpointStmt(_) | Stmt::GotoStmt(_), false) => self.warnings.push(Warning::CheckpointStmtHasNoEffect),
+                (Stmt::RollbackStmt(_) | Stmt::RedoStmt, false) => self.warnings.push(Warning::RollbackStmtHasNoEffect),
+                (Stmt::CheckStmt(_), false) => self.warnings.push(Warning::CheckStmtHasNoEffect),
+                (Stmt::FlattenStmt(_) | Stmt::UnflattenStmt(_), false) => self.warnings.push(Warning::FlattenStmtHasNoEffect),
+                (Stmt::CanonicalizeStmt, false) => self.warnings.push(Warning::CanonicalizeStmtHasNoEffect),
+                (Stmt::ReplaceStmt { .. }, false) => self.warnings.push(Warning::ReplaceStmtHasNoEffect),
+                (Stmt::FocusStmt(_) | Stmt::UnfocusStmt, false) => self.warnings.push(Warning::FocusStmtHasNoEffect),
+                (Stmt::FindStmt(_), false) => self.warnings.push(Warning::FindStmtHasNoEffect),
                 // If an expression is found, and we are not pattern matching
                 // i.e., currently still in the global state, then start pattern matching.
                 (Stmt::ExprStmt(expr), false) => {
+                    let expr = construct_rhs(&expr, &self.bindings);
                     self.is_matching = true;
-                    self.history.push(expr);
+                    self.stats.context_start = self.stats.applies.len();
+                    self.seen_exprs.insert(expr.clone());
+                    self.history.push(expr.clone());
                     self.print_current_expr("Start matching on: ");
+                    self.emit(Event::MatchStarted(expr));
                 },
                 // If an apply statement is found while in pattern matching state.
-                (Stmt::ApplyStmt { iden, depth }, true) => {
-                    // If variable identifier is a rule, then pattern match on the rule.
-                    if self.rules.contains_key(&iden) {
-                        
-                        let (left, right) = self.rules.get(&iden).unwrap();
-                        self.history.push(ast_traverse_match(
-                            self.get_expr().unwrap().clone(), 
-                            &left, 
-                            &right,
-                            depth,
-                        )?);
-                        self.derivation_history.push((left.to_owned(), right.to_owned(), depth));
-                        self.print_current_expr("    ");
-                    } else {
-                        self.warnings.push(Warning::RuleDoesNotExist(iden));
+                (Stmt::ApplyStmt { strategy, depth }, true) => {
+                    let current_expr = self.current_expr()?.clone();
+                    // A single rule is only applied to the chosen match, which is rewritten on its own
+                    let (path, runner_depth) = match &strategy {
+                        Strategy::Rule { iden, args } => match self.rules.get(iden).filter(|rule| rule.params.len() == args.len()) {
+                            Some(rule) => {
+                                let (left, _) = rule.instantiate(args);
+                                self.choose_match(&current_expr, &left, depth)?
+                            },
+                            None => (vec![], depth)
+                        },
+                        _ => (vec![], depth)
+                    };
+                    let mut runner = StrategyRunner::new(self, runner_depth);
+                    let res = runner.run(&strategy, current_expr.at_path(&path).unwrap().clone())?;
+                    let (steps, mut warnings, mismatch) = (runner.steps, runner.warnings, runner.mismatch);
+                    self.print_trace();
+                    // If the strategy failed, the expression is left unchanged.
+                    if res.is_none() && warnings.is_empty() {
+                        if !self.is_quiet() {
+                            self.print_current_expr("    ");
+                        }
+                        warnings.extend(mismatch);
+                    }
+                    self.warnings.append(&mut warnings);
+                    let is_rewritten = !steps.is_empty();
+                    // Each rule application of the strategy is a separate step in the history.
+                    for (expr, mut rule) in steps {
+                        let expr = current_expr.replace_at(&path, expr);
+                        self.limits.check(&expr)?;
+                        rule.depth += path.len();
+                        rule.path = path.clone();
+                        let prev_expr = self.current_expr()?.clone();
+                        self.push_step(expr, rule.clone());
+                        self.print_matched_depth(depth, &prev_expr, &rule);
+                    }
+                    if is_rewritten {
+                        self.apply_auto_rules()?;
+                    }
+                    self.print_goal()?;
+                    self.record_stats(start);
+                },
+                // A preview runs the strategy like an apply statement, but the derived expression 
+                // is only printed, and neither added to the history nor checked for cycles.
+                (Stmt::PreviewStmt { strategy, depth }, true) => {
+                    let current_expr = self.current_expr()?.clone();
+                    let mut runner = StrategyRunner::new(self, depth);
+                    let res = runner.run(&strategy, current_expr.clone())?;
+                    let mut warnings = runner.warnings;
+                    self.print_trace();
+                    self.warnings.append(&mut warnings);
+                    let echo_functors = !self.is_quiet();
+                    print_expr(&mut self.output, "    Preview: ", &res.unwrap_or(current_expr), &self.operators, echo_functors);
+                },
+                // Expressions are hash-consed, so structurally identical expressions are equal, 
+                // up to the order of the arguments of commutative functors.
+                (Stmt::CheckStmt(expr), true) => {
+                    let expr = construct_rhs(&expr, &self.bindings);
+                    let current_expr = self.current_expr()?;
+                    if self.is_equivalent(current_expr, &expr) {
+                        if !self.is_quiet() {
+                            let _ = writeln!(self.output, "    Check passed: {}", expr.to_string());
+                        }
+                    } else {
+                        self.warnings.push(Warning::CheckFailed { 
+                            expected: expr.to_string(), 
+                            got: current_expr.to_string() 
+                        });
+                    }
+                },
+                // Unlike a check, a failed expectation stops the interpretation, also outside of a pattern matching context.
+                (Stmt::ExpectStmt(expr), _) => {
+                    let expr = construct_rhs(&expr, &self.bindings);
+                    let current_expr = self.current_expr()?;
+                    if !self.is_equivalent(current_expr, &expr) {
+                        return Err(RuntimeError::ExpectationFailed { 
+                            expected: expr.to_string(), 
+                            got: current_expr.to_string() 
+                        });
+                    }
+                },
+                (Stmt::CheckpointStmt(name), true) => {
+                    self.checkpoints.insert(name, (self.history.clone(), self.derivation_history.clone()));
+                },
+                // Jumping back to a checkpoint keeps all checkpoints, so that other directions can be explored.
+                (Stmt::GotoStmt(name), true) => {
+                    match self.checkpoints.get(&name) {
+                        Some((history, derivation_history)) => {
+                            self.history = history.clone();
+                            self.derivation_history = derivation_history.clone();
+                            self.seen_exprs = self.history.iter().cloned().collect();
+                            self.redo_steps.clear();
+                            if !self.is_quiet() {
+                                self.print_current_expr("    ");
+                            }
+                        },
+                        None => self.warnings.push(Warning::CheckpointDoesNotExist(name)),
+                    }
+                },
+                // The steps after the given step are kept, so that a rollback can be redone step by step.
+                (Stmt::RollbackStmt(step), true) => {
+                    let num_steps = self.step_index();
+                    if step > num_steps {
+                        self.warnings.push(Warning::StepDoesNotExist { step, num_steps });
+                    } else {
+                        self.undo(num_steps - step);
+                        if !self.is_quiet() {
+                            self.print_current_expr("    ");
+                        }
+                    }
+                },
+                (Stmt::RedoStmt, true) => {
+                    match self.redo_steps.pop() {
+                        Some((expr, rule)) => {
+                            let redo_steps = std::mem::take(&mut self.redo_steps);
+                            self.push_step(expr, rule);
+                            self.redo_steps = redo_steps;
+                        },
+                        None => self.warnings.push(Warning::NothingToRedo),
+                    }
+                },
+                // A new context starts in the global state, so the next expression statement starts pattern matching in it.
+                (Stmt::ContextStmt(name), _) => {
+                    if name == self.context || self.contexts.contains_key(&name) {
+                        self.warnings.push(Warning::ContextAlreadyExists(name));
+                    } else {
+                        self.switch_context(name, Context::default());
+                    }
+                },
+                (Stmt::SwitchStmt(name), _) => {
+                    match self.contexts.remove(&name) {
+                        None if name != self.context => self.warnings.push(Warning::ContextDoesNotExist(name)),
+                        context => {
+                            if let Some(context) = context {
+                                self.switch_context(name, context);
+                            }
+                            if !self.is_quiet() {
+                                self.print_current_expr("    ");
+                            }
+                        }
+                    }
+                },
+                (Stmt::EqualStmt { left, right }, _) => {
+                    let exprs = self.context_expr(&left).and_then(|left_expr| Ok((left_expr, self.context_expr(&right)?)));
+                    match exprs {
+                        Ok((Some(left_expr), Some(right_expr))) => {
+                            if !self.is_equivalent(left_expr, right_expr) {
+                                self.warnings.push(Warning::ContextsDiffer { 
+                                    left_expr: left_expr.to_string(), 
+                                    right_expr: right_expr.to_string(),
+                                    left, 
+                                    right, 
+                                });
+                            } else if !self.is_quiet() {
+                                let expr = left_expr.to_string();
+                                let _ = writeln!(self.output, "    Equal: {} and {} both derive {}", left, right, expr);
+                            }
+                        },
+                        Ok((None, _)) => self.warnings.push(Warning::ContextHasNoExpr(left)),
+                        Ok((_, None)) => self.warnings.push(Warning::ContextHasNoExpr(right)),
+                        Err(warn) => self.warnings.push(warn),
+                    }
+                },
+                (Stmt::FlattenStmt(iden), true) => {
+                    let expr = self.current_expr()?.flatten(&iden);
+                    self.push_entire_step(format!("flatten {}", iden), expr)?;
+                },
+                (Stmt::UnflattenStmt(iden), true) => {
+                    let expr = self.current_expr()?.unflatten(&iden);
+                    self.push_entire_step(format!("unflatten {}", iden), expr)?;
+                },
+                (Stmt::CanonicalizeStmt, true) => {
+                    let expr = self.current_expr()?.canonicalize(&self.commutative);
+                    self.push_entire_step("canonicalize".to_string(), expr)?;
+                },
+                (Stmt::FocusStmt(path), true) => self.focus(path)?,
+                (Stmt::GoalStmt(goal), _) => {
+                    self.goal = goal.map(|goal| construct_rhs(&goal, &self.bindings));
+                },
+                // Matches are listed from the top of the current expression, and from left to right at each depth
+                (Stmt::FindStmt(pattern), true) => {
+                    let pattern = construct_rhs(&pattern, &self.bindings);
+                    let current_expr = self.current_expr()?.clone();
+                    let paths: Vec<Vec<usize>> = (0..=expr_depth(&current_expr))
+                        .flat_map(|depth| match_paths(&current_expr, &pattern, depth))
+                        .collect();
+                    if paths.is_empty() {
+                        self.warnings.push(Warning::PatternNotFound { pattern: pattern.to_string(), expr: current_expr.to_string() });
+                    } else {
+                        let _ = writeln!(self.output, "    Found {} match(es) of {}:", paths.len(), pattern.to_string_with(&self.operators));
+                    }
+                    for path in paths {
+                        let expr = current_expr.at_path(&path).unwrap().to_string_with(&self.operators);
+                        let _ = if path.is_empty() {
+                            writeln!(self.output, "        depth 0: {}", expr)
+                        } else {
+                            writeln!(self.output, "        depth {}, path {}: {}", path.len(), path_to_string(&path), expr)
+                        };
+                    }
+                },
+                (Stmt::UnfocusStmt, true) => self.unfocus()?,
+                (Stmt::ReplaceStmt { old, new }, true) => {
+                    let (old, new) = (construct_rhs(&old, &self.bindings), construct_rhs(&new, &self.bindings));
+                    let current_expr = self.current_expr()?.clone();
+                    let expr = current_expr.replace(&old, &new);
+                    if expr == current_expr && old != new {
+                        self.warnings.push(Warning::SubExprDoesNotOccur { sub_expr: old.to_string(), expr: current_expr.to_string() });
+                    } else {
+                        self.push_entire_step(format!("replace {} with {}", old.to_string(), new.to_string()), expr)?;
                     }
                 },
                 // Define statements can be constructed in either global or matching state.
-                (Stmt::DefineStmt { iden, left, right }, _) => {
-                    self.rules.insert(iden, (left, right));
+                (Stmt::DefineStmt { iden, params, left, right, tags, auto }, _) => {
+                    let rule = Rule { params, left, right, tags, auto };
+                    self.warnings.extend(lint_rule(&iden, &rule, self.rules.iter()));
+                    self.rules.insert(iden, rule);
                 },
                 // In-line rule statements are directly mathed upon.
                 (Stmt::RuleStmt { left, right, depth}, true) => {
-                    self.history.push(ast_traverse_match(
-                        self.get_expr().unwrap().clone(), 
+                    let current_expr = self.current_expr()?.clone();
+                    let (path, match_depth) = self.choose_match(&current_expr, &left, depth)?;
+                    let (expr, rule_depth) = ast_traverse_match_at(
+                        current_expr.at_path(&path).unwrap(), 
                         &left, 
                         &right,
-                        depth,
-                    )?);
-                    self.derivation_history.push((left, right, depth));
-                    self.print_current_expr("    ");
+                        match_depth,
+                        self,
+                    )?;
+                    let at = recorded_depth(match_depth, rule_depth);
+                    let (expr, rule_depth) = (current_expr.replace_at(&path, expr), rule_depth + path.len());
+                    self.limits.check(&expr)?;
+                    self.print_trace();
+                    let current_expr = self.current_expr()?.clone();
+                    if expr == current_expr {
+                        let reason = no_match_reason(&current_expr, &left, depth);
+                        self.warnings.push(Warning::RuleDidNotMatch { 
+                            rule: format!("{} => {}", left.to_string(), right.to_string()), 
+                            depth: depth.to_string(), 
+                            reason 
+                        });
+                    }
+                    let is_rewritten = expr != current_expr;
+                    let rule = AppliedRule { name: None, left, right, depth: rule_depth, path, at };
+                    self.push_step(expr, rule.clone());
+                    self.print_matched_depth(depth, &current_expr, &rule);
+                    if is_rewritten {
+                        self.apply_auto_rules()?;
+                    }
+                    self.print_goal()?;
+                    self.record_stats(start);
+                },
+                (Stmt::ShowStmt(notation), true) => {
+                    match self.formatters.get(&notation) {
+                        Some(formatter) => {
+                            let formatted = formatter.format(self.current_expr()?);
+                            let _ = writeln!(self.output, "    {}", formatted);
+                        },
+                        None => {
+                            let mut available: Vec<String> = self.formatters.keys().cloned().collect();
+                            available.sort();
+                            self.warnings.push(Warning::UnknownNotation { notation, available });
+                        }
+                    }
+                },
+                // Bound expressions are expanded immediately, so bindings may refer to earlier bindings.
+                (Stmt::LetStmt { iden, expr }, _) => {
+                    let expr = construct_rhs(&expr, &self.bindings);
+                    self.bindings.insert(Expr::variable(iden), expr);
+                },
+                (Stmt::FunctorStmt(arities), _) => {
+                    for (iden, arity) in arities.iter() {
+                        self.warnings.extend(self.operators.check_arity(iden, *arity));
+                    }
+                    self.arities.extend(arities);
+                },
+                (Stmt::InfixStmt(op) | Stmt::OperatorStmt(op), _) => {
+                    let iden = op.iden.clone();
+                    match self.operators.define(op) {
+                        Ok(()) => self.warnings.extend(self.arities.get(&iden).and_then(|arity| self.operators.check_arity(&iden, *arity))),
+                        Err(warning) => self.warnings.push(warning),
+                    }
+                },
+                (Stmt::CommutativeStmt(idens), _) => self.commutative.extend(idens),
+                (Stmt::ExportRulesStmt(file_path), _) => {
+                    let file_path = expand_placeholders(&self.resolve_path(&file_path));
+                    write_file(&file_path, export_library(&self.rules, &self.operators, &self.commutative), false)?;
+                },
+                (Stmt::ImportStmt(file_path), _) => {
+                    self.import_rules(file_path)?;
+                    // The statements of the library already emitted their warnings
+                    num_warnings = self.warnings.len();
+                },
+                (Stmt::CheckTerminationStmt(idens), _) => {
+                    for iden in idens {
+                        self.check_rule_termination(iden);
+                    }
+                },
+                (Stmt::OverlapsStmt(idens), _) => self.print_critical_pairs(idens),
+                // Unifiers are computed independently of the current expression, like proofs.
+                (Stmt::UnifyStmt { left, right }, _) => {
+                    let (left, right) = (construct_rhs(&left, &self.bindings), construct_rhs(&right, &self.bindings));
+                    match unify(&left, &right) {
+                        Some(unifier) => {
+                            let _ = writeln!(self.output, "Unifier of {} and {}:", left.to_string(), right.to_string());
+                            let mut vars: Vec<(String, String)> = unifier
+                                .iter()
+                                .map(|(var, expr)| (var.to_string(), expr.to_string()))
+                                .collect();
+                            vars.sort();
+                            for (var, expr) in vars {
+                                let _ = writeln!(self.output, "    {} = {}", var, expr);
+                            }
+                            let _ = writeln!(self.output, "    Unified: {}", substitute(&left, &unifier).to_string());
+                        },
+                        None => self.warnings.push(Warning::NotUnifiable { 
+                            left: left.to_string(), 
+                            right: right.to_string() 
+                        })
+                    }
+                },
+                // The intermediate expressions of the block are not printed, only the expression 
+                // it results in, e.g., when many statements are pasted into the REPL at once.
+                (Stmt::SilentStmt(stmts), _) => {
+                    let num_stmts = stmts.len();
+                    let verbosity = std::mem::replace(&mut self.config.verbosity, Verbosity::Quiet);
+                    let res = self.interpret(stmts);
+                    self.config.verbosity = verbosity;
+                    // The statements of the block already emitted their warnings
+                    num_warnings = self.warnings.len();
+                    res?;
+                    let _ = writeln!(self.output, "Silently interpreted {} statement(s)", num_stmts);
+                    if self.is_matching && !self.is_quiet() {
+                        self.print_current_expr("    ");
+                    }
+                },
+                (Stmt::AllowStmt(codes), _) => {
+                    for code in codes {
+                        self.allow_warning(code);
+                    }
                 },
-                (Stmt::EndStmt(path), true) => { 
+                (Stmt::SetStmt(setting), _) => {
+                    match setting {
+                        Setting::MaxDepth(n) => self.limits.max_depth = n,
+                        Setting::MaxNodes(n) => self.limits.max_nodes = n,
+                        Setting::Prompt(prompt) => self.config.prompt = prompt,
+                        Setting::Preamble(preamble) => self.config.preamble = preamble,
+                        Setting::Verbosity(verbosity) => self.config.verbosity = verbosity,
+                        Setting::Trace(on) => self.trace.enabled = on,
+                        Setting::Timeout(timeout) => self.limits.timeout = timeout,
+                    }
+                },
+                // Proofs are searched for independently of the current expression.
+                (Stmt::ProveStmt { left, right, rules }, _) => self.print_proof(left, right, rules)?,
+                (Stmt::EndStmt { path, append, format, binding }, true) => { 
+                    // The result is the entire expression, rather than a focused sub-expression
+                    while !self.focus.is_empty() {
+                        self.unfocus()?;
+                    }
                     self.print_current_expr("Result: ");
-                    match path {
-                        Some(file_path) => { self.write_to_file(file_path)?; },
+                    self.emit(Event::Result(self.current_expr()?.clone()));
+                    if let Some(file_path) = path {
+                        let name = format.unwrap_or_else(|| "text".to_string());
+                        match ExportFormat::from_name(&name) {
+                            Some(format) => self.write_to_file(file_path, format, append)?,
+                            None => self.warnings.push(Warning::UnknownExportFormat {
+                                format: name,
+                                available: ExportFormat::NAMES.iter().map(|name| name.to_string()).collect()
+                            }),
+                        }
+                    }
+                    // The result can start the next pattern matching context under the given name, or 
+                    // the derivation can be applied as a rule. As the variables in the arguments of the 
+                    // left expression match any sub-expression, the initial expression is already general.
+                    match binding {
+                        Some(Binding::Expr(iden)) => {
+                            self.bindings.insert(Expr::variable(iden), self.current_expr()?.clone());
+                        },
+                        Some(Binding::Rule(iden)) => {
+                            let left = self.history.first().ok_or(RuntimeError::NoCurrentExpr)?.clone();
+                            let right = self.current_expr()?.clone();
+                            self.rules.insert(iden, Rule { params: vec![], left, right, tags: vec![], auto: false });
+                        },
                         None => {}
                     }
                     self.history.clear();
                     self.derivation_history.clear();
+                    self.seen_exprs.clear();
+                    self.checkpoints.clear();
+                    self.redo_steps.clear();
                     self.is_matching = false;
                 },
             }
+
+            // Suppressed warnings are neither reported nor errors in strict mode
+            let mut new_warnings = self.warnings.split_off(num_warnings.min(self.warnings.len()));
+            self.warnings.retain(|warn| !self.allowed_warnings.contains(warn.code()));
+            new_warnings.retain(|warn| !self.allowed_warnings.contains(warn.code()));
+            for warn in &new_warnings {
+                self.emit(Event::Warning(warn.clone()));
+            }
+            self.warnings.append(&mut new_warnings);
+            if self.strict && !self.warnings.is_empty() {
+                return Err(RuntimeError::WarningInStrictMode { num_warnings: self.warnings.len() });
+            }
         }
         Ok(())
     }
 
-    fn write_to_file(&mut self, file_path: String) -> Result<(), Box<dyn Error>> {
-        let mut data = format!("Start pattern matching on {}\n", self.history.get(0).unwrap().to_string());
-        data.push_str(
-            &self.history
-            .iter()
-            .skip(1)
-            .enumerate()
-            .zip(self.derivation_history.iter())
-            .map(|((i, expr), (lhs, rhs, depth))| {
-                format!("\n{}. Applying rule: {} => {} at depth {}, results in:\n    {}\n", 
-                    i+1, 
-                    lhs.to_string(), 
-                    rhs.to_string(),
-                    depth,
-                    expr.to_string() 
-                )
+    // Make a notation available to the show statement, replacing any notation with the same name.
+    pub fn register_formatter(&mut self, name: impl Into<String>, formatter: Box<dyn Formatter>) {
+        self.formatters.insert(name.into(), formatter);
+    }
+
+    // Suppress the warnings with the given code for the remainder of the interpretation.
+    pub fn allow_warning(&mut self, code: String) {
+        if Warning::CODES.contains(&code.as_str()) {
+            self.allowed_warnings.insert(code);
+        } else {
+            self.warnings.push(Warning::UnknownWarningCode(code));
+        }
+    }
+
+    // Report whether the rule decreases in the term ordering, and warn if repeatedly applying it may not terminate.
+    fn check_rule_termination(&mut self, iden: String) {
+        let Some(rule) = self.rules.get(&iden) else {
+            self.warnings.push(Warning::RuleDoesNotExist(iden));
+            return;
+        };
+        let reason = match check_termination(&rule.left, &rule.right) {
+            Termination::Decreasing { left_size, right_size } => {
+                let decrease = if left_size == right_size {
+                    "in term order".to_string()
+                } else {
+                    format!("from {} to {} nodes", left_size, right_size)
+                };
+                let _ = writeln!(self.output, "Rule '{}' terminates: {} => {} decreases {}", iden, rule.left.to_string(), rule.right.to_string(), decrease);
+                return;
+            },
+            Termination::DuplicatedVariable(var) => 
+                format!("the variable '{}' occurs more often in the right expression than in the left expression", var),
+            Termination::NotSmaller { left_size, right_size } => 
+                format!("the right expression ({} nodes) is not smaller than the left expression ({} nodes)", right_size, left_size),
+        };
+        self.warnings.push(Warning::RuleMayNotTerminate { iden, reason });
+    }
+
+    // Print every overlap between each pair of the rules where the two rules derive different expressions, such 
+    // that the result depends on which rule is applied first. Rules whose left expression is a variable or a number 
+    // are skipped, as they only match that same variable or number, like a constant.
+    fn print_critical_pairs(&mut self, idens: Vec<String>) {
+        let rules: Vec<(String, Expr, Expr)> = self.get_proof_rules(idens)
+            .into_iter()
+            .filter(|(_, left, _)| matches!(left.node(), ExprNode::Functor { .. }))
+            .collect();
+
+        let mut num_pairs = 0;
+        for (i, (outer_iden, outer_left, outer_right)) in rules.iter().enumerate() {
+            for (j, (inner_iden, inner_left, inner_right)) in rules.iter().enumerate() {
+                for pair in critical_pairs((outer_left, outer_right), (inner_left, inner_right), i < j) {
+                    let _ = writeln!(self.output, "Overlap of '{}' and '{}' at depth {} on {}:", outer_iden, inner_iden, pair.depth, pair.peak.to_string());
+                    let _ = writeln!(self.output, "    '{}' derives {}", outer_iden, pair.by_outer.to_string());
+                    let _ = writeln!(self.output, "    '{}' derives {}", inner_iden, pair.by_inner.to_string());
+                    num_pairs += 1;
+                }
+            }
+        }
+        if num_pairs == 0 {
+            let idens: Vec<&str> = rules.iter().map(|(iden, _, _)| iden.as_str()).collect();
+            let _ = writeln!(self.output, "No overlaps between the rules {}", idens.join(", "));
+        }
+    }
+
+    // Search for a derivation of the right expression, or of an expression that matches the goal without one,
+    // and print it, independently of the current expression.
+    fn print_proof(&mut self, left: Expr, right: Option<Expr>, rules: Vec<String>) -> Result<(), RuntimeError> {
+        let (right, is_goal) = match (right, &self.goal) {
+            (Some(right), _) => (construct_rhs(&right, &self.bindings), false),
+            (None, Some(goal)) => (goal.clone(), true),
+            (None, None) => {
+                self.warnings.push(Warning::GoalNotSet);
+                return Ok(());
+            },
+        };
+        let left = construct_rhs(&left, &self.bindings);
+        let rules = self.get_proof_rules(rules);
+        let res = if is_goal {
+            prove_goal(&left, &right, &rules, self)?
+        } else {
+            prove(&left, &right, &rules, self)?
+        };
+        match res {
+            Some(steps) => {
+                let _ = writeln!(self.output, "Proof of {} == {}:", left.to_string(), right.to_string());
+                let _ = writeln!(self.output, "    {}", left.to_string());
+                // Each step names its rule, such that the proof can be replayed with apply statements
+                for (expr, rule) in steps {
+                    let _ = match &rule.name {
+                        Some(name) => writeln!(self.output, "    = {}    (by {} at depth {})", expr.to_string(), name, rule.depth),
+                        None => writeln!(self.output, "    = {}    (by {} => {} at depth {})", 
+                            expr.to_string(), 
+                            rule.left.to_string(), 
+                            rule.right.to_string(), 
+                            rule.depth
+                        ),
+                    };
+                }
+            },
+            None => self.warnings.push(Warning::ProofNotFound { 
+                left: left.to_string(), 
+                right: right.to_string(), 
+                max_steps: MAX_PROOF_STEPS 
             })
-            .collect::<String>()
-        );
-        data.push_str(&format!("\nResult: {}", self.get_expr().unwrap().to_string()));
+        }
+        Ok(())
+    }
+
+    // Collect the rules that a proof may use, which are all rules if none are specified.
+    // Rule schemas cannot be used, as there are no arguments for their meta-parameters.
+    fn get_proof_rules(&mut self, idens: Vec<String>) -> Vec<(String, Expr, Expr)> {
+        let mut idens = if idens.is_empty() {
+            let mut idens: Vec<String> = self.rules
+                .iter()
+                .filter(|(_, rule)| rule.params.is_empty())
+                .map(|(iden, _)| iden.clone())
+                .collect();
+            // Sort for a deterministic search order
+            idens.sort();
+            idens
+        } else {
+            idens
+        };
+
+        let mut rules = vec![];
+        for iden in idens.drain(..) {
+            match self.rules.get(&iden) {
+                Some(rule) if rule.params.is_empty() => rules.push((iden, rule.left.clone(), rule.right.clone())),
+                Some(rule) => self.warnings.push(Warning::RuleArityMismatch { 
+                    iden, 
+                    expected: rule.params.len(), 
+                    got: 0 
+                }),
+                None => self.warnings.push(Warning::RuleDoesNotExist(iden))
+            }
+        }
+        rules
+    }
+
+    fn write_to_file(&mut self, file_path: String, format: ExportFormat, append: bool) -> Result<(), RuntimeError> {
+        if self.history.is_empty() {
+            return Err(RuntimeError::NoCurrentExpr);
+        }
+        // The clock is only read for --stats, so the elapsed time of the context is otherwise unknown
+        let elapsed = self.stats.enabled.then(|| {
+            self.stats.applies.iter().skip(self.stats.context_start).map(|stats| stats.elapsed).sum()
+        });
+        let options = ExportOptions { elapsed, preamble: self.config.preamble.clone() };
+        let data = format.render(&self.history, &self.derivation_history, &options);
+        write_file(&expand_placeholders(&self.resolve_path(&file_path)), data, append)
+    }
+
+    // The path relative to the directory of the interpreted file, unless it is absolute
+    pub fn resolve_path(&self, file_path: &str) -> PathBuf {
+        match &self.base_dir {
+            Some(base_dir) => base_dir.join(file_path),
+            None => PathBuf::from(file_path),
+        }
+    }
 
-        fs::write(file_path, data)?;
+    // Interpret the statements of a rule library, which already emitted their warnings when this returns.
+    fn import_rules(&mut self, file_path: String) -> Result<(), RuntimeError> {
+        let resolved = resolve_library_path(&file_path, self.base_dir.as_deref());
+        if !resolved.starts_with("std:") {
+            self.imports.push(resolved.clone());
+        }
+        let stmts = read_library(&resolved).map_err(|reason| RuntimeError::ImportFailed { path: resolved, reason })?;
+        let num_rules = stmts.iter().filter(|stmt| matches!(stmt, Stmt::DefineStmt { .. })).count();
+        self.interpret(stmts)?;
+        if !self.is_quiet() {
+            let _ = writeln!(self.output, "Imported {} rule(s)", num_rules);
+        }
         Ok(())
     }
 
 }
 
+// Replace the placeholders of a path, where {n} is the smallest positive number for which the file does not exist yet, 
+// such that a script that writes many derivations does not overwrite earlier ones, and {time} is the number of seconds 
+// since the Unix epoch.
+fn expand_placeholders(file_path: &Path) -> PathBuf {
+    let mut path = file_path.to_string_lossy().into_owned();
+    if path.contains("{time}") {
+        let secs = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |elapsed| elapsed.as_secs());
+        path = path.replace("{time}", &secs.to_string());
+    }
+    if !path.contains("{n}") {
+        return PathBuf::from(path);
+    }
+    (1..)
+        .map(|n| PathBuf::from(path.replace("{n}", &n.to_string())))
+        .find(|path| !path.exists())
+        .unwrap()
+}
+
+// Write the data to the file, or add it after the existing contents of the file on a new line, 
+// after creating the directories that do not exist yet
+fn write_file(file_path: &Path, data: String, append: bool) -> Result<(), RuntimeError> {
+    let write = || -> io::Result<()> {
+        if let Some(dir) = file_path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+            fs::create_dir_all(dir)?;
+        }
+        if !append {
+            return fs::write(file_path, data);
+        }
+        let existing = fs::read_to_string(file_path).unwrap_or_default();
+        let separator = if existing.is_empty() || existing.ends_with('\n') { "" } else { "\n" };
+        let mut file = fs::OpenOptions::new().create(true).append(true).open(file_path)?;
+        write!(file, "{}{}", separator, data)
+    };
+    write().map_err(|err| RuntimeError::CannotWriteToPath { path: file_path.display().to_string(), reason: err.to_string() })
+}
+
+// Match sub-expressions at a certain depth of the current expression, 
+// and check that the resulting expression stays within the limits of the environment.
+pub fn ast_traverse_match(current_expr: &Expr, left: &Expr, right: &Expr, depth: usize, env: &Env) -> Result<Expr, RuntimeError>{
+    env.limits.check_time()?;
+    let (right, fresh_count) = instantiate_fresh(current_expr, left, right, env.fresh_count.load(Ordering::Relaxed));
+    if env.trace.enabled {
+        let attempt = trace_match(current_expr, left, depth);
+        env.trace.attempts.lock().unwrap().push(attempt);
+    }
+    let expr = if env.jobs > 1 {
+        par_traverse_match(current_expr, left, &right, depth, &env.stats, &env.limits, PARALLEL_MAX_FORKS)?
+    } else {
+        traverse_match(current_expr, left, &right, depth, &env.stats, Some(&env.match_cache), &env.limits)?
+    };
+    env.limits.check(&expr)?;
+    // Fresh variables are only used up by applications that change the expression
+    if expr != *current_expr {
+        env.fresh_count.store(fresh_count, Ordering::Relaxed);
+    }
+    Ok(expr)
+}
+
+// Match every sub-expression of the current expression from the bottom, and check that the resulting expression stays 
+// within the limits of the environment. Returns the derived expression together with the deepest depth at which it changed.
+pub fn ast_innermost_match(current_expr: &Expr, left: &Expr, right: &Expr, env: &Env) -> Result<(Expr, usize), RuntimeError> {
+    env.limits.check_time()?;
+    let (right, fresh_count) = instantiate_fresh(current_expr, left, right, env.fresh_count.load(Ordering::Relaxed));
+    if env.trace.enabled {
+        let attempts = (0..=expr_depth(current_expr)).rev().map(|depth| trace_match(current_expr, left, depth));
+        env.trace.attempts.lock().unwrap().extend(attempts);
+    }
+    let (expr, depth) = innermost_match(current_expr, left, &right, &env.stats, &env.limits)?;
+    env.limits.check(&expr)?;
+    if expr != *current_expr {
+        env.fresh_count.store(fresh_count, Ordering::Relaxed);
+    }
+    Ok((expr, depth.unwrap_or(0)))
+}
+
+// Match at the given depth, at every depth from the top until the current expression changes, or at every depth
+// of a range from the top, each on the expression derived at the depth before.
+// Returns the derived expression together with the first depth at which it changed.
+pub fn ast_traverse_match_at(current_expr: &Expr, left: &Expr, right: &Expr, depth: Depth, env: &Env) -> Result<(Expr, usize), RuntimeError> {
+    match depth {
+        Depth::At(depth) => Ok((ast_traverse_match(current_expr, left, right, depth, env)?, depth)),
+        Depth::Any => {
+            for depth in 0..=expr_depth(current_expr) {
+                let expr = ast_traverse_match(current_expr, left, right, depth, env)?;
+                if expr != *current_expr {
+                    return Ok((expr, depth));
+                }
+            }
+            Ok((current_expr.clone(), 0))
+        },
+        Depth::Range(start, end) => {
+            let mut expr = current_expr.clone();
+            let mut first_depth = None;
+            for depth in start..=end {
+                let new_expr = ast_traverse_match(&expr, left, right, depth, env)?;
+                if new_expr != expr {
+                    first_depth.get_or_insert(depth);
+                    expr = new_expr;
+                }
+            }
+            Ok((expr, first_depth.unwrap_or(start)))
+        },
+        Depth::Innermost => ast_innermost_match(current_expr, left, right, env),
+        Depth::Once => match first_match_path(current_expr, left) {
+            Some(path) => {
+                let expr = ast_traverse_match(current_expr.at_path(&path).unwrap(), left, right, 0, env)?;
+                let expr = current_expr.replace_at(&path, expr);
+                env.limits.check(&expr)?;
+                Ok((expr, path.len()))
+            },
+            None => Ok((current_expr.clone(), 0)),
+        },
+    }
+}
+
+// How a step is recorded to be applied again, where a rule applied at any depth is recorded at the depth that it changed
+pub fn recorded_depth(depth: Depth, changed_depth: usize) -> Depth {
+    match depth {
+        Depth::Any => Depth::At(changed_depth),
+        depth => depth,
+    }
+}
+
+// The depths that a rule is matched at, where any depth is every depth of the current expression
+fn depth_range(current_expr: &Expr, depth: Depth) -> std::ops::RangeInclusive<usize> {
+    match depth {
+        Depth::At(depth) => depth..=depth,
+        Depth::Any => 0..=expr_depth(current_expr),
+        Depth::Range(start, end) => start..=end,
+        Depth::Innermost | Depth::Once => 0..=expr_depth(current_expr),
+    }
+}
+
+// Replace each 'fresh v' and every other occurrence of v in the right expression by the variable v_N, where N is 
+// the lowest number after the last fresh variables such that none of the new variables occur in the current expression or the rule.
+fn instantiate_fresh(current_expr: &Expr, left: &Expr, right: &Expr, fresh_count: usize) -> (Expr, usize) {
+    let mut fresh = vec![];
+    let mut stack = vec![right];
+    while let Some(expr) = stack.pop() {
+        if let ExprNode::Functor { iden, args } = expr.node() {
+            match (iden.as_str(), args.as_slice().first().map(Expr::node)) {
+                ("fresh", Some(ExprNode::Variable { iden })) if args.len() == 1 => fresh.push((expr, iden.as_str())),
+                _ => stack.extend(args.iter()),
+            }
+        }
+    }
+    if fresh.is_empty() {
+        return (right.clone(), fresh_count);
+    }
+
+    let mut taken = HashSet::new();
+    let mut stack = vec![current_expr, left, right];
+    while let Some(expr) = stack.pop() {
+        match expr.node() {
+            ExprNode::Variable { iden } => { taken.insert(iden.as_str()); },
+            ExprNode::Number(_) | ExprNode::Str(_) | ExprNode::Segment { .. } => {},
+            ExprNode::Functor { args, .. } => stack.extend(args.iter()),
+        }
+    }
+
+    let mut n = fresh_count + 1;
+    while fresh.iter().any(|(_, iden)| taken.contains(format!("{}_{}", iden, n).as_str())) {
+        n += 1;
+    }
+    let fresh_table: HashMap<Expr, Expr> = fresh
+        .into_iter()
+        .flat_map(|(expr, iden)| {
+            let var = Expr::variable(format!("{}_{}", iden, n));
+            [(expr.clone(), var.clone()), (Expr::variable(iden), var)]
+        })
+        .collect();
+    (construct_rhs(right, &fresh_table), n)
+}
+
+// Minimum number of nodes of an expression for its arguments to be rewritten in parallel
+const PARALLEL_MIN_NODES: usize = 1024;
+
+// Maximum number of nested functors whose arguments are rewritten in parallel
+const PARALLEL_MAX_FORKS: usize = 8;
+
+// The arguments of a functor are independent of each other, so the arguments of large 
+// functors above the matching depth are rewritten in parallel on the rayon thread pool. 
+// Small expressions are rewritten sequentially, as forking costs more than it gains.
+// The match cache is skipped, as the threads would otherwise wait for each other to look up every functor.
+fn par_traverse_match(
+    current_expr: &Expr, left: &Expr, right: &Expr, depth: usize, 
+    stats: &Stats, limits: &Limits, forks: usize
+) -> Result<Expr, RuntimeError> {
+    match current_expr.node() {
+        ExprNode::Functor { iden, args } 
+            if depth > 0 && forks > 0 && args.len() > 1 && current_expr.num_nodes() >= PARALLEL_MIN_NODES => 
+        {
+            let args = args
+                .par_iter()
+                .map(|arg| par_traverse_match(arg, left, right, depth - 1, stats, limits, forks - 1))
+                .collect::<Result<_, _>>()?;
+            Ok(Expr::functor(iden.as_str(), args))
+        },
+        _ => traverse_match(current_expr, left, right, depth, stats, None, limits)
+    }
+}
+
+// Match the left expression against every sub-expression at the depth, like traverse_match, 
+// but record the outcome of each match instead of rebuilding the current expression.
+fn trace_match(current_expr: &Expr, left: &Expr, depth: usize) -> MatchAttempt {
+    let mut outcomes = vec![];
+    let mut stack = vec![(current_expr, depth)];
+    while let Some((expr, depth)) = stack.pop() {
+        match (expr.node(), depth) {
+            (_, 0) => outcomes.push((expr.clone(), match_bindings(expr, left))),
+            (ExprNode::Functor { args, .. }, _) => stack.extend(args.iter().rev().map(|arg| (arg, depth - 1))),
+            _ => {}
+        }
+    }
+    MatchAttempt { left: left.clone(), depth, outcomes }
+}
+
+// The bindings of the left expression of a step at each sub-expression that it rewrote, by path from left to right. 
+// A matched sub-expression counts as rewritten if it differs in the derived expression, or every match does if none differs,
+// e.g., for a rule that derives the same expression. The bindings are recomputed from the recorded step, rather than stored 
+// by every rule application, as only exports need them.
+pub fn step_bindings(prev_expr: &Expr, rule: &AppliedRule, next_expr: &Expr) -> Vec<(Vec<usize>, Bindings)> {
+    let matches: Vec<(Vec<usize>, Bindings)> = match_paths(prev_expr, &rule.left, rule.depth)
+        .into_iter()
+        .filter_map(|path| {
+            let bindings = match_bindings(prev_expr.at_path(&path)?, &rule.left).ok()?;
+            Some((path, bindings))
+        })
+        .collect();
+    let is_rewritten = |path: &[usize]| next_expr.at_path(path) != prev_expr.at_path(path);
+    if matches.iter().any(|(path, _)| is_rewritten(path)) {
+        matches.into_iter().filter(|(path, _)| is_rewritten(path)).collect()
+    } else {
+        matches
+    }
+}
+
+// Paths of the sub-expressions at the depth that the left expression matches, from left to right.
+pub fn match_paths(current_expr: &Expr, left: &Expr, depth: usize) -> Vec<Vec<usize>> {
+    let mut paths = vec![];
+    let mut stack = vec![(current_expr, vec![])];
+    while let Some((expr, path)) = stack.pop() {
+        match expr.node() {
+            _ if path.len() == depth => paths.extend(match_patterns(expr, left, left).ok().map(|_| path)),
+            ExprNode::Functor { args, .. } => stack.extend(args.iter().enumerate().rev().map(|(i, arg)| {
+                let mut path = path.clone();
+                path.push(i);
+                (arg, path)
+            })),
+            _ => {}
+        }
+    }
+    paths
+}
+
+// The path of the outermost-leftmost sub-expression that the left expression matches, i.e., the first match in pre-order,
+// where a functor comes before its arguments, and the arguments are visited from left to right.
+pub fn first_match_path(current_expr: &Expr, left: &Expr) -> Option<Vec<usize>> {
+    let mut stack = vec![(current_expr, vec![])];
+    while let Some((expr, path)) = stack.pop() {
+        if match_patterns(expr, left, left).is_ok() {
+            return Some(path);
+        }
+        if let ExprNode::Functor { args, .. } = expr.node() {
+            stack.extend(args.iter().enumerate().rev().map(|(i, arg)| {
+                let mut path = path.clone();
+                path.push(i);
+                (arg, path)
+            }));
+        }
+    }
+    None
+}
+
+// Whether the expression has the shape of the goal template, i.e., the template matches it like the left expression
+// of a rule matches the entire expression, where '_' is a hole that matches any sub-expression.
+pub fn matches_goal(expr: &Expr, goal: &Expr) -> bool {
+    match_patterns(expr, goal, goal).is_ok()
+}
+
+// Number of traversal frames between two reads of the clock, as reading it costs more than a frame
+const CHECK_TIME_INTERVAL: usize = 1024;
+
+// Post-order traversal frames for rebuilding the current expression after matching.
+// Once all arguments of a functor are rebuilt, they are collected by the exit frame.
+enum BuildFrame<'a> {
+    Enter(&'a Expr, usize),
+    Exit(&'a str, usize),
+}
+
 // Traverse the Abstract Syntax Tree of the current expression, 
 // and match sub-expression if and only if certain depth is reached.  
-fn ast_traverse_match(current_expr: Expr, left: &Expr, right: &Expr, depth: usize) -> Result<Expr, Box<dyn Error>>{
+// With a match cache, each rewritten functor is memoized, so an identical sub-expression at the same depth is not traversed again.
+fn traverse_match(
+    current_expr: &Expr, left: &Expr, right: &Expr, depth: usize, 
+    stats: &Stats, cache: Option<&MatchCache>, limits: &Limits
+) -> Result<Expr, RuntimeError> {
 
-    if depth == 0 {
-        // Update current_expr by matching on left and producing corresponding right expression. 
-        let current_expr = match_patterns(current_expr, left, right)?;
-        Ok(current_expr)
-    } else {
-        match current_expr {
-            cur @ Expr::Variable { .. } => Ok(cur),
-            Expr::Functor { iden, args } => {
-                let mut new_args = vec![];
-                for arg in args {
-                    let expr = ast_traverse_match(arg, left, right, depth - 1)?;
-                    new_args.push(expr);
+    let mut stack = vec![BuildFrame::Enter(current_expr, depth)];
+    // Functors of the exit frames on the stack with their depths and the match attempts before them, 
+    // which are popped in the same order
+    let mut exit_exprs = vec![];
+    let mut results = vec![];
+    let mut num_frames = 0;
+    let (mut match_attempts, mut cache_hits) = (0, 0);
+    while let Some(frame) = stack.pop() {
+        if num_frames % CHECK_TIME_INTERVAL == 0 {
+            limits.check_time()?;
+        }
+        num_frames += 1;
+        match frame {
+            BuildFrame::Enter(expr, depth) => {
+                // Only functors are memoized, as other expressions are matched in constant time
+                let cache = cache.filter(|_| matches!(expr.node(), ExprNode::Functor { .. }));
+                if let Some((result, attempts)) = cache.and_then(|cache| cache.get(expr, left, right, depth)) {
+                    match_attempts += attempts;
+                    cache_hits += 1;
+                    results.push(result);
+                } else if depth == 0 {
+                    // Update current_expr by matching on left and producing corresponding right expression. 
+                    match_attempts += 1;
+                    let result = match_patterns(expr, left, right).unwrap_or_else(|_| expr.clone());
+                    if let Some(cache) = cache {
+                        cache.insert(expr, left, right, 0, result.clone(), 1);
+                    }
+                    results.push(result);
+                } else if let ExprNode::Functor { iden, args } = expr.node() {
+                    stack.push(BuildFrame::Exit(iden, args.len()));
+                    exit_exprs.push((expr, depth, match_attempts));
+                    stack.extend(args.iter().rev().map(|arg| BuildFrame::Enter(arg, depth - 1)));
+                } else {
+                    results.push(expr.clone());
+                }
+            },
+            BuildFrame::Exit(iden, num_args) => {
+                let args = results.split_off(results.len() - num_args);
+                let result = Expr::functor(iden, args);
+                let (expr, depth, attempts_before) = exit_exprs.pop().unwrap();
+                if let Some(cache) = cache {
+                    cache.insert(expr, left, right, depth, result.clone(), match_attempts - attempts_before);
                 }
-                Ok(Expr::Functor { 
-                    iden: iden,
-                    args: new_args 
-                })
+                results.push(result);
+            }
+        }
+    }
+    stats.match_attempts.fetch_add(match_attempts, Ordering::Relaxed);
+    stats.cache_hits.fetch_add(cache_hits, Ordering::Relaxed);
+    Ok(results.pop().unwrap())
+}
+
+// Traverse the Abstract Syntax Tree of the current expression bottom-up, and match every sub-expression after its
+// arguments are rewritten, such that the deepest sub-expressions are rewritten first. A rewritten sub-expression is not
+// matched again. Returns the derived expression and the deepest depth at which a sub-expression was rewritten.
+fn innermost_match(current_expr: &Expr, left: &Expr, right: &Expr, stats: &Stats, limits: &Limits) -> Result<(Expr, Option<usize>), RuntimeError> {
+    let mut stack = vec![BuildFrame::Enter(current_expr, 0)];
+    // Depths of the functors of the exit frames on the stack, which are popped in the same order
+    let mut exit_depths = vec![];
+    let mut results = vec![];
+    let mut rewritten_depth = None;
+    let mut num_frames = 0;
+    while let Some(frame) = stack.pop() {
+        if num_frames % CHECK_TIME_INTERVAL == 0 {
+            limits.check_time()?;
+        }
+        num_frames += 1;
+        let (expr, depth) = match frame {
+            BuildFrame::Enter(expr, depth) => match expr.node() {
+                ExprNode::Functor { iden, args } => {
+                    stack.push(BuildFrame::Exit(iden, args.len()));
+                    exit_depths.push(depth);
+                    stack.extend(args.iter().rev().map(|arg| BuildFrame::Enter(arg, depth + 1)));
+                    continue;
+                },
+                ExprNode::Variable { .. } | ExprNode::Number(_) | ExprNode::Str(_) | ExprNode::Segment { .. } => (expr.clone(), depth),
+            },
+            BuildFrame::Exit(iden, num_args) => {
+                let args = results.split_off(results.len() - num_args);
+                (Expr::functor(iden, args), exit_depths.pop().unwrap())
+            }
+        };
+        stats.match_attempts.fetch_add(1, Ordering::Relaxed);
+        match match_patterns(&expr, left, right) {
+            Ok(new_expr) if new_expr != expr => {
+                rewritten_depth = rewritten_depth.max(Some(depth));
+                results.push(new_expr);
+            },
+            _ => results.push(expr),
+        }
+    }
+    Ok((results.pop().unwrap(), rewritten_depth))
+}
+
+// The bindings of the variables and segments of the left expression if it matches the current expression, or why it does not match.
+// A left expression that is not a functor binds nothing, as its variables only match themselves.
+fn match_bindings(current_expr: &Expr, left: &Expr) -> Result<Bindings, MatchFailure> {
+    let (current_expr, left) = align_groups(current_expr, left);
+    let (ExprNode::Functor { iden: current_iden, args: current_args }, ExprNode::Functor { iden: lhs_iden, args: lhs_args }) = 
+        (current_expr.node(), left.node()) else {
+        return match_patterns(current_expr, left, left).map(|_| vec![]);
+    };
+    check_functor(current_iden, current_args, lhs_iden, lhs_args)?;
+    let mut args_table = HashMap::<Expr, Expr>::new();
+    let mut segments_table = HashMap::<Expr, Vec<Expr>>::new();
+    fill_pattern_mapping(current_args, lhs_args, &mut args_table, &mut segments_table)?;
+
+    let mut bindings: Bindings = vec![];
+    let mut stack = vec![left];
+    while let Some(expr) = stack.pop() {
+        match expr.node() {
+            ExprNode::Functor { args, .. } => stack.extend(args.iter().rev()),
+            _ if bindings.iter().any(|(bound, _)| bound == expr) => {},
+            _ => match (segments_table.get(expr), args_table.get(expr)) {
+                (Some(exprs), _) => bindings.push((expr.clone(), exprs.clone())),
+                (_, Some(bound)) => bindings.push((expr.clone(), vec![bound.clone()])),
+                _ => {}
             }
         }
     }
+    Ok(bindings)
+}
+
+// The bindings as 'x = a, rest.. = [b, c]', which is how the trace prints them
+pub fn bindings_to_string(bindings: &Bindings) -> String {
+    bindings
+        .iter()
+        .map(|(var, exprs)| match exprs.as_slice() {
+            [expr] if !var.is_segment() => format!("{} = {}", var.to_string(), expr.to_string()),
+            _ => format!("{} = [{}]", var.to_string(), exprs.iter().map(Expr::to_string).collect::<Vec<_>>().join(", ")),
+        })
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+// A variable in a pattern that matches any expression without binding it, so that each of its occurrences can match another expression
+pub const WILDCARD: &str = "_";
+
+// Whether the expression is a group with one argument, which parentheses around an expression create
+fn is_group(expr: &Expr) -> bool {
+    matches!(expr.node(), ExprNode::Functor { iden, args } if iden == "group" && args.len() == 1)
+}
+
+// The expression inside any number of single-argument groups
+fn ungroup(expr: &Expr) -> &Expr {
+    let mut expr = expr;
+    while let (true, ExprNode::Functor { args, .. }) = (is_group(expr), expr.node()) {
+        expr = &args[0];
+    }
+    expr
+}
+
+// Parentheses only group for parsing, so a group matches like the expression inside it, e.g., (a + b) matches a + b
+// and the other way around. If both are groups, they match as functors instead, such that a rule can remove a group.
+fn align_groups<'a>(current_expr: &'a Expr, left: &'a Expr) -> (&'a Expr, &'a Expr) {
+    if is_group(current_expr) && is_group(left) {
+        (current_expr, left)
+    } else {
+        (ungroup(current_expr), ungroup(left))
+    }
+}
+
+// Whether the expressions are the same when single-argument groups are ignored, e.g., f((a)) and f(a)
+fn is_equal_ungrouped(expr: &Expr, other: &Expr) -> bool {
+    let mut stack = vec![(expr, other)];
+    while let Some((expr, other)) = stack.pop() {
+        let (expr, other) = (ungroup(expr), ungroup(other));
+        match (expr.node(), other.node()) {
+            (ExprNode::Functor { iden, args }, ExprNode::Functor { iden: other_iden, args: other_args }) => {
+                if iden != other_iden || args.len() != other_args.len() {
+                    return false;
+                }
+                stack.extend(args.iter().zip(other_args));
+            },
+            _ if expr != other => return false,
+            _ => {}
+        }
+    }
+    true
 }
 
-fn match_patterns(current_expr: Expr, left: &Expr, right: &Expr) -> Result<Expr, Box<dyn Error>>{
+// Returns the right expression for the bindings of the left expression if it matches the current expression,
+// or why it does not match.
+fn match_patterns(current_expr: &Expr, left: &Expr, right: &Expr) -> Result<Expr, MatchFailure> {
+    // A rewritten group stays a group, which keeps its parentheses when printed
+    let (inner_expr, left) = align_groups(current_expr, left);
+    if inner_expr != current_expr {
+        return match_patterns(inner_expr, left, right)
+            .map(|expr| if is_group(&expr) { expr } else { Expr::functor("group", vec![expr]) });
+    }
+    let mismatch = || MatchFailure::ExprMismatch { expected: left.to_string(), got: current_expr.to_string() };
 
-    match (current_expr, left) {
-        (Expr::Variable { iden: current , ..}, 
-         Expr::Variable { iden: lhs, .. }) => {
-            if current.as_str() == lhs.as_str() {
+    match (current_expr.node(), left.node()) {
+        (_, ExprNode::Variable { iden }) if iden == WILDCARD => Ok(right.clone()),
+        (ExprNode::Variable { .. } | ExprNode::Segment { .. }, 
+         ExprNode::Variable { .. } | ExprNode::Segment { .. }) => {
+            if current_expr == left {
                 Ok(right.clone())
             } else {
-                Ok(Expr::Variable { iden: current })
+                Err(mismatch())
             }
         },
-        (Expr::Functor { iden: current_iden, args: current_args },
-         Expr::Functor { iden: lhs_iden, args: lhs_args }) => {
-            // If both functors have the same arity and the same identifier
-            // then they are considered to produce the form of the right expr. 
-            if current_iden.as_str() == lhs_iden.as_str() &&
-               current_args.len() == lhs_args.len()  
-            {   
-                let mut args_table = HashMap::<Expr, Expr>::new();
-                // create mapping of (lhs args) -> (current_expr args)
-                // return whether there is a match
-                let is_match = fill_pattern_mapping(&current_args, lhs_args, &mut args_table);
-                
-                if is_match {
-                    let res = construct_rhs(right, &args_table)?;
-                    Ok(res)
-                } else {
-                    Ok(Expr::Functor { 
-                        iden: current_iden, 
-                        args: current_args 
-                    })
-                }
+        // Numbers and strings are constants, thus only an equal constant matches
+        (ExprNode::Number(_), ExprNode::Number(_)) |
+        (ExprNode::Str(_), ExprNode::Str(_)) => {
+            if current_expr == left {
+                Ok(right.clone())
             } else {
-                Ok(Expr::Functor { 
-                    iden: current_iden, 
-                    args: current_args 
-                })
+                Err(mismatch())
             }
         },
+        // A variable is a symbol and not a constant, so neither matches the other
+        (ExprNode::Variable { .. } | ExprNode::Segment { .. } | ExprNode::Str(_), ExprNode::Number(_)) |
+        (ExprNode::Variable { .. } | ExprNode::Segment { .. } | ExprNode::Number(_), ExprNode::Str(_)) |
+        (ExprNode::Number(_) | ExprNode::Str(_), ExprNode::Variable { .. } | ExprNode::Segment { .. }) => Err(mismatch()),
+        (ExprNode::Functor { iden: current_iden, args: current_args },
+         ExprNode::Functor { iden: lhs_iden, args: lhs_args }) => {
+            // If both functors have the same arity and the same identifier
+            // then they are considered to produce the form of the right expr. 
+            check_functor(current_iden, current_args, lhs_iden, lhs_args)?;
+            let mut args_table = HashMap::<Expr, Expr>::new();
+            let mut segments_table = HashMap::<Expr, Vec<Expr>>::new();
+            // create mapping of (lhs args) -> (current_expr args)
+            fill_pattern_mapping(current_args, lhs_args, &mut args_table, &mut segments_table)?;
+            Ok(construct_rhs_with_segments(right, &args_table, &segments_table))
+        },
         // Cannot match variable against functor as the functor is a superset of the variable
         // i.e., contains more information. For instance, if current_expr conveys the symbol x 
         // and we try to match the rule f(x) => g(x), then we fail to match because f(x) != x. 
-        (cur @ Expr::Variable { .. }, Expr::Functor { .. }) => Ok(cur),
+        (ExprNode::Variable { .. } | ExprNode::Number(_) | ExprNode::Str(_) | ExprNode::Segment { .. }, ExprNode::Functor { .. }) => Err(mismatch()),
 
         // In this case, we match current_expr (as a functor) against a variable.
         // This is possible as the functor may contain sub-expressions that match the left expr.
-        (Expr::Functor { iden: current_iden, args: current_args }, 
-        Expr::Variable { iden: lhs_iden, .. }) => {
-            let mut new_args = vec![];
-            for arg in current_args {
-                if let Expr::Variable { iden } = arg {
-                    if iden.as_str() == lhs_iden.as_str() {
-                        new_args.push(right.clone());
-                    } else {
-                        new_args.push(Expr::Variable { iden });
-                    }
-                } else {
-                    new_args.push(arg);
-                }
+        (ExprNode::Functor { iden: current_iden, args: current_args }, 
+         ExprNode::Variable { .. } | ExprNode::Number(_) | ExprNode::Str(_) | ExprNode::Segment { .. }) => {
+            if !current_args.contains(left) {
+                return Err(mismatch());
             }
-            Ok(Expr::Functor { 
-                iden: current_iden, 
-                args: new_args 
-            })
+            let new_args = current_args
+                .iter()
+                .map(|arg| if arg == left { right.clone() } else { arg.clone() })
+                .collect();
+            Ok(Expr::functor(current_iden.as_str(), new_args))
         }
     }
 }
 
+// Functors only match if they have the same identifier and a matching number of arguments.
+fn check_functor(cur_iden: &str, cur_args: &[Expr], lhs_iden: &str, lhs_args: &[Expr]) -> Result<(), MatchFailure> {
+    if cur_iden != lhs_iden {
+        return Err(MatchFailure::IdentifierMismatch { expected: lhs_iden.to_string(), got: cur_iden.to_string() });
+    }
+    if !is_matching_arity(cur_args, lhs_args) {
+        return Err(MatchFailure::ArityMismatch { iden: lhs_iden.to_string(), expected: lhs_args.len(), got: cur_args.len() });
+    }
+    Ok(())
+}
+
+// A segment matches any number of arguments, so an argument list with a segment 
+// matches every list that has at least as many arguments as the other patterns.
+fn is_matching_arity(cur_args: &[Expr], lhs_args: &[Expr]) -> bool {
+    if lhs_args.iter().any(Expr::is_segment) {
+        cur_args.len() + 1 >= lhs_args.len()
+    } else {
+        cur_args.len() == lhs_args.len()
+    }
+}
+
 // To fill the table of arguments, we recursively evaluate each sub-expression.
-// This function also returns a bool indicating whether it is possible to construct the right hand side.
-fn fill_pattern_mapping(cur_args: &Vec<Expr>, lhs_args: &Vec<Expr>, args_table: &mut HashMap<Expr, Expr>) -> bool {
+// This function also returns why the right hand side cannot be constructed, if it cannot.
+// The arguments of an argument list with a segment are aligned around the segment, e.g., f(a, rest.., z) 
+// matches f(1, 2, 3, 4) with a = 1 and z = 4, and the segment binds the arguments in between, i.e., rest = 2, 3.
+fn fill_pattern_mapping(
+    cur_args: &[Expr], 
+    lhs_args: &[Expr], 
+    args_table: &mut HashMap<Expr, Expr>, 
+    segments_table: &mut HashMap<Expr, Vec<Expr>>
+) -> Result<(), MatchFailure> {
     
-    for (lhs_arg, cur_arg) in lhs_args.iter().zip(cur_args.iter())
+    let pairs: Vec<(&Expr, &Expr)> = match lhs_args.iter().position(Expr::is_segment) {
+        Some(i) => {
+            let end = cur_args.len() + i + 1 - lhs_args.len();
+            segments_table.insert(lhs_args[i].clone(), cur_args[i..end].to_vec());
+            lhs_args[..i].iter().zip(&cur_args[..i])
+                .chain(lhs_args[i+1..].iter().zip(&cur_args[end..]))
+                .collect()
+        },
+        None => lhs_args.iter().zip(cur_args.iter()).collect()
+    };
+
+    for (lhs_arg, cur_arg) in pairs
     {
-        match (lhs_arg, cur_arg) {
-            (Expr::Variable { .. }, Expr::Variable { .. } | Expr::Functor { .. }) => {
-                args_table.insert(lhs_arg.clone(), cur_arg.clone());
+        let (cur_inner, lhs_arg) = align_groups(cur_arg, lhs_arg);
+        match (lhs_arg.node(), cur_inner.node()) {
+            (ExprNode::Variable { iden }, _) if iden == WILDCARD => {},
+            // The parser allows at most one segment per argument list, which is aligned above.
+            // A variable that occurs more than once, e.g., in add(x, x), matches the same expression each time,
+            // where the first binding keeps its groups, e.g., add(x, x) matches add((a), a) with x = (a).
+            (ExprNode::Variable { .. } | ExprNode::Segment { .. }, _) => {
+                match args_table.get(lhs_arg) {
+                    Some(bound) if !is_equal_ungrouped(bound, cur_arg) => return Err(MatchFailure::InconsistentBinding { 
+                        var: lhs_arg.to_string(), 
+                        first: bound.to_string(), 
+                        second: cur_arg.to_string() 
+                    }),
+                    Some(_) => {},
+                    None => { args_table.insert(lhs_arg.clone(), cur_arg.clone()); }
+                }
             },
+            // current_expr: f(2)
+            // f(1) => ..
             // current_expr: f(x)
             // f(g(x)) => ..
-            (Expr::Functor { .. }, Expr::Variable { .. }) => {
-                return false;
-            },
-            (Expr::Functor { iden: lhs_iden, args: _lhs_args }, 
-             Expr::Functor { iden: cur_iden, args: _cur_args  }) => {
-                if cur_iden.as_str() == lhs_iden.as_str() &&
-                   _cur_args.len() == _lhs_args.len()
-                {
-                    match fill_pattern_mapping(_cur_args, _lhs_args, args_table) {
-                        true => {},
-                        false => { return false; }
-                    }
-                } else {
-                    // current_expr: f(h(x))
-                    // f(g(x, y)) => ..
-                    return false;
+            (ExprNode::Number(_) | ExprNode::Str(_), _) |
+            (ExprNode::Functor { .. }, ExprNode::Variable { .. } | ExprNode::Number(_) | ExprNode::Str(_) | ExprNode::Segment { .. }) => {
+                if lhs_arg != cur_inner {
+                    return Err(MatchFailure::ExprMismatch { expected: lhs_arg.to_string(), got: cur_arg.to_string() });
                 }
+            },
+            // current_expr: f(h(x))
+            // f(g(x, y)) => ..
+            (ExprNode::Functor { iden: lhs_iden, args: lhs_args }, 
+             ExprNode::Functor { iden: cur_iden, args: cur_args }) => {
+                check_functor(cur_iden, cur_args, lhs_iden, lhs_args)?;
+                fill_pattern_mapping(cur_args, lhs_args, args_table, segments_table)?;
             }
         }
     }
-    return true;
+    Ok(())
 }
 
-// Recursively traverses the right hand side expression to produce a new expression 
+// Traverses the right hand side expression to produce a new expression 
 // with the corresponding symbols mapped using args_table 
-fn construct_rhs(right: &Expr, args_table: &HashMap<Expr, Expr>) -> Result<Expr, Box<dyn Error>> {
+fn construct_rhs(right: &Expr, args_table: &HashMap<Expr, Expr>) -> Expr {
+    construct_rhs_with_segments(right, args_table, &HashMap::new())
+}
+
+// Same as construct_rhs, where each bound segment in an argument list is 
+// replaced by the arguments it matched, e.g., g(rest..) with rest = 2, 3 results in g(2, 3).
+fn construct_rhs_with_segments(right: &Expr, args_table: &HashMap<Expr, Expr>, segments_table: &HashMap<Expr, Vec<Expr>>) -> Expr {
     
-    match right {
-        Expr::Variable { iden, .. } => {
-            // g(A)
-            // g(x) => x
-            if let Some(new_arg) = args_table.get(right) {
-                Ok(new_arg.clone())
-            } else {
-                Ok(Expr::Variable { iden: iden.clone() })
-            }
-        },
-        Expr::Functor { iden, args } => {
-            // g(A)
-            // g(x) => f(y, x)
-            let mut new_args = vec![];
-            for arg in args {
-                let res = construct_rhs(arg, args_table)?;
-                new_args.push(res);
+    let mut stack = vec![BuildFrame::Enter(right, 0)];
+    let mut results = vec![];
+    while let Some(frame) = stack.pop() {
+        match frame {
+            BuildFrame::Enter(expr, _) => match expr.node() {
+                ExprNode::Segment { .. } if segments_table.contains_key(expr) => {
+                    results.extend(segments_table[expr].iter().cloned());
+                },
+                ExprNode::Variable { .. } | ExprNode::Number(_) | ExprNode::Str(_) | ExprNode::Segment { .. } => {
+                    // g(A)
+                    // g(x) => x
+                    results.push(args_table.get(expr).unwrap_or(expr).clone());
+                },
+                // Fresh variables are the only functors that are replaced as a whole
+                ExprNode::Functor { .. } if args_table.contains_key(expr) => {
+                    results.push(args_table[expr].clone());
+                },
+                ExprNode::Functor { iden, args } => {
+                    // g(A)
+                    // g(x) => f(y, x)
+                    let num_args = args
+                        .iter()
+                        .map(|arg| segments_table.get(arg).map_or(1, Vec::len))
+                        .sum();
+                    stack.push(BuildFrame::Exit(iden, num_args));
+                    stack.extend(args.iter().rev().map(|arg| BuildFrame::Enter(arg, 0)));
+                }
+            },
+            BuildFrame::Exit(iden, num_args) => {
+                let args = results.split_off(results.len() - num_args);
+                results.push(Expr::functor(iden, args));
             }
-
-            Ok(Expr::Functor { 
-                iden: iden.clone(), 
-                args: new_args 
-            })
         }
     }
+    results.pop().unwrap()
 }
 
-fn find_binary_ops(expr: &Expr) -> bool {
-    match expr {
-        Expr::Variable { .. } => false,
-        Expr::Functor { iden, args } => {
-            if args.len() == 2 && Expr::get_binary_operator_str(iden.as_str()).is_some() {
-                true
-            } else {
-                for arg in args.iter() {
-                    if find_binary_ops(arg) {
-                        return true;
-                    }
+// A warning for each declared functor that is used with another number of arguments in the statement.
+pub fn arity_mismatches(stmt: &Stmt, arities: &HashMap<String, usize>) -> Vec<Warning> {
+    let mut mismatches = vec![];
+    let mut warnings = vec![];
+    let mut stack = stmt.exprs();
+    while let Some(expr) = stack.pop() {
+        if let ExprNode::Functor { iden, args } = expr.node() {
+            if let Some(&expected) = arities.get(iden) {
+                // Patterns with a segment match any number of arguments
+                let is_mismatch = expected != args.len() && !args.iter().any(Expr::is_segment);
+                if is_mismatch && !mismatches.contains(&(iden, args.len())) {
+                    mismatches.push((iden, args.len()));
+                    warnings.push(Warning::FunctorArityMismatch { 
+                        iden: iden.to_string(), 
+                        expected, 
+                        got: args.len() 
+                    });
                 }
-                false
             }
+            stack.extend(args.iter());
+        }
+    }
+    warnings
+}
+
+fn print_expr(output: &mut impl Write, prefix: &str, expr: &Expr, operators: &Operators, echo_functors: bool) {
+    let _ = writeln!(output, "{}{}", prefix, expr.to_string_with(operators));
+    // For readability, also print as functor prefix notation
+    if echo_functors && find_binary_ops(expr, operators) {
+        let _ = writeln!(output, "{:indent$}As functor: {}", "", expr, indent=prefix.len());
+    }
+}
+
+fn find_binary_ops(expr: &Expr, operators: &Operators) -> bool {
+    let mut stack = vec![expr];
+    while let Some(expr) = stack.pop() {
+        if let ExprNode::Functor { iden, args } = expr.node() {
+            if operators.infix(iden, args.len()).is_some() {
+                return true;
+            }
+            stack.extend(args.iter());
         }
     }
+    false
 }
 
 #[cfg(test)]
@@ -343,6 +2268,7 @@ mod tests {
 
     use crate::lexer::*;
     use crate::parser::Parser;
+    use crate::ast::Expr;
     use super::*;
     
 
@@ -364,24 +2290,1898 @@ mod tests {
 
         assert!(res.is_ok());
         assert_eq!(env.get_expr(), 
-                Some(&Expr::Functor { 
-                    iden: "g".to_string(), 
-                    args: vec![
-                        Expr::Functor { iden: "f".to_string(), args: vec![Expr::Variable { iden: "A".to_string() }] },
-                        Expr::Functor { iden: "f".to_string(), args: vec![Expr::Variable { iden: "A".to_string() }] }
-                    ]
-                })
+                Some(&Expr::functor("g", vec![
+                    Expr::functor("f", vec![Expr::variable("A")]),
+                    Expr::functor("f", vec![Expr::variable("A")])
+                ]))
         );
     }
 
+    #[test]
+    fn runtime_test_rule_schema() {
+        let input_string = "
+            def power_rule(n) as pow(x, n) => n * pow(x, n - 1)
+            pow(y, 3)
+            apply power_rule at 0
+            apply power_rule(3) at 0
+        ";
+        let mut lexer = Lexer::new();
+        lexer.lex(input_string);
+        
+        let mut parser = Parser::new();
+        let _ = parser.parse(&mut lexer);
+
+        let mut env = Env::new();
+        let res = env.interpret(parser.stmts);
+
+        assert!(res.is_ok());
+        assert!(matches!(env.warnings[0], Warning::RuleArityMismatch { expected: 1, got: 0, .. }));
+        assert_eq!(env.history.len(), 2);
+        assert_eq!(env.get_expr().unwrap().to_string(), "3 * pow(y, 3 - 1)");
+    }
+
+    #[test]
+    fn runtime_test_strategy() {
+        let input_string = "
+            def plus_0 as add(x, 0) => x
+            def plus_s as add(x, s(y)) => s(add(x, y))
+            add(s(0), s(s(0)))
+            apply (plus_s | plus_0)* at 0
+            apply plus_s; plus_s at 0
+        ";
+        let mut lexer = Lexer::new();
+        lexer.lex(input_string);
+        
+        let mut parser = Parser::new();
+        let _ = parser.parse(&mut lexer);
+
+        let mut env = Env::new();
+        let res = env.interpret(parser.stmts);
+
+        assert!(res.is_ok());
+        // plus_s is applied once, after which neither rule matches at depth 0,
+        // and the failed sequence leaves no steps behind.
+        assert_eq!(env.warnings, vec![Warning::RuleDidNotMatch {
+            rule: "plus_s".to_string(),
+            depth: "0".to_string(),
+            reason: "identifier mismatch, expected 'add', but got 's'".to_string()
+        }]);
+        assert_eq!(env.history.len(), 2);
+        assert_eq!(env.get_expr().unwrap().to_string(), "s(s(0) + s(0))");
+    }
+
+    #[test]
+    fn runtime_test_cycle_detection() {
+        let input_string = "
+            def swap as pair(x, y) => pair(y, x)
+            pair(A, B)
+            apply swap* at 0
+            apply swap at 0
+            apply swap at 0
+        ";
+        let mut lexer = Lexer::new();
+        lexer.lex(input_string);
+        
+        let mut parser = Parser::new();
+        let _ = parser.parse(&mut lexer);
+
+        let mut env = Env::new();
+        let res = env.interpret(parser.stmts);
+
+        assert!(res.is_ok());
+        // The repetition stops before deriving pair(A, B) again, 
+        // whereas the manual swaps are recorded with a warning.
+        assert_eq!(env.history.len(), 4);
+        assert_eq!(env.warnings.len(), 3);
+        assert!(env.warnings.iter().all(|w| matches!(w, Warning::RewriteCycleDetected(_))));
+    }
+
+    #[test]
+    fn runtime_test_limits() {
+        let input_string = "
+            set max_nodes 3
+            def grow as f(x) => f(f(x))
+            f(x)
+            apply grow at 0
+            apply grow at 0
+        ";
+        let mut lexer = Lexer::new();
+        lexer.lex(input_string);
+        
+        let mut parser = Parser::new();
+        let _ = parser.parse(&mut lexer);
+
+        let mut env = Env::new();
+        let res = env.interpret(parser.stmts);
+
+        assert!(res.is_err());
+        assert_eq!(res, Err(RuntimeError::MaxNodesExceeded { max_nodes: 3 }));
+        assert_eq!(env.get_expr().unwrap().to_string(), "f(f(x))");
+    }
+
+    #[test]
+    fn runtime_test_timeout() {
+        let input_string = "
+            def swap as f(x, y) => f(y, x)
+            f(a, b)
+            apply swap at 0
+            set timeout 0ms
+            apply swap* at 0
+        ";
+        let mut lexer = Lexer::new();
+        lexer.lex(input_string);
+        
+        let mut parser = Parser::new();
+        let _ = parser.parse(&mut lexer);
+
+        let mut env = Env::new();
+        env.set_output(io::sink());
+        let res = env.interpret(parser.stmts);
+
+        let timeout = Duration::ZERO;
+        assert_eq!(res, Err(RuntimeError::TimeoutExceeded { timeout }));
+        assert_eq!(env.get_expr().unwrap().to_string(), "f(b, a)");
+
+        let mut lexer = Lexer::new();
+        lexer.lex("prove f(a, b) == f(b, a) using swap");
+        let mut parser = Parser::new();
+        let _ = parser.parse(&mut lexer);
+        assert_eq!(env.interpret(parser.stmts), Err(RuntimeError::TimeoutExceeded { timeout }));
+    }
+
+    #[test]
+    fn runtime_test_timeout_in_traversal() {
+        // A wide expression, whose traversal is large enough to be rewritten in parallel
+        let leaves = (0..PARALLEL_MIN_NODES).map(|i| Expr::functor("s", vec![Expr::variable(format!("a{}", i))])).collect();
+        let expr = Expr::functor("f", vec![Expr::functor("g", leaves), Expr::variable("b")]);
+        let (left, right) = (Expr::functor("s", vec![Expr::variable("x")]), Expr::variable("x"));
+        let (stats, cache) = (Stats::default(), MatchCache::default());
+
+        let mut limits = Limits::new();
+        let rewritten = traverse_match(&expr, &left, &right, 2, &stats, Some(&cache), &limits).unwrap();
+        assert_eq!(innermost_match(&expr, &left, &right, &stats, &limits).unwrap(), (rewritten, Some(2)));
+
+        // The traversals themselves stop at the deadline, not only the statement that started them
+        limits.timeout = Some(Duration::ZERO);
+        limits.start_clock();
+        let timeout = Err(RuntimeError::TimeoutExceeded { timeout: Duration::ZERO });
+        assert_eq!(traverse_match(&expr, &left, &right, 2, &stats, Some(&MatchCache::default()), &limits), timeout);
+        assert_eq!(par_traverse_match(&expr, &left, &right, 2, &stats, &limits, PARALLEL_MAX_FORKS), timeout);
+        assert_eq!(innermost_match(&expr, &left, &right, &stats, &limits).map(|(expr, _)| expr), timeout);
+    }
+
+    #[test]
+    fn runtime_test_edit() {
+        let mut lexer = Lexer::new();
+        lexer.lex("f(a)");
+        let mut parser = Parser::new();
+        let _ = parser.parse(&mut lexer);
+
+        let mut env = Env::new();
+        env.set_output(io::sink());
+        assert_eq!(env.edit(Expr::variable("b")), Err(RuntimeError::NoCurrentExpr));
+        let _ = env.interpret(parser.stmts);
+        assert_eq!(env.edit(Expr::variable("b")), Ok(()));
+
+        assert_eq!(env.get_expr().unwrap().to_string(), "b");
+        assert_eq!(env.derivation_history.last().unwrap().name.as_deref(), Some("edit"));
+        // An edit is undone like any other step
+        assert_eq!(env.undo(1), 1);
+        assert_eq!(env.get_expr().unwrap().to_string(), "f(a)");
+    }
+
+    #[test]
+    fn runtime_test_interrupt() {
+        let input_string = "
+            def grow as f(x) => f(f(x))
+            f(x)
+            apply grow* at 0
+        ";
+        let mut lexer = Lexer::new();
+        lexer.lex(input_string);
+        
+        let mut parser = Parser::new();
+        let _ = parser.parse(&mut lexer);
+
+        let mut env = Env::new();
+        env.set_output(io::sink());
+        env.limits.interrupted.store(true, Ordering::Relaxed);
+        let res = env.interpret(parser.stmts);
+
+        // The environment stays usable after the interrupted statement
+        assert_eq!(res, Err(RuntimeError::Interrupted));
+        assert_eq!(env.get_expr().unwrap().to_string(), "f(x)");
+        assert!(env.rules.contains_key("grow"));
+    }
+
+    #[test]
+    fn runtime_test_deep_expression() {
+        let depth = 100_000;
+        let mut expr = Expr::variable("x");
+        for _ in 0..depth {
+            expr = Expr::functor("f", vec![expr]);
+        }
+
+        let mut env = Env::new();
+        env.limits.max_depth = depth;
+        env.limits.max_nodes = depth + 1;
+        let res = env.interpret(vec![
+            Stmt::ExprStmt(expr),
+            Stmt::RuleStmt { 
+                left: Expr::variable("x"), 
+                right: Expr::variable("y"), 
+                depth: Depth::At(depth - 1)
+            },
+        ]);
+
+        assert!(res.is_ok());
+        let res = env.get_expr().unwrap().to_string();
+        assert_eq!(res.len(), 3 * depth + 1);
+        assert!(res.contains("f(y)"));
+    }
+
+    #[test]
+    fn runtime_test_prove() {
+        let input_string = "
+            def plus_0 as add(x, zero) => x
+            def plus_s as add(x, s(y)) => s(add(x, y))
+            prove add(s(zero), s(zero)) == s(s(zero)) using plus_s, plus_0
+            prove s(zero) == zero
+        ";
+        let mut lexer = Lexer::new();
+        lexer.lex(input_string);
+        
+        let mut parser = Parser::new();
+        let _ = parser.parse(&mut lexer);
+
+        let output = OutputBuffer::new();
+        let mut env = Env::new();
+        env.set_output(output.clone());
+        let res = env.interpret(parser.stmts);
+
+        assert!(res.is_ok());
+        assert_eq!(env.warnings.len(), 1);
+        assert!(matches!(env.warnings[0], Warning::ProofNotFound { .. }));
+        assert!(output.contents().contains(concat!(
+            "    s(zero) + s(zero)\n",
+            "    = s(s(zero) + zero)    (by plus_s at depth 0)\n",
+            "    = s(s(zero))    (by plus_0 at depth 1)\n",
+        )));
+    }
+
+    #[test]
+    fn runtime_test_goal() {
+        let input_string = "
+            prove f(a, g(b)) == goal
+            def swap as f(x, y) => f(y, x)
+            def wrap as g(x) => h(x, x)
+            goal f(h(_, _), _)
+            f(a, g(b))
+            apply swap at 0
+            apply wrap at 1
+            end
+            prove f(a, g(b)) == goal
+        ";
+        let mut lexer = Lexer::new();
+        lexer.lex(input_string);
+        
+        let mut parser = Parser::new();
+        let _ = parser.parse(&mut lexer);
+
+        let output = OutputBuffer::new();
+        let mut env = Env::new();
+        env.set_output(output.clone());
+        let res = env.interpret(parser.stmts);
+
+        assert!(res.is_ok());
+        assert_eq!(env.warnings, vec![Warning::GoalNotSet]);
+        let output = output.contents();
+        assert!(output.contains("    f(g(b), a)\n    (goal f(h(_, _), _) not reached yet)\n"));
+        assert!(output.contains("    f(h(b, b), a)\n    (goal f(h(_, _), _) reached)\n"));
+        assert!(output.contains("Proof of f(a, g(b)) == f(h(_, _), _):\n    f(a, g(b))\n    = f(g(b), a)"));
+    }
+
+    #[test]
+    fn runtime_test_stats() {
+        let input_string = "
+            def swap as pair(x, y) => pair(y, x)
+            pair(f(A), B)
+            apply swap at 0
+            pair(x, y) => pair(y, x) at 1
+            prove pair(A, B) == pair(B, A)
+        ";
+        let mut lexer = Lexer::new();
+        lexer.lex(input_string);
+        
+        let mut parser = Parser::new();
+        let _ = parser.parse(&mut lexer);
+
+        let mut env = Env::new();
+        let res = env.interpret(parser.stmts);
+
+        assert!(res.is_ok());
+        // Only the apply statement and the in-line rule are measured.
+        assert_eq!(env.stats.applies.len(), 2);
+        assert_eq!(env.stats.applies[0].num_nodes, 4);
+        assert_eq!(env.stats.applies[0].match_attempts, 1);
+        assert_eq!(env.stats.applies[1].match_attempts, 2);
+    }
+
+    #[test]
+    fn runtime_test_match_cache() {
+        let input_string = "
+            f(g(s(a)), g(s(a)), g(s(a)))
+            s(x) => t(x) at 2
+            expect f(g(t(a)), g(t(a)), g(t(a)))
+            end
+            h(g(s(a)), g(s(b)))
+            s(x) => t(x) at 2
+            expect h(g(t(a)), g(t(b)))
+        ";
+        let mut lexer = Lexer::new();
+        lexer.lex(input_string);
+        
+        let mut parser = Parser::new();
+        let _ = parser.parse(&mut lexer);
+
+        let mut env = Env::new();
+        env.set_output(io::sink());
+        assert_eq!(env.interpret(parser.stmts), Ok(()));
+        // Identical sub-expressions are rewritten once, also by a later rule application, 
+        // and the match attempts of a rewrite from the cache are counted as well
+        assert_eq!((env.stats.applies[0].match_attempts, env.stats.applies[0].cache_hits), (3, 2));
+        assert_eq!((env.stats.applies[1].match_attempts, env.stats.applies[1].cache_hits), (2, 1));
+        assert!(!env.match_cache.is_empty());
+    }
+
+    #[test]
+    fn runtime_test_match_cache_generations() {
+        let generation = MATCH_CACHE_CAPACITY / MATCH_CACHE_SHARDS / 2;
+        let (expr, left, right) = (Expr::variable("a"), Expr::variable("x"), Expr::variable("y"));
+        // Keys of the same sub-expression are in the same shard
+        let cache = MatchCache::default();
+        for depth in 0..=generation {
+            cache.insert(&expr, &left, &right, depth, expr.clone(), depth);
+        }
+        // The full generation is kept instead of starting over, and a used entry moves back to the recent ones
+        assert_eq!(cache.len(), generation + 1);
+        assert_eq!(cache.get(&expr, &left, &right, 0), Some((expr.clone(), 0)));
+        for depth in generation + 1..2 * generation {
+            cache.insert(&expr, &left, &right, depth, expr.clone(), depth);
+        }
+        assert_eq!(cache.get(&expr, &left, &right, 0), Some((expr.clone(), 0)));
+        assert_eq!(cache.get(&expr, &left, &right, 1), None);
+        assert!(cache.len() <= 2 * generation);
+    }
+
+    #[test]
+    fn runtime_test_match_cache_stats() {
+        let s_a = Expr::functor("s", vec![Expr::variable("a")]);
+        let f = |args: Vec<Expr>| Expr::functor("f", args);
+        let expr = f(vec![f(vec![s_a.clone(), s_a.clone()]), f(vec![s_a.clone(), s_a.clone()]), s_a.clone()]);
+        let (left, right) = (Expr::functor("s", vec![Expr::variable("x")]), Expr::variable("x"));
+        let limits = Limits::new();
+
+        let (cached, cache) = (Stats::default(), MatchCache::default());
+        let uncached = Stats::default();
+        for depth in 0..4 {
+            // The second traversal is entirely served from the cache
+            for _ in 0..2 {
+                let expr_cached = traverse_match(&expr, &left, &right, depth, &cached, Some(&cache), &limits).unwrap();
+                assert_eq!(expr_cached, traverse_match(&expr, &left, &right, depth, &uncached, None, &limits).unwrap());
+                assert_eq!(cached.match_attempts.load(Ordering::Relaxed), uncached.match_attempts.load(Ordering::Relaxed));
+            }
+        }
+        assert_eq!(uncached.match_attempts.load(Ordering::Relaxed), 2 * (1 + 3 + 5 + 4));
+        assert_eq!(uncached.cache_hits.load(Ordering::Relaxed), 0);
+        assert!(cached.cache_hits.load(Ordering::Relaxed) > 0);
+    }
+
+    // Rewrite every sub-expression at the depth without the match cache, as the reference of the memoized traversal
+    fn traverse_uncached(expr: &Expr, left: &Expr, right: &Expr, depth: usize) -> Expr {
+        match expr.node() {
+            _ if depth == 0 => match_patterns(expr, left, right).unwrap_or_else(|_| expr.clone()),
+            ExprNode::Functor { iden, args } => {
+                let args = args.iter().map(|arg| traverse_uncached(arg, left, right, depth - 1)).collect();
+                Expr::functor(iden.as_str(), args)
+            },
+            _ => expr.clone(),
+        }
+    }
+
+    #[test]
+    fn runtime_test_match_cache_equals_uncached() {
+        let s_a = Expr::functor("s", vec![Expr::variable("a")]);
+        let f = |arg: &Expr| Expr::functor("f", vec![arg.clone()]);
+        // Repeated sub-expressions, at the same depth and at different depths
+        let expr = Expr::functor("g", vec![f(&s_a), f(&s_a), Expr::functor("h", vec![f(&s_a), s_a.clone()])]);
+        let left = f(&Expr::variable("x"));
+        let right = Expr::functor("k", vec![Expr::variable("x")]);
+
+        let env = Env::new();
+        for depth in 0..4 {
+            let uncached = traverse_uncached(&expr, &left, &right, depth);
+            assert_eq!(ast_traverse_match(&expr, &left, &right, depth, &env).unwrap(), uncached);
+            // The second traversal is served from the cache
+            assert_eq!(ast_traverse_match(&expr, &left, &right, depth, &env).unwrap(), uncached);
+        }
+
+        // Fresh variables are instantiated before matching, so each application derives new variables instead of cached ones
+        let right = Expr::functor("pair", vec![Expr::variable("x"), Expr::functor("fresh", vec![Expr::variable("y")])]);
+        let expr = Expr::functor("g", vec![f(&s_a), f(&s_a)]);
+        let (first_right, _) = instantiate_fresh(&expr, &left, &right, env.fresh_count.load(Ordering::Relaxed));
+        let first = ast_traverse_match(&expr, &left, &right, 1, &env).unwrap();
+        assert_eq!(first, traverse_uncached(&expr, &left, &first_right, 1));
+        let (second_right, _) = instantiate_fresh(&expr, &left, &right, env.fresh_count.load(Ordering::Relaxed));
+        let second = ast_traverse_match(&expr, &left, &right, 1, &env).unwrap();
+        assert_eq!(second, traverse_uncached(&expr, &left, &second_right, 1));
+        assert_ne!(first, second);
+    }
+
+    #[test]
+    fn runtime_test_strict() {
+        let input_string = "
+            f(x)
+            apply unknown at 0
+            f(x) => g(x) at 0
+        ";
+        let mut lexer = Lexer::new();
+        lexer.lex(input_string);
+        
+        let mut parser = Parser::new();
+        let _ = parser.parse(&mut lexer);
+
+        let mut env = Env::new();
+        env.strict = true;
+        let res = env.interpret(parser.stmts);
+
+        assert_eq!(res, Err(RuntimeError::WarningInStrictMode { num_warnings: 1 }));
+        assert_eq!(env.get_expr().unwrap().to_string(), "f(x)");
+    }
+
+    #[test]
+    fn runtime_test_allow_warnings() {
+        let input_string = "
+            allow W001, W100
+            f(x)
+            g(x)
+            apply unknown at 0
+        ";
+        let mut lexer = Lexer::new();
+        lexer.lex(input_string);
+        
+        let mut parser = Parser::new();
+        let _ = parser.parse(&mut lexer);
+
+        let mut env = Env::new();
+        let res = env.interpret(parser.stmts);
+
+        assert!(res.is_ok());
+        let codes: Vec<&str> = env.warnings.iter().map(Warning::code).collect();
+        assert_eq!(codes, vec!["W009", "W005"]);
+    }
+
+    #[test]
+    fn runtime_test_write_failure() {
+        // A file cannot be a directory
+        let input_string = "
+            f(x)
+            end \"Cargo.toml/f.txt\"
+        ";
+        let mut lexer = Lexer::new();
+        lexer.lex(input_string);
+        
+        let mut parser = Parser::new();
+        let _ = parser.parse(&mut lexer);
+
+        let mut env = Env::new();
+        let res = env.interpret(parser.stmts);
+
+        assert!(matches!(res, Err(RuntimeError::CannotWriteToPath { path, .. }) if path == "Cargo.toml/f.txt"));
+    }
+
+    #[test]
+    fn runtime_test_print_steps() {
+        let input_string = "
+            def swap as pair(x, y) => pair(y, x)
+            pair(a + b, c)
+                apply swap at 0
+                pair(x, y) => f(y) at 0
+        ";
+        let mut lexer = Lexer::new();
+        lexer.lex(input_string);
+        
+        let mut parser = Parser::new();
+        let _ = parser.parse(&mut lexer);
+
+        let output = OutputBuffer::new();
+        let mut env = Env::new();
+        env.set_output(io::sink());
+        assert_eq!(env.interpret(parser.stmts), Ok(()));
+        env.set_output(output.clone());
+        env.print_steps();
+
+        assert_eq!(output.contents(), [
+            "Start matching on: pair(a + b, c)",
+            "[0] swap: pair(x, y) => pair(y, x) at depth 0",
+            "    pair(c, a + b)",
+            "[1] in-line rule: pair(x, y) => f(y) at depth 0",
+            "    f(a + b)",
+            ""
+        ].join("\n"));
+    }
+
+    #[test]
+    fn runtime_test_append_and_placeholders() {
+        let input_string = "
+            f(x)
+            end append \"log.txt\"
+            g(x)
+            end append \"log.txt\"
+            f(x)
+            end \"derivation_{n}.txt\"
+            g(x)
+            end \"derivation_{n}.txt\"
+        ";
+        let mut lexer = Lexer::new();
+        lexer.lex(input_string);
+        
+        let mut parser = Parser::new();
+        let _ = parser.parse(&mut lexer);
+
+        let base_dir = std::env::temp_dir().join(format!("raxio_append_{}", std::process::id()));
+        let mut env = Env::new();
+        env.set_output(io::sink());
+        env.base_dir = Some(base_dir.clone());
+        let res = env.interpret(parser.stmts);
+
+        let read = |file_name: &str| fs::read_to_string(base_dir.join(file_name)).unwrap_or_default();
+        let (log, first, second) = (read("log.txt"), read("derivation_1.txt"), read("derivation_2.txt"));
+        let _ = fs::remove_dir_all(&base_dir);
+        assert_eq!(res, Ok(()));
+        assert_eq!(log.matches("Result: ").count(), 2);
+        assert!(log.contains("Result: f(x)\n") && log.ends_with("Result: g(x)"));
+        assert!(first.ends_with("Result: f(x)"));
+        assert!(second.ends_with("Result: g(x)"));
+    }
+
+    #[test]
+    fn runtime_test_write_relative_to_base_dir() {
+        let input_string = "
+            f(x)
+            end \"results/nested/f.txt\"
+        ";
+        let mut lexer = Lexer::new();
+        lexer.lex(input_string);
+        
+        let mut parser = Parser::new();
+        let _ = parser.parse(&mut lexer);
+
+        let base_dir = std::env::temp_dir().join(format!("raxio_base_dir_{}", std::process::id()));
+        let mut env = Env::new();
+        env.set_output(io::sink());
+        env.base_dir = Some(base_dir.clone());
+        let res = env.interpret(parser.stmts);
+
+        assert_eq!(res, Ok(()));
+        let written = fs::read_to_string(base_dir.join("results/nested/f.txt"));
+        let _ = fs::remove_dir_all(&base_dir);
+        assert!(written.is_ok_and(|data| data.ends_with("Result: f(x)")));
+    }
+
+    #[test]
+    fn runtime_test_imports() {
+        let input_string = "
+            def swap as f(x, y) => f(y, x)
+            export rules \"swap.rxlib\"
+            import \"swap.rxlib\"
+            import \"std:logic\"
+        ";
+        let mut lexer = Lexer::new();
+        lexer.lex(input_string);
+        
+        let mut parser = Parser::new();
+        let _ = parser.parse(&mut lexer);
+
+        let base_dir = std::env::temp_dir().join(format!("raxio_imports_{}", std::process::id()));
+        let _ = fs::create_dir_all(&base_dir);
+        let mut env = Env::new();
+        env.set_output(io::sink());
+        env.base_dir = Some(base_dir.clone());
+        let res = env.interpret(parser.stmts);
+        let _ = fs::remove_dir_all(&base_dir);
+
+        assert_eq!(res, Ok(()));
+        // Only libraries imported from files are recorded, by their resolved path
+        assert_eq!(env.imports, vec![base_dir.join("swap.rxlib").display().to_string()]);
+    }
+
+    #[test]
+    fn runtime_test_export_format() {
+        let input_string = "
+            f(x)
+            end \"does_not_exist/f.pdf\" as pdf
+        ";
+        let mut lexer = Lexer::new();
+        lexer.lex(input_string);
+        
+        let mut parser = Parser::new();
+        let _ = parser.parse(&mut lexer);
+
+        let mut env = Env::new();
+        let res = env.interpret(parser.stmts);
+
+        // The derivation is not written, hence the missing directory is no error
+        assert!(res.is_ok());
+        assert_eq!(env.warnings.len(), 1);
+        assert!(matches!(&env.warnings[0], Warning::UnknownExportFormat { format, .. } if format == "pdf"));
+    }
+
+    #[test]
+    fn runtime_test_let_bindings() {
+        let input_string = "
+            let e1 = integral(mul(x, x), x)
+            let e2 = e1 + 3
+            e2
+        ";
+        let mut lexer = Lexer::new();
+        lexer.lex(input_string);
+        
+        let mut parser = Parser::new();
+        let _ = parser.parse(&mut lexer);
+
+        let mut env = Env::new();
+        let res = env.interpret(parser.stmts);
+
+        assert!(res.is_ok());
+        assert_eq!(env.history[0].to_string(), "integral(x * x, x) + 3");
+    }
+
+    #[test]
+    fn runtime_test_end_as_binding() {
+        let input_string = "
+            f(x)
+            f(x) => g(x, x) at 0
+            end as lemma1
+            h(lemma1)
+        ";
+        let mut lexer = Lexer::new();
+        lexer.lex(input_string);
+        
+        let mut parser = Parser::new();
+        let _ = parser.parse(&mut lexer);
+
+        let mut env = Env::new();
+        let res = env.interpret(parser.stmts);
+
+        assert!(res.is_ok());
+        assert_eq!(env.history, vec![Expr::functor("h", vec![
+            Expr::functor("g", vec![Expr::variable("x"), Expr::variable("x")])
+        ])]);
+    }
+
+    #[test]
+    fn runtime_test_end_as_rule() {
+        let input_string = "
+            f(x)
+            f(x) => g(x, x) at 0
+            g(x, y) => h(y, x) at 0
+            end as rule my_lemma
+            k(f(a))
+            apply my_lemma at 1
+        ";
+        let mut lexer = Lexer::new();
+        lexer.lex(input_string);
+        
+        let mut parser = Parser::new();
+        let _ = parser.parse(&mut lexer);
+
+        let mut env = Env::new();
+        let res = env.interpret(parser.stmts);
+
+        assert!(res.is_ok());
+        assert_eq!(env.rules["my_lemma"].left.to_string(), "f(x)");
+        assert_eq!(env.rules["my_lemma"].right.to_string(), "h(x, x)");
+        assert_eq!(env.history.last().unwrap().to_string(), "k(h(a, a))");
+    }
+
+    #[test]
+    fn runtime_test_fresh_variables() {
+        let input_string = "
+            f(y_1)
+            f(x) => g(fresh y, x) at 0
+            g(z, x) => h(fresh y, z, x, k(y)) at 0
+            f(a) => b at 0
+        ";
+        let mut lexer = Lexer::new();
+        lexer.lex(input_string);
+        
+        let mut parser = Parser::new();
+        let _ = parser.parse(&mut lexer);
+
+        let mut env = Env::new();
+        let res = env.interpret(parser.stmts);
+
+        assert!(res.is_ok());
+        // y_1 is already taken, and the last rule does not match
+        assert_eq!(env.history.last().unwrap().to_string(), "h(y_3, y_2, y_1, k(y_3))");
+        assert_eq!(env.fresh_count.load(Ordering::Relaxed), 3);
+    }
+
+    #[test]
+    fn runtime_test_segments() {
+        let input_string = "
+            add(a, b, f(c, d, e))
+            add(x, rest..) => add(rest.., x) at 0
+            expect add(b, f(c, d, e), a)
+            f(first, rest.., last) => g(last, rest.., first) at 1
+            expect add(b, g(e, d, c), a)
+            g(x, y, z, w, rest..) => h at 1
+            expect add(b, g(e, d, c), a)
+        ";
+        let mut lexer = Lexer::new();
+        lexer.lex(input_string);
+        
+        let mut parser = Parser::new();
+        let _ = parser.parse(&mut lexer);
+
+        let mut env = Env::new();
+        assert_eq!(env.interpret(parser.stmts), Ok(()));
+    }
+
+    #[test]
+    fn runtime_test_strings() {
+        let input_string = "
+            pair(case(\"base\", f(0)), case(\"step\", f(n)))
+            case(\"base\", x) => x at 1
+            expect pair(f(0), case(\"step\", f(n)))
+            case(\"base\", x) => x at 1
+            expect pair(f(0), case(\"step\", f(n)))
+            \"step\" => s at 2
+            expect pair(f(0), case(s, f(n)))
+        ";
+        let mut lexer = Lexer::new();
+        lexer.lex(input_string);
+        
+        let mut parser = Parser::new();
+        let _ = parser.parse(&mut lexer);
+
+        let mut env = Env::new();
+        env.set_output(io::sink());
+        assert_eq!(env.interpret(parser.stmts), Ok(()));
+    }
+
+    #[test]
+    fn runtime_test_auto_rules() {
+        let input_string = "
+            def zero as x + 0 => x auto
+            def one as x * 1 => x auto
+            f(a + 0)
+            f(x) => g(x * 1 + 0, x) at 0
+            expect g(a, a)
+            end
+            def swap as pair(x, y) => pair(y, x) auto
+            f(a)
+            f(x) => pair(x, b) at 0
+        ";
+        let mut lexer = Lexer::new();
+        lexer.lex(input_string);
+        
+        let mut parser = Parser::new();
+        let _ = parser.parse(&mut lexer);
+
+        let mut env = Env::new();
+        env.set_output(io::sink());
+        let second = parser.stmts.split_off(5);
+        assert_eq!(env.interpret(parser.stmts), Ok(()));
+        // The expression that starts pattern matching is not simplified, only the rewrites after it
+        let names: Vec<_> = env.derivation_history.iter().map(|rule| rule.name.as_deref()).collect();
+        assert_eq!(names, vec![None, Some("one"), Some("zero")]);
+
+        assert_eq!(env.interpret(second), Ok(()));
+        assert_eq!(env.get_expr(), Some(&Expr::functor("pair", vec![Expr::variable("b"), Expr::variable("a")])));
+        assert!(matches!(&env.warnings[..], [Warning::RewriteCycleDetected(expr)] if expr == "pair(a, b)"));
+    }
+
+    #[test]
+    fn runtime_test_groups() {
+        let input_string = "
+            c * (a + b)
+            x + y => y + x at 1
+            expect c * (b + a)
+            group(x) => x at 1
+            expect mul(c, add(b, a))
+            (x + y) => s(x, y) at 1
+            expect c * s(b, a)
+            end
+            (a) + a
+            x + x => double(x) at 0
+            expect double((a))
+            end
+            f((a + 0))
+            x + 0 => x at 1
+            expect f((a))
+        ";
+        let mut lexer = Lexer::new();
+        lexer.lex(input_string);
+        
+        let mut parser = Parser::new();
+        let _ = parser.parse(&mut lexer);
+
+        let mut env = Env::new();
+        env.set_output(io::sink());
+        assert_eq!(env.interpret(parser.stmts), Ok(()));
+    }
+
+    #[test]
+    fn runtime_test_lists() {
+        let input_string = "
+            map(f, map(g, [a, b]))
+            map(f, map(g, xs)) => map(comp(f, g), xs) at 0
+            expect map(comp(f, g), list(a, b))
+            def map_cons as map(f, [x, rest..]) => cons(app(f, x), map(f, [rest..]))
+            def map_nil as map(f, []) => []
+            def cons as cons(x, [rest..]) => [x, rest..]
+            apply map_cons at any
+            apply map_cons at any
+            apply map_nil at any
+            apply cons at any
+            apply cons at any
+            expect [app(comp(f, g), a), app(comp(f, g), b)]
+        ";
+        let mut lexer = Lexer::new();
+        lexer.lex(input_string);
+        
+        let mut parser = Parser::new();
+        let _ = parser.parse(&mut lexer);
+
+        let mut env = Env::new();
+        env.set_output(io::sink());
+        assert_eq!(env.interpret(parser.stmts), Ok(()));
+    }
+
+    #[test]
+    fn runtime_test_flatten() {
+        let input_string = "
+            add(a + b, add(c, d))
+            flatten add
+            expect add(a, b, c, d)
+            add(x, rest..) => add(rest.., x) at 0
+            unflatten add
+            expect b + c + d + a
+        ";
+        let mut lexer = Lexer::new();
+        lexer.lex(input_string);
+        
+        let mut parser = Parser::new();
+        let _ = parser.parse(&mut lexer);
+
+        let mut env = Env::new();
+        assert_eq!(env.interpret(parser.stmts), Ok(()));
+        assert_eq!(env.derivation_history[0].name.as_deref(), Some("flatten add"));
+        assert_eq!(env.history.len(), 4);
+    }
+
+    #[test]
+    fn runtime_test_canonicalize() {
+        let input_string = "
+            commutative add, mul
+            def double as g(x) => x * 2
+            prove add(b, g(a)) == add(mul(2, a), b) using double
+            f(b + a, c * 2)
+            canonicalize
+            expect f(add(a, b), mul(2, c))
+            check f(a + b, c * 2)
+        ";
+        let mut lexer = Lexer::new();
+        lexer.lex(input_string);
+        
+        let mut parser = Parser::new();
+        let _ = parser.parse(&mut lexer);
+
+        let mut env = Env::new();
+        assert_eq!(env.interpret(parser.stmts), Ok(()));
+        assert!(env.warnings.is_empty());
+        assert_eq!(env.derivation_history[0].name.as_deref(), Some("canonicalize"));
+    }
+
+    #[test]
+    fn runtime_test_check_termination() {
+        let input_string = "
+            def plus_0 as add(x, zero) => x
+            def swap as add(x, y) => add(y, x)
+            def distribute as mul(x, add(y, z)) => add(mul(x, y), mul(x, z))
+            check-termination plus_0, swap, distribute, unknown
+        ";
+        let mut lexer = Lexer::new();
+        lexer.lex(input_string);
+        
+        let mut parser = Parser::new();
+        let _ = parser.parse(&mut lexer);
+
+        let mut env = Env::new();
+        let res = env.interpret(parser.stmts);
+
+        assert!(res.is_ok());
+        assert_eq!(env.warnings.len(), 3);
+        assert!(matches!(&env.warnings[0], Warning::RuleMayNotTerminate { iden, .. } if iden == "swap"));
+        assert!(matches!(&env.warnings[1], Warning::RuleMayNotTerminate { iden, reason } if iden == "distribute" && reason.contains("'x'")));
+        assert!(matches!(&env.warnings[2], Warning::RuleDoesNotExist(iden) if iden == "unknown"));
+    }
+
+    #[test]
+    fn runtime_test_overlaps() {
+        let input_string = "
+            def distribute as mul(x, add(y, z)) => add(mul(x, y), mul(x, z))
+            def plus_0 as add(x, 0) => x
+            def double(n) as f(x) => mul(n, x)
+            overlaps distribute, plus_0, double, unknown
+        ";
+        let mut lexer = Lexer::new();
+        lexer.lex(input_string);
+        
+        let mut parser = Parser::new();
+        let _ = parser.parse(&mut lexer);
+
+        let mut env = Env::new();
+        let res = env.interpret(parser.stmts);
+
+        assert!(res.is_ok());
+        assert_eq!(env.warnings.len(), 2);
+        assert!(matches!(&env.warnings[0], Warning::RuleArityMismatch { iden, .. } if iden == "double"));
+        assert!(matches!(&env.warnings[1], Warning::RuleDoesNotExist(iden) if iden == "unknown"));
+    }
+
+    #[test]
+    fn runtime_test_set_output() {
+        let input_string = "
+            set prompt \"λ> \"
+            set verbosity quiet
+            silent { set max_depth 20 }
+        ";
+        let mut lexer = Lexer::new();
+        lexer.lex(input_string);
+        
+        let mut parser = Parser::new();
+        let _ = parser.parse(&mut lexer);
+
+        let mut env = Env::new();
+        let res = env.interpret(parser.stmts);
+
+        assert!(res.is_ok());
+        assert_eq!(env.config.prompt, "λ> ");
+        assert!(env.is_quiet());
+        assert_eq!(env.limits.max_depth, 20);
+    }
+
+    #[test]
+    fn runtime_test_silent() {
+        let input_string = "
+            def swap as f(x, y) => f(y, x)
+            f(a, b)
+            silent {
+                apply swap at 0
+                apply swap at 0
+                apply swap at 0
+                apply g at 0
+            }
+        ";
+        let mut lexer = Lexer::new();
+        lexer.lex(input_string);
+        
+        let mut parser = Parser::new();
+        let _ = parser.parse(&mut lexer);
+
+        let mut env = Env::new();
+        let res = env.interpret(parser.stmts);
+
+        assert!(res.is_ok());
+        assert_eq!(env.config.verbosity, Verbosity::Normal);
+        assert_eq!(env.history.len(), 4);
+        assert_eq!(env.history.last(), Some(&Expr::functor("f", vec![Expr::variable("b"), Expr::variable("a")])));
+        // Warnings inside the block are still reported
+        assert!(matches!(env.warnings[..], [
+            Warning::RewriteCycleDetected(_), 
+            Warning::RewriteCycleDetected(_), 
+            Warning::RuleDoesNotExist(_)
+        ]));
+    }
+
+    #[test]
+    fn runtime_test_unify() {
+        let input_string = "
+            unify f(x, g(y)) with f(g(z), x)
+            unify x with g(x)
+            unify f(1) with f(2)
+        ";
+        let mut lexer = Lexer::new();
+        lexer.lex(input_string);
+        
+        let mut parser = Parser::new();
+        let _ = parser.parse(&mut lexer);
+
+        let mut env = Env::new();
+        let res = env.interpret(parser.stmts);
+
+        assert!(res.is_ok());
+        assert_eq!(env.warnings.len(), 2);
+        assert!(env.warnings.iter().all(|warn| matches!(warn, Warning::NotUnifiable { .. })));
+    }
+
+    #[test]
+    fn runtime_test_any_depth() {
+        let input_string = "
+            def swap as pair(x, y) => pair(y, x)
+            f(g(pair(a, b)), pair(c, d))
+            apply swap at any
+            g(x) => h(x) at any
+        ";
+        let mut lexer = Lexer::new();
+        lexer.lex(input_string);
+        
+        let mut parser = Parser::new();
+        let _ = parser.parse(&mut lexer);
+
+        let mut env = Env::new();
+        let res = env.interpret(parser.stmts);
+
+        assert!(res.is_ok());
+        assert_eq!(env.history.last().unwrap().to_string(), "f(h(pair(a, b)), pair(d, c))");
+        let depths: Vec<usize> = env.derivation_history.iter().map(|rule| rule.depth).collect();
+        assert_eq!(depths, vec![1, 1]);
+    }
+
+    #[test]
+    fn runtime_test_depth_range() {
+        let input_string = "
+            def swap as pair(x, y) => pair(y, x)
+            pair(f(pair(a, b)), g(h(pair(c, d))))
+            apply swap at 1..3
+            apply swap at 4..6
+        ";
+        let mut lexer = Lexer::new();
+        lexer.lex(input_string);
+        
+        let mut parser = Parser::new();
+        let _ = parser.parse(&mut lexer);
+
+        let mut env = Env::new();
+        env.set_output(io::sink());
+        let res = env.interpret(parser.stmts);
+
+        assert!(res.is_ok());
+        // The pairs at depth 2 and 3 are swapped, but the pair at depth 0 is outside of the range
+        assert_eq!(env.history.last().unwrap().to_string(), "pair(f(pair(b, a)), g(h(pair(d, c))))");
+        let depths: Vec<usize> = env.derivation_history.iter().map(|rule| rule.depth).collect();
+        assert_eq!(depths, vec![2]);
+        assert!(matches!(env.warnings.as_slice(), [Warning::RuleDidNotMatch { depth, .. }] if depth == "4..6"));
+    }
+
+    #[test]
+    fn runtime_test_innermost() {
+        let input_string = "
+            def plus_0 as add(x, 0) => x
+            def nest as f(x) => f(g(x))
+            add(add(f(a), 0), 0)
+            apply plus_0 innermost
+            apply nest innermost
+        ";
+        let mut lexer = Lexer::new();
+        lexer.lex(input_string);
+        
+        let mut parser = Parser::new();
+        let _ = parser.parse(&mut lexer);
+
+        let mut env = Env::new();
+        env.set_output(io::sink());
+        let res = env.interpret(parser.stmts);
+
+        assert!(res.is_ok());
+        // The inner addition is rewritten first, such that the outer one matches, but a rewritten sub-expression is not matched again
+        let exprs: Vec<String> = env.history.iter().map(Expr::to_string).collect();
+        assert_eq!(exprs, vec!["f(a) + 0 + 0", "f(a)", "f(g(a))"]);
+        let depths: Vec<usize> = env.derivation_history.iter().map(|rule| rule.depth).collect();
+        assert_eq!(depths, vec![1, 0]);
+    }
+
+    #[test]
+    fn runtime_test_once() {
+        let input_string = "
+            def swap as pair(x, y) => pair(y, x)
+            f(pair(pair(a, b), c), pair(d, e))
+            apply swap once
+            apply swap once
+            pair(x, y) => pair(y, y) once
+        ";
+        let mut lexer = Lexer::new();
+        lexer.lex(input_string);
+        
+        let mut parser = Parser::new();
+        let _ = parser.parse(&mut lexer);
+
+        let output = OutputBuffer::new();
+        let mut env = Env::new();
+        env.set_output(output.clone());
+        let res = env.interpret(parser.stmts);
+
+        assert!(res.is_ok());
+        // Only the outermost-leftmost match is rewritten, and not the matches inside of it or to the right of it
+        let exprs: Vec<String> = env.history.iter().map(Expr::to_string).collect();
+        assert_eq!(exprs, vec![
+            "f(pair(pair(a, b), c), pair(d, e))",
+            "f(pair(c, pair(a, b)), pair(d, e))",
+            "f(pair(pair(a, b), c), pair(d, e))",
+            "f(pair(c, c), pair(d, e))",
+        ]);
+        assert!(output.contents().contains("    (matched at depth 1, path 0)\n"));
+    }
+
+    #[test]
+    fn runtime_test_preview() {
+        let input_string = "
+            preview swap at 0
+            def swap as pair(x, y) => pair(y, x)
+            pair(a, b)
+            preview swap at 0
+            preview unknown at 0
+        ";
+        let mut lexer = Lexer::new();
+        lexer.lex(input_string);
+        
+        let mut parser = Parser::new();
+        let _ = parser.parse(&mut lexer);
+
+        let mut env = Env::new();
+        let res = env.interpret(parser.stmts);
+
+        assert!(res.is_ok());
+        assert_eq!(env.history, vec![Expr::functor("pair", vec![Expr::variable("a"), Expr::variable("b")])]);
+        assert!(env.derivation_history.is_empty());
+        let codes: Vec<&str> = env.warnings.iter().map(Warning::code).collect();
+        assert_eq!(codes, vec!["W013", "W005"]);
+    }
+
+    #[test]
+    fn runtime_test_checkpoints() {
+        let input_string = "
+            checkpoint start
+            f(x)
+            checkpoint start
+            f(x) => g(x) at 0
+            goto start
+            f(x) => h(x) at 0
+            goto unknown
+        ";
+        let mut lexer = Lexer::new();
+        lexer.lex(input_string);
+        
+        let mut parser = Parser::new();
+        let _ = parser.parse(&mut lexer);
+
+        let mut env = Env::new();
+        let res = env.interpret(parser.stmts);
+
+        assert!(res.is_ok());
+        assert_eq!(env.history, vec![
+            Expr::functor("f", vec![Expr::variable("x")]), 
+            Expr::functor("h", vec![Expr::variable("x")])
+        ]);
+        assert_eq!(env.derivation_history.len(), 1);
+        assert!(!env.seen_exprs.contains(&Expr::functor("g", vec![Expr::variable("x")])));
+        let codes: Vec<&str> = env.warnings.iter().map(Warning::code).collect();
+        assert_eq!(codes, vec!["W014", "W015"]);
+    }
+
+    #[test]
+    fn runtime_test_rollback() {
+        let input_string = "
+            rollback 0
+            f(x)
+            f(x) => g(x) at 0
+            g(x) => h(x) at 0
+            h(x) => k(x) at 0
+            rollback 1
+            redo
+            rollback 4
+            redo
+            redo
+        ";
+        let mut lexer = Lexer::new();
+        lexer.lex(input_string);
+        
+        let mut parser = Parser::new();
+        let _ = parser.parse(&mut lexer);
+
+        let mut env = Env::new();
+        env.set_output(io::sink());
+        let res = env.interpret(parser.stmts);
+
+        assert!(res.is_ok());
+        // The redone steps are identical to the rolled back steps
+        assert_eq!(env.history, vec![
+            Expr::functor("f", vec![Expr::variable("x")]), 
+            Expr::functor("g", vec![Expr::variable("x")]),
+            Expr::functor("h", vec![Expr::variable("x")]),
+            Expr::functor("k", vec![Expr::variable("x")])
+        ]);
+        assert_eq!(env.derivation_history.len(), 3);
+        assert!(env.redo_steps.is_empty());
+        assert_eq!(env.warnings, vec![
+            Warning::RollbackStmtHasNoEffect,
+            Warning::StepDoesNotExist { step: 4, num_steps: 2 },
+            Warning::NothingToRedo
+        ]);
+    }
+
+    #[test]
+    fn runtime_test_contexts() {
+        let input_string = "
+            def plus_0 as add(x, 0) => x
+            def mul_1 as mul(x, 1) => x
+            context lhs
+            add(a, 0)
+            context rhs
+            mul(a, 1)
+            equal? lhs, rhs
+            apply mul_1 at 0
+            switch lhs
+            equal? lhs, rhs
+            apply plus_0 at 0
+            equal? rhs, lhs
+            equal? main, lhs
+            switch other
+        ";
+        let mut lexer = Lexer::new();
+        lexer.lex(input_string);
+        
+        let mut parser = Parser::new();
+        let _ = parser.parse(&mut lexer);
+
+        let output = OutputBuffer::new();
+        let mut env = Env::new();
+        env.set_output(output.clone());
+        env.config.verbosity = Verbosity::Quiet;
+        let res = env.interpret(parser.stmts);
+
+        assert!(res.is_ok());
+        // Each context has its own derivation
+        assert_eq!(env.context, "lhs");
+        assert_eq!(env.derivation_history.len(), 1);
+        assert_eq!(env.contexts["rhs"].derivation_history.len(), 1);
+        assert!(!env.contexts["main"].is_matching);
+        assert_eq!(env.warnings, vec![
+            Warning::ContextsDiffer { 
+                left: "lhs".to_string(), 
+                right: "rhs".to_string(), 
+                left_expr: "a + 0".to_string(), 
+                right_expr: "a * 1".to_string() 
+            },
+            Warning::ContextsDiffer { 
+                left: "lhs".to_string(), 
+                right: "rhs".to_string(), 
+                left_expr: "a + 0".to_string(), 
+                right_expr: "a".to_string() 
+            },
+            Warning::ContextHasNoExpr("main".to_string()),
+            Warning::ContextDoesNotExist("other".to_string()),
+        ]);
+        assert!(output.contents().ends_with("Start matching on: a * 1\n"));
+    }
+
+    #[test]
+    fn runtime_test_tagged_rules() {
+        let input_string = "
+            def plus_0 as add(x, 0) => x tags [arith, simplify]
+            def mul_1 as mul(x, 1) => x tags [simplify]
+            def scale(k) as x => mul(k, x) tags [simplify]
+            def swap as add(x, y) => add(y, x)
+            mul(add(a, 0), 1)
+            apply (tagged simplify)* at any
+            apply tagged ring at 0
+        ";
+        let mut lexer = Lexer::new();
+        lexer.lex(input_string);
+        
+        let mut parser = Parser::new();
+        let _ = parser.parse(&mut lexer);
+
+        let mut env = Env::new();
+        env.set_output(io::sink());
+        let res = env.interpret(parser.stmts);
+
+        assert!(res.is_ok());
+        assert_eq!(env.tagged_rules("simplify"), vec!["mul_1", "plus_0"]);
+        // The rules are tried in the order of their identifiers, and rule schemas are skipped
+        let names: Vec<_> = env.derivation_history.iter().map(|rule| rule.name.clone().unwrap()).collect();
+        assert_eq!(names, vec!["mul_1", "plus_0"]);
+        assert_eq!(env.history.last(), Some(&Expr::variable("a")));
+        assert_eq!(env.warnings, vec![
+            Warning::LeftExprIsVariable { rule: "scale".to_string(), var: "x".to_string() },
+            Warning::TagHasNoRules("ring".to_string()),
+        ]);
+    }
+
+    #[test]
+    fn runtime_test_infix_operators() {
+        let input_string = "
+            functor oplus/2, add/3
+            infix ⊕ as oplus precedence 1
+            infix + as plus precedence 1
+            infix ⊗ as oplus precedence 2
+            def dist as a * (b ⊕ c) => a * b ⊕ a * c
+            x * (y ⊕ z)
+            apply dist at 0
+        ";
+        let mut lexer = Lexer::new();
+        lexer.lex(input_string);
+        
+        let mut parser = Parser::new();
+        let _ = parser.parse(&mut lexer);
+
+        let output = OutputBuffer::new();
+        let mut env = Env::new();
+        env.set_output(output.clone());
+        env.config.verbosity = Verbosity::Quiet;
+        let res = env.interpret(parser.stmts);
+
+        assert!(res.is_ok());
+        assert_eq!(env.warnings, vec![
+            Warning::OperatorArityMismatch { iden: "add".to_string(), symbol: "+".to_string(), arity: 3 },
+            Warning::OperatorSymbolExists { symbol: "+".to_string(), iden: "add".to_string() },
+            Warning::OperatorFunctorExists { iden: "oplus".to_string(), symbol: "⊕".to_string() },
+        ]);
+        assert_eq!(env.get_expr().unwrap().to_string_with(&env.operators), "x * y ⊕ x * z");
+        assert_eq!(output.contents(), "Start matching on: x * (y ⊕ z)\n");
+    }
+
+    #[test]
+    fn runtime_test_replace() {
+        let input_string = "
+            replace a with b
+            let s = f(x)
+            g(f(x), f(y), x)
+            replace s with z
+            replace f(x) with z
+        ";
+        let mut lexer = Lexer::new();
+        lexer.lex(input_string);
+        
+        let mut parser = Parser::new();
+        let _ = parser.parse(&mut lexer);
+
+        let mut env = Env::new();
+        env.set_output(io::sink());
+        let res = env.interpret(parser.stmts);
+
+        assert!(res.is_ok());
+        assert_eq!(env.get_expr().unwrap().to_string(), "g(z, f(y), x)");
+        assert_eq!(env.derivation_history[0].name.as_deref(), Some("replace f(x) with z"));
+        assert_eq!(env.warnings, vec![
+            Warning::ReplaceStmtHasNoEffect,
+            Warning::SubExprDoesNotOccur { sub_expr: "f(x)".to_string(), expr: "g(z, f(y), x)".to_string() },
+        ]);
+    }
+
+    #[test]
+    fn runtime_test_find() {
+        let input_string = "
+            g(f(a, b), h(f(c, c)))
+            find f(x, _)
+            find f(x, x)
+            find k(_)
+            f(_, _) => z at 1
+        ";
+        let mut lexer = Lexer::new();
+        lexer.lex(input_string);
+        
+        let mut parser = Parser::new();
+        let _ = parser.parse(&mut lexer);
+
+        let output = OutputBuffer::new();
+        let mut env = Env::new();
+        env.set_output(output.clone());
+        env.config.verbosity = Verbosity::Quiet;
+        let res = env.interpret(parser.stmts);
+
+        assert!(res.is_ok());
+        // Every occurrence of the wildcard matches another expression, unlike a variable
+        assert_eq!(output.contents(), [
+            "Start matching on: g(f(a, b), h(f(c, c)))",
+            "    Found 2 match(es) of f(x, _):",
+            "        depth 1, path 0: f(a, b)",
+            "        depth 2, path 1.0: f(c, c)",
+            "    Found 1 match(es) of f(x, x):",
+            "        depth 2, path 1.0: f(c, c)",
+            "",
+        ].join("\n"));
+        assert_eq!(env.warnings, vec![
+            Warning::PatternNotFound { pattern: "k(_)".to_string(), expr: "g(f(a, b), h(f(c, c)))".to_string() },
+        ]);
+        assert_eq!(env.get_expr().unwrap().to_string(), "g(z, h(f(c, c)))");
+    }
+
+    #[test]
+    fn runtime_test_focus() {
+        let input_string = "
+            def plus_0 as add(x, 0) => x
+            f(g(a, b + 0), c)
+            focus path 0.1
+            apply plus_0 at 0
+            focus path 3
+            unfocus
+            unfocus
+            focus path 0
+            focus path 0
+            a => z at 0
+            end
+        ";
+        let mut lexer = Lexer::new();
+        lexer.lex(input_string);
+        
+        let mut parser = Parser::new();
+        let _ = parser.parse(&mut lexer);
+
+        let mut env = Env::new();
+        let (sender, receiver) = std::sync::mpsc::channel();
+        env.subscribe(Box::new(sender));
+        env.set_output(io::sink());
+        let res = env.interpret(parser.stmts);
+
+        assert!(res.is_ok());
+        // The derivation of a focused sub-expression is a single step of the expression around it, also at the end
+        let steps: Vec<_> = receiver.try_iter().filter_map(|event| match event {
+            Event::StepApplied { expr, rule } => Some((expr.to_string(), rule.name.unwrap_or_default(), rule.depth)),
+            _ => None
+        }).collect();
+        assert_eq!(steps, vec![
+            ("b".to_string(), "plus_0".to_string(), 0),
+            ("f(g(a, b), c)".to_string(), "focus path 0.1".to_string(), 2),
+            ("z".to_string(), String::new(), 0),
+            ("g(z, b)".to_string(), "focus path 0".to_string(), 1),
+            ("f(g(z, b), c)".to_string(), "focus path 0".to_string(), 1),
+        ]);
+        assert!(env.focus.is_empty());
+        assert_eq!(env.warnings, vec![
+            Warning::PathDoesNotExist { path: "3".to_string(), expr: "b".to_string() },
+            Warning::NotFocused,
+        ]);
+    }
+
+    #[test]
+    fn runtime_test_choose_match() {
+        let input_string = "
+            def swap as f(x, y) => f(y, x)
+            g(f(a, b), h(f(c, d)), f(e, c))
+            apply swap at any
+            f(x, y) => k at 1
+            apply swap at 2
+            end
+        ";
+        let mut lexer = Lexer::new();
+        lexer.lex(input_string);
+        
+        let mut parser = Parser::new();
+        let _ = parser.parse(&mut lexer);
+
+        let mut env = Env::new();
+        let (sender, receiver) = std::sync::mpsc::channel();
+        env.subscribe(Box::new(sender));
+        let (candidate_sender, candidate_receiver) = std::sync::mpsc::channel();
+        let mut choices = vec![Choice::All, Choice::One(1)];
+        env.chooser = Some(Box::new(move |candidates: &[(Vec<usize>, Expr)]| {
+            let _ = candidate_sender.send(candidates.iter().map(|(path, _)| path_to_string(path)).collect::<Vec<_>>());
+            choices.pop().unwrap_or(Choice::All)
+        }));
+        env.set_output(io::sink());
+        let res = env.interpret(parser.stmts);
+
+        assert!(res.is_ok());
+        // A single match at the depth is rewritten without asking
+        assert_eq!(candidate_receiver.try_iter().collect::<Vec<_>>(), vec![
+            vec!["0".to_string(), "2".to_string()],
+            vec!["0".to_string(), "2".to_string()],
+        ]);
+        let steps: Vec<_> = receiver.try_iter().filter_map(|event| match event {
+            Event::StepApplied { expr, rule } => Some((expr.to_string(), rule.depth)),
+            _ => None
+        }).collect();
+        assert_eq!(steps, vec![
+            ("g(f(a, b), h(f(c, d)), f(c, e))".to_string(), 1),
+            ("g(k, h(f(c, d)), k)".to_string(), 1),
+            ("g(k, h(f(d, c)), k)".to_string(), 2),
+        ]);
+        assert!(env.warnings.is_empty());
+    }
+
+    #[test]
+    fn runtime_test_check() {
+        let input_string = "
+            check f(x)
+            f(x)
+            f(x) => g(x, x) at 0
+            check g(x, x)
+            check g(x, y)
+        ";
+        let mut lexer = Lexer::new();
+        lexer.lex(input_string);
+        
+        let mut parser = Parser::new();
+        let _ = parser.parse(&mut lexer);
+
+        let mut env = Env::new();
+        let res = env.interpret(parser.stmts);
+
+        assert!(res.is_ok());
+        assert_eq!(env.warnings.len(), 2);
+        assert!(matches!(env.warnings[0], Warning::CheckStmtHasNoEffect));
+        assert!(matches!(&env.warnings[1], Warning::CheckFailed { expected, .. } if expected == "g(x, y)"));
+    }
+
+    #[test]
+    fn runtime_test_expect() {
+        let input_string = "
+            f(x)
+            f(x) => g(f(x), f(x)) at 0
+            expect g(f(x), f(x))
+            x => A at 2
+            expect g(f(A), f(x))
+        ";
+        let mut lexer = Lexer::new();
+        lexer.lex(input_string);
+        
+        let mut parser = Parser::new();
+        let _ = parser.parse(&mut lexer);
+
+        let mut env = Env::new();
+        let res = env.interpret(parser.stmts);
+
+        assert_eq!(res, Err(RuntimeError::ExpectationFailed { 
+            expected: "g(f(A), f(x))".to_string(), 
+            got: "g(f(A), f(A))".to_string() 
+        }));
+
+        let mut env = Env::new();
+        let res = env.interpret(vec![Stmt::ExpectStmt(Expr::variable("x"))]);
+        assert_eq!(res, Err(RuntimeError::NoCurrentExpr));
+    }
+
+    #[test]
+    fn runtime_test_functor_arities() {
+        let input_string = "
+            functor add/2, neg/1
+            def neg_add as neg(add(x, y)) => add(neg(x), neg(y, y))
+            neg(a) + b
+        ";
+        let mut lexer = Lexer::new();
+        lexer.lex(input_string);
+        
+        let mut parser = Parser::new();
+        let _ = parser.parse(&mut lexer);
+
+        let mut env = Env::new();
+        let res = env.interpret(parser.stmts);
+
+        assert!(res.is_ok());
+        assert_eq!(env.warnings.len(), 1);
+        assert!(matches!(&env.warnings[0], Warning::FunctorArityMismatch { iden, expected: 1, got: 2 } if iden == "neg"));
+    }
+
+    #[test]
+    fn runtime_test_numbers() {
+        let input_string = "
+            f(1, 2.5)
+            f(0, y) => g(y) at 0
+            expect f(1, 2.5)
+            f(1, y) => g(y) at 0
+            expect g(2.5)
+            2.5 => 2 at 1
+            expect g(2)
+        ";
+        let mut lexer = Lexer::new();
+        lexer.lex(input_string);
+
+        let mut parser = Parser::new();
+        let _ = parser.parse(&mut lexer);
+
+        let mut env = Env::new();
+        assert_eq!(env.interpret(parser.stmts), Ok(()));
+
+        // Previously, numbers were variables that match any expression
+        let input_string = "
+            f(1)
+            f(0) => g(0) at 0
+            expect g(1)
+        ";
+        let mut lexer = Lexer::new();
+        lexer.lex(input_string);
+
+        let mut parser = Parser::new();
+        parser.numbers_as_variables = true;
+        let _ = parser.parse(&mut lexer);
+
+        let mut env = Env::new();
+        assert_eq!(env.interpret(parser.stmts), Ok(()));
+    }
+
+    #[test]
+    fn runtime_test_show() {
+        struct Upper;
+        impl Formatter for Upper {
+            fn format(&self, expr: &Expr) -> String {
+                expr.to_string().to_uppercase()
+            }
+        }
+
+        let input_string = "
+            show
+            f(x)
+            show as latex
+            show as upper
+            show as unknown
+        ";
+        let mut lexer = Lexer::new();
+        lexer.lex(input_string);
+        
+        let mut parser = Parser::new();
+        let _ = parser.parse(&mut lexer);
+
+        let mut env = Env::new();
+        env.register_formatter("upper", Box::new(Upper));
+        let res = env.interpret(parser.stmts);
+
+        assert!(res.is_ok());
+        assert_eq!(env.warnings.len(), 2);
+        assert!(matches!(env.warnings[0], Warning::ShowStmtHasNoEffect));
+        assert!(matches!(&env.warnings[1], Warning::UnknownNotation { available, .. } if available.len() == 6));
+    }
+
+    #[test]
+    fn runtime_test_events() {
+        let input_string = "
+            def swap as f(x, y) => f(y, x)
+            f(a, b)
+            apply swap at 0
+            apply swp at 0
+            silent {
+                allow W005
+                apply swp at 0
+                apply swap at 0
+            }
+            end
+        ";
+        let mut lexer = Lexer::new();
+        lexer.lex(input_string);
+
+        let mut parser = Parser::new();
+        let _ = parser.parse(&mut lexer);
+
+        let (sender, receiver) = std::sync::mpsc::channel();
+        let mut env = Env::new();
+        env.subscribe(Box::new(sender));
+        let res = env.interpret(parser.stmts);
+        drop(env);
+
+        assert!(res.is_ok());
+        let (f_ab, f_ba) = (Expr::functor("f", vec![Expr::variable("a"), Expr::variable("b")]), Expr::functor("f", vec![Expr::variable("b"), Expr::variable("a")]));
+        let events: Vec<Event> = receiver.iter().collect();
+        assert_eq!(events.len(), 6);
+        assert_eq!(events[0], Event::MatchStarted(f_ab.clone()));
+        assert!(matches!(&events[1], Event::StepApplied { expr, rule } if *expr == f_ba && rule.name.as_deref() == Some("swap")));
+        assert_eq!(events[2], Event::Warning(Warning::RuleDoesNotExist("swp".to_string())));
+        assert!(matches!(&events[3], Event::StepApplied { expr, .. } if *expr == f_ab));
+        assert_eq!(events[4], Event::Warning(Warning::RewriteCycleDetected(f_ab.to_string())));
+        assert_eq!(events[5], Event::Result(f_ab));
+    }
+
+    #[test]
+    fn runtime_test_output() {
+        let input_string = "
+            def swap as x + y => y + x
+            a + b
+            apply swap at 0
+            show as sexpr
+            end
+        ";
+        let mut lexer = Lexer::new();
+        lexer.lex(input_string);
+
+        let mut parser = Parser::new();
+        let _ = parser.parse(&mut lexer);
+
+        let output = OutputBuffer::new();
+        let mut env = Env::new();
+        env.set_output(output.clone());
+        let res = env.interpret(parser.stmts);
+        env.print_prefix();
+
+        assert!(res.is_ok());
+        assert_eq!(output.contents(), concat!(
+            "Start matching on: a + b\n",
+            "                   As functor: add(a, b)\n",
+            "    b + a\n",
+            "    As functor: add(b, a)\n",
+            "    (add b a)\n",
+            "Result: b + a\n",
+            "        As functor: add(b, a)\n",
+            "> ",
+        ));
+    }
+
+    #[test]
+    fn runtime_test_trace() {
+        let input_string = "
+            def twice as add(x, x) => mul(2, x)
+            f(add(a, b), g(a), add(c, c))
+            apply twice at 1
+            set trace on
+            apply twice at 1
+            apply twice at 3
+        ";
+        let mut lexer = Lexer::new();
+        lexer.lex(input_string);
+
+        let mut parser = Parser::new();
+        let _ = parser.parse(&mut lexer);
+
+        let output = OutputBuffer::new();
+        let mut env = Env::new();
+        env.set_output(output.clone());
+        env.config.verbosity = Verbosity::Quiet;
+        let res = env.interpret(parser.stmts);
+
+        assert!(res.is_ok());
+        // A variable that occurs twice only matches the same expression twice
+        assert_eq!(env.history[1].to_string(), "f(a + b, g(a), 2 * c)");
+        assert_eq!(output.contents().lines().skip(1).collect::<Vec<_>>(), vec![
+            "    Trace of x + x at depth 1:",
+            "        a + b: inconsistent binding, 'x' is bound to both 'a' and 'b'",
+            "        g(a): identifier mismatch, expected 'add', but got 'g'",
+            "        2 * c: identifier mismatch, expected 'add', but got 'mul'",
+            "    Trace of x + x at depth 3:",
+            "        the current expression has no sub-expression at depth 3",
+        ]);
+    }
+
+    #[test]
+    fn runtime_test_trace_bindings() {
+        let input_string = "
+            set trace on
+            f(g(a, 1, 2), h(b), c)
+            f(g(x, rest..), y, _) => k(x, y) at 0
+        ";
+        let mut lexer = Lexer::new();
+        lexer.lex(input_string);
+
+        let mut parser = Parser::new();
+        let _ = parser.parse(&mut lexer);
+
+        let output = OutputBuffer::new();
+        let mut env = Env::new();
+        env.set_output(output.clone());
+        env.config.verbosity = Verbosity::Quiet;
+        let res = env.interpret(parser.stmts);
+
+        assert!(res.is_ok());
+        // Wildcards are not bound, and segments are bound to their arguments
+        assert_eq!(output.contents().lines().skip(1).collect::<Vec<_>>(), vec![
+            "    Trace of f(g(x, rest..), y, _) at depth 0:",
+            "        f(g(a, 1, 2), h(b), c): matched with x = a, rest.. = [1, 2], y = h(b)",
+        ]);
+    }
+
+    #[test]
+    fn runtime_test_undo() {
+        let input_string = "
+            f(x)
+            f(x) => g(x) at 0
+            g(x) => h(x) at 0
+            h(x) => k(x) at 0
+        ";
+        let mut lexer = Lexer::new();
+        lexer.lex(input_string);
+
+        let mut parser = Parser::new();
+        let _ = parser.parse(&mut lexer);
+
+        let output = OutputBuffer::new();
+        let mut env = Env::new();
+        env.set_output(output.clone());
+        env.config.verbosity = Verbosity::Quiet;
+        let res = env.interpret(parser.stmts);
+
+        assert!(res.is_ok());
+        env.print_prefix();
+        env.print_continuation_prefix();
+        assert_eq!(env.undo(2), 2);
+        env.print_current_expr("    ");
+        env.print_prefix();
+        // Steps before the start of the pattern matching context cannot be undone
+        assert_eq!(env.undo(5), 1);
+        env.print_current_expr("    ");
+        assert_eq!(env.undo(1), 0);
+        assert_eq!(env.history, vec![Expr::functor("f", vec![Expr::variable("x")])]);
+        assert!(env.derivation_history.is_empty());
+        assert_eq!(output.contents(), concat!(
+            "Start matching on: f(x)\n",
+            "[3] ~>    ... ",
+            "    g(x)\n",
+            "[1] ~> ",
+            "    f(x)\n",
+        ));
+    }
+
+    #[test]
+    fn runtime_test_rule_did_not_match() {
+        let input_string = "
+            def twice as add(x, x) => mul(2, x)
+            f(add(a, b), g(a))
+            apply twice at 1
+            apply twice at any
+            g(x) => g(x) at 1
+        ";
+        let mut lexer = Lexer::new();
+        lexer.lex(input_string);
+
+        let mut parser = Parser::new();
+        let _ = parser.parse(&mut lexer);
+
+        let mut env = Env::new();
+        env.set_output(io::sink());
+        let res = env.interpret(parser.stmts);
+
+        assert!(res.is_ok());
+        assert_eq!(env.warnings, vec![
+            Warning::RuleDidNotMatch {
+                rule: "twice".to_string(),
+                depth: "1".to_string(),
+                reason: "inconsistent binding, 'x' is bound to both 'a' and 'b'".to_string()
+            },
+            Warning::RuleDidNotMatch {
+                rule: "twice".to_string(),
+                depth: "any".to_string(),
+                reason: "identifier mismatch, expected 'add', but got 'f'".to_string()
+            },
+            Warning::RuleDidNotMatch {
+                rule: "g(x) => g(x)".to_string(),
+                depth: "1".to_string(),
+                reason: "the rule derives the same expression".to_string()
+            },
+        ]);
+    }
+
+    #[test]
+    fn runtime_test_parallel() {
+        // A wide expression with enough nodes to be rewritten in parallel
+        let leaves: Vec<Expr> = (0..2 * PARALLEL_MIN_NODES)
+            .map(|i| Expr::functor("f", vec![Expr::variable(format!("x{}", i % 3))]))
+            .collect();
+        let expr = Expr::functor("list", leaves);
+        let left = Expr::functor("f", vec![Expr::variable("x1")]);
+        let right = Expr::functor("g", vec![]);
+
+        let mut env = Env::new();
+        let sequential = ast_traverse_match(&expr, &left, &right, 1, &env).unwrap();
+        // Each of the three distinct leaves is matched once, after which the sequential traversal hits the cache
+        let stats = |env: &Env| (env.stats.match_attempts.swap(0, Ordering::Relaxed), env.stats.cache_hits.swap(0, Ordering::Relaxed));
+        assert_eq!(stats(&env), (2 * PARALLEL_MIN_NODES, 2 * PARALLEL_MIN_NODES - 3));
+        env.jobs = 4;
+        let parallel = ast_traverse_match(&expr, &left, &right, 1, &env).unwrap();
+
+        assert_eq!(sequential, parallel);
+        assert_ne!(sequential, expr);
+        // The parallel traversal skips the cache, but counts the same match attempts
+        assert_eq!(stats(&env), (2 * PARALLEL_MIN_NODES, 0));
+    }
+
     #[test]
     fn runtime_test_all_examples() -> Result<(), Box<dyn Error>>{
         
-        let file_names = vec![
+        let file_names = [
             "swap_pair".to_string(),
             "peano".to_string(),
             "simple_power_rule_calculus".to_string(),
             "limit_power_rule_calculus".to_string(),
+            "product_rule_calculus".to_string(),
         ];
         let path = "examples/".to_string();
         for file_name in file_names.iter() {
@@ -396,6 +4196,7 @@ mod tests {
             assert!(res.is_ok());
             
             let mut env = Env::new();
+            env.base_dir = Some(path.clone().into());
             let res = env.interpret(parser.stmts);
     
             assert!(res.is_ok());
@@ -406,7 +4207,8 @@ mod tests {
             "Result: pair(B, A)",
             "Result: s(s(s(0)))",
             "Result: 2 * y",
-            "Result: 2 * x"
+            "Result: 2 * x",
+            "Result: sin(x) + x * cos(x)"
         ];
         for (file_name, test_result) in file_names.iter().zip(test_results) {
             
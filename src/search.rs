@@ -0,0 +1,132 @@
+use std::collections::{HashMap, VecDeque};
+
+use crate::{ast::{Expr, ExprNode}, runtime::{ast_traverse_match, matches_goal, AppliedRule, Env}, error::RuntimeError};
+
+// Maximum number of rule applications in a single proof
+pub const MAX_PROOF_STEPS: usize = 8;
+
+// Maximum number of distinct expressions visited while searching for a proof
+pub const MAX_VISITED_EXPRS: usize = 10_000;
+
+// Resulting expression and applied rule of a derivation step
+type Step = (Expr, AppliedRule);
+
+// Maps each visited expression to its predecessor and the applied rule that derived it
+type Visited = HashMap<Expr, Option<(Expr, AppliedRule)>>;
+
+// Breadth-first search over the applications of the given (name, lhs, rhs) rules at every depth, starting
+// from the left expression until the right expression is derived, up to the order of the arguments of
+// commutative functors. Returns the steps of the shortest derivation, or None if there is no derivation 
+// within the bounds of the search.
+pub fn prove(left: &Expr, right: &Expr, rules: &[(String, Expr, Expr)], env: &Env) -> Result<Option<Vec<Step>>, RuntimeError> {
+    let right = &right.canonicalize(&env.commutative);
+    search(left, |expr| &expr.canonicalize(&env.commutative) == right, rules, env)
+}
+
+// Like prove, but the search stops at the first expression that matches the goal template.
+pub fn prove_goal(left: &Expr, goal: &Expr, rules: &[(String, Expr, Expr)], env: &Env) -> Result<Option<Vec<Step>>, RuntimeError> {
+    search(left, |expr| matches_goal(expr, goal), rules, env)
+}
+
+fn search(left: &Expr, is_target: impl Fn(&Expr) -> bool, rules: &[(String, Expr, Expr)], env: &Env) -> Result<Option<Vec<Step>>, RuntimeError> {
+
+    if is_target(left) {
+        return Ok(Some(vec![]));
+    }
+
+    let mut visited = Visited::new();
+    let mut queue = VecDeque::new();
+    visited.insert(left.clone(), None);
+    queue.push_back((left.clone(), 0));
+
+    while let Some((expr, num_steps)) = queue.pop_front() {
+        if num_steps == MAX_PROOF_STEPS {
+            continue;
+        }
+        env.limits.check_time()?;
+
+        for depth in 0..=expr_depth(&expr) {
+            for (name, lhs, rhs) in rules {
+                let new_expr = ast_traverse_match(&expr, lhs, rhs, depth, env)?;
+                if visited.contains_key(&new_expr) {
+                    continue;
+                }
+                if visited.len() == MAX_VISITED_EXPRS {
+                    return Ok(None);
+                }
+
+                let rule = AppliedRule::new(Some(name.clone()), lhs.clone(), rhs.clone(), depth);
+                visited.insert(new_expr.clone(), Some((expr.clone(), rule)));
+                if is_target(&new_expr) {
+                    return Ok(Some(reconstruct_derivation(&visited, new_expr)));
+                }
+                queue.push_back((new_expr, num_steps + 1));
+            }
+        }
+    }
+    Ok(None)
+}
+
+// Walk back from the derived expression to the start of the search.
+fn reconstruct_derivation(visited: &Visited, expr: Expr) -> Vec<Step> {
+    let mut steps = vec![];
+    let mut current = expr;
+    while let Some(Some((prev, rule))) = visited.get(&current) {
+        steps.push((current, rule.clone()));
+        current = prev.clone();
+    }
+    steps.reverse();
+    steps
+}
+
+// The depth of the deepest sub-expression, where the entire expression is at depth 0.
+pub fn expr_depth(expr: &Expr) -> usize {
+    let mut max_depth = 0;
+    let mut stack = vec![(expr, 0)];
+    while let Some((expr, depth)) = stack.pop() {
+        max_depth = max_depth.max(depth);
+        if let ExprNode::Functor { args, .. } = expr.node() {
+            stack.extend(args.iter().map(|arg| (arg, depth + 1)));
+        }
+    }
+    max_depth
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // The expression s(f_1(f_2(...(a)))) of the functors in order
+    fn chain(functors: &[&str]) -> Expr {
+        let inner = functors.iter().rev().fold(Expr::variable("a"), |expr, iden| Expr::functor(*iden, vec![expr]));
+        Expr::functor("s", vec![inner])
+    }
+
+    // A rule that inserts the functor directly below s
+    fn insert(iden: &str) -> (String, Expr, Expr) {
+        let x = Expr::variable("x");
+        (format!("insert_{}", iden), Expr::functor("s", vec![x.clone()]), Expr::functor("s", vec![Expr::functor(iden, vec![x])]))
+    }
+
+    #[test]
+    fn prove_step_limit() {
+        let env = Env::new();
+        let rules = [insert("f")];
+        let steps = prove(&chain(&[]), &chain(&["f"; MAX_PROOF_STEPS]), &rules, &env).unwrap().unwrap();
+        assert_eq!(steps.len(), MAX_PROOF_STEPS);
+        assert_eq!(steps[0], (chain(&["f"]), AppliedRule::new(Some("insert_f".to_string()), rules[0].1.clone(), rules[0].2.clone(), 0)));
+        // One more step is beyond the bounds of the search
+        assert_eq!(prove(&chain(&[]), &chain(&["f"; MAX_PROOF_STEPS + 1]), &rules, &env), Ok(None));
+    }
+
+    #[test]
+    fn prove_visited_limit() {
+        // Each step derives four times as many expressions, so the 5461 expressions of at most 6 steps are all visited, 
+        // whereas the expressions of 7 steps, which is within the step limit, exceed the limit before the last one is visited
+        let env = Env::new();
+        let rules = ["f", "g", "h", "k"].map(insert);
+        let steps = prove(&chain(&[]), &chain(&["k"; 6]), &rules, &env).unwrap().unwrap();
+        assert_eq!(steps.len(), 6);
+        assert_eq!(prove(&chain(&[]), &chain(&["k"; 7]), &rules, &env), Ok(None));
+    }
+}
@@ -0,0 +1,150 @@
+use crate::{ast::Expr, parser::{Strategy, Depth}, runtime::{Env, AppliedRule, ast_traverse_match_at, no_match_reason, recorded_depth}, error::{Warning, RuntimeError}};
+
+// Interprets a strategy on an expression at a fixed depth, or at the first matching depth of each rule. Every rule application that
+// changes the expression is recorded as a step, so that the derivation can be undone
+// and written to a file one rule at a time.
+pub struct StrategyRunner<'a> {
+    // Environment with the predefined rules, limits and expressions derived before running the strategy
+    env: &'a Env,
+    depth: Depth,
+
+    // Resulting expression and applied rule of each step
+    pub steps: Vec<(Expr, AppliedRule)>,
+
+    // Warnings that need to be printed to the user
+    pub warnings: Vec<Warning>,
+
+    // Why the first rule that left the expression unchanged did not match, which is reported if the strategy fails
+    pub mismatch: Option<Warning>,
+}
+
+impl<'a> StrategyRunner<'a> {
+    pub fn new(env: &'a Env, depth: Depth) -> Self {
+        Self {
+            env,
+            depth,
+            steps: vec![],
+            warnings: vec![],
+            mismatch: None,
+        }
+    }
+
+    // Returns the rewritten expression if the strategy succeeds and None if it fails.
+    // Steps of a failed strategy are discarded.
+    pub fn run(&mut self, strategy: &Strategy, expr: Expr) -> Result<Option<Expr>, RuntimeError> {
+        let mark = self.steps.len();
+        let res = self.run_strategy(strategy, expr)?;
+        if res.is_none() {
+            self.steps.truncate(mark);
+        }
+        Ok(res)
+    }
+
+    fn run_strategy(&mut self, strategy: &Strategy, expr: Expr) -> Result<Option<Expr>, RuntimeError> {
+        match strategy {
+            Strategy::Rule { iden, args } => self.run_rule(iden, args, expr),
+            Strategy::Try(strategy) => {
+                match self.run(strategy, expr.clone())? {
+                    Some(new_expr) => Ok(Some(new_expr)),
+                    None => Ok(Some(expr))
+                }
+            },
+            Strategy::Sequence(strategies) => {
+                let mut current = expr;
+                for strategy in strategies {
+                    match self.run_strategy(strategy, current)? {
+                        Some(new_expr) => current = new_expr,
+                        None => return Ok(None)
+                    }
+                }
+                Ok(Some(current))
+            },
+            Strategy::Choice(strategies) => {
+                for strategy in strategies {
+                    if let Some(new_expr) = self.run(strategy, expr.clone())? {
+                        return Ok(Some(new_expr));
+                    }
+                }
+                Ok(None)
+            },
+            Strategy::Tagged(tag) => {
+                let idens = self.env.tagged_rules(tag);
+                if idens.is_empty() {
+                    self.warnings.push(Warning::TagHasNoRules(tag.clone()));
+                    return Ok(None);
+                }
+                for iden in idens {
+                    if let Some(new_expr) = self.run(&Strategy::Rule { iden, args: vec![] }, expr.clone())? {
+                        return Ok(Some(new_expr));
+                    }
+                }
+                Ok(None)
+            },
+            Strategy::Repeat(strategy) => {
+                // Repeat until the strategy fails or stops changing the expression
+                let mut current = expr;
+                loop {
+                    let mark = self.steps.len();
+                    let Some(new_expr) = self.run(strategy, current.clone())? else { break; };
+                    if new_expr == current {
+                        break;
+                    }
+                    // Stop before an iteration that derives an earlier expression again, 
+                    // as the repetition would otherwise never end.
+                    if self.is_seen(&new_expr, mark) {
+                        self.steps.truncate(mark);
+                        self.warnings.push(Warning::RewriteCycleDetected(new_expr.to_string()));
+                        break;
+                    }
+                    current = new_expr;
+                }
+                Ok(Some(current))
+            }
+        }
+    }
+
+    // Whether the expression was derived before the strategy, or by the first steps of the strategy.
+    fn is_seen(&self, expr: &Expr, num_steps: usize) -> bool {
+        self.env.seen_exprs.contains(expr) || 
+        self.steps[..num_steps].iter().any(|(step_expr, _)| step_expr == expr)
+    }
+
+    // A rule succeeds if and only if it changes the expression.
+    fn run_rule(&mut self, iden: &str, args: &[Expr], expr: Expr) -> Result<Option<Expr>, RuntimeError> {
+        let Some(rule) = self.env.rules.get(iden) else {
+            self.warnings.push(Warning::RuleDoesNotExist(iden.to_string()));
+            return Ok(None);
+        };
+        // Rule schemas must receive exactly one argument per meta-parameter.
+        if rule.params.len() != args.len() {
+            self.warnings.push(Warning::RuleArityMismatch {
+                iden: iden.to_string(),
+                expected: rule.params.len(),
+                got: args.len()
+            });
+            return Ok(None);
+        }
+
+        let (left, right) = rule.instantiate(args);
+        let (new_expr, depth) = ast_traverse_match_at(&expr, &left, &right, self.depth, self.env)?;
+        if new_expr == expr {
+            if self.mismatch.is_none() {
+                self.mismatch = Some(Warning::RuleDidNotMatch { 
+                    rule: iden.to_string(), 
+                    depth: self.depth.to_string(), 
+                    reason: no_match_reason(&expr, &left, self.depth) 
+                });
+            }
+            return Ok(None);
+        }
+        self.steps.push((new_expr.clone(), AppliedRule { 
+            name: Some(iden.to_string()), 
+            left, 
+            right, 
+            depth,
+            path: vec![],
+            at: recorded_depth(self.depth, depth),
+        }));
+        Ok(Some(new_expr))
+    }
+}
@@ -0,0 +1,81 @@
+use std::{cmp::Ordering, collections::HashMap};
+
+use crate::ast::{Expr, ExprNode};
+
+// Outcome of comparing both sides of a rule in the term ordering, where expressions are ordered by
+// their number of nodes first, and expressions of the same size by the term ordering of expressions.
+// Repeatedly applying a rule whose right expression is smaller always terminates, as long as no
+// variable occurs more often on the right, which could duplicate arbitrarily large sub-expressions.
+#[derive(Debug, PartialEq)]
+pub enum Termination {
+    Decreasing { left_size: usize, right_size: usize },
+    DuplicatedVariable(String),
+    NotSmaller { left_size: usize, right_size: usize },
+}
+
+pub fn check_termination(left: &Expr, right: &Expr) -> Termination {
+    let left_vars = count_variables(left);
+    let right_vars = count_variables(right);
+    let mut duplicated: Vec<&str> = right_vars
+        .iter()
+        .filter(|(iden, n)| **n > left_vars.get(*iden).copied().unwrap_or(0))
+        .map(|(iden, _)| *iden)
+        .collect();
+    // Report the first variable in alphabetical order, for a deterministic warning
+    duplicated.sort();
+    if let Some(iden) = duplicated.first() {
+        return Termination::DuplicatedVariable(iden.to_string());
+    }
+
+    let (left_size, right_size) = (left.num_nodes(), right.num_nodes());
+    match right_size.cmp(&left_size).then_with(|| right.cmp_terms(left)) {
+        Ordering::Less => Termination::Decreasing { left_size, right_size },
+        _ => Termination::NotSmaller { left_size, right_size },
+    }
+}
+
+// Number of occurrences of each variable and segment, by identifier
+fn count_variables(expr: &Expr) -> HashMap<&str, usize> {
+    let mut counts = HashMap::new();
+    let mut stack = vec![expr];
+    while let Some(expr) = stack.pop() {
+        match expr.node() {
+            ExprNode::Variable { iden } | ExprNode::Segment { iden } => *counts.entry(iden.as_str()).or_default() += 1,
+            ExprNode::Functor { args, .. } => stack.extend(args.iter()),
+            ExprNode::Number(_) | ExprNode::Str(_) => {}
+        }
+    }
+    counts
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn termination() {
+        // add(x, zero) => x
+        let plus_zero = (
+            Expr::functor("add", vec![Expr::variable("x"), Expr::variable("zero")]),
+            Expr::variable("x")
+        );
+        // add(x, y) => add(y, x)
+        let swap = (
+            Expr::functor("add", vec![Expr::variable("x"), Expr::variable("y")]),
+            Expr::functor("add", vec![Expr::variable("y"), Expr::variable("x")])
+        );
+        // mul(x, add(y, z)) => add(mul(x, y), mul(x, z))
+        let distribute = (
+            Expr::functor("mul", vec![Expr::variable("x"), Expr::functor("add", vec![Expr::variable("y"), Expr::variable("z")])]),
+            Expr::functor("add", vec![
+                Expr::functor("mul", vec![Expr::variable("x"), Expr::variable("y")]),
+                Expr::functor("mul", vec![Expr::variable("x"), Expr::variable("z")])
+            ])
+        );
+
+        assert_eq!(check_termination(&plus_zero.0, &plus_zero.1), Termination::Decreasing { left_size: 3, right_size: 1 });
+        assert_eq!(check_termination(&swap.0, &swap.1), Termination::NotSmaller { left_size: 3, right_size: 3 });
+        assert_eq!(check_termination(&swap.1, &swap.0), Termination::Decreasing { left_size: 3, right_size: 3 });
+        assert_eq!(check_termination(&distribute.0, &distribute.1), Termination::DuplicatedVariable("x".to_string()));
+    }
+}
@@ -0,0 +1,225 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::ast::{Expr, ExprNode};
+
+// Maps variables to the expressions that replace them
+pub type Substitution = HashMap<Expr, Expr>;
+
+// Syntactic unification of two expressions, where the variables of both expressions may be replaced.
+// Returns the most general unifier, whose expressions contain none of the replaced variables, or None
+// if the expressions cannot be made identical. Numbers and segments only unify with themselves.
+pub fn unify(left: &Expr, right: &Expr) -> Option<Substitution> {
+    // Triangular substitution, i.e., bound expressions may contain variables that are bound later on
+    let mut bindings = Substitution::new();
+    let mut stack = vec![(left.clone(), right.clone())];
+    while let Some((left, right)) = stack.pop() {
+        let (left, right) = (resolve(&left, &bindings), resolve(&right, &bindings));
+        if left == right {
+            continue;
+        }
+        match (left.node(), right.node()) {
+            (ExprNode::Variable { .. }, _) => {
+                if occurs(&left, &right, &bindings) {
+                    return None;
+                }
+                bindings.insert(left, right);
+            },
+            (_, ExprNode::Variable { .. }) => {
+                if occurs(&right, &left, &bindings) {
+                    return None;
+                }
+                bindings.insert(right, left);
+            },
+            (ExprNode::Functor { iden: left_iden, args: left_args },
+             ExprNode::Functor { iden: right_iden, args: right_args }) => {
+                if left_iden != right_iden || left_args.len() != right_args.len() {
+                    return None;
+                }
+                stack.extend(left_args.iter().cloned().zip(right_args.iter().cloned()));
+            },
+            _ => return None
+        }
+    }
+
+    Some(bindings
+        .keys()
+        .map(|var| (var.clone(), substitute(var, &bindings)))
+        .collect())
+}
+
+// Follow the bindings of a variable until an unbound variable or another expression is found.
+fn resolve(expr: &Expr, bindings: &Substitution) -> Expr {
+    let mut expr = expr;
+    while let Some(bound) = bindings.get(expr) {
+        expr = bound;
+    }
+    expr.clone()
+}
+
+// Whether the variable occurs in the expression after following the bindings,
+// in which case binding the variable to the expression would be infinite.
+fn occurs(var: &Expr, expr: &Expr, bindings: &Substitution) -> bool {
+    let mut stack = vec![expr.clone()];
+    while let Some(expr) = stack.pop() {
+        let expr = resolve(&expr, bindings);
+        if &expr == var {
+            return true;
+        }
+        if let ExprNode::Functor { args, .. } = expr.node() {
+            stack.extend(args.iter().cloned());
+        }
+    }
+    false
+}
+
+// Replace all variables of the expression by their bound expressions, which are substituted in turn.
+pub fn substitute(expr: &Expr, bindings: &Substitution) -> Expr {
+    enum Frame<'a> {
+        Enter(&'a Expr),
+        Exit(&'a str, usize),
+    }
+
+    let mut stack = vec![Frame::Enter(expr)];
+    let mut results = vec![];
+    while let Some(frame) = stack.pop() {
+        match frame {
+            Frame::Enter(expr) => match (bindings.get(expr), expr.node()) {
+                (Some(bound), _) => stack.push(Frame::Enter(bound)),
+                (None, ExprNode::Functor { iden, args }) => {
+                    stack.push(Frame::Exit(iden, args.len()));
+                    stack.extend(args.iter().rev().map(Frame::Enter));
+                },
+                (None, _) => results.push(expr.clone())
+            },
+            Frame::Exit(iden, num_args) => {
+                let args = results.split_off(results.len() - num_args);
+                results.push(Expr::functor(iden, args));
+            }
+        }
+    }
+    results.pop().unwrap()
+}
+
+// Renames every variable of the expression by appending a prime, e.g., x becomes x',
+// which cannot be written in an expression, so that it is distinct from all other variables.
+fn renaming(expr: &Expr) -> Substitution {
+    let mut renaming = Substitution::new();
+    let mut stack = vec![expr];
+    while let Some(expr) = stack.pop() {
+        match expr.node() {
+            ExprNode::Variable { iden } => {
+                renaming.insert(expr.clone(), Expr::variable(format!("{}'", iden)));
+            },
+            ExprNode::Functor { args, .. } => stack.extend(args.iter()),
+            _ => {}
+        }
+    }
+    renaming
+}
+
+// Replace the sub-expression at the path of argument indices by another expression.
+fn replace_at(expr: &Expr, path: &[usize], new: Expr) -> Expr {
+    let mut parents = vec![];
+    let mut current = expr;
+    for &idx in path {
+        let ExprNode::Functor { args, .. } = current.node() else { unreachable!() };
+        parents.push((current, idx));
+        current = &args[idx];
+    }
+    parents.into_iter().rev().fold(new, |new, (parent, idx)| {
+        let ExprNode::Functor { iden, args } = parent.node() else { unreachable!() };
+        let mut args = args.clone();
+        args[idx] = new;
+        Expr::functor(iden.as_str(), args)
+    })
+}
+
+// Two rules overlap if the left expression of the inner rule unifies with a functor in the left
+// expression of the outer rule. The most general expression on which both rules match is the peak,
+// which derives one expression by the outer rule and another by the inner rule at the given depth.
+#[derive(Debug, PartialEq)]
+pub struct CriticalPair {
+    pub peak: Expr,
+    pub depth: usize,
+    pub by_outer: Expr,
+    pub by_inner: Expr,
+}
+
+// The critical pairs of two rules, given as (left, right), where the derived expressions differ. Overlaps of
+// both entire left expressions are excluded unless include_root is true, as they are symmetric, and trivial if 
+// both rules are the same rule.
+pub fn critical_pairs(outer: (&Expr, &Expr), inner: (&Expr, &Expr), include_root: bool) -> Vec<CriticalPair> {
+    // Only the variables of the left expression match, the others are constants
+    let renaming = renaming(inner.0);
+    let (inner_left, inner_right) = (substitute(inner.0, &renaming), substitute(inner.1, &renaming));
+    let inner_vars: HashSet<&Expr> = renaming.values().collect();
+    let mut pairs = vec![];
+    let mut stack = vec![(outer.0, vec![])];
+    while let Some((expr, path)) = stack.pop() {
+        let ExprNode::Functor { args, .. } = expr.node() else { continue; };
+        for (idx, arg) in args.iter().enumerate().rev() {
+            let mut arg_path = path.clone();
+            arg_path.push(idx);
+            stack.push((arg, arg_path));
+        }
+        if !include_root && path.is_empty() {
+            continue;
+        }
+        let Some(unifier) = unify(expr, &inner_left) else { continue; };
+        let peak = substitute(outer.0, &unifier);
+        let by_outer = substitute(outer.1, &unifier);
+        // The constants of the inner rule may have the same name as variables of the outer rule
+        let inner_unifier: Substitution = unifier
+            .into_iter()
+            .filter(|(var, _)| inner_vars.contains(var))
+            .collect();
+        let by_inner = replace_at(&peak, &path, substitute(&inner_right, &inner_unifier));
+        if by_outer != by_inner {
+            pairs.push(CriticalPair { peak, depth: path.len(), by_outer, by_inner });
+        }
+    }
+    pairs
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn unify_exprs() {
+        // f(x, g(y)) and f(g(z), x)
+        let left = Expr::functor("f", vec![Expr::variable("x"), Expr::functor("g", vec![Expr::variable("y")])]);
+        let right = Expr::functor("f", vec![Expr::functor("g", vec![Expr::variable("z")]), Expr::variable("x")]);
+
+        let unifier = unify(&left, &right).unwrap();
+        assert_eq!(substitute(&left, &unifier), substitute(&right, &unifier));
+        assert_eq!(unifier[&Expr::variable("x")], Expr::functor("g", vec![Expr::variable("z")]));
+        assert_eq!(unifier[&Expr::variable("y")], Expr::variable("z"));
+
+        // occurs check: x and g(x)
+        assert_eq!(unify(&Expr::variable("x"), &Expr::functor("g", vec![Expr::variable("x")])), None);
+        assert_eq!(unify(&Expr::functor("f", vec![]), &Expr::functor("g", vec![])), None);
+    }
+
+    #[test]
+    fn critical_pairs_of_rules() {
+        // f(g(x)) => h(x) and g(a) => b overlap at depth 1 on f(g(a))
+        let outer = (
+            Expr::functor("f", vec![Expr::functor("g", vec![Expr::variable("x")])]),
+            Expr::functor("h", vec![Expr::variable("x")])
+        );
+        let inner = (
+            Expr::functor("g", vec![Expr::variable("a")]),
+            Expr::variable("b")
+        );
+
+        let pairs = critical_pairs((&outer.0, &outer.1), (&inner.0, &inner.1), true);
+        assert_eq!(pairs.len(), 1);
+        assert_eq!(format!("{}", pairs[0].peak), "f(g(a'))");
+        assert_eq!(pairs[0].depth, 1);
+        assert_eq!(format!("{}", pairs[0].by_outer), "h(a')");
+        assert_eq!(format!("{}", pairs[0].by_inner), "f(b)");
+
+        assert!(critical_pairs((&inner.0, &inner.1), (&inner.0, &inner.1), false).is_empty());
+    }
+}
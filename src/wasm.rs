@@ -0,0 +1,114 @@
+use std::{io, sync::mpsc};
+
+use crate::{
+    diagnostic::{line_column, plain_message},
+    error::LexError,
+    event::Event,
+    json::Json,
+    lexer::Span,
+    parser::Syntax,
+    runtime::Env
+};
+
+#[cfg(feature = "wasm")]
+use wasm_bindgen::prelude::wasm_bindgen;
+
+// Interpret a program in a new environment, and return what a front-end needs to render the derivations:
+//     steps: each expression of every pattern matching context in order, where the first expression of a
+//            context has a null rule and every following expression has the rule that derived it,
+//     warnings: the warnings with their code, message, line and column,
+//     errors: the syntax errors, or the runtime error that stopped the interpretation.
+// Syntax errors stop the program from being interpreted at all, like for files.
+pub fn interpret(source: &str) -> Json {
+    let mut env = Env::new();
+    let mut lexer = env.lex(source);
+
+    let mut errors: Vec<Json> = lexer.errors
+        .iter()
+        .map(|err| {
+            let span = err.downcast_ref::<LexError>().map_or(Span::default(), |err| err.span(source));
+            located(source, span, [("message", plain_message(err).into())])
+        })
+        .collect();
+    let mut parser = env.parser(Syntax::detect(&lexer));
+    if let Err(err) = parser.parse(&mut lexer) {
+        errors.push(located(source, lexer.span(0), [("message", plain_message(&err).into())]));
+    }
+
+    let mut steps = vec![];
+    let mut warnings = vec![];
+    if errors.is_empty() {
+        // The steps replace the printed output, which has no destination in the browser
+        env.set_output(io::sink());
+        let (sender, receiver) = mpsc::channel();
+        env.subscribe(Box::new(sender));
+        for (stmt, span) in parser.stmts.into_iter().zip(parser.spans) {
+            let res = env.interpret(vec![stmt]);
+            warnings.extend(env.warnings.drain(..).map(|warn| located(source, span, [
+                ("code", warn.code().into()),
+                ("message", plain_message(&warn).into())
+            ])));
+            for event in receiver.try_iter() {
+                let (expr, rule) = match event {
+                    Event::MatchStarted(expr) => (expr, Json::Null),
+                    Event::StepApplied { expr, rule } => (expr, Json::object([
+                        ("name", rule.name.map_or(Json::Null, Json::from)),
+                        ("left", rule.left.to_string().into()),
+                        ("right", rule.right.to_string().into()),
+                        ("depth", rule.depth.into()),
+                    ])),
+                    Event::Warning(_) | Event::Result(_) => continue,
+                };
+                steps.push(Json::object([("expr", expr.to_string().into()), ("rule", rule)]));
+            }
+
+            if let Err(err) = res {
+                errors.push(located(source, span, [("message", plain_message(&err).into())]));
+                break;
+            }
+        }
+    }
+
+    Json::object([("steps", steps.into()), ("warnings", warnings.into()), ("errors", errors.into())])
+}
+
+fn located<const N: usize>(source: &str, span: Span, members: [(&str, Json); N]) -> Json {
+    let (line, column) = line_column(source, span.start);
+    let mut members: Vec<(String, Json)> = members.into_iter().map(|(key, value)| (key.to_string(), value)).collect();
+    members.push(("line".to_string(), line.into()));
+    members.push(("column".to_string(), column.into()));
+    Json::Object(members)
+}
+
+// JavaScript binding of interpret, which returns the result as JSON text, e.g., JSON.parse(interpret(source))
+#[cfg(feature = "wasm")]
+#[wasm_bindgen(js_name = interpret)]
+pub fn interpret_js(source: &str) -> String {
+    interpret(source).to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn interpret_to_json() {
+        let source = "def swap as f(x, y) => f(y, x)\nf(a, b)\n    apply swap at 0\n    apply swp at 0\n    expect f(a, b)\nend";
+        let res = interpret(source);
+        assert_eq!(res.get("steps").unwrap().to_string(), concat!(
+            r#"[{"expr":"f(a, b)","rule":null},"#,
+            r#"{"expr":"f(b, a)","rule":{"name":"swap","left":"f(x, y)","right":"f(y, x)","depth":0}}]"#
+        ));
+        let warnings = res.get("warnings").and_then(Json::as_array).unwrap();
+        assert_eq!(warnings.len(), 1);
+        assert_eq!(warnings[0].get("code").and_then(Json::as_str), Some("W005"));
+        assert_eq!(warnings[0].get("line").and_then(Json::as_usize), Some(4));
+        let errors = res.get("errors").and_then(Json::as_array).unwrap();
+        assert_eq!(errors[0].get("message").and_then(Json::as_str), Some("expected the current expression to be 'f(a, b)', but got 'f(b, a)'"));
+        assert_eq!(errors[0].get("line").and_then(Json::as_usize), Some(5));
+
+        let res = interpret("f(a, b)\n    apply at 0");
+        assert_eq!(res.get("steps"), Some(&Json::Array(vec![])));
+        assert_eq!(res.get("errors").and_then(Json::as_array).map(<[Json]>::len), Some(1));
+    }
+}
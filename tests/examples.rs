@@ -0,0 +1,78 @@
+// Golden-file tests of the examples: every examples/*.rx file is interpreted, and its printed derivation,
+// warnings and errors are compared with the transcript in tests/golden/ with the same name.
+// Run with RAXIO_BLESS=1 to write the transcripts of the current behavior instead, e.g., after adding an example.
+
+use std::{env, fs, io::Write, path::Path};
+
+use raxio::{
+    diagnostic::{line_column, plain_message},
+    lexer::Lexer,
+    parser::{Parser, Syntax},
+    runtime::{Env, OutputBuffer}
+};
+
+// Interpret the source like the command line does, and return everything it printed.
+fn transcript(source: &str) -> String {
+    let output = OutputBuffer::new();
+    let mut out = output.clone();
+
+    let mut lexer = Lexer::new();
+    lexer.lex(source);
+    for err in lexer.errors.iter() {
+        let _ = writeln!(out, "error: {}", plain_message(err));
+    }
+    let mut parser = Parser::with_syntax(Syntax::detect(&lexer));
+    let res = parser.parse(&mut lexer);
+    if let Err(err) = &res {
+        let _ = writeln!(out, "error: {}", plain_message(err));
+    }
+    // Syntax errors stop the source from being interpreted at all
+    if !lexer.errors.is_empty() || res.is_err() {
+        return output.contents();
+    }
+
+    let mut env = Env::new();
+    env.set_output(output.clone());
+    env.base_dir = Some("examples".into());
+    for (stmt, span) in parser.stmts.into_iter().zip(parser.spans) {
+        let res = env.interpret(vec![stmt]);
+        let (line, _) = line_column(source, span.start);
+        for warn in env.warnings.drain(..) {
+            let _ = writeln!(out, "warning[{}] at line {}: {}", warn.code(), line, plain_message(&warn));
+        }
+        if let Err(err) = res {
+            let _ = writeln!(out, "error at line {}: {}", line, plain_message(&err));
+            break;
+        }
+    }
+    output.contents()
+}
+
+#[test]
+fn examples_match_golden_transcripts() {
+    let bless = env::var_os("RAXIO_BLESS").is_some();
+    let mut file_names: Vec<_> = fs::read_dir("examples")
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.extension().is_some_and(|ext| ext == "rx"))
+        .collect();
+    file_names.sort();
+    assert!(!file_names.is_empty());
+
+    let mut mismatches = vec![];
+    for file_name in file_names {
+        let source = fs::read_to_string(&file_name).unwrap();
+        let actual = transcript(&source);
+        let golden = Path::new("tests/golden").join(file_name.with_extension("txt").file_name().unwrap());
+        if bless {
+            fs::write(&golden, &actual).unwrap();
+            continue;
+        }
+        match fs::read_to_string(&golden) {
+            Ok(expected) if expected == actual => {},
+            Ok(expected) => mismatches.push(format!("{}:\n--- expected\n{}+++ actual\n{}", golden.display(), expected, actual)),
+            Err(_) => mismatches.push(format!("{}: missing, run with RAXIO_BLESS=1 to create it", golden.display())),
+        }
+    }
+    assert!(mismatches.is_empty(), "transcripts differ from the golden files:\n{}", mismatches.join("\n"));
+}
@@ -0,0 +1,116 @@
+// Properties of random expressions and rules, which are generated by proptest. Failing cases are shrunk to
+// a minimal expression, and stored in tests/properties.proptest-regressions to be run first from then on.
+
+use proptest::prelude::*;
+
+use raxio::{
+    ast::{Expr, ExprNode},
+    lexer::{Lexer, Number},
+    parser::{Depth, Parser, Stmt, Syntax},
+    printer::print_stmts,
+    runtime::{ast_traverse_match, ast_traverse_match_at, Env}
+};
+
+// Identifiers include the arithmetic operators, which are printed infix, and group, which is printed as parentheses
+const FUNCTORS: [&str; 8] = ["f", "g", "add", "sub", "mul", "div", "pow", "group"];
+const VARIABLES: [&str; 4] = ["x", "y", "a", "b"];
+
+fn arb_expr() -> impl Strategy<Value = Expr> {
+    let leaf = prop_oneof![
+        prop::sample::select(&VARIABLES[..]).prop_map(Expr::variable),
+        (0..100i64).prop_map(|n| Expr::number(Number::Int(n))),
+    ];
+    leaf.prop_recursive(6, 64, 3, |inner| {
+        (prop::sample::select(&FUNCTORS[..]), prop::collection::vec(inner, 1..=3))
+            .prop_map(|(iden, args)| match iden {
+                // Parentheses contain a single expression
+                "group" => Expr::functor(iden, args[..1].to_vec()),
+                _ => Expr::functor(iden, args),
+            })
+    })
+}
+
+// A rule whose left expression is a functor, as variables match any expression
+fn arb_rule() -> impl Strategy<Value = (Expr, Expr)> {
+    (prop::sample::select(&FUNCTORS[..2]), prop::collection::vec(arb_expr(), 1..=2), arb_expr())
+        .prop_map(|(iden, args, right)| (Expr::functor(iden, args), right))
+}
+
+fn parse(source: &str, syntax: Syntax) -> Vec<Stmt> {
+    let mut lexer = Lexer::new();
+    lexer.lex(source);
+    assert!(lexer.errors.is_empty(), "lexing failed for {:?}", source);
+    let mut parser = Parser::with_syntax(syntax);
+    parser.parse(&mut lexer).unwrap_or_else(|err| panic!("parsing failed for {:?}: {}", source, err));
+    parser.stmts
+}
+
+proptest! {
+    #[test]
+    fn printed_expr_parses_to_the_same_expr(expr in arb_expr()) {
+        for syntax in [Syntax::Functor, Syntax::SExpr] {
+            let stmts = vec![Stmt::ExprStmt(expr.clone())];
+            let source = print_stmts(&stmts, syntax);
+            prop_assert_eq!(parse(&source, syntax), stmts);
+        }
+    }
+
+    #[test]
+    fn expr_source_parses_to_the_same_expr(expr in arb_expr()) {
+        prop_assert_eq!(parse(&expr.to_source(), Syntax::Functor), vec![Stmt::ExprStmt(expr)]);
+    }
+
+    #[test]
+    fn printed_rule_parses_to_the_same_rule((left, right) in arb_rule(), depth in 0..4usize) {
+        let stmts = vec![Stmt::DefineStmt { iden: "r".to_string(), params: vec![], left, right: right.clone(), tags: vec!["t".to_string()], auto: false }];
+        let source = print_stmts(&stmts, Syntax::Functor);
+        prop_assert_eq!(parse(&source, Syntax::Functor), stmts);
+
+        let stmts = vec![Stmt::ExprStmt(right.clone()), Stmt::RuleStmt { left: right.clone(), right, depth: Depth::At(depth) }];
+        let source = print_stmts(&stmts, Syntax::Functor);
+        prop_assert_eq!(parse(&source, Syntax::Functor), stmts);
+    }
+
+    #[test]
+    fn applying_a_rule_never_panics(expr in arb_expr(), (left, right) in arb_rule(), depth in 0..8usize) {
+        let mut env = Env::new();
+        env.limits.max_nodes = 10_000;
+        if let Ok(res) = ast_traverse_match(&expr, &left, &right, depth, &env) {
+            // A rule that does not match leaves the expression unchanged
+            if !contains_match(&expr, &left) {
+                prop_assert_eq!(res, expr.clone());
+            }
+        }
+        env.jobs = 2;
+        let _ = ast_traverse_match_at(&expr, &left, &right, Depth::Any, &env);
+    }
+
+    #[test]
+    fn interpreting_random_derivations_never_panics(expr in arb_expr(), rules in prop::collection::vec(arb_rule(), 1..4)) {
+        let mut source = String::new();
+        for (i, (left, right)) in rules.iter().enumerate() {
+            source += &format!("def r{} as {} => {}\n", i, left.to_string(), right.to_string());
+        }
+        source += &format!("{}\n", expr.to_string());
+        let names: Vec<String> = (0..rules.len()).map(|i| format!("r{}", i)).collect();
+        source += &format!("apply ({})* at any\nprove {} == {}\nend\n", names.join(" | "), expr.to_string(), expr.to_string());
+        raxio::fuzz::parse_and_interpret(source.as_bytes());
+    }
+}
+
+// Whether the functor of the left expression occurs anywhere in the expression, which a match requires
+fn contains_match(expr: &Expr, left: &Expr) -> bool {
+    let ExprNode::Functor { iden, .. } = left.node() else {
+        return true;
+    };
+    let mut stack = vec![expr];
+    while let Some(expr) = stack.pop() {
+        if let ExprNode::Functor { iden: other, args } = expr.node() {
+            if other == iden {
+                return true;
+            }
+            stack.extend(args.iter());
+        }
+    }
+    false
+}